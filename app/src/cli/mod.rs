@@ -1,5 +1,6 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
+use ambient_network::tls::CertFingerprint;
 use clap::{Args, Parser};
 
 pub mod new_project;
@@ -28,6 +29,11 @@ pub enum Cli {
     Build {
         #[command(flatten)]
         project_args: ProjectCli,
+        /// Also package the built project into an asset archive at this path, to distribute it as a single file
+        ///
+        /// The archive can be run or joined from with `ambient run <path or URL of the archive>`, and served from any web server that supports range requests
+        #[arg(long)]
+        archive: Option<PathBuf>,
     },
     /// Builds and runs the project in server-only mode
     Serve {
@@ -50,6 +56,21 @@ pub enum Cli {
         /// The server to connect to; defaults to localhost
         host: Option<String>,
     },
+    /// Joins a server with headless bots that play with random or scripted input, and reports their latency and bandwidth
+    Bots {
+        #[command(flatten)]
+        bots_args: BotsCli,
+        /// The server to connect to; defaults to localhost
+        host: Option<String>,
+    },
+    /// Replays a session recorded with `--record` headless, and reports the first tick that differs from the recording
+    Replay {
+        #[command(flatten)]
+        project_args: ProjectCli,
+        /// The recording to replay
+        #[arg(long)]
+        recording: PathBuf,
+    },
 }
 #[derive(Args, Clone)]
 pub struct RunCli {
@@ -68,6 +89,60 @@ pub struct RunCli {
     /// The user ID to join this server with
     #[clap(short, long)]
     pub user_id: Option<String>,
+
+    /// The token to authenticate with, if the server requires one. Can also be provided through the `AMBIENT_AUTH_TOKEN` environment variable
+    #[arg(long)]
+    pub auth_token: Option<String>,
+
+    /// Only accept the server if its certificate has this SHA-256 fingerprint, which servers with a self-signed certificate print when they start. By default, the certificate is verified against the root certificates of the system
+    #[arg(long, conflicts_with = "insecure")]
+    pub pin: Option<CertFingerprint>,
+
+    /// Don't verify the certificate of the server. Anyone between you and the server can then intercept the connection
+    #[arg(long)]
+    pub insecure: bool,
+}
+#[derive(Args, Clone)]
+pub struct BotsCli {
+    /// How many bots join the server
+    #[arg(long, default_value_t = 1)]
+    pub count: usize,
+
+    /// A TOML file with the input of the bots: a list of `[[steps]]`, each with a `duration` in seconds and the `keys`, `mouse_buttons` and `mouse_velocity` held during it, which the bots repeat. By default, the bots press random keys and move the mouse randomly
+    #[arg(long)]
+    pub script: Option<PathBuf>,
+
+    /// How many seconds to wait between two bots joining
+    #[arg(long, default_value_t = 0.1, value_parser = parse_seconds)]
+    pub join_interval: f32,
+
+    /// How many seconds the bots play for; by default, until Ctrl+C is pressed
+    #[arg(long, value_parser = parse_seconds)]
+    pub duration: Option<f32>,
+
+    /// The bots join with the user ids `<prefix>_0`, `<prefix>_1`, ...
+    #[arg(long, default_value = "bot")]
+    pub user_id_prefix: String,
+
+    /// The token the bots authenticate with, if the server requires one. Can also be provided through the `AMBIENT_AUTH_TOKEN` environment variable
+    #[arg(long)]
+    pub auth_token: Option<String>,
+
+    /// Only accept the server if its certificate has this SHA-256 fingerprint
+    #[arg(long, conflicts_with = "insecure")]
+    pub pin: Option<CertFingerprint>,
+
+    /// Don't verify the certificate of the server
+    #[arg(long)]
+    pub insecure: bool,
+}
+/// Parses a number of seconds that can be turned into a `Duration`
+fn parse_seconds(value: &str) -> Result<f32, String> {
+    let seconds: f32 = value.parse().map_err(|err| format!("{err}"))?;
+    match Duration::try_from_secs_f32(seconds) {
+        Ok(_) => Ok(seconds),
+        Err(_) => Err(format!("{seconds} is not a finite, non-negative number of seconds")),
+    }
 }
 #[derive(Args, Clone)]
 pub struct ProjectCli {
@@ -98,6 +173,10 @@ pub struct HostCli {
     #[arg(long)]
     pub quic_interface_port: Option<u16>,
 
+    /// Send the project files to the players over the game connection instead of the HTTP interface, so that only the QUIC (UDP) port needs to be reachable
+    #[arg(long)]
+    pub assets_over_quic: bool,
+
     /// Don't use proxy for NAT traversal
     #[arg(long)]
     pub no_proxy: bool,
@@ -109,6 +188,70 @@ pub struct HostCli {
     /// Pre-cache assets on the proxy
     #[arg(long)]
     pub proxy_pre_cache_assets: bool,
+
+    /// Step the server deterministically: with a fixed time step, seeded entity ids and deterministic physics
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Record every server tick to this file, so that the session can be replayed with `ambient replay`; implies `--deterministic`
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Only let in players with a JSON Web Token signed (HS256) with this secret; the user id is the subject of the token, and the token must expire (`exp`). Can also be provided through the `AMBIENT_AUTH_SECRET` environment variable
+    #[arg(long, conflicts_with = "auth_users")]
+    pub auth_secret: Option<String>,
+
+    /// Only let in players with a token listed in this file, a `[users]` table of `user_id = "token"` entries
+    #[arg(long)]
+    pub auth_users: Option<PathBuf>,
+
+    /// The TLS certificate (chain) of the server, PEM or DER encoded. If not specified, a self-signed certificate is generated, and its fingerprint printed so that players can pin it
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// The private key of the TLS certificate, PEM or DER encoded
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// How many seconds the server keeps the player of a lost connection, so that the client can reconnect and resume its session; 0 disables resuming sessions
+    #[arg(long, value_name = "SECONDS")]
+    pub resume_grace_period: Option<u64>,
+
+    /// Read admin commands (`players`, `kick`, `ban`, ...; see `help`) from the terminal
+    #[arg(long)]
+    pub admin_console: bool,
+
+    /// Accept admin commands on a Unix socket at this path
+    #[arg(long)]
+    pub admin_socket: Option<PathBuf>,
+
+    /// Accept admin commands sent to `POST /admin` on the HTTP interface from this machine, with the bearer token in this file. The token can also be provided through the `AMBIENT_ADMIN_TOKEN` environment variable
+    #[arg(long)]
+    pub admin_token_file: Option<PathBuf>,
+
+    /// The file the banned players are saved to. Defaults to `bans.toml` in the project directory
+    #[arg(long)]
+    pub ban_list: Option<PathBuf>,
+
+    /// How many players can be on the server at once
+    #[arg(long)]
+    pub max_players: Option<usize>,
+
+    /// How many players can be in each instance at once
+    #[arg(long)]
+    pub max_instance_players: Option<usize>,
+
+    /// How many of the player slots, on the server and in each instance, are kept for the admins
+    #[arg(long, default_value_t = 0)]
+    pub reserved_slots: usize,
+
+    /// How many players can wait in the join queue for a slot when the server is full; when the queue is full too, players are turned away
+    #[arg(long, default_value_t = 0)]
+    pub join_queue: usize,
+
+    /// The user ids of the admins, who can take the reserved slots and go first in the join queue
+    #[arg(long, value_delimiter = ',')]
+    pub admins: Vec<String>,
 }
 
 impl Cli {
@@ -121,6 +264,8 @@ impl Cli {
             Cli::Serve { .. } => None,
             Cli::View { .. } => None,
             Cli::Join { run_args, .. } => Some(run_args),
+            Cli::Bots { .. } => None,
+            Cli::Replay { .. } => None,
         }
     }
     /// Extract project-relevant state only
@@ -132,6 +277,8 @@ impl Cli {
             Cli::Serve { project_args, .. } => Some(project_args),
             Cli::View { project_args, .. } => Some(project_args),
             Cli::Join { .. } => None,
+            Cli::Bots { .. } => None,
+            Cli::Replay { project_args, .. } => Some(project_args),
         }
     }
     /// Extract host-relevant state only
@@ -143,6 +290,8 @@ impl Cli {
             Cli::Serve { host_args, .. } => Some(host_args),
             Cli::View { .. } => None,
             Cli::Join { .. } => None,
+            Cli::Bots { .. } => None,
+            Cli::Replay { .. } => None,
         }
     }
 }
@@ -0,0 +1,234 @@
+//! Headless bots: clients without a window that join a server and play with random or scripted input, to load test it.
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use ambient_ecs::{Entity, SystemGroup, World};
+use ambient_input::player_raw_input;
+use ambient_network::{
+    client::{bi_stream_handlers, datagram_handlers, uni_stream_handlers, GameClientNetworkStats, HeadlessGameClient},
+    tls::ServerVerification,
+};
+use ambient_shared_types::{MouseButton, VirtualKeyCode};
+use ambient_std::{asset_cache::AssetCache, cb, to_byte_unit};
+use anyhow::Context;
+use glam::{vec2, Vec2};
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Deserialize;
+
+use super::{player, wasm};
+use crate::{cli::BotsCli, shared};
+
+/// The input of the bots: they hold the input of each step for its duration, and start over after the last step
+#[derive(Debug, Clone, Deserialize)]
+pub struct BotScript {
+    pub steps: Vec<BotStep>,
+}
+impl BotScript {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let script: Self =
+            toml::from_str(&std::fs::read_to_string(path).with_context(|| format!("Failed to read the bot script {path:?}"))?)
+                .with_context(|| format!("Failed to parse the bot script {path:?}"))?;
+        // The bots would never get past a step with a negative or infinite duration
+        if let Some(index) = script.steps.iter().position(|step| !step.duration.is_finite() || step.duration < 0.) {
+            anyhow::bail!("Step {} of the bot script {path:?} has an invalid duration: {}", index + 1, script.steps[index].duration);
+        }
+        if !script.steps.iter().any(|step| step.duration > 0.) {
+            anyhow::bail!("The bot script {path:?} has no step with a duration");
+        }
+        Ok(script)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BotStep {
+    /// How long the step lasts, in seconds
+    pub duration: f32,
+    pub keys: HashSet<VirtualKeyCode>,
+    pub mouse_buttons: HashSet<MouseButton>,
+    /// How fast the mouse moves, in pixels per second
+    pub mouse_velocity: Vec2,
+}
+impl BotStep {
+    fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        let keys = [
+            (VirtualKeyCode::W, 0.4),
+            (VirtualKeyCode::A, 0.2),
+            (VirtualKeyCode::S, 0.2),
+            (VirtualKeyCode::D, 0.2),
+            (VirtualKeyCode::Space, 0.1),
+        ]
+        .into_iter()
+        .filter(|(_, probability)| rng.gen_bool(*probability))
+        .map(|(key, _)| key)
+        .collect();
+        let mouse_buttons = if rng.gen_bool(0.1) { HashSet::from([MouseButton::Left]) } else { HashSet::new() };
+        Self {
+            duration: rng.gen_range(0.5..2.),
+            keys,
+            mouse_buttons,
+            mouse_velocity: vec2(rng.gen_range(-200. ..200.), rng.gen_range(-50. ..50.)),
+        }
+    }
+}
+
+/// Drives the input of a bot, from a script or randomly
+struct BotInput {
+    script: Option<Arc<BotScript>>,
+    index: usize,
+    step: BotStep,
+    remaining: f32,
+}
+impl BotInput {
+    fn new(script: Option<Arc<BotScript>>) -> Self {
+        let index = script.as_ref().map(|script| script.steps.len() - 1).unwrap_or_default();
+        Self { script, index, step: BotStep::default(), remaining: 0. }
+    }
+    fn update(&mut self, world: &mut World, dtime: f32) {
+        self.remaining -= dtime;
+        while self.remaining <= 0. {
+            self.step = match &self.script {
+                Some(script) => {
+                    self.index = (self.index + 1) % script.steps.len();
+                    script.steps[self.index].clone()
+                }
+                None => BotStep::random(),
+            };
+            self.remaining += self.step.duration;
+        }
+
+        let input = world.resource_mut(player_raw_input());
+        input.keys = self.step.keys.clone();
+        input.mouse_buttons = self.step.mouse_buttons.clone();
+        input.mouse_delta += self.step.mouse_velocity * dtime;
+    }
+}
+
+/// The latest network stats of the connected bots
+#[derive(Default)]
+struct BotStats {
+    network: HashMap<usize, GameClientNetworkStats>,
+    disconnected: usize,
+}
+impl BotStats {
+    fn summary(&self, count: usize) -> String {
+        let connected = self.network.len();
+        if connected == 0 {
+            return format!("0/{count} bots connected, {} disconnected", self.disconnected);
+        }
+        let mut latencies = self.network.values().map(|stats| stats.latency_ms).collect::<Vec<_>>();
+        latencies.sort();
+        let sent = self.network.values().map(|stats| stats.bytes_sent).sum::<u64>();
+        let received = self.network.values().map(|stats| stats.bytes_received).sum::<u64>();
+        format!(
+            "{connected}/{count} bots connected, {} disconnected; rtt min {} ms, avg {} ms, p95 {} ms, max {} ms; {}/s out, {}/s in ({}/s out, {}/s in per bot)",
+            self.disconnected,
+            latencies[0],
+            latencies.iter().sum::<u64>() / connected as u64,
+            latencies[(connected * 95 / 100).min(connected - 1)],
+            latencies[connected - 1],
+            to_byte_unit(sent),
+            to_byte_unit(received),
+            to_byte_unit(sent / connected as u64),
+            to_byte_unit(received / connected as u64),
+        )
+    }
+}
+
+fn systems_and_resources() -> (SystemGroup, Entity) {
+    let resources = Entity::new()
+        .with(bi_stream_handlers(), HashMap::new())
+        .with(uni_stream_handlers(), HashMap::new())
+        .with(datagram_handlers(), HashMap::new());
+    (SystemGroup::new("bot", vec![Box::new(wasm::systems()), Box::new(player::systems_final())]), resources)
+}
+
+/// Joins the server with the bots, and reports their stats until the duration runs out or Ctrl+C is pressed
+pub async fn run(
+    assets: AssetCache,
+    server_addr: SocketAddr,
+    server_name: String,
+    server_verification: ServerVerification,
+    args: &BotsCli,
+) -> anyhow::Result<()> {
+    let script = args.script.as_deref().map(BotScript::load).transpose()?.map(Arc::new);
+    let auth_token = args.auth_token.clone().or_else(|| std::env::var("AMBIENT_AUTH_TOKEN").ok());
+    let frame_time = Duration::from_secs_f32(1. / 60.);
+    let stats = Arc::new(Mutex::new(BotStats::default()));
+
+    log::info!("Joining {server_addr} with {} bots", args.count);
+    for index in 0..args.count {
+        let client = HeadlessGameClient {
+            server_addr,
+            server_name: server_name.clone(),
+            server_verification: server_verification.clone(),
+            user_id: format!("{}_{index}", args.user_id_prefix),
+            auth_token: auth_token.clone(),
+            frame_time,
+            systems_and_resources: cb(systems_and_resources),
+            create_rpc_registry: cb(shared::create_server_rpc_registry),
+        };
+        let join_delay = Duration::from_secs_f32(args.join_interval).saturating_mul(index as u32);
+        let mut input = BotInput::new(script.clone());
+        let assets = assets.clone();
+        let stats = stats.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(join_delay).await;
+            let result = client
+                .run(
+                    assets,
+                    |world| {
+                        if let Err(err) = wasm::initialize_headless(world) {
+                            log::error!("Failed to initialize the modules of bot {index}: {err:?}");
+                        }
+                    },
+                    move |world| input.update(world, frame_time.as_secs_f32()),
+                    {
+                        let stats = stats.clone();
+                        move |network| {
+                            stats.lock().network.insert(index, network);
+                        }
+                    },
+                )
+                .await;
+
+            let mut stats = stats.lock();
+            stats.network.remove(&index);
+            stats.disconnected += 1;
+            match result {
+                Ok(()) => log::info!("Bot {index} disconnected"),
+                Err(err) => log::warn!("Bot {index} disconnected: {err:?}"),
+            }
+        });
+    }
+
+    let end = async {
+        match args.duration {
+            Some(seconds) => tokio::time::sleep(Duration::from_secs_f32(seconds)).await,
+            None => {
+                if let Err(err) = tokio::signal::ctrl_c().await {
+                    log::error!("Unable to listen for shutdown signal: {}", err);
+                    std::future::pending::<()>().await;
+                }
+            }
+        }
+    };
+    tokio::pin!(end);
+
+    let mut report_interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        tokio::select! {
+            _ = report_interval.tick() => log::info!("{}", stats.lock().summary(args.count)),
+            _ = &mut end => break,
+        }
+    }
+    log::info!("Done: {}", stats.lock().summary(args.count));
+    Ok(())
+}
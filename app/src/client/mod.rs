@@ -14,14 +14,17 @@ use ambient_core::{
 use ambient_debugger::Debugger;
 use ambient_ecs::{Entity, EntityId, SystemGroup};
 use ambient_element::{element_component, Element, ElementComponentExt, Hooks};
-use ambient_network::client::{
-    GameClient, GameClientNetworkStats, GameClientRenderTarget, GameClientServerStats,
-    GameClientView, GameClientWorld, UseOnce,
+use ambient_network::{
+    client::{
+        GameClient, GameClientNetworkStats, GameClientRenderTarget, GameClientServerStats,
+        GameClientView, GameClientWorld, UseOnce,
+    },
+    tls::ServerVerification,
 };
 use ambient_std::{asset_cache::AssetCache, cb, friendly_id};
 use ambient_ui_native::{
-    Button, Dock, FlowColumn, FocusRoot, MeasureSize, ScrollArea, ScrollAreaSizing, StylesExt,
-    Text, UIExt, WindowSized, STREET,
+    Button, Dock, FlowColumn, FocusRootWithoutNavigation, MeasureSize, ScrollArea,
+    ScrollAreaSizing, StylesExt, Text, UIExt, WindowSized, STREET,
 };
 use glam::{uvec2, vec4, Vec2};
 
@@ -29,6 +32,7 @@ use crate::{cli::RunCli, shared};
 use ambient_ecs_editor::{ECSEditor, InspectableAsyncWorld};
 use ambient_layout::{docking, padding, Borders};
 
+pub mod bots;
 pub mod player;
 mod wasm;
 
@@ -36,6 +40,8 @@ mod wasm;
 pub async fn run(
     assets: AssetCache,
     server_addr: SocketAddr,
+    server_name: String,
+    server_verification: ServerVerification,
     run: &RunCli,
     golden_image_output_dir: Option<PathBuf>,
 ) {
@@ -43,6 +49,10 @@ pub async fn run(
         .user_id
         .clone()
         .unwrap_or_else(|| format!("user_{}", friendly_id()));
+    let auth_token = run
+        .auth_token
+        .clone()
+        .or_else(|| std::env::var("AMBIENT_AUTH_TOKEN").ok());
     let headless = if run.headless {
         Some(uvec2(600, 600))
     } else {
@@ -60,7 +70,10 @@ pub async fn run(
             *app.world.resource_mut(window_title()) = "Ambient".to_string();
             MainApp {
                 server_addr,
+                server_name,
+                server_verification,
                 user_id,
+                auth_token,
                 show_debug: is_debug,
                 golden_image_test: run.golden_image_test,
                 golden_image_output_dir,
@@ -101,8 +114,11 @@ fn TitleUpdater(hooks: &mut Hooks) -> Element {
 fn MainApp(
     hooks: &mut Hooks,
     server_addr: SocketAddr,
+    server_name: String,
+    server_verification: ServerVerification,
     golden_image_output_dir: Option<PathBuf>,
     user_id: String,
+    auth_token: Option<String>,
     show_debug: bool,
     golden_image_test: Option<f32>,
 ) -> Element {
@@ -110,13 +126,17 @@ fn MainApp(
     let update_server_stats = hooks.provide_context(GameClientServerStats::default);
     let (loaded, set_loaded) = hooks.use_state(false);
 
-    FocusRoot::el([
+    // The keyboard and gamepads belong to the game, so they don't move the focus of the debug UI
+    FocusRootWithoutNavigation::el([
         UICamera.el(),
         player::PlayerRawInputHandler.el(),
         TitleUpdater.el(),
         WindowSized::el([GameClientView {
             server_addr,
+            server_name,
+            server_verification,
             user_id,
+            auth_token,
             on_disconnect: cb(move || {}),
             init_world: cb(UseOnce::new(Box::new(move |world, _render_target| {
                 wasm::initialize(world).unwrap();
@@ -337,11 +357,13 @@ fn GameView(hooks: &mut Hooks, show_debug: bool) -> Element {
                 get_state: cb(move |cb| {
                     let mut game_state = state.game_state.lock();
                     let game_state = &mut *game_state;
-                    cb(
-                        &mut game_state.renderer,
-                        &render_target.0,
-                        &mut game_state.world,
-                    );
+                    if let Some(rendering) = &mut game_state.rendering {
+                        cb(
+                            &mut rendering.renderer,
+                            &render_target.0,
+                            &mut game_state.world,
+                        );
+                    }
                 }),
             }
             .el()
@@ -1,4 +1,5 @@
 use ambient_audio::Source;
+use ambient_core::runtime;
 use ambient_ecs::{EntityId, SystemGroup, World};
 use ambient_wasm::shared::{get_module_name, MessageType};
 use ambient_world_audio::{audio_sender, AudioMessage, SoundInfo};
@@ -10,20 +11,20 @@ pub fn systems() -> SystemGroup {
     ambient_wasm::client::systems()
 }
 
-pub fn initialize(world: &mut World) -> anyhow::Result<()> {
-    let messenger = Arc::new(|world: &World, id: EntityId, type_: MessageType, message: &str| {
-        let name = get_module_name(world, id);
-        let (prefix, level) = match type_ {
-            MessageType::Info => ("info", log::Level::Info),
-            MessageType::Warn => ("warn", log::Level::Warn),
-            MessageType::Error => ("error", log::Level::Error),
-            MessageType::Stdout => ("stdout", log::Level::Info),
-            MessageType::Stderr => ("stderr", log::Level::Info),
-        };
+fn log_message(world: &World, id: EntityId, type_: MessageType, message: &str) {
+    let name = get_module_name(world, id);
+    let (prefix, level) = match type_ {
+        MessageType::Info => ("info", log::Level::Info),
+        MessageType::Warn => ("warn", log::Level::Warn),
+        MessageType::Error => ("error", log::Level::Error),
+        MessageType::Stdout => ("stdout", log::Level::Info),
+        MessageType::Stderr => ("stderr", log::Level::Info),
+    };
 
-        log::log!(level, "[{name}] {prefix}: {}", message.strip_suffix('\n').unwrap_or(message));
-    });
+    log::log!(level, "[{name}] {prefix}: {}", message.strip_suffix('\n').unwrap_or(message));
+}
 
+pub fn initialize(world: &mut World) -> anyhow::Result<()> {
     let (tx, rx): (Sender<AudioMessage>, Receiver<AudioMessage>) = flume::unbounded();
 
     std::thread::spawn(move || {
@@ -85,7 +86,18 @@ pub fn initialize(world: &mut World) -> anyhow::Result<()> {
     });
     world.add_resource(audio_sender(), Arc::new(tx));
 
-    ambient_wasm::client::initialize(world, messenger)?;
+    ambient_wasm::client::initialize(world, Arc::new(log_message))?;
+
+    Ok(())
+}
+
+/// Initializes the modules of a client without audio, like the headless bots
+pub fn initialize_headless(world: &mut World) -> anyhow::Result<()> {
+    let (tx, rx) = flume::unbounded::<AudioMessage>();
+    world.resource(runtime()).spawn(async move { while rx.recv_async().await.is_ok() {} });
+    world.add_resource(audio_sender(), Arc::new(tx));
+
+    ambient_wasm::client::initialize(world, Arc::new(log_message))?;
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 use ambient_std::{
     asset_cache::{AssetCache, SyncAssetKeyExt},
     download_asset::AssetsCacheOnDisk, asset_url::{AbsAssetUrl, ContentBaseUrlKey},
+    asset_archive::{self, ARCHIVE_EXTENSION},
 };
 use clap::Parser;
 
@@ -9,11 +10,13 @@ mod client;
 mod server;
 mod shared;
 
+use ambient_network::tls::{CertFingerprint, ServerVerification};
 use ambient_physics::physx::PhysicsKey;
 use anyhow::Context;
 use cli::Cli;
 use log::LevelFilter;
 use server::QUIC_INTERFACE_PORT;
+use std::net::{IpAddr, SocketAddr};
 
 fn setup_logging() -> anyhow::Result<()> {
     const MODULES: &[(LevelFilter, &[&str])] = &[
@@ -133,6 +136,15 @@ impl TryFrom<Option<String>> for ProjectPath {
 
     fn try_from(project_path: Option<String>) -> anyhow::Result<Self> {
         match project_path {
+            // An archive holds a built project; see `ambient build --archive`
+            Some(project_path) if project_path.ends_with(&format!(".{ARCHIVE_EXTENSION}")) => {
+                let archive = if project_path.starts_with("http://") || project_path.starts_with("https://") {
+                    AbsAssetUrl::parse(project_path)?
+                } else {
+                    AbsAssetUrl::from_file_path(project_path)
+                };
+                Ok(Self { url: AbsAssetUrl::from_archive(&archive)?, fs_path: None })
+            }
             Some(project_path) if project_path.starts_with("http://") || project_path.starts_with("https://") => {
                 let url = AbsAssetUrl::parse(project_path)?;
                 Ok(Self { url, fs_path: None })
@@ -158,6 +170,40 @@ impl TryFrom<Option<String>> for ProjectPath {
     }
 }
 
+/// The address of the server at `host` (`<name>[:<port>]`, `<ip>`, or `[<ipv6>]:<port>`; localhost by default), and the
+/// name its certificate is verified against
+fn resolve_host(runtime: &tokio::runtime::Runtime, host: Option<String>) -> anyhow::Result<(SocketAddr, String)> {
+    match host {
+        Some(host) => {
+            if let Ok(addr) = host.parse::<SocketAddr>() {
+                return Ok((addr, addr.ip().to_string()));
+            }
+            if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+                return Ok((SocketAddr::new(ip, QUIC_INTERFACE_PORT), ip.to_string()));
+            }
+            // Not an IP address, so the name can't contain a colon
+            let (name, port) = match host.rsplit_once(':') {
+                Some((name, port)) => (name, port.parse::<u16>().with_context(|| format!("Invalid port in host {host:?}"))?),
+                None => (host.as_str(), QUIC_INTERFACE_PORT),
+            };
+            let addr = runtime
+                .block_on(tokio::net::lookup_host((name, port)))?
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No address found for host {host}"))?;
+            Ok((addr, name.to_string()))
+        }
+        None => Ok((format!("127.0.0.1:{QUIC_INTERFACE_PORT}").parse()?, "localhost".to_string())),
+    }
+}
+
+fn server_verification(pin: Option<CertFingerprint>, insecure: bool) -> ServerVerification {
+    match (pin, insecure) {
+        (Some(fingerprint), _) => ServerVerification::Pinned(fingerprint),
+        (None, true) => ServerVerification::Insecure,
+        (None, false) => ServerVerification::SystemRoots,
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     setup_logging()?;
 
@@ -225,31 +271,65 @@ fn main() -> anyhow::Result<()> {
         None
     };
 
-    // If this is just a build, exit now
-    if matches!(&cli, Cli::Build { .. }) {
+    // If this is just a build, package it if asked to and exit now
+    if let Cli::Build { archive, .. } = &cli {
+        if let Some(archive) = archive {
+            let project_fs_path = project_path
+                .fs_path
+                .as_ref()
+                .context("Only local projects can be packaged into an archive, as it is built from their files")?;
+            let index = asset_archive::pack_directory(&project_fs_path.join("build"), "build/", archive)?;
+            log::info!(
+                "Packaged {} files ({}) into {archive:?} ({})",
+                index.files.len(),
+                ambient_std::to_byte_unit(index.files_size()),
+                ambient_std::to_byte_unit(index.chunks_size())
+            );
+        }
         return Ok(());
     }
 
+    // If this is a replay, run it headless and exit
+    if let Cli::Replay { recording, .. } = &cli {
+        return server::replay(
+            &runtime,
+            assets,
+            project_path.url,
+            manifest.as_ref().expect("no manifest"),
+            metadata.as_ref().expect("no build metadata"),
+            recording,
+        );
+    }
+
+    // If these are bots, join the server with them and exit
+    if let Cli::Bots { host, bots_args } = &cli {
+        let (server_addr, server_name) = resolve_host(&runtime, host.clone())?;
+        let verification = server_verification(bots_args.pin, bots_args.insecure);
+        return runtime.block_on(client::bots::run(assets, server_addr, server_name, verification, bots_args));
+    }
+
     // Otherwise, either connect to a server or host one
-    let server_addr = if let Cli::Join { host, .. } = &cli {
-        if let Some(mut host) = host.clone() {
-            if !host.contains(':') {
-                host = format!("{host}:{QUIC_INTERFACE_PORT}");
-            }
-            runtime.block_on(tokio::net::lookup_host(&host))?.next().ok_or_else(|| anyhow::anyhow!("No address found for host {host}"))?
-        } else {
-            format!("127.0.0.1:{QUIC_INTERFACE_PORT}").parse()?
-        }
+    let (server_addr, server_name, server_verification) = if let Cli::Join { host, run_args, .. } = &cli {
+        let (addr, server_name) = resolve_host(&runtime, host.clone())?;
+        (addr, server_name, server_verification(run_args.pin, run_args.insecure))
     } else {
-        let port = server::start(&runtime, assets.clone(), cli.clone(), project_path.url, manifest.as_ref().expect("no manifest"), metadata.as_ref().expect("no build metadata"));
-        format!("127.0.0.1:{port}").parse()?
+        let (port, fingerprint) = server::start(
+            &runtime,
+            assets.clone(),
+            cli.clone(),
+            project_path.url,
+            manifest.as_ref().expect("no manifest"),
+            metadata.as_ref().expect("no build metadata"),
+        );
+        // We know which certificate our own server has
+        (format!("127.0.0.1:{port}").parse()?, "localhost".to_string(), ServerVerification::Pinned(fingerprint))
     };
 
     // Time to join!
     let handle = runtime.handle().clone();
     if let Some(run) = cli.run() {
         // If we have run parameters, start a client and join a server
-        runtime.block_on(client::run(assets, server_addr, run, project_path.fs_path));
+        runtime.block_on(client::run(assets, server_addr, server_name, server_verification, run, project_path.fs_path));
     } else {
         // Otherwise, wait for the Ctrl+C signal
         handle.block_on(async move {
@@ -1,31 +1,43 @@
 use std::{
     collections::HashMap,
     net::SocketAddr,
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use ambient_core::{app_start_time, asset_cache, dtime, name, no_sync, project_name, time};
 use ambient_ecs::{
-    dont_store, world_events, ComponentDesc, ComponentRegistry, Entity, Networked, SystemGroup, World, WorldEventsSystem,
-    WorldStreamCompEvent,
+    dont_store, world_events, ComponentDesc, ComponentRegistry, Entity, Networked, SystemGroup, World, WorldEventsSystem, WorldStream,
+    WorldStreamCompEvent, WorldStreamFilter,
 };
 use ambient_network::{
+    admin::{verify_token, AdminConsole, BanList},
+    auth::{FileAuthenticator, JwtAuthenticator},
+    limits::PlayerLimits,
+    metrics::MetricsEndpoint,
     persistent_resources,
-    server::{ForkingEvent, GameServer, ProxySettings, ShutdownEvent},
+    recording::{Recorder, Replay},
+    server::{ForkingEvent, GameServer, ProxySettings, ShutdownEvent, WorldInstance},
     synced_resources,
+    tls::{CertFingerprint, ServerCertificate},
+};
+use ambient_physics::{
+    layers::{collision_layers, CollisionLayers},
+    DeterministicPhysicsKey,
 };
 use ambient_prefab::PrefabFromUrl;
 use ambient_std::{
     asset_cache::{AssetCache, AsyncAssetKeyExt, SyncAssetKeyExt},
-    asset_url::{AbsAssetUrl, ServerBaseUrlKey},
+    asset_url::{AbsAssetUrl, ContentBaseUrlKey, ServerBaseUrlKey},
 };
 use ambient_sys::{task::RuntimeHandle, time::SystemTime};
 use anyhow::Context;
 use axum::{
-    http::{Method, StatusCode},
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Method, StatusCode},
     response::IntoResponse,
-    routing::{get, get_service},
+    routing::{get, get_service, post},
     Router,
 };
 use tower_http::{cors::CorsLayer, services::ServeDir};
@@ -41,7 +53,7 @@ pub fn start(
     project_path: AbsAssetUrl,
     manifest: &ambient_project::Manifest,
     metadata: &ambient_build::Metadata,
-) -> u16 {
+) -> (u16, CertFingerprint) {
     log::info!("Creating server");
     let host_cli = cli.host().unwrap();
     let quic_interface_port = host_cli.quic_interface_port;
@@ -54,16 +66,85 @@ pub fn start(
             project_id: manifest.project.id.to_string(),
         }
     });
-    let server = runtime.block_on(async move {
+    let record = host_cli.record.clone();
+    let deterministic = host_cli.deterministic || record.is_some();
+    DeterministicPhysicsKey.insert(&assets, deterministic);
+    let self_signed = host_cli.tls_cert.is_none();
+    let certificate = match (&host_cli.tls_cert, &host_cli.tls_key) {
+        (Some(cert), Some(key)) => ServerCertificate::from_files(cert, key).context("failed to load the TLS certificate").unwrap(),
+        _ => {
+            let names = ["localhost".to_string()].into_iter().chain(host_cli.public_host.clone()).collect();
+            ServerCertificate::self_signed(names).unwrap()
+        }
+    };
+    let fingerprint = certificate.fingerprint();
+    let mut server = runtime.block_on(async move {
         if let Some(port) = quic_interface_port {
-            GameServer::new_with_port(port, false, proxy_settings).await.context("failed to create game server with port").unwrap()
+            GameServer::new_with_port(port, &certificate, false, proxy_settings)
+                .await
+                .context("failed to create game server with port")
+                .unwrap()
         } else {
-            GameServer::new_with_port_in_range(QUIC_INTERFACE_PORT..(QUIC_INTERFACE_PORT + 10), false, proxy_settings)
+            GameServer::new_with_port_in_range(QUIC_INTERFACE_PORT..(QUIC_INTERFACE_PORT + 10), &certificate, false, proxy_settings)
                 .await
                 .context("failed to create game server with port in range")
                 .unwrap()
         }
     });
+    server.fixed_time_step = deterministic;
+    if let Some(seconds) = host_cli.resume_grace_period {
+        server.resume_grace_period = Duration::from_secs(seconds);
+    }
+    server.player_limits = PlayerLimits {
+        max_players: host_cli.max_players,
+        max_instance_players: host_cli.max_instance_players,
+        reserved_slots: host_cli.reserved_slots,
+        max_queue_length: host_cli.join_queue,
+        admins: host_cli.admins.iter().cloned().collect(),
+    };
+    if let Some(secret) = host_cli.auth_secret.clone().or_else(|| std::env::var("AMBIENT_AUTH_SECRET").ok()) {
+        log::info!("Authenticating players with JSON Web Tokens");
+        server.authenticator = Arc::new(JwtAuthenticator::new(secret.as_bytes()));
+    } else if let Some(path) = &host_cli.auth_users {
+        log::info!("Authenticating players with the tokens in {path:?}");
+        server.authenticator = Arc::new(FileAuthenticator::new(path));
+    } else {
+        log::info!("Players are not authenticated; anyone can join as any user id. Use `--auth-secret` or `--auth-users` to require a token");
+    }
+    let ban_list = host_cli.ban_list.clone().or_else(|| project_path.to_file_path().ok().flatten().map(|path| path.join("bans.toml")));
+    let admin = match ban_list {
+        Some(path) => AdminConsole::new(BanList::load(path).context("failed to load the ban list").unwrap()),
+        None => AdminConsole::default(),
+    };
+    server.admin = admin.clone();
+    if host_cli.admin_console {
+        let admin = admin.clone();
+        runtime.spawn(async move {
+            if let Err(err) = admin.serve_stdin().await {
+                log::error!("Admin console failed: {err}");
+            }
+        });
+    }
+    if let Some(path) = host_cli.admin_socket.clone() {
+        #[cfg(unix)]
+        {
+            let admin = admin.clone();
+            runtime.spawn(async move {
+                if let Err(err) = admin.serve_unix_socket(&path).await {
+                    log::error!("Admin socket {path:?} failed: {err}");
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        log::warn!("Ignoring the admin socket {path:?}; Unix sockets are not supported on this platform");
+    }
+    // The token is not taken on the command line, where other users of the machine could see it
+    let admin_token = match &host_cli.admin_token_file {
+        Some(path) => Some(std::fs::read_to_string(path).context("failed to read the admin token file").unwrap().trim().to_string()),
+        None => std::env::var("AMBIENT_ADMIN_TOKEN").ok(),
+    }
+    .filter(|token| !token.is_empty());
+    let metrics = server.metrics.clone();
     let port = server.port;
 
     let public_host = cli
@@ -72,43 +153,45 @@ pub fn start(
         .or_else(|| local_ip_address::local_ip().ok().map(|x| x.to_string()))
         .unwrap_or("localhost".to_string());
     log::info!("Created server, running at {public_host}:{port}");
+    if self_signed {
+        log::info!("The server has a self-signed certificate; join it with `ambient join {public_host}:{port} --pin {fingerprint}`");
+    }
     let http_interface_port = cli.host().unwrap().http_interface_port.unwrap_or(HTTP_INTERFACE_PORT);
 
     // here the key is inserted into the asset cache
     if let Ok(Some(project_path_fs)) = project_path.to_file_path() {
         let key = format!("http://{public_host}:{http_interface_port}/content/");
         ServerBaseUrlKey.insert(&assets, AbsAssetUrl::parse(key).unwrap());
-        start_http_interface(runtime, &project_path_fs, http_interface_port);
+        start_http_interface(runtime, &project_path_fs, http_interface_port, metrics, admin_token.map(|token| (admin, token)));
     } else {
         ServerBaseUrlKey.insert(&assets, project_path.push("build/").unwrap());
     }
+    // Only the files of a local project are sent over the game connection
+    let assets_over_quic = match project_path.to_file_path() {
+        Ok(Some(project_path_fs)) if host_cli.assets_over_quic => {
+            log::info!("Sending the project files over the game connection; only the QUIC port needs to be reachable");
+            // The server itself keeps reading them from where they are
+            if !ContentBaseUrlKey.exists(&assets) {
+                ContentBaseUrlKey.insert(&assets, project_path.push("build/").unwrap());
+            }
+            ServerBaseUrlKey.insert(&assets, AbsAssetUrl::asset_server_root());
+            Some(project_path_fs.join("build"))
+        }
+        _ if host_cli.assets_over_quic => {
+            log::warn!("Ignoring --assets-over-quic; the files of a remote project are downloaded from {}", ServerBaseUrlKey.get(&assets));
+            None
+        }
+        _ => None,
+    };
 
     ComponentRegistry::get_mut().add_external(ambient_project_native::all_defined_components(manifest, false).unwrap());
 
     let manifest = manifest.clone();
     let metadata = metadata.clone();
     runtime.spawn(async move {
-        let mut server_world = World::new_with_config("server", true);
-        server_world.init_shape_change_tracking();
-
-        server_world.add_components(server_world.resource_entity(), create_resources(assets.clone())).unwrap();
-
-        // Keep track of the project name
-        let name = manifest.project.name.clone().unwrap_or_else(|| "Ambient".into());
-        server_world.add_components(server_world.resource_entity(), Entity::new().with(project_name(), name)).unwrap();
-
-        Entity::new()
-            .with(ambient_core::name(), "Synced resources".to_string())
-            .with(synced_resources(), ())
-            .with(dont_store(), ())
-            .spawn(&mut server_world);
-        // Note: this should not be reset every time the server is created. Remove this when it becomes possible to load/save worlds.
-        Entity::new()
-            .with(ambient_core::name(), "Persistent resources".to_string())
-            .with(persistent_resources(), ())
-            .spawn(&mut server_world);
-
-        wasm::initialize(&mut server_world, assets.clone(), project_path.clone(), &manifest, &metadata).await.unwrap();
+        let entity_id_seed = deterministic.then_some(ENTITY_ID_SEED);
+        let mut server_world =
+            create_server_world(assets.clone(), project_path.clone(), &manifest, &metadata, entity_id_seed, assets_over_quic).await;
 
         if let Cli::View { asset_path, .. } = cli.clone() {
             let asset_path = project_path
@@ -119,12 +202,103 @@ pub fn start(
             let obj = PrefabFromUrl(asset_path.into()).get(&assets).await.unwrap();
             obj.spawn_into_world(&mut server_world, None);
         }
+        if let Some(path) = record {
+            match Recorder::new(&path, &server_world, ENTITY_ID_SEED) {
+                Ok(recorder) => {
+                    log::info!("Recording the session to {path:?}");
+                    server.recorder = Some(recorder);
+                }
+                Err(err) => log::error!("Failed to start recording: {err:?}"),
+            }
+        }
         log::info!("Starting server");
         server
             .run(server_world, Arc::new(systems), Arc::new(on_forking_systems), Arc::new(on_shutdown_systems), Arc::new(is_sync_component))
             .await;
     });
-    port
+    (port, fingerprint)
+}
+
+/// Replays a session recorded with `--record` headless; see [`ambient_network::recording`]
+pub fn replay(
+    runtime: &tokio::runtime::Runtime,
+    assets: AssetCache,
+    project_path: AbsAssetUrl,
+    manifest: &ambient_project::Manifest,
+    metadata: &ambient_build::Metadata,
+    recording: &Path,
+) -> anyhow::Result<()> {
+    let replay = Replay::open(recording)?;
+    DeterministicPhysicsKey.insert(&assets, true);
+    ServerBaseUrlKey.insert(&assets, project_path.push("build/")?);
+
+    ComponentRegistry::get_mut().add_external(ambient_project_native::all_defined_components(manifest, false).unwrap());
+
+    runtime.block_on(async move {
+        let mut world = create_server_world(assets, project_path, manifest, metadata, Some(replay.header.entity_id_seed), None).await;
+        world.set(world.resource_entity(), app_start_time(), replay.header.app_start_time)?;
+        let mut instance =
+            WorldInstance { systems: systems(&mut world), world, world_stream: WorldStream::new(WorldStreamFilter::default()) };
+
+        log::info!("Replaying {recording:?}");
+        let ticks = tokio::task::block_in_place(|| replay.run(&mut instance))?;
+        log::info!("Replayed {ticks} ticks without diverging from the recording");
+        Ok(())
+    })
+}
+
+/// The seed of the entity ids of deterministic servers. It's stored in recordings, so it can be changed without breaking them
+const ENTITY_ID_SEED: u64 = 0;
+
+async fn create_server_world(
+    assets: AssetCache,
+    project_path: AbsAssetUrl,
+    manifest: &ambient_project::Manifest,
+    metadata: &ambient_build::Metadata,
+    entity_id_seed: Option<u64>,
+    assets_over_quic: Option<PathBuf>,
+) -> World {
+    let mut server_world = World::new_with_config("server", true);
+    if let Some(seed) = entity_id_seed {
+        server_world.seed_entity_ids(seed);
+    }
+    server_world.init_shape_change_tracking();
+
+    server_world.add_components(server_world.resource_entity(), create_resources(assets.clone(), assets_over_quic)).unwrap();
+
+    // Keep track of the project name
+    let name = manifest.project.name.clone().unwrap_or_else(|| "Ambient".into());
+    server_world.add_components(server_world.resource_entity(), Entity::new().with(project_name(), name)).unwrap();
+
+    // Collision layers are declared in the manifest, and are shared by all colliders and queries
+    let physics = &manifest.physics;
+    let layers = CollisionLayers::new(
+        physics.collision_layers.iter().map(|layer| layer.to_string()).collect(),
+        &physics
+            .collides_with
+            .iter()
+            .map(|(layer, others)| (layer.to_string(), others.iter().map(|other| other.to_string()).collect()))
+            .collect(),
+    )
+    .unwrap_or_else(|err| {
+        log::error!("Invalid collision layers in the manifest: {err:?}");
+        CollisionLayers::default()
+    });
+    server_world.add_resource(collision_layers(), layers);
+
+    Entity::new()
+        .with(ambient_core::name(), "Synced resources".to_string())
+        .with(synced_resources(), ())
+        .with(dont_store(), ())
+        .spawn(&mut server_world);
+    // Note: this should not be reset every time the server is created. Remove this when it becomes possible to load/save worlds.
+    Entity::new()
+        .with(ambient_core::name(), "Persistent resources".to_string())
+        .with(persistent_resources(), ())
+        .spawn(&mut server_world);
+
+    wasm::initialize(&mut server_world, assets, project_path, manifest, metadata).await.unwrap();
+    server_world
 }
 
 fn systems(_world: &mut World) -> SystemGroup {
@@ -158,7 +332,8 @@ fn is_sync_component(component: ComponentDesc, _: WorldStreamCompEvent) -> bool
     component.has_attribute::<Networked>()
 }
 
-fn create_resources(assets: AssetCache) -> Entity {
+/// `assets_over_quic` is the directory of the files to send to the players over the game connection, if they are
+fn create_resources(assets: AssetCache, assets_over_quic: Option<PathBuf>) -> Entity {
     let mut server_resources = Entity::new()
         .with(name(), "Resources".to_string())
         .with(asset_cache(), assets.clone())
@@ -175,6 +350,9 @@ fn create_resources(assets: AssetCache) -> Entity {
 
     let mut bistream_handlers = HashMap::new();
     ambient_network::server::register_rpc_bi_stream_handler(&mut bistream_handlers, shared::create_server_rpc_registry());
+    if let Some(content_root) = assets_over_quic {
+        ambient_network::assets::register_assets_bi_stream_handler(&mut bistream_handlers, content_root);
+    }
     server_resources.set(ambient_network::server::bi_stream_handlers(), bistream_handlers);
 
     let unistream_handlers = HashMap::new();
@@ -188,14 +366,34 @@ fn create_resources(assets: AssetCache) -> Entity {
 
 pub const HTTP_INTERFACE_PORT: u16 = 8999;
 pub const QUIC_INTERFACE_PORT: u16 = 9000;
-fn start_http_interface(runtime: &tokio::runtime::Runtime, project_path: &Path, http_interface_port: u16) {
-    let router = Router::new()
-        .route("/ping", get(|| async move { "ok" }))
+fn start_http_interface(
+    runtime: &tokio::runtime::Runtime,
+    project_path: &Path,
+    http_interface_port: u16,
+    metrics: MetricsEndpoint,
+    admin: Option<(AdminConsole, String)>,
+) {
+    let token = admin.as_ref().map(|(_, token)| token.clone());
+    let mut router = Router::new().route("/ping", get(|| async move { "ok" })).route(
+        "/metrics",
+        get(move |ConnectInfo(peer): ConnectInfo<SocketAddr>, headers: HeaderMap| {
+            metrics_request(metrics.clone(), token.clone(), peer, headers)
+        }),
+    );
+    if let Some((admin, token)) = admin {
+        router = router.route(
+            "/admin",
+            post(move |ConnectInfo(peer): ConnectInfo<SocketAddr>, headers: HeaderMap, command: String| {
+                admin_request(admin.clone(), token.clone(), peer, headers, command)
+            }),
+        );
+    }
+    let router = router
         .nest_service("/content", get_service(ServeDir::new(project_path.join("build"))).handle_error(handle_error))
         .layer(CorsLayer::new().allow_origin(tower_http::cors::Any).allow_methods(vec![Method::GET]).allow_headers(tower_http::cors::Any));
 
     let serve = |addr| async move {
-        axum::Server::try_bind(&addr)?.serve(router.into_make_service()).await?;
+        axum::Server::try_bind(&addr)?.serve(router.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
         Ok::<_, anyhow::Error>(())
     };
@@ -209,6 +407,48 @@ fn start_http_interface(runtime: &tokio::runtime::Runtime, project_path: &Path,
     });
 }
 
+/// Renders the metrics of the server in the Prometheus text format.
+///
+/// The metrics name the players, so they are only served to this machine, and only with the admin token if there is one
+async fn metrics_request(metrics: MetricsEndpoint, token: Option<String>, peer: SocketAddr, headers: HeaderMap) -> impl IntoResponse {
+    if let Err((status, message)) = check_local_request(token.as_deref(), peer, &headers) {
+        return (status, [(header::CONTENT_TYPE, "text/plain")], message);
+    }
+    match metrics.render() {
+        Some(metrics) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics),
+        None => (StatusCode::SERVICE_UNAVAILABLE, [(header::CONTENT_TYPE, "text/plain")], "The server is not running yet".to_string()),
+    }
+}
+
+/// Runs the admin command in the body of the request, if the request has the admin token.
+///
+/// The HTTP interface is plain HTTP, so only requests from this machine are accepted, to keep the token from being sent in
+/// clear over the network; use a TLS terminating proxy on the same machine to administer the server remotely
+async fn admin_request(admin: AdminConsole, token: String, peer: SocketAddr, headers: HeaderMap, command: String) -> (StatusCode, String) {
+    if let Err(err) = check_local_request(Some(&token), peer, &headers) {
+        return err;
+    }
+    match tokio::task::block_in_place(|| admin.execute(&command)) {
+        Ok(output) => (StatusCode::OK, output),
+        Err(err) => (StatusCode::BAD_REQUEST, format!("{err:#}")),
+    }
+}
+
+/// Only accepts requests from this machine, which have `token` as their bearer token if there is one
+fn check_local_request(token: Option<&str>, peer: SocketAddr, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    if !peer.ip().is_loopback() {
+        return Err((StatusCode::FORBIDDEN, "Only requests from the server's machine are accepted".to_string()));
+    }
+    if let Some(token) = token {
+        let given =
+            headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer "));
+        if !given.map(|given| verify_token(token, given)).unwrap_or(false) {
+            return Err((StatusCode::UNAUTHORIZED, "Missing or wrong admin token".to_string()));
+        }
+    }
+    Ok(())
+}
+
 async fn handle_error(_err: std::io::Error) -> impl IntoResponse {
     (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong...")
 }
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use ambient_core::{
+    hierarchy::parent,
+    transform::{
+        get_world_position, local_to_parent, local_to_world, rotation, scale, translation,
+    },
+};
+use ambient_ecs::{query, DynSystem, EntityId, SystemGroup, World};
+use ambient_model::animation_binder;
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{ik_fabrik, ik_look_at, ik_two_bone};
+
+const EPSILON: f32 = 0.0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IkTarget {
+    /// A position in world space
+    Position(Vec3),
+    /// The world position of an entity
+    Entity(EntityId),
+}
+impl IkTarget {
+    fn position(&self, world: &World) -> Option<Vec3> {
+        match self {
+            IkTarget::Position(position) => Some(*position),
+            IkTarget::Entity(id) => get_world_position(world, *id).ok(),
+        }
+    }
+}
+
+/// Rotates the `root` and `mid` bones so that the `end` bone reaches the target, for instance
+/// a leg (upper leg, lower leg and foot) or an arm.
+///
+/// The bones are bind ids, resolved through the `animation_binder` of the entity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TwoBoneIk {
+    pub root: String,
+    pub mid: String,
+    pub end: String,
+    pub target: IkTarget,
+    /// The mid bone (knee or elbow) bends towards the pole. If not set, it keeps bending in the
+    /// same direction as the animated pose.
+    pub pole: Option<IkTarget>,
+    pub weight: f32,
+}
+
+/// Rotates `bone` so that its `forward` axis points at the target, for instance a head
+/// tracking something.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LookAtIk {
+    pub bone: String,
+    pub target: IkTarget,
+    /// The axis of the bone, in the bone's local space, which should point at the target
+    pub forward: Vec3,
+    /// The maximum angle (in radians) the bone is rotated away from the animated pose
+    pub max_angle: f32,
+    pub weight: f32,
+}
+
+/// Solves a chain of bones with FABRIK so that the last bone reaches the target. Each bone
+/// must be the parent of the next one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FabrikIk {
+    pub bones: Vec<String>,
+    pub target: IkTarget,
+    pub iterations: usize,
+    /// The solver stops once the end of the chain is closer than this to the target
+    pub tolerance: f32,
+    pub weight: f32,
+}
+
+/// Returns the world space rotations to apply to the root and mid bones so that the end
+/// reaches the target. The rotation of the mid bone includes the one of the root bone.
+pub fn solve_two_bone(
+    root: Vec3,
+    mid: Vec3,
+    end: Vec3,
+    target: Vec3,
+    pole: Option<Vec3>,
+) -> (Quat, Quat) {
+    let upper = (mid - root).length();
+    let lower = (end - mid).length();
+    let to_target = target - root;
+    let dir = to_target.normalize_or_zero();
+    if upper < EPSILON || lower < EPSILON || dir == Vec3::ZERO {
+        return (Quat::IDENTITY, Quat::IDENTITY);
+    }
+    let min_dist = (upper - lower).abs() + EPSILON;
+    let max_dist = (upper + lower - EPSILON).max(min_dist);
+    let dist = to_target.length().clamp(min_dist, max_dist);
+
+    let perpendicular = |v: Vec3| (v - dir * v.dot(dir)).normalize_or_zero();
+    let mut bend = pole
+        .map(|pole| perpendicular(pole - root))
+        .unwrap_or(Vec3::ZERO);
+    if bend == Vec3::ZERO {
+        bend = perpendicular(mid - root);
+    }
+    if bend == Vec3::ZERO {
+        bend = dir.any_orthonormal_vector();
+    }
+
+    let cos_root =
+        ((upper * upper + dist * dist - lower * lower) / (2. * upper * dist)).clamp(-1., 1.);
+    let sin_root = (1. - cos_root * cos_root).sqrt();
+    let new_mid = root + dir * upper * cos_root + bend * upper * sin_root;
+    let new_end = root + dir * dist;
+
+    let root_delta =
+        Quat::from_rotation_arc((mid - root).normalize(), (new_mid - root).normalize());
+    let moved_end = root + root_delta * (end - root);
+    let mid_delta = Quat::from_rotation_arc(
+        (moved_end - new_mid).normalize(),
+        (new_end - new_mid).normalize(),
+    );
+    (root_delta, mid_delta * root_delta)
+}
+
+/// Returns the world space rotation to apply to a bone at `position` with the world `rotation`,
+/// so that its `forward` axis points at the target, limited to `max_angle`
+pub fn solve_look_at(
+    position: Vec3,
+    rotation: Quat,
+    forward: Vec3,
+    target: Vec3,
+    max_angle: f32,
+) -> Quat {
+    let current = (rotation * forward).normalize_or_zero();
+    let desired = (target - position).normalize_or_zero();
+    if current == Vec3::ZERO || desired == Vec3::ZERO {
+        return Quat::IDENTITY;
+    }
+    let delta = Quat::from_rotation_arc(current, desired);
+    let angle = current.angle_between(desired);
+    if angle > max_angle {
+        Quat::IDENTITY.slerp(delta, max_angle.max(0.) / angle)
+    } else {
+        delta
+    }
+}
+
+/// Returns the new positions of the joints of the chain, with the first one staying in place
+pub fn solve_fabrik(
+    positions: &[Vec3],
+    target: Vec3,
+    iterations: usize,
+    tolerance: f32,
+) -> Vec<Vec3> {
+    let mut positions = positions.to_vec();
+    if positions.len() < 2 {
+        return positions;
+    }
+    let lengths = positions
+        .windows(2)
+        .map(|p| (p[1] - p[0]).length())
+        .collect::<Vec<_>>();
+    let root = positions[0];
+    let last = positions.len() - 1;
+
+    if (target - root).length() >= lengths.iter().sum::<f32>() {
+        // Out of reach; stretch the chain towards the target
+        let dir = (target - root).normalize_or_zero();
+        for i in 0..last {
+            positions[i + 1] = positions[i] + dir * lengths[i];
+        }
+        return positions;
+    }
+    for _ in 0..iterations {
+        if (positions[last] - target).length() <= tolerance {
+            break;
+        }
+        // Forward reaching
+        positions[last] = target;
+        for i in (0..last).rev() {
+            let dir = (positions[i] - positions[i + 1]).normalize_or_zero();
+            positions[i] = positions[i + 1] + dir * lengths[i];
+        }
+        // Backward reaching
+        positions[0] = root;
+        for i in 0..last {
+            let dir = (positions[i + 1] - positions[i]).normalize_or_zero();
+            positions[i + 1] = positions[i] + dir * lengths[i];
+        }
+    }
+    positions
+}
+
+/// The pose of a bone before the constraints are applied
+#[derive(Debug, Clone, Copy)]
+struct BonePose {
+    id: EntityId,
+    position: Vec3,
+    rotation: Quat,
+    parent_rotation: Quat,
+    local_rotation: Quat,
+}
+impl BonePose {
+    fn new(world: &World, id: EntityId) -> Self {
+        let parent_transform = world
+            .get(id, parent())
+            .ok()
+            .filter(|parent| world.exists(*parent))
+            .map(|parent| world_transform(world, parent))
+            .unwrap_or_default();
+        let transform = parent_transform * local_transform(world, id);
+        let (_, parent_rotation, _) = parent_transform.to_scale_rotation_translation();
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        Self {
+            id,
+            position,
+            rotation,
+            parent_rotation,
+            local_rotation: world.get(id, self::rotation()).unwrap_or_default(),
+        }
+    }
+    /// Applies the world space rotation `delta`, scaled by `weight`, to the bone, knowing that
+    /// its parent has been rotated by `parent_delta`; returns the rotation that was applied.
+    ///
+    /// The weight blends the world rotation of the bone, so that the bone ends up between its
+    /// animated and solved rotations even when its parent was only partly rotated too
+    fn rotate(&self, world: &mut World, delta: Quat, parent_delta: Quat, weight: f32) -> Quat {
+        let delta = Quat::IDENTITY.slerp(delta, weight.clamp(0., 1.));
+        let local = ((parent_delta * self.parent_rotation).inverse()
+            * delta
+            * self.parent_rotation
+            * self.local_rotation)
+            .normalize();
+        world.add_component(self.id, rotation(), local).ok();
+        delta
+    }
+}
+
+/// The animation has just written the local transforms, so `local_to_world` isn't up to date
+/// yet; this computes the world transform from the local transforms up the hierarchy
+fn world_transform(world: &World, id: EntityId) -> Mat4 {
+    let local = local_transform(world, id);
+    match world.get(id, parent()) {
+        Ok(parent) if world.exists(parent) => world_transform(world, parent) * local,
+        _ => local,
+    }
+}
+fn local_transform(world: &World, id: EntityId) -> Mat4 {
+    if world.has_component(id, translation())
+        || world.has_component(id, rotation())
+        || world.has_component(id, scale())
+    {
+        Mat4::from_scale_rotation_translation(
+            world.get(id, scale()).unwrap_or(Vec3::ONE),
+            world.get(id, rotation()).unwrap_or_default(),
+            world.get(id, translation()).unwrap_or_default(),
+        )
+    } else if let Ok(transform) = world.get(id, local_to_parent()) {
+        transform
+    } else {
+        world.get(id, local_to_world()).unwrap_or_default()
+    }
+}
+
+fn resolve_bones<'a>(
+    binder: &HashMap<String, EntityId>,
+    bones: impl IntoIterator<Item = &'a String>,
+) -> Option<Vec<EntityId>> {
+    bones
+        .into_iter()
+        .map(|bone| binder.get(bone).copied())
+        .collect()
+}
+
+fn apply_two_bone(
+    world: &mut World,
+    binder: &HashMap<String, EntityId>,
+    ik: &TwoBoneIk,
+) -> Option<()> {
+    let bones = resolve_bones(binder, [&ik.root, &ik.mid, &ik.end])?;
+    let target = ik.target.position(world)?;
+    let pole = match &ik.pole {
+        Some(pole) => Some(pole.position(world)?),
+        None => None,
+    };
+    let [root, mid, end] = [0, 1, 2].map(|i| BonePose::new(world, bones[i]));
+    let (root_delta, mid_delta) =
+        solve_two_bone(root.position, mid.position, end.position, target, pole);
+    let root_delta = root.rotate(world, root_delta, Quat::IDENTITY, ik.weight);
+    mid.rotate(world, mid_delta, root_delta, ik.weight);
+    Some(())
+}
+
+fn apply_look_at(
+    world: &mut World,
+    binder: &HashMap<String, EntityId>,
+    ik: &LookAtIk,
+) -> Option<()> {
+    let bone = BonePose::new(world, *binder.get(&ik.bone)?);
+    let target = ik.target.position(world)?;
+    let delta = solve_look_at(
+        bone.position,
+        bone.rotation,
+        ik.forward,
+        target,
+        ik.max_angle,
+    );
+    bone.rotate(world, delta, Quat::IDENTITY, ik.weight);
+    Some(())
+}
+
+fn apply_fabrik(
+    world: &mut World,
+    binder: &HashMap<String, EntityId>,
+    ik: &FabrikIk,
+) -> Option<()> {
+    let bones = resolve_bones(binder, &ik.bones)?
+        .into_iter()
+        .map(|id| BonePose::new(world, id))
+        .collect::<Vec<_>>();
+    let target = ik.target.position(world)?;
+    let old_positions = bones.iter().map(|bone| bone.position).collect::<Vec<_>>();
+    let new_positions = solve_fabrik(&old_positions, target, ik.iterations, ik.tolerance);
+    // The solved rotation of the parent, and the one that was applied to it
+    let (mut parent_delta, mut applied_parent_delta) = (Quat::IDENTITY, Quat::IDENTITY);
+    for i in 0..bones.len().saturating_sub(1) {
+        let old_dir =
+            (parent_delta * (old_positions[i + 1] - old_positions[i])).normalize_or_zero();
+        let new_dir = (new_positions[i + 1] - new_positions[i]).normalize_or_zero();
+        let delta = if old_dir == Vec3::ZERO || new_dir == Vec3::ZERO {
+            parent_delta
+        } else {
+            Quat::from_rotation_arc(old_dir, new_dir) * parent_delta
+        };
+        applied_parent_delta = bones[i].rotate(world, delta, applied_parent_delta, ik.weight);
+        parent_delta = delta;
+    }
+    Some(())
+}
+
+/// Applies the IK constraints; this needs to run after the animations have been sampled and
+/// before the `TransformSystem`
+pub fn ik_system() -> DynSystem {
+    Box::new(SystemGroup::new(
+        "ik",
+        vec![
+            query((animation_binder(), ik_two_bone())).to_system(|q, world, qs, _| {
+                for (_, (binder, iks)) in q.collect_cloned(world, qs) {
+                    for ik in iks {
+                        apply_two_bone(world, &binder, &ik);
+                    }
+                }
+            }),
+            query((animation_binder(), ik_fabrik())).to_system(|q, world, qs, _| {
+                for (_, (binder, iks)) in q.collect_cloned(world, qs) {
+                    for ik in iks {
+                        apply_fabrik(world, &binder, &ik);
+                    }
+                }
+            }),
+            query((animation_binder(), ik_look_at())).to_system(|q, world, qs, _| {
+                for (_, (binder, iks)) in q.collect_cloned(world, qs) {
+                    for ik in iks {
+                        apply_look_at(world, &binder, &ik);
+                    }
+                }
+            }),
+        ],
+    ))
+}
+
+#[test]
+fn test_two_bone_ik() {
+    use glam::vec3;
+
+    let (root, mid, end) = (Vec3::ZERO, vec3(0., 0., -1.), vec3(0., 0., -2.));
+    let target = vec3(0.5, 0., -1.5);
+    let (root_delta, mid_delta) = solve_two_bone(root, mid, end, target, Some(vec3(0., 1., -1.)));
+    let new_mid = root + root_delta * (mid - root);
+    let new_end = new_mid + mid_delta * (end - mid);
+    assert!((new_end - target).length() < 0.001);
+    assert!(((new_mid - root).length() - 1.).abs() < 0.001);
+    // The knee bends towards the pole
+    assert!(new_mid.y > 0.);
+}
+
+#[test]
+fn test_fabrik_ik() {
+    use glam::vec3;
+
+    let positions = [
+        Vec3::ZERO,
+        vec3(1., 0., 0.),
+        vec3(2., 0., 0.),
+        vec3(3., 0., 0.),
+    ];
+    let target = vec3(1., 1.5, 0.);
+    let solved = solve_fabrik(&positions, target, 20, 0.001);
+    assert!((solved[3] - target).length() < 0.001);
+    assert_eq!(solved[0], Vec3::ZERO);
+    for p in solved.windows(2) {
+        assert!(((p[1] - p[0]).length() - 1.).abs() < 0.001);
+    }
+}
@@ -1,4 +1,9 @@
-use ambient_core::{asset_cache, hierarchy::children, time};
+use ambient_core::{
+    asset_cache,
+    hierarchy::{children, parent},
+    time,
+    transform::{get_world_transform, rotation, translation},
+};
 use ambient_ecs::{
     components, query, Debuggable, EntityId, MakeDefault, Networked, Store, SystemGroup,
 };
@@ -10,14 +15,21 @@ use ambient_std::{
 use ambient_sys::time::SystemTime;
 use convert_case::{Case, Casing};
 use derive_more::Display;
+use glam::{Quat, Vec3};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
+mod ik;
+mod pose_override;
 mod resources;
 mod retargeting;
+mod root_motion;
 
+pub use ik::*;
+pub use pose_override::*;
 pub use resources::*;
 pub use retargeting::*;
+pub use root_motion::*;
 
 components!("animation", {
     @[Debuggable, Networked, Store]
@@ -37,6 +49,35 @@ components!("animation", {
     /// This is a shorthand for working directly with the animation_controller
     @[MakeDefault,  Debuggable, Networked, Store]
     loop_animation: TypedAssetUrl<AnimationAssetType>,
+
+    /// Extract the motion of a bone from the animation instead of baking it into the pose
+    @[Debuggable, Networked, Store]
+    animation_root_motion: RootMotion,
+    /// The root motion translation extracted during the last frame, in world space
+    @[Debuggable]
+    animation_root_motion_delta: Vec3,
+    /// The root motion rotation extracted during the last frame, in world space
+    @[Debuggable]
+    animation_root_motion_rotation_delta: Quat,
+    /// The animation time of each action during the last frame
+    animation_root_motion_times: Vec<f32>,
+
+    /// Two-bone IK constraints (legs, arms) applied on top of the animation
+    @[Debuggable, Networked, Store]
+    ik_two_bone: Vec<TwoBoneIk>,
+    /// Look-at IK constraints (heads, eyes) applied on top of the animation
+    @[Debuggable, Networked, Store]
+    ik_look_at: Vec<LookAtIk>,
+    /// FABRIK chain constraints (tails, tentacles, spines) applied on top of the animation
+    @[Debuggable, Networked, Store]
+    ik_fabrik: Vec<FabrikIk>,
+
+    /// A pose blended over the animated pose of this node, such as the pose of its ragdoll body
+    @[Debuggable]
+    animation_pose_override: PoseOverride,
+    /// The animated pose of a node with an `animation_pose_override`, and the pose blended over it
+    @[Debuggable]
+    animation_pose_override_state: PoseOverrideState,
 });
 
 // Running
@@ -155,6 +196,77 @@ impl AnimationController {
             apply_base_pose: true,
         }
     }
+    /// Samples the local pose of the nodes animated by this controller at `time`, by their
+    /// `animation_bind_id`, blending the actions like the animation systems do. This lets the
+    /// animation be followed where the model isn't spawned, such as by ragdolls on the server.
+    ///
+    /// The clips are read without loading any model, so translations retargeted to a model
+    /// (anything but [`AnimationRetargeting::None`]) are left out. Returns `None` until every
+    /// clip is loaded, or if one of them fails to load.
+    pub fn sample_pose(
+        &self,
+        assets: &AssetCache,
+        retarget: AnimationRetargeting,
+        time: Duration,
+    ) -> Option<HashMap<String, SampledPose>> {
+        let mut outputs: HashMap<(String, u32), (AnimationOutput, f32)> = HashMap::new();
+        for action in &self.actions {
+            let clip = match &action.clip {
+                AnimationClipRef::Clip(clip) => clip.clone(),
+                AnimationClipRef::FromModelAsset(def) => {
+                    AnimationClipFromUrl::new(def.abs()?, true)
+                        .peek(assets)?
+                        .ok()?
+                }
+            };
+            if action.weight == 0.0 {
+                continue;
+            }
+            let anim_time = action.time(time, &clip);
+            for track in clip.tracks.iter() {
+                let bind_id = match &track.target {
+                    AnimationTarget::BinderId(bind_id) => bind_id,
+                    AnimationTarget::Entity(_) => continue,
+                };
+                let component = track.outputs.component();
+                if component != rotation()
+                    && (component != translation() || retarget != AnimationRetargeting::None)
+                {
+                    continue;
+                }
+                let value = AnimationTrackInterpolator::new().value(track, anim_time);
+                match outputs.get_mut(&(bind_id.clone(), component.index())) {
+                    Some((output, weight)) => {
+                        *weight += action.weight;
+                        *output = output.mix(value, action.weight / *weight);
+                    }
+                    None => {
+                        outputs
+                            .insert((bind_id.clone(), component.index()), (value, action.weight));
+                    }
+                }
+            }
+        }
+
+        let mut pose: HashMap<String, SampledPose> = HashMap::new();
+        for ((bind_id, _), (output, _)) in outputs {
+            let node = pose.entry(bind_id).or_default();
+            match output {
+                AnimationOutput::Vec3 { value, .. } => node.translation = Some(value),
+                AnimationOutput::Quat { value, .. } => node.rotation = Some(value),
+                _ => {}
+            }
+        }
+        Some(pose)
+    }
+}
+
+/// The local pose of a node sampled by [`AnimationController::sample_pose`]; `None` where the
+/// node isn't animated
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SampledPose {
+    pub translation: Option<Vec3>,
+    pub rotation: Option<Quat>,
 }
 
 #[derive(Debug)]
@@ -193,6 +305,9 @@ pub fn animation_systems() -> SystemGroup {
                 .to_system(|q, world, qs, _| {
                     for (id, ctrlr) in q.collect_cloned(world, qs) {
                         world.remove_component(id, animation_errors()).unwrap();
+                        world
+                            .remove_component(id, animation_root_motion_times())
+                            .unwrap();
                         if ctrlr.apply_base_pose {
                             if let Some(action) = ctrlr.actions.get(0) {
                                 if let AnimationClipRef::FromModelAsset(def) = &action.clip {
@@ -231,6 +346,7 @@ pub fn animation_systems() -> SystemGroup {
                     let time = *world.resource(time());
                     let mut outputs: HashMap<String, AnimationBlendOutput> = HashMap::new();
                     let mut in_error = Vec::new();
+                    let mut root_motions = Vec::new();
                     for (id, (controller, binder)) in q.iter(world, qs) {
                         let retaget = world
                             .get(id, animation_retargeting())
@@ -239,8 +355,29 @@ pub fn animation_systems() -> SystemGroup {
                             .get_ref(id, model_from_url())
                             .ok()
                             .and_then(|def| TypedAssetUrl::parse(def).ok());
+                        let prev_times = world
+                            .get_ref(id, animation_root_motion_times())
+                            .ok()
+                            .filter(|times| times.len() == controller.actions.len());
+                        let mut root_motion = world
+                            .get_ref(id, animation_root_motion())
+                            .ok()
+                            .and_then(|config| {
+                                let bone = *binder.get(&config.bone)?;
+                                let parent_transform = world
+                                    .get(bone, parent())
+                                    .ok()
+                                    .and_then(|parent| get_world_transform(world, parent).ok())
+                                    .unwrap_or_default();
+                                Some(RootMotionSampler::new(
+                                    id,
+                                    config.clone(),
+                                    parent_transform,
+                                    controller.actions.len(),
+                                ))
+                            });
                         // Calc
-                        for action in controller.actions.iter() {
+                        for (action_index, action) in controller.actions.iter().enumerate() {
                             match action.clip.get_clip(assets.clone(), retaget, model.clone()) {
                                 Some(Err(err)) => {
                                     in_error.push((id, err));
@@ -248,8 +385,14 @@ pub fn animation_systems() -> SystemGroup {
                                 }
                                 Some(Ok(clip)) => {
                                     let anim_time = action.time(time, &clip);
+                                    let prev_time = prev_times
+                                        .map(|times| times[action_index])
+                                        .unwrap_or(anim_time);
+                                    if let Some(root_motion) = &mut root_motion {
+                                        root_motion.times[action_index] = anim_time;
+                                    }
                                     for track in clip.tracks.iter() {
-                                        let value = AnimationTrackInterpolator::new()
+                                        let mut value = AnimationTrackInterpolator::new()
                                             .value(track, anim_time);
                                         let key = format!(
                                             "{}_{:?}_{}_{:?}",
@@ -261,6 +404,15 @@ pub fn animation_systems() -> SystemGroup {
                                         if action.weight == 0.0 {
                                             continue;
                                         }
+                                        if let Some(root_motion) = &mut root_motion {
+                                            root_motion.sample(
+                                                track,
+                                                prev_time,
+                                                anim_time,
+                                                action.weight,
+                                                &mut value,
+                                            );
+                                        }
                                         if let Some(o) = outputs.get_mut(&key) {
                                             o.weight += action.weight;
                                             let p = action.weight / o.weight;
@@ -290,6 +442,9 @@ pub fn animation_systems() -> SystemGroup {
                                 None => {}
                             }
                         }
+                        if let Some(root_motion) = root_motion {
+                            root_motions.push(root_motion);
+                        }
                     }
 
                     // Apply
@@ -314,12 +469,20 @@ pub fn animation_systems() -> SystemGroup {
                                     }
                                 }
                             }
+                            AnimationOutput::Weights { component, value } => {
+                                world.set(output.target, component, value).ok();
+                            }
                         }
                     }
+                    for root_motion in root_motions {
+                        root_motion.apply(world);
+                    }
                     for (id, err) in in_error {
                         world.add_component(id, animation_errors(), err).unwrap();
                     }
                 }),
+            ik_system(),
+            pose_override_system(),
         ],
     )
 }
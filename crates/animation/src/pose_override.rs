@@ -0,0 +1,109 @@
+use ambient_core::transform::{rotation, translation};
+use ambient_ecs::{query, DynSystem, SystemGroup};
+use glam::{Quat, Vec3};
+
+use super::{animation_pose_override, animation_pose_override_state};
+
+/// A local pose that is blended over the animated pose of a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoseOverride {
+    /// If not set, the node keeps its animated translation
+    pub translation: Option<Vec3>,
+    pub rotation: Quat,
+    /// How much of the pose comes from the override, from 0 to 1
+    pub weight: f32,
+}
+
+/// The pose of a node before and after its [`PoseOverride`] was blended over it.
+///
+/// Nodes that no animation drives keep the blended pose from one frame to the next; it must not
+/// be blended again, or the node would drift towards the override whatever the weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoseOverrideState {
+    animated: (Vec3, Quat),
+    blended: (Vec3, Quat),
+}
+impl PoseOverrideState {
+    /// The animated pose of the node, given its current pose
+    fn animated(state: Option<Self>, current: (Vec3, Quat)) -> (Vec3, Quat) {
+        match state {
+            // Nothing wrote the pose since it was blended
+            Some(state) if state.blended == current => state.animated,
+            _ => current,
+        }
+    }
+}
+
+/// Blends the pose overrides over the animated poses; this needs to run after the animations
+/// have been sampled and the IK has been applied, and before the `TransformSystem`
+pub fn pose_override_system() -> DynSystem {
+    Box::new(SystemGroup::new(
+        "pose_override",
+        vec![
+            query((animation_pose_override(), translation(), rotation())).to_system(
+                |q, world, qs, _| {
+                    for (id, (pose, current_translation, current_rotation)) in
+                        q.collect_cloned(world, qs)
+                    {
+                        let state = world.get(id, animation_pose_override_state()).ok();
+                        let (anim_translation, anim_rotation) = PoseOverrideState::animated(
+                            state,
+                            (current_translation, current_rotation),
+                        );
+                        let weight = pose.weight.clamp(0., 1.);
+                        let blended_translation = match pose.translation {
+                            Some(pose_translation) => {
+                                anim_translation.lerp(pose_translation, weight)
+                            }
+                            None => anim_translation,
+                        };
+                        let blended_rotation = anim_rotation.slerp(pose.rotation, weight);
+                        world.set(id, translation(), blended_translation).unwrap();
+                        world.set(id, rotation(), blended_rotation).unwrap();
+                        world
+                            .add_component(
+                                id,
+                                animation_pose_override_state(),
+                                PoseOverrideState {
+                                    animated: (anim_translation, anim_rotation),
+                                    blended: (blended_translation, blended_rotation),
+                                },
+                            )
+                            .unwrap();
+                    }
+                },
+            ),
+            // Gives the nodes whose override was removed their animated pose back
+            query((animation_pose_override_state(), translation(), rotation()))
+                .excl(animation_pose_override())
+                .to_system(|q, world, qs, _| {
+                    for (id, (state, current_translation, current_rotation)) in
+                        q.collect_cloned(world, qs)
+                    {
+                        let (anim_translation, anim_rotation) = PoseOverrideState::animated(
+                            Some(state),
+                            (current_translation, current_rotation),
+                        );
+                        world.set(id, translation(), anim_translation).unwrap();
+                        world.set(id, rotation(), anim_rotation).unwrap();
+                        world
+                            .remove_component(id, animation_pose_override_state())
+                            .unwrap();
+                    }
+                }),
+        ],
+    ))
+}
+
+#[test]
+fn test_pose_override_state() {
+    let animated = (Vec3::X, Quat::IDENTITY);
+    let blended = (Vec3::Y, Quat::from_rotation_z(1.));
+    let state = PoseOverrideState { animated, blended };
+    // The node wasn't animated since the last frame, so it still has the blended pose
+    assert_eq!(PoseOverrideState::animated(Some(state), blended), animated);
+    // The animation wrote a new pose
+    let new = (Vec3::Z, Quat::IDENTITY);
+    assert_eq!(PoseOverrideState::animated(Some(state), new), new);
+    assert_eq!(PoseOverrideState::animated(None, new), new);
+}
@@ -13,6 +13,7 @@ pub enum AnimationOutput {
     Vec3 { component: Component<glam::Vec3>, value: glam::Vec3 },
     Quat { component: Component<glam::Quat>, value: glam::Quat },
     Vec3Field { component: Component<glam::Vec3>, field: Vec3Field, value: f32 },
+    Weights { component: Component<Vec<f32>>, value: Vec<f32> },
 }
 impl AnimationOutput {
     pub fn mix(&self, value: AnimationOutput, p: f32) -> Self {
@@ -30,6 +31,13 @@ impl AnimationOutput {
                 AnimationOutput::Vec3Field { component, field, value: mix(*left, right, p) }
             }
 
+            (AnimationOutput::Weights { value: left, .. }, AnimationOutput::Weights { value: right, component }) => {
+                let value = (0..left.len().max(right.len()))
+                    .map(|i| mix(left.get(i).copied().unwrap_or_default(), right.get(i).copied().unwrap_or_default(), p))
+                    .collect();
+                AnimationOutput::Weights { component, value }
+            }
+
             _ => unreachable!(),
         }
     }
@@ -57,6 +65,7 @@ pub enum AnimationOutputs {
     Vec3 { component: Component<glam::Vec3>, data: Vec<glam::Vec3> },
     Quat { component: Component<glam::Quat>, data: Vec<glam::Quat> },
     Vec3Field { component: Component<glam::Vec3>, field: Vec3Field, data: Vec<f32> },
+    Weights { component: Component<Vec<f32>>, data: Vec<Vec<f32>> },
 }
 impl AnimationOutputs {
     pub fn component(&self) -> ComponentDesc {
@@ -64,6 +73,7 @@ impl AnimationOutputs {
             AnimationOutputs::Vec3 { component, .. } => component.desc(),
             AnimationOutputs::Quat { component, .. } => component.desc(),
             AnimationOutputs::Vec3Field { component, .. } => component.desc(),
+            AnimationOutputs::Weights { component, .. } => component.desc(),
         }
     }
     pub fn field(&self) -> Option<Vec3Field> {
@@ -85,6 +95,7 @@ impl AnimationOutputs {
             AnimationOutputs::Vec3Field { data, component, field } => {
                 AnimationOutput::Vec3Field { component: *component, field: *field, value: data[index] }
             }
+            AnimationOutputs::Weights { data, component } => AnimationOutput::Weights { component: *component, value: data[index].clone() },
         }
     }
 }
@@ -136,7 +136,7 @@ fn retarget_track(
                 *v *= scale;
             }
         }
-        AnimationOutputs::Quat { .. } => unreachable!(),
+        AnimationOutputs::Quat { .. } | AnimationOutputs::Weights { .. } => unreachable!(),
         AnimationOutputs::Vec3Field { data, .. } => {
             for v in data.iter_mut() {
                 *v *= scale;
@@ -0,0 +1,262 @@
+use ambient_core::transform::{rotation, translation};
+use ambient_ecs::{EntityId, World};
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    animation_root_motion_delta, animation_root_motion_rotation_delta, animation_root_motion_times,
+    AnimationOutput, AnimationOutputs, AnimationTrack, AnimationTrackInterpolator,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RootMotionMode {
+    /// The extracted motion is added to the entity's `translation` and `rotation`.
+    Transform,
+    /// The extracted motion is only written to `animation_root_motion_delta` and
+    /// `animation_root_motion_rotation_delta`, so that it can be consumed elsewhere,
+    /// for instance by feeding it into `move_character` for physics characters.
+    DeltaOnly,
+}
+
+/// Extracts the motion of a bone (usually the hips) from the animation, and holds that bone
+/// in place in the visual pose.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootMotion {
+    /// The bind id of the bone to extract the motion from
+    pub bone: String,
+    pub mode: RootMotionMode,
+    /// Extract the vertical motion too; by default only the horizontal motion is extracted,
+    /// so that the bone can still bob up and down
+    pub extract_vertical: bool,
+    /// Extract the rotation around the up axis (yaw)
+    pub extract_rotation: bool,
+}
+impl Default for RootMotion {
+    fn default() -> Self {
+        Self {
+            bone: "Hips".to_string(),
+            mode: RootMotionMode::Transform,
+            extract_vertical: false,
+            extract_rotation: false,
+        }
+    }
+}
+impl RootMotion {
+    fn project(&self, value: Vec3, up: Vec3) -> Vec3 {
+        if self.extract_vertical {
+            value
+        } else {
+            value - up * value.dot(up)
+        }
+    }
+
+    /// Returns the translation of the root bone to use in the visual pose at `time`,
+    /// and the motion which was extracted from `track` since `prev_time`.
+    ///
+    /// Both the value and the delta are in the bone's parent space; `up` is the up axis
+    /// expressed in that space.
+    pub fn extract_translation(
+        &self,
+        track: &AnimationTrack,
+        prev_time: f32,
+        time: f32,
+        up: Vec3,
+    ) -> (Vec3, Vec3) {
+        let sample = |time: f32| {
+            *AnimationTrackInterpolator::new()
+                .value(track, time)
+                .as_vec3_value()
+                .unwrap()
+        };
+        let (start, end) = track_range(track);
+        let value = sample(time);
+        let held = value - self.project(value - sample(start), up);
+        let delta = if is_loop_wrap(prev_time, time, start, end) {
+            (sample(end) - sample(prev_time)) + (value - sample(start))
+        } else {
+            value - sample(prev_time)
+        };
+        (held, self.project(delta, up))
+    }
+
+    /// Same as [Self::extract_translation], but for the rotation around the `up` axis
+    pub fn extract_rotation(
+        &self,
+        track: &AnimationTrack,
+        prev_time: f32,
+        time: f32,
+        up: Vec3,
+    ) -> (Quat, Quat) {
+        let sample = |time: f32| as_quat(AnimationTrackInterpolator::new().value(track, time));
+        let (start, end) = track_range(track);
+        let value = sample(time);
+        let twist_at = |time: f32| twist(sample(time), up);
+        let held = (twist_at(start) * twist(value, up).inverse() * value).normalize();
+        let delta = if is_loop_wrap(prev_time, time, start, end) {
+            (twist(value, up) * twist_at(start).inverse())
+                * (twist_at(end) * twist_at(prev_time).inverse())
+        } else {
+            twist(value, up) * twist_at(prev_time).inverse()
+        };
+        (held, delta.normalize())
+    }
+}
+
+/// Accumulates the root motion of all the actions of an animation controller during a frame
+pub(crate) struct RootMotionSampler {
+    id: EntityId,
+    config: RootMotion,
+    /// The bone's parent to world transform
+    parent_transform: Mat4,
+    /// The up axis in the bone's parent space
+    up: Vec3,
+    translation: Vec3,
+    translation_weight: f32,
+    rotation: Quat,
+    rotation_weight: f32,
+    pub times: Vec<f32>,
+}
+impl RootMotionSampler {
+    pub fn new(id: EntityId, config: RootMotion, parent_transform: Mat4, n_actions: usize) -> Self {
+        Self {
+            id,
+            config,
+            parent_transform,
+            up: parent_transform
+                .inverse()
+                .transform_vector3(Vec3::Z)
+                .normalize_or_zero(),
+            translation: Vec3::ZERO,
+            translation_weight: 0.,
+            rotation: Quat::IDENTITY,
+            rotation_weight: 0.,
+            times: vec![0.; n_actions],
+        }
+    }
+    /// If `track` drives the root bone, replaces `value` with the held pose and accumulates the extracted motion
+    pub fn sample(
+        &mut self,
+        track: &AnimationTrack,
+        prev_time: f32,
+        time: f32,
+        weight: f32,
+        value: &mut AnimationOutput,
+    ) {
+        if track.target.bind_id() != Some(self.config.bone.as_str()) {
+            return;
+        }
+        match &track.outputs {
+            AnimationOutputs::Vec3 { component, .. } if *component == translation() => {
+                let (held, delta) = self
+                    .config
+                    .extract_translation(track, prev_time, time, self.up);
+                *value = AnimationOutput::Vec3 {
+                    component: *component,
+                    value: held,
+                };
+                self.translation_weight += weight;
+                self.translation = self
+                    .translation
+                    .lerp(delta, weight / self.translation_weight);
+            }
+            AnimationOutputs::Quat { component, .. }
+                if self.config.extract_rotation && *component == rotation() =>
+            {
+                let (held, delta) = self
+                    .config
+                    .extract_rotation(track, prev_time, time, self.up);
+                *value = AnimationOutput::Quat {
+                    component: *component,
+                    value: held,
+                };
+                self.rotation_weight += weight;
+                self.rotation = self.rotation.slerp(delta, weight / self.rotation_weight);
+            }
+            _ => {}
+        }
+    }
+    pub fn apply(self, world: &mut World) {
+        let (_, parent_rotation, _) = self.parent_transform.to_scale_rotation_translation();
+        let delta = self.parent_transform.transform_vector3(self.translation);
+        let rotation_delta =
+            (parent_rotation * self.rotation * parent_rotation.inverse()).normalize();
+        if self.config.mode == RootMotionMode::Transform {
+            if let Ok(value) = world.get_mut(self.id, translation()) {
+                *value += delta;
+            }
+            if let Ok(value) = world.get_mut(self.id, rotation()) {
+                *value = rotation_delta * *value;
+            }
+        }
+        world
+            .add_component(self.id, animation_root_motion_delta(), delta)
+            .unwrap();
+        world
+            .add_component(
+                self.id,
+                animation_root_motion_rotation_delta(),
+                rotation_delta,
+            )
+            .unwrap();
+        world
+            .add_component(self.id, animation_root_motion_times(), self.times)
+            .unwrap();
+    }
+}
+
+fn as_quat(output: AnimationOutput) -> Quat {
+    match output {
+        AnimationOutput::Quat { value, .. } => value,
+        _ => Quat::IDENTITY,
+    }
+}
+
+fn track_range(track: &AnimationTrack) -> (f32, f32) {
+    (track.inputs[0], track.duration())
+}
+
+/// A looping animation that wrapped around since the last frame will have jumped back by
+/// (almost) the whole clip. Smaller backwards jumps are treated as reverse playback.
+fn is_loop_wrap(prev_time: f32, time: f32, start: f32, end: f32) -> bool {
+    time < prev_time && prev_time - time > (end - start) / 2.
+}
+
+/// The rotation around `axis` of `rotation` (the "twist" of a swing-twist decomposition)
+pub fn twist(rotation: Quat, axis: Vec3) -> Quat {
+    let projected = axis * rotation.xyz().dot(axis);
+    let twist = Quat::from_xyzw(projected.x, projected.y, projected.z, rotation.w);
+    if twist.length_squared() < f32::EPSILON {
+        Quat::IDENTITY
+    } else {
+        twist.normalize()
+    }
+}
+
+#[test]
+fn test_root_motion() {
+    use super::AnimationTarget;
+    use ambient_core::transform;
+    use glam::vec3;
+
+    ambient_ecs::init_components();
+    transform::init_components();
+
+    let track = AnimationTrack {
+        target: AnimationTarget::BinderId("Hips".to_string()),
+        inputs: vec![0., 1.],
+        outputs: AnimationOutputs::Vec3 {
+            component: translation(),
+            data: vec![vec3(0., 0., 1.), vec3(2., 0., 1.5)],
+        },
+    };
+    let root_motion = RootMotion::default();
+
+    let (held, delta) = root_motion.extract_translation(&track, 0.25, 0.5, Vec3::Z);
+    assert_eq!(held, vec3(0., 0., 1.25));
+    assert_eq!(delta, vec3(0.5, 0., 0.));
+
+    // Wrapping around from the end of the clip to the start
+    let (held, delta) = root_motion.extract_translation(&track, 0.9, 0.2, Vec3::Z);
+    assert!(held.abs_diff_eq(vec3(0., 0., 1.1), 1e-5));
+    assert!(delta.abs_diff_eq(vec3(0.6, 0., 0.), 1e-5));
+}
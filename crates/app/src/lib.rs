@@ -180,6 +180,51 @@ pub fn world_instance_resources(resources: AppResources) -> Entity {
         .with(ambient_core::window::window_ctl(), resources.ctl_tx)
 }
 
+/// The resources of a world instance that is simulated without a window or a GPU, like the world of a
+/// headless client. Window control messages are sent to `ctl_tx`.
+pub fn headless_world_instance_resources(
+    assets: AssetCache,
+    runtime: RuntimeHandle,
+    ctl_tx: flume::Sender<WindowCtl>,
+) -> Entity {
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    Entity::new()
+        .with(name(), "Resources".to_string())
+        .with(self::runtime(), runtime)
+        .with(self::window_title(), "".to_string())
+        .with(self::fps_stats(), FpsSample::default())
+        .with(self::asset_cache(), assets)
+        .with_default(world_events())
+        .with(frame_index(), 0_usize)
+        .with(ambient_core::window::cursor_position(), Vec2::ZERO)
+        .with(ambient_core::app_start_time(), current_time)
+        .with(ambient_core::time(), current_time)
+        .with(ambient_core::dtime(), 0.)
+        .with_merge(ambient_input::resources())
+        .with_merge(ambient_core::async_ecs::async_ecs_resources())
+        .with(ambient_core::window::window_physical_size(), UVec2::ONE)
+        .with(ambient_core::window::window_logical_size(), UVec2::ONE)
+        .with(ambient_core::window::window_scale_factor(), 1.)
+        .with(ambient_core::window::window_ctl(), ctl_tx)
+}
+
+/// The systems of [`world_instance_systems`] that don't need a GPU
+pub fn headless_world_instance_systems() -> SystemGroup {
+    SystemGroup::new(
+        "headless_world_instance",
+        vec![
+            Box::new(TimeResourcesSystem::new()),
+            Box::new(async_ecs_systems()),
+            remove_at_time_system(),
+            Box::new(WorldEventsSystem),
+            Box::new(TransformSystem::new()),
+            Box::new(camera_systems()),
+        ],
+    )
+}
+
 pub fn get_time_since_app_start(world: &World) -> Duration {
     *world.resource(time()) - *world.resource(app_start_time())
 }
@@ -20,6 +20,7 @@ use futures::{
     future::{pending, BoxFuture, Shared, WeakShared},
     Future, FutureExt,
 };
+use itertools::Itertools;
 use parking_lot::Mutex;
 use pin_project::{pin_project, pinned_drop};
 use serde::{Deserialize, Serialize};
@@ -100,9 +101,39 @@ struct AsyncAssetLoc {
     keepalive_guard: Weak<KeepaliveGuard>,
     content: ContentState,
     keepalive_task: Option<ChildTask<()>>,
+    /// The keepalive of `keepalive_task`; [`AssetKeepalive::None`] when it only keeps the asset loading
+    keepalive: AssetKeepalive,
+    /// The reference which `keepalive_task` holds on the asset, so that it can be released before the task ends
+    keepalive_ref: Option<KeepaliveRef>,
+    /// The memory used by the loaded asset, as reported by [`AsyncAssetKey::cpu_size`] and [`AsyncAssetKey::gpu_size`]
+    cpu_size: u64,
+    gpu_size: u64,
+    /// When the asset was last requested; the keepalive of the least recently used assets is released first when the cache
+    /// is over budget
+    last_used: time::Instant,
 }
 
+/// A strong reference to a loaded asset, shared with the task keeping it alive
+type KeepaliveRef = Arc<Mutex<Option<Box<dyn AssetHolder>>>>;
+
 impl AsyncAssetLoc {
+    /// Whether the asset is loaded and still referenced, by its keepalive or elsewhere
+    fn is_alive(&self) -> bool {
+        matches!(&self.content, ContentState::Loaded { check_alive, .. } if check_alive())
+    }
+
+    /// Whether the keepalive of the asset is still running
+    fn is_kept_alive(&self) -> bool {
+        self.keepalive.is_active() && self.keepalive_task.as_ref().map(|task| !task.is_finished()).unwrap_or(false)
+    }
+
+    /// Replaces the keepalive task with one that only keeps the asset loading, if any
+    fn set_loading_task(&mut self, task: Option<ChildTask<()>>) {
+        self.keepalive_task = task;
+        self.keepalive = AssetKeepalive::None;
+        self.keepalive_ref = None;
+    }
+
     /// Checks if the resource has been dropped since this method was called last time
     fn state(&mut self) -> AsyncAssetState {
         match &mut self.content {
@@ -157,29 +188,48 @@ struct SyncAssetLoc {
     content: Arc<Mutex<Option<Arc<dyn AssetHolder>>>>,
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AssetCacheConfig {
+    /// Caps the [`AssetKeepalive::Timeout`] of all assets
+    pub max_keepalive: Option<Duration>,
+    /// How much memory, in bytes, the loaded assets may use before the keepalive of the least recently used assets is released.
+    /// Assets that are still referenced stay loaded, so this is a soft limit.
+    pub cpu_budget: Option<u64>,
+    /// Same as `cpu_budget`, for the GPU memory reported by [`AsyncAssetKey::gpu_size`]
+    pub gpu_budget: Option<u64>,
+}
+impl AssetCacheConfig {
+    fn is_over_budget(&self, cpu_size: u64, gpu_size: u64) -> bool {
+        self.cpu_budget.map(|budget| cpu_size > budget).unwrap_or(false) || self.gpu_budget.map(|budget| gpu_size > budget).unwrap_or(false)
+    }
+}
+
 #[derive(Clone)]
 pub struct AssetCache {
     async_cache: Arc<Mutex<HashMap<AssetKey, AsyncAssetLoc>>>,
     sync: Arc<Mutex<HashMap<AssetKey, SyncAssetLoc>>>,
     pub timeline: Arc<Mutex<AssetsTimeline>>,
     runtime: RuntimeHandle,
-    max_keepalive: Option<Duration>,
+    config: AssetCacheConfig,
     /// stack is used for nested asset loading, to visualize for the timeline who loaded what
     stack: Vec<AssetKey>,
 }
 impl AssetCache {
     pub fn new(runtime: impl Into<RuntimeHandle>) -> Self {
-        Self::new_with_config(runtime.into(), None)
+        Self::new_with_config(runtime.into(), AssetCacheConfig::default())
     }
-    pub fn new_with_config(runtime: impl Into<RuntimeHandle>, max_keepalive: Option<Duration>) -> Self {
+    pub fn new_with_config(runtime: impl Into<RuntimeHandle>, config: AssetCacheConfig) -> Self {
         let runtime = runtime.into();
 
+        let mut timeline = AssetsTimeline::new();
+        timeline.cpu_budget = config.cpu_budget;
+        timeline.gpu_budget = config.gpu_budget;
         let assets = Self {
             async_cache: Arc::new(Mutex::new(HashMap::new())),
             sync: Arc::new(Mutex::new(HashMap::new())),
-            timeline: Arc::new(Mutex::new(AssetsTimeline::new())),
+            timeline: Arc::new(Mutex::new(timeline)),
             runtime: runtime.clone(),
-            max_keepalive,
+            config,
             stack: Vec::new(),
         };
         {
@@ -188,11 +238,15 @@ impl AssetCache {
                 loop {
                     time::sleep(Duration::from_millis(1000)).await;
                     assets.clean_up_dropped();
+                    assets.evict_over_budget();
                 }
             });
         }
         assets
     }
+    pub fn config(&self) -> &AssetCacheConfig {
+        &self.config
+    }
     #[deprecated(note = "Use a SyncAssetKey instead")]
     pub fn get_sync<K: Into<String>, T: Clone + Sync + Send + 'static>(
         &self,
@@ -250,6 +304,44 @@ impl AssetCache {
         }
     }
 
+    /// Releases the keepalive of the least recently used assets until the loaded assets fit in the budget
+    fn evict_over_budget(&self) {
+        if self.config.cpu_budget.is_none() && self.config.gpu_budget.is_none() {
+            return;
+        }
+        let evicted = {
+            let mut async_ = self.async_cache.lock();
+            let (mut cpu_size, mut gpu_size) =
+                async_.values().filter(|loc| loc.is_alive()).fold((0, 0), |(cpu, gpu), loc| (cpu + loc.cpu_size, gpu + loc.gpu_size));
+            let mut evicted = Vec::new();
+            let kept_alive = async_.iter_mut().filter(|(_, loc)| loc.is_alive() && loc.is_kept_alive());
+            for (key, loc) in kept_alive.sorted_by_key(|(_, loc)| loc.last_used) {
+                if !self.config.is_over_budget(cpu_size, gpu_size) {
+                    break;
+                }
+                tracing::debug!("Evicting asset: {key:?}");
+                evicted.push((key.clone(), loc.keepalive_task.take()));
+                loc.keepalive = AssetKeepalive::None;
+                // Releasing the keepalive only frees the asset if nothing else references it
+                if let Some(keepalive_ref) = loc.keepalive_ref.take() {
+                    drop(keepalive_ref.lock().take());
+                }
+                if !loc.is_alive() {
+                    cpu_size -= loc.cpu_size;
+                    gpu_size -= loc.gpu_size;
+                }
+            }
+            evicted
+        };
+        let mut timeline = self.timeline.lock();
+        for (key, _) in &evicted {
+            timeline.evicted(key);
+        }
+        // The keepalive tasks are aborted once the locks are released, as ending them updates the timeline
+        drop(timeline);
+        drop(evicted);
+    }
+
     /// Returns a snapshot of the current state of the asset
     pub(crate) fn content_state<T: 'static + Clone + Asset + Send + Sync, K: AsyncAssetKeyExt<T>>(&self, key: &K) -> Option<ContentState> {
         let key = AssetKey::new(key.key());
@@ -284,7 +376,13 @@ impl AssetCache {
             // No future loading the value was found.
             //
             // Initiate the loading
-            timeline.lock().start_load(asset_key.clone(), key.long_name(), self.stack.clone(), keepalive.is_active());
+            timeline.lock().start_load(
+                asset_key.clone(),
+                key.long_name(),
+                short_type_name::<T>(),
+                self.stack.clone(),
+                keepalive.is_active(),
+            );
 
             let fork = self.fork(asset_key.clone());
             let drop_policy = key.drop_policy();
@@ -325,7 +423,7 @@ impl AssetCache {
                             // fresh future
                             let (fut, c, k) = load();
                             loc.content = c;
-                            loc.keepalive_task = k;
+                            loc.set_loading_task(k);
                             fut
                         }
                     }
@@ -339,13 +437,13 @@ impl AssetCache {
 
                         let (fut, c, k) = load();
                         loc.content = c;
-                        loc.keepalive_task = k;
+                        loc.set_loading_task(k);
                         fut
                     }
                     ContentState::Aborted | ContentState::Expired => {
                         let (fut, c, k) = load();
                         loc.content = c;
-                        loc.keepalive_task = k;
+                        loc.set_loading_task(k);
                         fut
                     }
                 }
@@ -357,7 +455,17 @@ impl AssetCache {
                 let (fut, content, keepalive_task) = load();
                 let key = slot.key().clone();
 
-                slot.insert(AsyncAssetLoc { key, content, keepalive_task, keepalive_guard: Weak::new() });
+                slot.insert(AsyncAssetLoc {
+                    key,
+                    content,
+                    keepalive_task,
+                    keepalive: AssetKeepalive::None,
+                    keepalive_ref: None,
+                    keepalive_guard: Weak::new(),
+                    cpu_size: 0,
+                    gpu_size: 0,
+                    last_used: time::Instant::now(),
+                });
 
                 fut
             }
@@ -385,10 +493,11 @@ impl AssetCache {
 
         let mut cache = self.async_cache.lock();
         let loc = cache.get_mut(&asset_key).expect("Asset loc was removed during loading");
+        loc.last_used = time::Instant::now();
 
         // Start or replace the keepalive task
 
-        let keepalive_ref = value.clone();
+        let keepalive_ref: KeepaliveRef = Arc::new(Mutex::new(Some(Box::new(value.clone()) as Box<dyn AssetHolder>)));
         // Use a drop impl since cancelling a task causes the task to not reach the end, and
         // therefore not registering that the keepalive ended.
         //
@@ -401,19 +510,23 @@ impl AssetCache {
 
         match keepalive {
             AssetKeepalive::Timeout(mut dur) => {
-                if let Some(max_keepalive) = self.max_keepalive {
+                if let Some(max_keepalive) = self.config.max_keepalive {
                     dur = dur.min(max_keepalive);
                 }
 
+                loc.keepalive = AssetKeepalive::Timeout(dur);
+                loc.keepalive_ref = Some(keepalive_ref.clone());
                 let task = self.runtime.spawn(async move {
                     time::sleep(dur).await;
                     tracing::debug!("Keepalive timed out for {asset_key:?}");
-                    drop((keepalive_ref, guard));
+                    drop((keepalive_ref.lock().take(), guard));
                 });
 
                 loc.keepalive_task = Some(task.into());
             }
             AssetKeepalive::Forever => {
+                loc.keepalive = AssetKeepalive::Forever;
+                loc.keepalive_ref = Some(keepalive_ref.clone());
                 let task = self.runtime.spawn(async move {
                     pending::<()>().await;
                     drop((keepalive_ref, guard));
@@ -620,6 +733,9 @@ pub struct AssetLifetime {
     pub keepalive_end: Option<chrono::DateTime<chrono::Utc>>,
     pub dropped: Option<chrono::DateTime<chrono::Utc>>,
     pub aborted: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the keepalive was released early because the cache was over budget
+    #[serde(default)]
+    pub evicted: Option<chrono::DateTime<chrono::Utc>>,
     pub keepalive: bool,
 }
 impl AssetLifetime {
@@ -637,6 +753,9 @@ impl AssetLifetime {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AssetTimeline {
     pub long_name: String,
+    /// The type of the asset, without module paths
+    #[serde(default)]
+    pub type_name: String,
     pub stack: Vec<AssetKey>,
     pub cpu_size: Option<u64>,
     pub gpu_size: Option<u64>,
@@ -650,25 +769,62 @@ impl AssetTimeline {
     pub fn is_aborted(&self) -> bool {
         self.lifetimes.last().map(|x| x.aborted.is_some()).unwrap_or(false)
     }
+    /// Whether the asset is loaded and still in use or kept alive
+    pub fn is_loaded(&self) -> bool {
+        self.is_alive && self.lifetimes.last().map(|x| x.end_load.is_some()).unwrap_or(false)
+    }
+}
+
+/// How many assets of a type are loaded, and how much memory they use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetTypeStats {
+    pub count: usize,
+    pub cpu_size: u64,
+    pub gpu_size: u64,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AssetsTimeline {
     pub assets: HashMap<AssetKey, AssetTimeline>,
     pub start_time: chrono::DateTime<chrono::Utc>,
+    /// See [`AssetCacheConfig::cpu_budget`]
+    #[serde(default)]
+    pub cpu_budget: Option<u64>,
+    #[serde(default)]
+    pub gpu_budget: Option<u64>,
 }
 
 impl AssetsTimeline {
     pub fn new() -> Self {
-        Self { assets: Default::default(), start_time: chrono::Utc::now() }
+        Self { assets: Default::default(), start_time: chrono::Utc::now(), cpu_budget: None, gpu_budget: None }
     }
     pub fn n_loading(&self) -> usize {
         self.assets.values().filter(|x| x.is_loading()).count()
     }
+    /// The loaded assets, grouped by type
+    pub fn stats_by_type(&self) -> HashMap<String, AssetTypeStats> {
+        let mut stats = HashMap::<String, AssetTypeStats>::new();
+        for asset in self.assets.values().filter(|x| x.is_loaded()) {
+            let entry = stats.entry(asset.type_name.clone()).or_default();
+            entry.count += 1;
+            entry.cpu_size += asset.cpu_size.unwrap_or_default();
+            entry.gpu_size += asset.gpu_size.unwrap_or_default();
+        }
+        stats
+    }
+    /// All the loaded assets
+    pub fn total_stats(&self) -> AssetTypeStats {
+        self.stats_by_type().into_values().fold(AssetTypeStats::default(), |total, stats| AssetTypeStats {
+            count: total.count + stats.count,
+            cpu_size: total.cpu_size + stats.cpu_size,
+            gpu_size: total.gpu_size + stats.gpu_size,
+        })
+    }
 
-    fn start_load(&mut self, key: AssetKey, long_name: String, stack: Vec<AssetKey>, keepalive: bool) {
+    fn start_load(&mut self, key: AssetKey, long_name: String, type_name: String, stack: Vec<AssetKey>, keepalive: bool) {
         let asset = self.assets.entry(key).or_default();
         asset.long_name = long_name;
+        asset.type_name = type_name;
         asset.stack = stack;
         asset.is_alive = true;
         asset.lifetimes.push(AssetLifetime {
@@ -678,6 +834,7 @@ impl AssetsTimeline {
             keepalive_end: None,
             dropped: None,
             aborted: None,
+            evicted: None,
             keepalive,
         });
     }
@@ -702,6 +859,10 @@ impl AssetsTimeline {
         lf.keepalive_start.get_or_insert(chrono::Utc::now());
     }
 
+    fn evicted(&mut self, key: &AssetKey) {
+        self.last_lifetime(key).evicted = Some(chrono::Utc::now());
+    }
+
     fn dropped(&mut self, key: &AssetKey) {
         let asset = self.assets.get_mut(key).unwrap();
         asset.lifetimes.last_mut().unwrap().dropped = Some(chrono::Utc::now());
@@ -768,6 +929,8 @@ where
             // Replace the loading state with the loaded state
             assert!(loc.content.is_loading());
             loc.content = ContentState::Loaded { value: weak_res, check_alive };
+            loc.cpu_size = cpu_size.unwrap_or_default();
+            loc.gpu_size = gpu_size.unwrap_or_default();
 
             Poll::Ready(LoadPayload { asset_key: p.asset_key.clone(), strong: value })
         } else {
@@ -788,6 +951,22 @@ impl<F, K> PinnedDrop for AssetLoadFuture<F, K> {
     }
 }
 
+/// The name of the type without module paths, e.g. `Result<Arc<Texture>, AssetError>`
+fn short_type_name<T>() -> String {
+    let name = std::any::type_name::<T>();
+    let mut short = String::with_capacity(name.len());
+    let mut start = 0;
+    for (i, c) in name.char_indices() {
+        if matches!(c, '<' | '>' | ',' | '(' | ')' | '[' | ']' | ';' | '&' | ' ') {
+            short.push_str(name[start..i].rsplit("::").next().unwrap());
+            short.push(c);
+            start = i + 1;
+        }
+    }
+    short.push_str(name[start..].rsplit("::").next().unwrap());
+    short
+}
+
 #[cfg(test)]
 mod test {
     use tokio::{runtime, time::timeout};
@@ -836,6 +1015,12 @@ mod test {
         assert!(matches!(state, Some(ContentState::Loaded { .. })));
     }
 
+    #[test]
+    fn short_type_names() {
+        assert_eq!(short_type_name::<Result<Arc<TestAsset>, String>>(), "Result<Arc<TestAsset>, String>");
+        assert_eq!(short_type_name::<(u32, Vec<TestAssetKey>)>(), "(u32, Vec<TestAssetKey>)");
+    }
+
     #[tokio::test]
     async fn test_weak_asset() {
         use std::sync::atomic::{AtomicU32, Ordering};
@@ -0,0 +1,104 @@
+use std::{sync::Arc, time::Duration};
+
+use ambient_asset_cache::{AssetCache, AssetCacheConfig, AssetKeepalive, AssetTypeStats, AsyncAssetKey, AsyncAssetKeyExt};
+use async_trait::async_trait;
+use pretty_assertions::assert_eq;
+use tokio::{runtime, time::sleep};
+
+#[derive(PartialEq, Eq, Debug)]
+struct Clip {
+    name: String,
+}
+
+#[derive(Debug, Clone)]
+struct ClipKey {
+    name: String,
+    keepalive: AssetKeepalive,
+}
+
+#[async_trait]
+impl AsyncAssetKey<Arc<Clip>> for ClipKey {
+    async fn load(self, _: AssetCache) -> Arc<Clip> {
+        Arc::new(Clip { name: self.name })
+    }
+
+    fn keepalive(&self) -> AssetKeepalive {
+        self.keepalive.clone()
+    }
+
+    fn cpu_size(&self, _: &Arc<Clip>) -> Option<u64> {
+        Some(100)
+    }
+}
+
+fn clip(name: &str) -> ClipKey {
+    ClipKey { name: name.into(), keepalive: AssetKeepalive::Forever }
+}
+
+#[tokio::test]
+async fn evict_least_recently_used() {
+    let assets = AssetCache::new_with_config(runtime::Handle::current(), AssetCacheConfig { cpu_budget: Some(250), ..Default::default() });
+
+    for name in ["a", "b", "c"] {
+        clip(name).get(&assets).await;
+        sleep(Duration::from_millis(10)).await;
+    }
+    // Use `a` again, so that `b` is the least recently used
+    clip("a").get(&assets).await;
+
+    assert_eq!(assets.timeline.lock().total_stats(), AssetTypeStats { count: 3, cpu_size: 300, gpu_size: 0 });
+
+    sleep(Duration::from_millis(1500)).await;
+    assert!(clip("a").is_loaded(&assets).is_some());
+    assert!(clip("b").is_loaded(&assets).is_none());
+    assert!(clip("c").is_loaded(&assets).is_some());
+}
+
+#[tokio::test]
+async fn keep_referenced_assets() {
+    let assets = AssetCache::new_with_config(runtime::Handle::current(), AssetCacheConfig { cpu_budget: Some(50), ..Default::default() });
+
+    let a = clip("a").get(&assets).await;
+
+    // The keepalive is released, but the asset is still in use
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(clip("a").is_loaded(&assets), Some(a.clone()));
+
+    drop(a);
+    sleep(Duration::from_millis(1000)).await;
+    assert!(clip("a").is_loaded(&assets).is_none());
+    assert_eq!(assets.timeline.lock().stats_by_type().get("Arc<Clip>"), None);
+}
+
+#[tokio::test]
+async fn no_budget() {
+    let assets = AssetCache::new(runtime::Handle::current());
+
+    for name in ["a", "b", "c"] {
+        clip(name).get(&assets).await;
+    }
+
+    sleep(Duration::from_millis(1500)).await;
+    for name in ["a", "b", "c"] {
+        assert!(clip(name).is_loaded(&assets).is_some());
+    }
+    assert_eq!(assets.timeline.lock().stats_by_type().get("Arc<Clip>"), Some(&AssetTypeStats { count: 3, cpu_size: 300, gpu_size: 0 }));
+}
+
+#[tokio::test]
+async fn evict_past_referenced_assets() {
+    let assets = AssetCache::new_with_config(runtime::Handle::current(), AssetCacheConfig { cpu_budget: Some(150), ..Default::default() });
+
+    // The least recently used asset is still in use, so releasing its keepalive doesn't free anything
+    let referenced = ClipKey { name: "referenced".into(), keepalive: AssetKeepalive::Timeout(Duration::from_secs(60)) };
+    let a = referenced.get(&assets).await;
+    for name in ["b", "c"] {
+        sleep(Duration::from_millis(10)).await;
+        clip(name).get(&assets).await;
+    }
+
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(referenced.is_loaded(&assets), Some(a));
+    assert!(clip("b").is_loaded(&assets).is_none());
+    assert!(clip("c").is_loaded(&assets).is_none());
+}
@@ -164,10 +164,49 @@ impl ElementComponent for AssetTimelineVisualizer {
             ])
             .keyboard(),
         );
+        children.insert(1, AssetTypeStatsTable(timeline.clone()).el());
         FlowColumn::el(children).with(fit_horizontal(), Fit::Parent)
     }
 }
 
+/// The memory used by the loaded assets of each type, and the budget of the cache
+#[derive(Debug, Clone)]
+struct AssetTypeStatsTable(Arc<AssetsTimeline>);
+impl ElementComponent for AssetTypeStatsTable {
+    fn render(self: Box<Self>, _: &mut Hooks) -> Element {
+        let usage = |size: u64, budget: Option<u64>| match budget {
+            Some(budget) => format!("{} / {}", to_byte_unit(size), to_byte_unit(budget)),
+            None => to_byte_unit(size),
+        };
+        let timeline = self.0;
+        let total = timeline.total_stats();
+        let rows = timeline
+            .stats_by_type()
+            .into_iter()
+            .sorted_by(|x, y| y.1.cpu_size.cmp(&x.1.cpu_size).then_with(|| x.0.cmp(&y.0)))
+            .map(|(type_name, stats)| {
+                Text::el(format!(
+                    "{type_name}: {} assets, cpu {}, gpu {}",
+                    stats.count,
+                    to_byte_unit(stats.cpu_size),
+                    to_byte_unit(stats.gpu_size)
+                ))
+            });
+        FlowColumn::el(
+            [Text::el(format!(
+                "Loaded: {} assets, cpu {}, gpu {}",
+                total.count,
+                usage(total.cpu_size, timeline.cpu_budget),
+                usage(total.gpu_size, timeline.gpu_budget)
+            ))]
+            .into_iter()
+            .chain(rows)
+            .collect_vec(),
+        )
+        .with_padding_even(STREET)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct AssetTimelineRow {
     key: AssetKey,
@@ -3,7 +3,7 @@ use std::{
     sync::Arc,
 };
 
-use ambient_asset_cache::{AssetCache, SyncAssetKeyExt};
+use ambient_asset_cache::{AssetCache, AssetCacheConfig, SyncAssetKeyExt};
 use ambient_physics::physx::{Physics, PhysicsKey};
 use ambient_project::Manifest as ProjectManifest;
 use ambient_std::{asset_url::AbsAssetUrl, path::path_to_unix_string};
@@ -68,7 +68,7 @@ async fn build_assets(physics: Physics, assets_path: &Path, build_path: &Path) {
         .filter(|e| e.metadata().map(|x| x.is_file()).unwrap_or(false))
         .map(|x| AbsAssetUrl::from_file_path(x.into_path()))
         .collect_vec();
-    let assets = AssetCache::new_with_config(tokio::runtime::Handle::current(), None);
+    let assets = AssetCache::new_with_config(tokio::runtime::Handle::current(), AssetCacheConfig::default());
     PhysicsKey.insert(&assets, physics);
     let ctx = ProcessCtx {
         assets: assets.clone(),
@@ -2,7 +2,7 @@ use std::{path::PathBuf, sync::Arc};
 
 use ambient_core::hierarchy::children;
 use ambient_ecs::Entity;
-use ambient_model_import::{model_crate::ModelCrate, MaterialFilter, ModelTextureSize, ModelTransform, TextureResolver};
+use ambient_model_import::{model_crate::ModelCrate, MaterialFilter, ModelTextureSize, ModelTransform, RagdollSettings, TextureResolver};
 use ambient_physics::collider::{collider_type, ColliderType};
 use ambient_std::asset_url::AssetType;
 use futures::FutureExt;
@@ -65,6 +65,13 @@ pub struct ModelsPipeline {
     /// If a collider is present, this controls how it will interact with other colliders.
     #[serde(default)]
     collider_type: ColliderType,
+    /// If specified, a ragdoll is generated from the skin of this model: one capsule body per bone, jointed to its parent.
+    /// The bodies are spawned with the prefab, and are simulated when the `ragdoll` component is added to it.
+    #[serde(default)]
+    ragdoll: Option<RagdollSettings>,
+    /// If specified, lower detail LODs will be generated for the meshes by simplifying them.
+    #[serde(default)]
+    generate_lods: Option<GenerateLods>,
     /// Whether or not this mesh should have its texture sizes capped.
     cap_texture_sizes: Option<ModelTextureSize>,
     /// Treats all assets in the pipeline as variations, and outputs a single asset which is a collection of all assets.
@@ -77,6 +84,9 @@ pub struct ModelsPipeline {
     /// Output the animations that belonged to this model.
     #[serde(default = "true_value")]
     output_animations: bool,
+    /// Also output the model and its animations as a binary glTF file (`main.glb`), for use in external tools. Off by default.
+    #[serde(default)]
+    output_glb: bool,
     /// If specified, these components will be added to the prefabs produced by `output_prefabs`.
     ///
     /// This is a great way to specify additional information about your prefab that can be used by gameplay logic.
@@ -109,6 +119,9 @@ impl ModelsPipeline {
                 mat.material.to_mat(ctx, &ctx.in_root(), &ctx.out_root().push(out_model_path.as_ref().join("materials"))?).await?;
             model_crate.override_material(&mat.filter, material);
         }
+        if let Some(lods) = &self.generate_lods {
+            model_crate.generate_mesh_lods(&lods.ratios, lods.lod_cutoffs.clone())?;
+        }
         if let Some(max_size) = self.cap_texture_sizes {
             model_crate.cap_texture_sizes(max_size.size());
         }
@@ -119,8 +132,13 @@ impl ModelsPipeline {
                 model_crate.create_collider_from_model(&ctx.process_ctx.assets, flip_normals, reverse_indices).unwrap();
             }
             Collider::Character { radius, height } => model_crate.create_character_collider(radius, height),
+            Collider::Capsule { radius, height } => model_crate.create_capsule_collider(radius, height),
+            Collider::ConvexHull => model_crate.create_convex_collider(&ctx.process_ctx.assets)?,
         }
         model_crate.add_component_to_prefab(collider_type(), self.collider_type);
+        if let Some(ragdoll) = &self.ragdoll {
+            model_crate.create_ragdoll(ragdoll)?;
+        }
         let world = model_crate.prefab_world_mut();
         let obj = world.resource(children())[0];
         world.add_components(obj, self.prefab_components.clone()).unwrap();
@@ -128,6 +146,20 @@ impl ModelsPipeline {
     }
 }
 
+fn default_lod_ratios() -> Vec<f32> {
+    vec![0.5, 0.25, 0.1]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateLods {
+    /// The triangle count of each generated LOD, as a fraction of the original mesh. Defaults to `[0.5, 0.25, 0.1]`.
+    #[serde(default = "default_lod_ratios")]
+    pub ratios: Vec<f32>,
+    /// The screen sizes at which to switch to each LOD, starting with the original mesh, so one more than `ratios`.
+    /// If not specified, they will be spread out down to 4% of the screen.
+    pub lod_cutoffs: Option<Vec<f32>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MaterialOverride {
     /// The filter for this override (i.e. what it should apply to).
@@ -173,6 +205,16 @@ pub enum Collider {
         /// The height of the collider.
         height: Option<f32>,
     },
+    /// Use a capsule collider, extending along the Z axis.
+    Capsule {
+        /// The radius of the collider. Defaults to 0.5.
+        radius: Option<f32>,
+        /// The distance between the centers of the two caps of the collider. Defaults to 1.
+        height: Option<f32>,
+    },
+    /// Use the convex hull of the model as the collider.
+    /// The hull is stored in the prefab, so no cooked mesh asset is needed.
+    ConvexHull,
 }
 
 fn create_texture_resolver(ctx: &PipelineCtx) -> TextureResolver {
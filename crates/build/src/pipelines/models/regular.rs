@@ -32,6 +32,9 @@ pub async fn pipeline(ctx: &PipelineCtx, config: ModelsPipeline) -> Vec<OutAsset
                 config.apply(&ctx, &mut model_crate, &out_model_path).await?;
 
                 let model_crate_url = ctx.write_model_crate(&model_crate, &out_model_path).await;
+                if config.output_glb {
+                    ctx.write_file(out_model_path.join("main.glb"), model_crate.to_glb()?).await;
+                }
 
                 if config.output_prefabs {
                     res.push(OutAsset {
@@ -454,7 +454,8 @@ fn update_transform_recursive(world: &mut World, id: EntityId, mut parent_transf
         }
     }
 }
-fn get_fbx_transform(world: &World, id: EntityId) -> Mat4 {
+/// The local to parent transform of an entity with `fbx_complex_transform`
+pub fn get_fbx_transform(world: &World, id: EntityId) -> Mat4 {
     world
         .get(id, translation())
         .map(Mat4::from_translation)
@@ -76,7 +76,7 @@ impl<'a> Debug for DebugWorldArchetypes<'a> {
 mod internal_components {
     use super::Message;
 
-    use crate::{components, Description, Resource, WorldEvents};
+    use crate::{components, Description, Resource, RunTimings, WorldEvents};
 
     pub trait WorldEventsExt {
         fn add_message<M: Message>(&mut self, message: M);
@@ -94,10 +94,15 @@ mod internal_components {
             Description["A global general event queue for this ecs World. Can be used to dispatch or listen to any kinds of events."]
         ]
         world_events: WorldEvents,
+        @[
+            Resource,
+            Description["If present, the system groups (and other runtimes, like the WASM modules) record how long they run for in it."]
+        ]
+        run_timings: RunTimings,
     });
 }
 pub use generated::components::core::ecs::*;
-pub use internal_components::{world_events, WorldEventsExt};
+pub use internal_components::{run_timings, world_events, WorldEventsExt};
 
 pub fn init_components() {
     generated::components::init();
@@ -115,6 +120,8 @@ pub struct World {
     /// Used for reset_events. Prevents change events in queries when you use reset_events
     ignore_query_inits: bool,
     query_ticker: CloneableAtomicU64,
+    /// When set, spawned entities get their ids from this instead of `EntityId::new`
+    entity_id_sequence: Option<EntityIdSequence>,
 }
 impl World {
     pub fn new(name: &'static str) -> Self {
@@ -133,6 +140,7 @@ impl World {
             shape_change_events: None,
             ignore_query_inits: false,
             query_ticker: CloneableAtomicU64::new(0),
+            entity_id_sequence: None,
         };
         if resources {
             world.spawn_with_id(EntityId::resources(), Entity::new());
@@ -176,7 +184,7 @@ impl World {
     }
 
     pub fn batch_spawn(&mut self, entity_data: Entity, count: usize) -> Vec<EntityId> {
-        let ids = (0..count).map(|_| EntityId::new()).collect_vec();
+        let ids = (0..count).map(|_| self.new_entity_id()).collect_vec();
         for id in &ids {
             self.locs.insert(*id, EntityLocation::empty());
         }
@@ -184,6 +192,26 @@ impl World {
         ids
     }
 
+    /// Makes the ids of entities spawned in this world a deterministic sequence, so that a session
+    /// can be reproduced from its recorded inputs
+    pub fn seed_entity_ids(&mut self, seed: u64) {
+        self.entity_id_sequence = Some(EntityIdSequence::new(seed));
+    }
+    pub fn entity_id_sequence(&self) -> Option<EntityIdSequence> {
+        self.entity_id_sequence
+    }
+    pub fn set_entity_id_sequence(&mut self, sequence: Option<EntityIdSequence>) {
+        self.entity_id_sequence = sequence;
+    }
+
+    /// Returns a new entity id from the entity id sequence of this world. Worlds that weren't seeded
+    /// with [`World::seed_entity_ids`] get a sequence with a random seed
+    pub fn new_entity_id(&mut self) -> EntityId {
+        self.entity_id_sequence
+            .get_or_insert_with(|| EntityIdSequence::new(rand::random()))
+            .next_id()
+    }
+
     /// Returns false if the id already exists
     pub fn spawn_with_id(&mut self, entity_id: EntityId, entity_data: Entity) -> bool {
         if let std::collections::hash_map::Entry::Vacant(e) = self.locs.entry(entity_id) {
@@ -93,10 +93,38 @@ impl<'de> Deserialize<'de> for EntityId {
     }
 }
 
+/// A deterministic sequence of entity ids; see [`crate::World::seed_entity_ids`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityIdSequence {
+    pub seed: u64,
+    /// The number of ids taken from the sequence so far
+    pub position: u64,
+}
+impl EntityIdSequence {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, position: 0 }
+    }
+    pub fn next_id(&mut self) -> EntityId {
+        let position = self.position.wrapping_mul(2);
+        self.position += 1;
+        EntityId::from_u64s(splitmix64(self.seed, position), splitmix64(self.seed, position.wrapping_add(1)))
+    }
+}
+
+/// The value at `index` of the SplitMix64 sequence of `seed`; hashing the position, rather than
+/// seeding an rng with `seed + position`, keeps the sequences of nearby seeds apart
+fn splitmix64(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 #[test]
 fn test_entity_id_bytes() {
+    let mut ids = EntityIdSequence::new(0);
     for _ in 0..100 {
-        let id = EntityId::new();
+        let id = ids.next_id();
         let (a, b) = id.to_u64s();
         assert_eq!(id, EntityId::from_u64s(a, b));
     }
@@ -108,9 +136,22 @@ fn test_entity_id_bytes() {
     assert_eq!(id, EntityId::from_u64s(a, b));
 }
 #[test]
+fn test_entity_id_sequence() {
+    // The same seed gives the same ids, even when resumed from a position
+    let ids = (0..100).scan(EntityIdSequence::new(7), |ids, _| Some(ids.next_id())).collect::<Vec<_>>();
+    let mut resumed = EntityIdSequence { seed: 7, position: 50 };
+    assert_eq!(resumed.next_id(), ids[50]);
+
+    // Nearby seeds don't share ids
+    let mut next = EntityIdSequence::new(8);
+    let next_ids = (0..100).map(|_| next.next_id()).collect::<std::collections::HashSet<_>>();
+    assert!(ids.iter().all(|id| !next_ids.contains(id)));
+}
+#[test]
 fn test_entity_id_serialization() {
+    let mut ids = EntityIdSequence::new(0);
     for _ in 0..100 {
-        let id = EntityId::new();
+        let id = ids.next_id();
         assert_eq!(id, serde_json::from_str(&serde_json::to_string(&id).unwrap()).unwrap());
     }
 }
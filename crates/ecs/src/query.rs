@@ -1,5 +1,6 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
+use ambient_sys::time::Instant;
 use itertools::Itertools;
 
 use super::*;
@@ -1070,12 +1071,17 @@ enum Label {
     Static(&'static str),
     Dynamic(String),
 }
-impl Display for Label {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
+impl Label {
+    fn as_str(&self) -> &str {
+        match self {
             Label::Static(s) => s,
             Label::Dynamic(s) => s,
-        })
+        }
+    }
+}
+impl Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -1096,6 +1102,7 @@ impl<E> SystemGroup<E> {
 }
 impl<E> System<E> for SystemGroup<E> {
     fn run(&mut self, world: &mut World, event: &E) {
+        let start = Instant::now();
         let mut execute = || {
             for system in self.1.iter_mut() {
                 // ambient_profiling::scope!("sub", format!("iteration {}", i).as_str());
@@ -1115,6 +1122,9 @@ impl<E> System<E> for SystemGroup<E> {
                 execute();
             }
         }
+        if let Some(timings) = world.resource_mut_opt(run_timings()) {
+            timings.record("system_group", self.0.as_str(), start.elapsed());
+        }
     }
 }
 impl<E> std::fmt::Debug for SystemGroup<E> {
@@ -1177,3 +1187,39 @@ pub fn ensure_has_component_with_make_default<
             },
         )
 }
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunTiming {
+    pub runs: u64,
+    pub total: Duration,
+}
+
+/// The cumulative run times of the world's [`SystemGroup`]s (as `"system_group"`, by label), and of anything else that
+/// records its own, like the WASM modules. Only recorded while the world has the [`run_timings`] resource.
+#[derive(Debug, Clone, Default)]
+pub struct RunTimings(HashMap<&'static str, HashMap<String, RunTiming>>);
+impl RunTimings {
+    pub fn record(&mut self, kind: &'static str, name: &str, time: Duration) {
+        let timings = self.0.entry(kind).or_default();
+        if let Some(timing) = timings.get_mut(name) {
+            timing.runs += 1;
+            timing.total += time;
+        } else {
+            timings.insert(
+                name.to_string(),
+                RunTiming {
+                    runs: 1,
+                    total: time,
+                },
+            );
+        }
+    }
+    /// The timings, as `(kind, name, timing)`
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str, &RunTiming)> + '_ {
+        self.0.iter().flat_map(|(kind, timings)| {
+            timings
+                .iter()
+                .map(|(name, timing)| (*kind, name.as_str(), timing))
+        })
+    }
+}
@@ -60,7 +60,7 @@ pub async fn rpc_select(args: ServerRpcArgs, (method, mode): (SelectMethod, Sele
             SelectMethod::Ray(ray) => {
                 if let Some((entity, _)) = raycast_filtered(
                     world,
-                    RaycastFilter { entities: Some(ArchetypeFilter::new().incl(selectable())), collider_type: None },
+                    RaycastFilter { entities: Some(ArchetypeFilter::new().incl(selectable())), collider_type: None, collision_layer: None },
                     ray,
                 ) {
                     Selection::new([entity])
@@ -240,10 +240,11 @@ impl ElementComponent for EditorBuildMode {
                                             game_client.game_state.lock().screen_ray(get_mouse_clip_space_position(world))
                                         };
                                         let position = ray.origin + ray.dir * 10.;
+                                        let entity_id = game_client.game_state.lock().world.new_entity_id();
                                         world.resource(runtime()).spawn(async move {
                                             client_push_intent(game_client, intent_spawn_object(), IntentSpawnObject {
                                                 object_url,
-                                                entity_id: EntityId::new(),
+                                                entity_id,
                                                 position,
                                                 select: true
                                             }, None, Some(Box::new(move || {
@@ -296,8 +297,12 @@ impl ElementComponent for EditorBuildMode {
                                 let game_client = game_client.clone();
 
                                 tracing::info!("Duplicating {targets:?}");
+                                let new_uids = {
+                                    let mut game_state = game_client.game_state.lock();
+                                    targets.iter().map(|_| game_state.world.new_entity_id()).collect()
+                                };
                                 world.resource(runtime()).spawn(
-                                    client_push_intent(game_client, intent_duplicate(), IntentDuplicate { new_uids: targets.iter().map(|_| EntityId::new()).collect(), entities: targets.to_vec(), select: true }, None, Some(Box::new(move || {
+                                    client_push_intent(game_client, intent_duplicate(), IntentDuplicate { new_uids, entities: targets.to_vec(), select: true }, None, Some(Box::new(move || {
                                         tracing::info!("Entering translate move");
 
 
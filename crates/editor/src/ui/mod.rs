@@ -14,7 +14,7 @@ use ambient_naturals::{get_default_natural_layers, natural_layers, NaturalsPrese
 use ambient_network::{
     client::GameClient,
     hooks::{use_remote_persisted_resource, use_remote_player_component},
-    log_network_result,
+    log_network_error,
     rpc::{rpc_fork_instance, rpc_get_instances_info, rpc_join_instance, RpcForkInstance},
     server::MAIN_INSTANCE_ID,
     unwrap_log_network_err,
@@ -122,13 +122,9 @@ pub fn EditorUI(hooks: &mut Hooks) -> Element {
                             )
                             .await
                     );
-                    log_network_result!(game_client.rpc(rpc_join_instance, id).await);
+                    join_instance(&game_client, id).await;
                 } else {
-                    log_network_result!(
-                        game_client
-                            .rpc(rpc_join_instance, MAIN_INSTANCE_ID.to_string())
-                            .await
-                    );
+                    join_instance(&game_client, MAIN_INSTANCE_ID.to_string()).await;
                 }
             });
             |_| {}
@@ -251,6 +247,14 @@ pub fn EditorUI(hooks: &mut Hooks) -> Element {
     .el()
 }
 
+async fn join_instance(game_client: &GameClient, id: String) {
+    match game_client.rpc(rpc_join_instance, id.clone()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => log::warn!("Can't join instance {id}: {err}"),
+        Err(err) => log_network_error(&err.into()),
+    }
+}
+
 #[element_component]
 fn ServerInstancesInfo(hooks: &mut Hooks) -> Element {
     let (game_client, _) = hooks.consume_context::<GameClient>().unwrap();
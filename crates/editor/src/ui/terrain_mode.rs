@@ -506,6 +506,7 @@ impl ElementComponent for EditorTerrainMode {
                     filter: RaycastFilter {
                         entities: Some(ArchetypeFilter::new().incl(terrain_world_cell())),
                         collider_type: Some(ColliderScene::Physics),
+                        collision_layer: None,
                     },
                     layer,
                     brush,
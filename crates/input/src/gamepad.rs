@@ -0,0 +1,73 @@
+use std::{sync::mpsc, time::Duration};
+
+use ambient_shared_types::GamepadButton;
+use gilrs::{Button, EventType, Gilrs};
+
+use crate::GamepadButtonEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+/// Polls the gamepads on a thread of its own, as `Gilrs` can't be sent between threads on all platforms.
+/// Returns `None` if gamepads are not supported on this system.
+pub(crate) fn listen() -> Option<mpsc::Receiver<GamepadButtonEvent>> {
+    let (tx, rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("gamepads".to_string())
+        .spawn(move || {
+            let mut gilrs = match Gilrs::new() {
+                Ok(gilrs) => {
+                    ready_tx.send(true).ok();
+                    gilrs
+                }
+                Err(err) => {
+                    tracing::warn!("Gamepads are not available: {err}");
+                    ready_tx.send(false).ok();
+                    return;
+                }
+            };
+            loop {
+                while let Some(event) = gilrs.next_event() {
+                    let (button, pressed) = match event.event {
+                        EventType::ButtonPressed(button, _) => (button, true),
+                        EventType::ButtonReleased(button, _) => (button, false),
+                        _ => continue,
+                    };
+                    let button = match gamepad_button(button) {
+                        Some(button) => button,
+                        None => continue,
+                    };
+                    if tx.send(GamepadButtonEvent { gamepad: usize::from(event.id) as u32, button, pressed }).is_err() {
+                        // The input system is gone
+                        return;
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })
+        .ok()?;
+    ready_rx.recv().ok()?.then_some(rx)
+}
+
+fn gamepad_button(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::RightTrigger2 => GamepadButton::RightTrigger,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::Mode => GamepadButton::Mode,
+        Button::LeftThumb => GamepadButton::LeftThumb,
+        Button::RightThumb => GamepadButton::RightThumb,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        Button::C | Button::Z | Button::Unknown => return None,
+    })
+}
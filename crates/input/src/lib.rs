@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::mpsc};
 
 use ambient_ecs::{components, generated::messages, world_events, Debuggable, Entity, Resource, System, SystemGroup, WorldEventsExt};
 use glam::{vec2, Vec2};
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use winit::event::ModifiersState;
 pub use winit::event::{DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
 
+#[cfg(not(target_os = "unknown"))]
+mod gamepad;
 pub mod picking;
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
@@ -39,15 +41,28 @@ pub fn resources() -> Entity {
     Entity::new().with_default(player_raw_input()).with_default(player_prev_raw_input())
 }
 
+/// A button of a gamepad being pressed or released
+#[derive(Debug, Clone, Copy)]
+pub struct GamepadButtonEvent {
+    pub gamepad: u32,
+    pub button: ambient_shared_types::GamepadButton,
+    pub pressed: bool,
+}
+
 #[derive(Debug)]
 pub struct InputSystem {
     modifiers: ModifiersState,
     is_focused: bool,
+    gamepad_events: Option<mpsc::Receiver<GamepadButtonEvent>>,
 }
 
 impl InputSystem {
     pub fn new() -> Self {
-        Self { modifiers: ModifiersState::empty(), is_focused: true }
+        #[cfg(not(target_os = "unknown"))]
+        let gamepad_events = gamepad::listen();
+        #[cfg(target_os = "unknown")]
+        let gamepad_events = None;
+        Self { modifiers: ModifiersState::empty(), is_focused: true, gamepad_events }
     }
 }
 
@@ -108,6 +123,20 @@ impl System<Event<'static, ()>> for InputSystem {
             Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
                 world.resource_mut(world_events()).add_message(messages::WindowMouseMotion::new(vec2(delta.0 as f32, delta.1 as f32)));
             }
+            Event::MainEventsCleared => {
+                if let Some(gamepad_events) = &self.gamepad_events {
+                    for event in gamepad_events.try_iter() {
+                        // Like the keyboard, gamepads only drive the focused window
+                        if self.is_focused {
+                            world.resource_mut(world_events()).add_message(messages::GamepadInput::new(
+                                event.button.to_string(),
+                                event.gamepad,
+                                event.pressed,
+                            ));
+                        }
+                    }
+                }
+            }
             _ => {}
         }
     }
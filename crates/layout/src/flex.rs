@@ -0,0 +1,140 @@
+//! Flexbox-style line breaking, growing and shrinking of the children of a flow, along its main axis.
+
+/// A child of a flow, along the main axis of the flow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlexItem {
+    /// The size of the child, including its margins
+    pub size: f32,
+    /// The smallest size the child can be shrunk to, including its margins
+    pub min_size: f32,
+    pub grow: f32,
+    pub shrink: f32,
+}
+impl FlexItem {
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            min_size: 0.,
+            grow: 0.,
+            shrink: 0.,
+        }
+    }
+}
+
+/// Returns the line of each item; an item starts a new line when it doesn't fit in the
+/// `available` space after the items before it on the same line
+pub fn flex_lines(items: &[FlexItem], available: f32, gap: f32, wrap: bool) -> Vec<usize> {
+    let mut line = 0;
+    let mut offset = 0.;
+    let mut line_len = 0;
+    items
+        .iter()
+        .map(|item| {
+            if wrap && line_len > 0 && offset + item.size > available {
+                line += 1;
+                offset = 0.;
+                line_len = 0;
+            }
+            offset += item.size + gap;
+            line_len += 1;
+            line
+        })
+        .collect()
+}
+
+/// Resolves the sizes of the items of a line, so that they fill the `available` space: free space is
+/// shared out by `grow`, and overflow is taken back by `shrink` weighted by the size of the items.
+///
+/// Like in CSS, if the `grow` factors add up to less than 1, only that fraction of the free space is used.
+pub fn flex_resolve_line(items: &[FlexItem], available: f32, gap: f32) -> Vec<f32> {
+    let used = items.iter().map(|item| item.size).sum::<f32>()
+        + gap * items.len().saturating_sub(1) as f32;
+    let free = available - used;
+    if free > 0. {
+        let total_grow = items.iter().map(|item| item.grow).sum::<f32>();
+        if total_grow > 0. {
+            return items
+                .iter()
+                .map(|item| item.size + free * item.grow / total_grow.max(1.))
+                .collect();
+        }
+    } else if free < 0. {
+        let total_shrink = items
+            .iter()
+            .map(|item| item.shrink * item.size)
+            .sum::<f32>();
+        if total_shrink > 0. {
+            return items
+                .iter()
+                .map(|item| {
+                    (item.size + free * item.shrink * item.size / total_shrink).max(item.min_size)
+                })
+                .collect();
+        }
+    }
+    items.iter().map(|item| item.size).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grow(size: f32, grow: f32) -> FlexItem {
+        FlexItem {
+            grow,
+            ..FlexItem::new(size)
+        }
+    }
+    fn shrink(size: f32, shrink: f32) -> FlexItem {
+        FlexItem {
+            shrink,
+            ..FlexItem::new(size)
+        }
+    }
+
+    #[test]
+    fn lines_wrap_when_full() {
+        let items = [FlexItem::new(40.); 5];
+        assert_eq!(flex_lines(&items, 100., 10., true), vec![0, 0, 1, 1, 2]);
+        assert_eq!(flex_lines(&items, 100., 10., false), vec![0; 5]);
+        // Items that exactly fill the line stay on it
+        let items = [FlexItem::new(50.); 2];
+        assert_eq!(flex_lines(&items, 100., 0., true), vec![0, 0]);
+        // An item larger than the line gets a line of its own
+        let items = [FlexItem::new(20.), FlexItem::new(150.), FlexItem::new(20.)];
+        assert_eq!(flex_lines(&items, 100., 0., true), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn grow_shares_free_space() {
+        let sizes = flex_resolve_line(&[grow(10., 1.), grow(10., 3.), grow(20., 0.)], 100., 0.);
+        assert_eq!(sizes, vec![25., 55., 20.]);
+        // Gaps are not free space
+        let sizes = flex_resolve_line(&[grow(10., 1.), grow(10., 1.)], 100., 20.);
+        assert_eq!(sizes, vec![45., 45.]);
+        // Growth factors below 1 only use part of the free space
+        let sizes = flex_resolve_line(&[grow(0., 0.25), grow(0., 0.25)], 100., 0.);
+        assert_eq!(sizes, vec![25., 25.]);
+    }
+
+    #[test]
+    fn shrink_is_weighted_by_size() {
+        let sizes = flex_resolve_line(&[shrink(100., 1.), shrink(50., 1.)], 120., 0.);
+        assert_eq!(sizes, vec![80., 40.]);
+        let sizes = flex_resolve_line(&[shrink(100., 1.), shrink(50., 0.)], 120., 0.);
+        assert_eq!(sizes, vec![70., 50.]);
+        let item = FlexItem {
+            min_size: 90.,
+            ..shrink(100., 1.)
+        };
+        let sizes = flex_resolve_line(&[item, shrink(50., 0.)], 120., 0.);
+        assert_eq!(sizes, vec![90., 50.]);
+    }
+
+    #[test]
+    fn no_flex_keeps_sizes() {
+        let items = [FlexItem::new(30.), FlexItem::new(40.)];
+        assert_eq!(flex_resolve_line(&items, 100., 0.), vec![30., 40.]);
+        assert_eq!(flex_resolve_line(&items, 50., 0.), vec![30., 40.]);
+    }
+}
@@ -0,0 +1,555 @@
+//! A CSS-style grid layout, which places the children of an entity in the cells of rows and columns.
+//!
+//! The tracks of the grid are set with `grid_columns` and `grid_rows`, as a list such as
+//! `"100 1fr 2fr auto"`: a number is a fixed size, `fr` is a share of the space left by the other
+//! tracks, and `auto` fits the largest child in the track. Rows (or columns) that the children need
+//! beyond the listed ones are `auto`.
+//!
+//! Children go in the cell given by their `grid_cell` (column, row) and cover `grid_span` cells;
+//! children without a `grid_cell` fill the free cells row by row.
+
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use ambient_core::transform::{local_to_parent, translation};
+use ambient_ecs::{Component, EntityId, World};
+use glam::{uvec2, vec2, UVec2, Vec2};
+use itertools::Itertools;
+
+use crate::{
+    align_horizontal, align_vertical, fit_horizontal, fit_vertical, grid_cell, grid_columns,
+    grid_gap, grid_rows, grid_span, height, invalidate_parent_layout, margin, min_height,
+    min_width, padding, width, Align, Borders, Fit, Orientation, Z_DELTA,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridTrack {
+    /// A fixed size
+    Fixed(f32),
+    /// A share of the space left after the other tracks
+    Fraction(f32),
+    /// The size of the largest child in the track
+    Auto,
+}
+impl GridTrack {
+    /// Parses a whitespace separated list of tracks, such as `"100 1fr auto"`
+    pub fn parse_list(tracks: &str) -> Result<Vec<Self>, InvalidGridTrack> {
+        tracks
+            .split_whitespace()
+            .map(|track| track.parse())
+            .collect()
+    }
+}
+impl FromStr for GridTrack {
+    type Err = InvalidGridTrack;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidGridTrack(s.to_string());
+        if s == "auto" {
+            Ok(Self::Auto)
+        } else if let Some(fraction) = s.strip_suffix("fr") {
+            Ok(Self::Fraction(fraction.parse().map_err(|_| invalid())?))
+        } else {
+            Ok(Self::Fixed(
+                s.strip_suffix("px")
+                    .unwrap_or(s)
+                    .parse()
+                    .map_err(|_| invalid())?,
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidGridTrack(pub String);
+impl fmt::Display for InvalidGridTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid grid track {:?}; expected a size, a fraction such as `1fr`, or `auto`",
+            self.0
+        )
+    }
+}
+impl std::error::Error for InvalidGridTrack {}
+
+/// The number of columns and rows that children can be explicitly placed in or span
+pub const MAX_GRID_TRACKS: u32 = 1024;
+
+/// Where a child of a grid should go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridPlacement {
+    /// The (column, row) of the child; if not set, the child goes in the next free cell
+    pub cell: Option<UVec2>,
+    /// The number of (columns, rows) the child covers
+    pub span: UVec2,
+}
+impl GridPlacement {
+    /// Keeps the cell and the span of the child within the first [`MAX_GRID_TRACKS`] columns and
+    /// rows, and makes it cover at least one cell
+    pub fn clamped(self) -> Self {
+        let max = UVec2::splat(MAX_GRID_TRACKS);
+        let cell = self.cell.map(|cell| cell.min(max - 1));
+        let span = self
+            .span
+            .max(UVec2::ONE)
+            .min(max - cell.unwrap_or(UVec2::ZERO));
+        Self { cell, span }
+    }
+}
+
+/// Returns the (column, row) of each child. Children with a cell are placed first, then the others
+/// fill the free cells row by row, in `columns` columns. The placements are [`GridPlacement::clamped`]
+pub fn grid_place(placements: &[GridPlacement], columns: u32) -> Vec<UVec2> {
+    let columns = columns.max(1);
+    let placements = placements.iter().map(|placement| placement.clamped());
+    let cells_of = |cell: UVec2, span: UVec2| {
+        (cell.x..cell.x.saturating_add(span.x))
+            .cartesian_product(cell.y..cell.y.saturating_add(span.y))
+    };
+    let mut occupied = HashSet::new();
+    for placement in placements.clone() {
+        if let Some(cell) = placement.cell {
+            occupied.extend(cells_of(cell, placement.span));
+        }
+    }
+    let mut cursor = UVec2::ZERO;
+    placements
+        .map(|placement| {
+            if let Some(cell) = placement.cell {
+                return cell;
+            }
+            loop {
+                // A child wider than the grid can only start a row
+                if cursor.x > 0 && cursor.x.saturating_add(placement.span.x) > columns {
+                    cursor = uvec2(0, cursor.y + 1);
+                } else if cells_of(cursor, placement.span).any(|cell| occupied.contains(&cell)) {
+                    cursor.x += 1;
+                } else {
+                    break;
+                }
+            }
+            let cell = cursor;
+            occupied.extend(cells_of(cell, placement.span));
+            cursor.x = cursor.x.saturating_add(placement.span.x);
+            cell
+        })
+        .collect()
+}
+
+/// A child of a grid, along one axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridItem {
+    /// The first track the child is in
+    pub track: u32,
+    pub span: u32,
+    /// The size the child needs, including its margins
+    pub size: f32,
+}
+
+/// Resolves the sizes of the tracks of one axis of a grid. `count` is the number of tracks, which is
+/// at least the number of `tracks`; tracks that are not listed are `auto`. `available` is the space
+/// to fill, or `None` when the grid is sized to its children, in which case fractional tracks are
+/// sized like `auto` ones
+pub fn grid_track_sizes(
+    tracks: &[GridTrack],
+    count: usize,
+    items: &[GridItem],
+    available: Option<f32>,
+    gap: f32,
+) -> Vec<f32> {
+    let track = |i: usize| tracks.get(i).copied().unwrap_or(GridTrack::Auto);
+    let content_sized = |i: usize| match track(i) {
+        GridTrack::Fixed(_) => false,
+        GridTrack::Fraction(_) => available.is_none(),
+        GridTrack::Auto => true,
+    };
+    let mut sizes = (0..count)
+        .map(|i| match track(i) {
+            GridTrack::Fixed(size) => size,
+            _ => 0.,
+        })
+        .collect_vec();
+
+    // Children in a single track size it, then children spanning several tracks grow the content
+    // sized ones among them evenly if they don't fit
+    for item in items.iter().filter(|item| item.span == 1) {
+        let i = item.track as usize;
+        if content_sized(i) {
+            sizes[i] = sizes[i].max(item.size);
+        }
+    }
+    for item in items.iter().filter(|item| item.span > 1) {
+        let range = item.track as usize..(item.track as usize + item.span as usize).min(count);
+        let current = sizes[range.clone()].iter().sum::<f32>() + gap * (item.span - 1) as f32;
+        let growable = range.filter(|&i| content_sized(i)).collect_vec();
+        if item.size > current && !growable.is_empty() {
+            let extra = (item.size - current) / growable.len() as f32;
+            for i in growable {
+                sizes[i] += extra;
+            }
+        }
+    }
+
+    // Fractional tracks share what's left; like in CSS, fractions adding up to less than 1 only
+    // use part of it
+    if let Some(available) = available {
+        let total_fraction = (0..count)
+            .filter_map(|i| match track(i) {
+                GridTrack::Fraction(fraction) => Some(fraction),
+                _ => None,
+            })
+            .sum::<f32>();
+        if total_fraction > 0. {
+            let used = sizes.iter().sum::<f32>() + gap * count.saturating_sub(1) as f32;
+            let free = (available - used).max(0.);
+            for (i, size) in sizes.iter_mut().enumerate() {
+                if let GridTrack::Fraction(fraction) = track(i) {
+                    *size = free * fraction / total_fraction.max(1.);
+                }
+            }
+        }
+    }
+    sizes
+}
+
+/// The start of each track, and the total size of the tracks
+pub fn grid_track_offsets(sizes: &[f32], gap: f32) -> (Vec<f32>, f32) {
+    let mut offset = 0.;
+    let offsets = sizes
+        .iter()
+        .map(|size| {
+            let start = offset;
+            offset += size + gap;
+            start
+        })
+        .collect();
+    (offsets, (offset - gap).max(0.))
+}
+
+fn tracks(world: &World, id: EntityId, component: Component<String>) -> Vec<GridTrack> {
+    match world.get_ref(id, component) {
+        Ok(tracks) => GridTrack::parse_list(tracks).unwrap_or_else(|err| {
+            log::warn!("{err}");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[ambient_profiling::function]
+pub(crate) fn grid_layout(world: &mut World, id: EntityId, children: Vec<EntityId>) {
+    let children = children
+        .into_iter()
+        .filter(|&c| world.has_component(c, local_to_parent()))
+        .collect_vec();
+    let self_padding: Borders = world
+        .get(id, padding())
+        .unwrap_or(Borders::ZERO.into())
+        .into();
+    let self_size = vec2(
+        world.get(id, width()).unwrap_or(0.),
+        world.get(id, height()).unwrap_or(0.),
+    );
+    let self_fit_horizontal = world.get(id, fit_horizontal()).unwrap_or(Fit::None);
+    let self_fit_vertical = world.get(id, fit_vertical()).unwrap_or(Fit::None);
+    let columns = tracks(world, id, grid_columns());
+    let rows = tracks(world, id, grid_rows());
+    let gap = world.get(id, grid_gap()).unwrap_or(Vec2::ZERO);
+
+    let placements = children
+        .iter()
+        .map(|&c| {
+            GridPlacement {
+                cell: world.get(c, grid_cell()).ok(),
+                span: world.get(c, grid_span()).unwrap_or(UVec2::ONE),
+            }
+            .clamped()
+        })
+        .collect_vec();
+    let cells = grid_place(&placements, columns.len() as u32);
+
+    struct Child {
+        id: EntityId,
+        cell: UVec2,
+        span: UVec2,
+        margin: Borders,
+        fit: (Fit, Fit),
+        size: Vec2,
+    }
+    let children = children
+        .iter()
+        .zip(cells)
+        .zip(placements)
+        .map(|((&c, cell), placement)| {
+            let fit = (
+                world.get(c, fit_horizontal()).unwrap_or(Fit::Parent),
+                world.get(c, fit_vertical()).unwrap_or(Fit::Parent),
+            );
+            // Stretched children only need their minimum size
+            let size = vec2(
+                if fit.0 == Fit::Parent {
+                    world.get(c, min_width()).unwrap_or(0.)
+                } else {
+                    world.get(c, width()).unwrap_or(0.)
+                },
+                if fit.1 == Fit::Parent {
+                    world.get(c, min_height()).unwrap_or(0.)
+                } else {
+                    world.get(c, height()).unwrap_or(0.)
+                },
+            );
+            Child {
+                id: c,
+                cell,
+                span: placement.span,
+                margin: world
+                    .get(c, margin())
+                    .unwrap_or(Borders::ZERO.into())
+                    .into(),
+                fit,
+                size,
+            }
+        })
+        .collect_vec();
+
+    let column_count = children
+        .iter()
+        .map(|child| child.cell.x as usize + child.span.x as usize)
+        .fold(columns.len(), usize::max);
+    let row_count = children
+        .iter()
+        .map(|child| child.cell.y as usize + child.span.y as usize)
+        .fold(rows.len(), usize::max);
+    let column_items = children
+        .iter()
+        .map(|child| GridItem {
+            track: child.cell.x,
+            span: child.span.x,
+            size: child.size.x + child.margin.get_horizontal(),
+        })
+        .collect_vec();
+    let row_items = children
+        .iter()
+        .map(|child| GridItem {
+            track: child.cell.y,
+            span: child.span.y,
+            size: child.size.y + child.margin.get_vertical(),
+        })
+        .collect_vec();
+    let column_sizes = grid_track_sizes(
+        &columns,
+        column_count,
+        &column_items,
+        (self_fit_horizontal != Fit::Children)
+            .then_some(self_size.x - self_padding.get_horizontal()),
+        gap.x,
+    );
+    let row_sizes = grid_track_sizes(
+        &rows,
+        row_count,
+        &row_items,
+        (self_fit_vertical != Fit::Children).then_some(self_size.y - self_padding.get_vertical()),
+        gap.y,
+    );
+    let (column_offsets, columns_width) = grid_track_offsets(&column_sizes, gap.x);
+    let (row_offsets, rows_height) = grid_track_offsets(&row_sizes, gap.y);
+
+    let align_horizontal = world.get(id, align_horizontal()).unwrap_or(Align::Begin);
+    let align_vertical = world.get(id, align_vertical()).unwrap_or(Align::Begin);
+    // The position and size of a child along one axis of its cell
+    let place = |start: f32,
+                 cell_size: f32,
+                 size: f32,
+                 margins: (f32, f32),
+                 stretch: bool,
+                 align: Align| {
+        if stretch {
+            (start + margins.0, Some(cell_size - margins.0 - margins.1))
+        } else {
+            let position = match align {
+                Align::Begin => start + margins.0,
+                Align::Center => {
+                    start + margins.0 + (cell_size - size - margins.0 - margins.1) / 2.
+                }
+                Align::End => start + cell_size - size - margins.1,
+            };
+            (position, None)
+        }
+    };
+    for child in children {
+        let (x, span_x) = (child.cell.x as usize, child.span.x);
+        let (y, span_y) = (child.cell.y as usize, child.span.y);
+        let cell_width =
+            column_sizes[x..x + span_x as usize].iter().sum::<f32>() + gap.x * (span_x - 1) as f32;
+        let cell_height =
+            row_sizes[y..y + span_y as usize].iter().sum::<f32>() + gap.y * (span_y - 1) as f32;
+        let (left, new_width) = place(
+            self_padding.left + column_offsets[x],
+            cell_width,
+            child.size.x,
+            (child.margin.left, child.margin.right),
+            child.fit.0 == Fit::Parent,
+            align_horizontal,
+        );
+        let (top, new_height) = place(
+            self_padding.top + row_offsets[y],
+            cell_height,
+            child.size.y,
+            (child.margin.top, child.margin.bottom),
+            child.fit.1 == Fit::Parent,
+            align_vertical,
+        );
+        if let Some(new_width) = new_width {
+            world.set_if_changed(child.id, width(), new_width).ok();
+        }
+        if let Some(new_height) = new_height {
+            world.set_if_changed(child.id, height(), new_height).ok();
+        }
+        world
+            .set_if_changed(child.id, translation(), vec2(left, top).extend(Z_DELTA))
+            .ok();
+    }
+
+    let new_width = columns_width + self_padding.get_horizontal();
+    if self_fit_horizontal == Fit::Children && self_size.x != new_width {
+        world.set(id, width(), new_width).ok();
+        invalidate_parent_layout(world, id, Orientation::Horizontal);
+    }
+    let new_height = rows_height + self_padding.get_vertical();
+    if self_fit_vertical == Fit::Children && self_size.y != new_height {
+        world.set(id, height(), new_height).ok();
+        invalidate_parent_layout(world, id, Orientation::Vertical);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(track: u32, span: u32, size: f32) -> GridItem {
+        GridItem { track, span, size }
+    }
+    fn auto(cell: Option<UVec2>, span: UVec2) -> GridPlacement {
+        GridPlacement { cell, span }
+    }
+
+    #[test]
+    fn parse_tracks() {
+        assert_eq!(
+            GridTrack::parse_list("100 1fr  2.5fr auto 20px").unwrap(),
+            vec![
+                GridTrack::Fixed(100.),
+                GridTrack::Fraction(1.),
+                GridTrack::Fraction(2.5),
+                GridTrack::Auto,
+                GridTrack::Fixed(20.),
+            ]
+        );
+        assert_eq!(GridTrack::parse_list("").unwrap(), vec![]);
+        assert!(GridTrack::parse_list("100 big").is_err());
+    }
+
+    #[test]
+    fn auto_placement_fills_rows() {
+        let one = auto(None, UVec2::ONE);
+        let cells = grid_place(&[one, one, one, one], 3);
+        assert_eq!(
+            cells,
+            vec![uvec2(0, 0), uvec2(1, 0), uvec2(2, 0), uvec2(0, 1)]
+        );
+    }
+
+    #[test]
+    fn auto_placement_skips_occupied_cells() {
+        let one = auto(None, UVec2::ONE);
+        let placements = [
+            one,
+            auto(Some(uvec2(1, 0)), uvec2(1, 2)),
+            auto(None, uvec2(2, 1)),
+            one,
+            one,
+        ];
+        // The explicit child covers (1, 0) and (1, 1), so the two column wide child doesn't fit
+        // before row 2
+        assert_eq!(
+            grid_place(&placements, 3),
+            vec![
+                uvec2(0, 0),
+                uvec2(1, 0),
+                uvec2(0, 2),
+                uvec2(2, 2),
+                uvec2(0, 3),
+            ]
+        );
+        // Children wider than the grid get rows of their own
+        let cells = grid_place(&[one, auto(None, uvec2(4, 1)), one], 2);
+        assert_eq!(cells, vec![uvec2(0, 0), uvec2(0, 1), uvec2(0, 2)]);
+    }
+
+    #[test]
+    fn placements_are_clamped() {
+        let huge = auto(Some(UVec2::splat(u32::MAX)), UVec2::splat(u32::MAX)).clamped();
+        assert_eq!(
+            huge,
+            auto(Some(UVec2::splat(MAX_GRID_TRACKS - 1)), UVec2::ONE)
+        );
+        let wide = auto(Some(uvec2(10, 0)), uvec2(u32::MAX, 0)).clamped();
+        assert_eq!(
+            wide,
+            auto(Some(uvec2(10, 0)), uvec2(MAX_GRID_TRACKS - 10, 1))
+        );
+        assert_eq!(auto(None, UVec2::ZERO).clamped().span, UVec2::ONE);
+        // Spans running past the end of the tracks don't overflow
+        let one = auto(None, UVec2::ONE);
+        let cells = grid_place(
+            &[auto(Some(uvec2(1, 1)), uvec2(u32::MAX, 1)), one, one, one],
+            2,
+        );
+        assert_eq!(
+            cells,
+            vec![uvec2(1, 1), uvec2(0, 0), uvec2(1, 0), uvec2(0, 1)]
+        );
+    }
+
+    #[test]
+    fn fixed_fraction_and_auto_tracks() {
+        let tracks = GridTrack::parse_list("100 1fr 3fr auto").unwrap();
+        let items = [item(3, 1, 40.), item(3, 1, 60.)];
+        let sizes = grid_track_sizes(&tracks, 4, &items, Some(500.), 10.);
+        // 500 - 100 - 60 - 3 gaps of 10 leaves 310 for 4 fractions
+        assert_eq!(sizes, vec![100., 77.5, 232.5, 60.]);
+        let (offsets, total) = grid_track_offsets(&sizes, 10.);
+        assert_eq!(offsets, vec![0., 110., 197.5, 440.]);
+        assert_eq!(total, 500.);
+    }
+
+    #[test]
+    fn implicit_tracks_are_auto() {
+        let tracks = GridTrack::parse_list("50").unwrap();
+        let sizes = grid_track_sizes(&tracks, 3, &[item(2, 1, 30.)], Some(200.), 0.);
+        assert_eq!(sizes, vec![50., 0., 30.]);
+    }
+
+    #[test]
+    fn spans_grow_auto_tracks() {
+        let tracks = GridTrack::parse_list("auto auto 20").unwrap();
+        let items = [item(0, 1, 10.), item(0, 3, 100.)];
+        let sizes = grid_track_sizes(&tracks, 3, &items, None, 10.);
+        // The spanning child needs 100 - 20 - 2 gaps of 10 - 10 = 50 more, shared by the auto tracks
+        assert_eq!(sizes, vec![35., 25., 20.]);
+        // Children that fit their tracks change nothing
+        let sizes = grid_track_sizes(&tracks, 3, &[item(1, 2, 20.)], None, 0.);
+        assert_eq!(sizes, vec![0., 0., 20.]);
+    }
+
+    #[test]
+    fn fractions_without_available_space_fit_children() {
+        let tracks = GridTrack::parse_list("1fr 1fr").unwrap();
+        let sizes = grid_track_sizes(&tracks, 2, &[item(0, 1, 30.)], None, 0.);
+        assert_eq!(sizes, vec![30., 0.]);
+        // Fractions that add up to less than one leave space free
+        let tracks = GridTrack::parse_list("0.25fr 0.25fr").unwrap();
+        let sizes = grid_track_sizes(&tracks, 2, &[], Some(100.), 0.);
+        assert_eq!(sizes, vec![25., 25.]);
+    }
+}
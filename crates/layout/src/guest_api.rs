@@ -13,6 +13,7 @@ pub fn systems() -> SystemGroup {
             ensure_has_component(layout_flow(), layout(), Layout::Flow),
             ensure_has_component(layout_dock(), layout(), Layout::Dock),
             ensure_has_component(layout_bookcase(), layout(), Layout::Bookcase),
+            ensure_has_component(layout_grid(), layout(), Layout::Grid),
             ensure_has_component(
                 layout_width_to_children(),
                 layout(),
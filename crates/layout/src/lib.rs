@@ -13,11 +13,20 @@ use glam::{vec2, vec3, vec4, Mat4, Vec2, Vec4};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    flex::{flex_lines, flex_resolve_line, FlexItem},
+    grid::grid_layout,
+};
+
+pub mod flex;
+pub mod grid;
 pub mod guest_api;
 
 pub use ambient_ecs::generated::components::core::layout::{
-    fit_horizontal_parent, gpu_ui_size, height, is_book_file, margin, max_height, max_width,
-    mesh_to_local_from_size, min_height, min_width, padding, screen, space_between_items, width,
+    fit_horizontal_parent, flex_basis, flex_grow, flex_no_wrap, flex_shrink, gpu_ui_size,
+    grid_cell, grid_columns, grid_gap, grid_rows, grid_span, height, is_book_file, margin,
+    max_height, max_width, mesh_to_local_from_size, min_height, min_width, padding, screen,
+    space_between_items, width,
 };
 
 components!("layout", {
@@ -27,6 +36,8 @@ components!("layout", {
     fit_horizontal: Fit,
     docking: Docking,
     orientation: Orientation,
+    /// The size along the main axis of its flow that a child had before it was first grown or shrunk
+    flex_base_size: f32,
     align_horizontal: Align,
     align_vertical: Align,
 });
@@ -196,6 +207,8 @@ pub enum Layout {
     Bookcase,
     /// Just copy the width of this component to it's children. Used for the ScrollArea
     WidthToChildren,
+    /// Place the children in the cells of a grid; see [`grid`]
+    Grid,
 }
 
 pub fn layout_systems() -> SystemGroup {
@@ -245,6 +258,9 @@ pub fn layout_systems() -> SystemGroup {
                             Layout::WidthToChildren => {
                                 width_to_children(world, id, children);
                             }
+                            Layout::Grid => {
+                                grid_layout(world, id, children);
+                            }
                         }
                     }
                     if !changed {
@@ -450,16 +466,6 @@ fn flow_layout(world: &mut World, id: EntityId, children: Vec<EntityId>) {
     let self_fit_vertical = world.get(id, fit_vertical()).unwrap_or(Fit::None);
     let self_min_width = world.get(id, min_width()).unwrap_or(0.);
     let self_min_height = world.get(id, min_height()).unwrap_or(0.);
-    let self_max_width = if self_fit_horizontal == Fit::Children {
-        f32::INFINITY
-    } else {
-        self_size.x
-    };
-    let self_max_height = if self_fit_vertical == Fit::Children {
-        f32::INFINITY
-    } else {
-        self_size.y
-    };
     let mut children_width: f32 = 0.;
     let mut children_height: f32 = 0.;
     let mut line_width = 0.;
@@ -469,9 +475,19 @@ fn flow_layout(world: &mut World, id: EntityId, children: Vec<EntityId>) {
         .filter(|id| world.has_component(**id, local_to_parent()))
         .copied()
         .collect_vec();
+    let lines = flex_flow_children(
+        world,
+        id,
+        &children,
+        orientation,
+        self_size,
+        space_between_items,
+    );
+    let mut current_line = 0;
     let items = children
         .iter()
-        .map(|&c| {
+        .zip(lines)
+        .map(|(&c, line)| {
             let child_margin: Borders = world
                 .get(c, margin())
                 .unwrap_or(Borders::ZERO.into())
@@ -492,11 +508,8 @@ fn flow_layout(world: &mut World, id: EntityId, children: Vec<EntityId>) {
                     world.get(c, height()).unwrap_or(0.) + child_margin.top + child_margin.bottom
                 },
             );
-            let break_line = match orientation {
-                Orientation::Horizontal => offset.x + child_size.x >= self_max_width,
-                Orientation::Vertical => offset.y + child_size.y >= self_max_height,
-            };
-            if break_line {
+            if line != current_line {
+                current_line = line;
                 match orientation {
                     Orientation::Horizontal => {
                         offset.x = 0.;
@@ -612,6 +625,105 @@ fn flow_layout(world: &mut World, id: EntityId, children: Vec<EntityId>) {
     }
 }
 
+/// Breaks the children of a flow into lines, and grows or shrinks them along the main axis of the
+/// flow to fill their lines. Returns the line of each child
+fn flex_flow_children(
+    world: &mut World,
+    id: EntityId,
+    children: &[EntityId],
+    orientation: Orientation,
+    self_size: Vec2,
+    space_between_items: f32,
+) -> Vec<usize> {
+    let (fit, size, min_size) = match orientation {
+        Orientation::Horizontal => (fit_horizontal(), width(), min_width()),
+        Orientation::Vertical => (fit_vertical(), height(), min_height()),
+    };
+    let main_axis = |borders: Borders| match orientation {
+        Orientation::Horizontal => borders.get_horizontal(),
+        Orientation::Vertical => borders.get_vertical(),
+    };
+    let self_padding: Borders = world
+        .get(id, padding())
+        .unwrap_or(Borders::ZERO.into())
+        .into();
+    let self_fit = world.get(id, fit).unwrap_or(Fit::None);
+    let self_main_size = match orientation {
+        Orientation::Horizontal => self_size.x,
+        Orientation::Vertical => self_size.y,
+    };
+    let mut margins = Vec::with_capacity(children.len());
+    let items = children
+        .iter()
+        .map(|&c| {
+            let child_margin = main_axis(
+                world
+                    .get(c, margin())
+                    .unwrap_or(Borders::ZERO.into())
+                    .into(),
+            );
+            margins.push(child_margin);
+            if world.get(c, fit).unwrap_or(Fit::None) == Fit::Parent {
+                return FlexItem::new(0.);
+            }
+            let grow = world.get(c, flex_grow()).unwrap_or(0.);
+            let shrink = world.get(c, flex_shrink()).unwrap_or(0.);
+            // Flexed children are sized from their basis, or from the size they had before they
+            // were first flexed
+            let current_size = world.get(c, size).unwrap_or(0.);
+            let base_size = if grow > 0. || shrink > 0. {
+                world
+                    .get(c, flex_basis())
+                    .or_else(|_| world.get(c, flex_base_size()))
+                    .unwrap_or(current_size)
+            } else {
+                current_size
+            };
+            FlexItem {
+                size: base_size + child_margin,
+                min_size: world.get(c, min_size).unwrap_or(0.) + child_margin,
+                grow,
+                shrink,
+            }
+        })
+        .collect_vec();
+    let max_size = if self_fit == Fit::Children {
+        f32::INFINITY
+    } else {
+        self_main_size
+    };
+    let wrap = !world.has_component(id, flex_no_wrap());
+    let lines = flex_lines(&items, max_size, space_between_items, wrap);
+    if self_fit == Fit::Children
+        || items
+            .iter()
+            .all(|item| item.grow == 0. && item.shrink == 0.)
+    {
+        return lines;
+    }
+
+    let available = self_main_size - main_axis(self_padding);
+    let line_count = lines.last().map(|line| line + 1).unwrap_or(0);
+    for line in 0..line_count {
+        let indices = lines.iter().positions(|&l| l == line).collect_vec();
+        let line_items = indices.iter().map(|&i| items[i]).collect_vec();
+        let sizes = flex_resolve_line(&line_items, available, space_between_items);
+        for (&i, new_size) in indices.iter().zip(sizes) {
+            let (c, item) = (children[i], items[i]);
+            if item.grow == 0. && item.shrink == 0. {
+                continue;
+            }
+            if !world.has_component(c, flex_basis()) && !world.has_component(c, flex_base_size()) {
+                world
+                    .add_component(c, flex_base_size(), item.size - margins[i])
+                    .ok();
+            }
+            world.set_if_changed(c, size, new_size - margins[i]).ok();
+        }
+    }
+    lines
+}
+
 #[ambient_profiling::function]
 fn bookcase_layout(world: &mut World, id: EntityId, files: Vec<EntityId>) {
     let orientation = world
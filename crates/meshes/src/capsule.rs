@@ -374,6 +374,7 @@ impl From<CapsuleMesh> for Mesh {
             joint_indices: None,
             joint_weights: None,
             indices: Some(tris),
+            morph_targets: Vec::new(),
         };
         mesh.create_tangents();
         mesh
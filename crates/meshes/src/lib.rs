@@ -114,6 +114,7 @@ pub fn triangle() -> Mesh {
         joint_indices: None,
         joint_weights: None,
         indices: None,
+        morph_targets: Vec::new(),
     }
 }
 
@@ -140,6 +141,7 @@ pub fn pentagon() -> Mesh {
         joint_indices: None,
         joint_weights: None,
         indices: Some(vec![0, 1, 4, 1, 2, 4, 2, 3, 4]),
+        morph_targets: Vec::new(),
     }
 }
 
@@ -186,6 +188,7 @@ impl From<QuadMesh> for Mesh {
             joint_indices: None,
             joint_weights: None,
             indices: Some(vec![0, 1, 2, 1, 3, 2]),
+            morph_targets: Vec::new(),
         };
         mesh.create_tangents();
         mesh
@@ -33,9 +33,11 @@ use glam::{vec4, Vec3};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 mod model;
+mod morph_targets;
 
 use ambient_meshes::CubeMeshKey;
 pub use model::*;
+pub use morph_targets::*;
 use tokio::sync::Semaphore;
 
 use self::loading_material::{LoadingMaterialKey, LoadingShaderKey};
@@ -62,6 +64,14 @@ components!("model", {
 
     @[Debuggable, Networked, Store]
     is_model_node: (),
+
+    /// The weights of the morph targets of this node's meshes
+    @[Debuggable, Networked, Store]
+    morph_target_weights: Vec<f32>,
+    @[Debuggable, Networked, Store]
+    morph_target_names: Vec<String>,
+    @[Debuggable, Networked, Store]
+    morph_target_bounds: MorphTargetBounds,
 });
 
 #[tracing::instrument(skip(assets, async_run))]
@@ -201,6 +211,7 @@ pub fn model_systems() -> SystemGroup {
 
                 runtime.spawn(async move { internal_spawn_models_from_defs(&assets, async_run, new_models).await });
             }),
+            morph_target_bounds_system(),
         ],
     )
 }
@@ -29,7 +29,7 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use super::{
-    animation_bind_id, animation_binder, is_model_node, model_animatable, model_loaded, model_skin_ix, model_skins,
+    animation_bind_id, animation_binder, is_model_node, model_animatable, model_loaded, model_skin_ix, model_skins, morph_target_bounds,
     pbr_renderer_primitives_from_url,
 };
 
@@ -455,6 +455,9 @@ impl Model {
             if let Some(bounding) = ed.get_mut(local_bounding_aabb()) {
                 *bounding = bounding.transform(&mat).to_aabb();
             }
+            if let Some(bounds) = ed.get_mut(morph_target_bounds()) {
+                *bounds = bounds.transform(&mat);
+            }
         } else {
             if let Ok(transform) = self.0.get(node, mesh_to_local()) {
                 ed.set(mesh_to_local(), transform);
@@ -0,0 +1,88 @@
+use ambient_core::bounding::local_bounding_aabb;
+use ambient_ecs::{query, DynSystem};
+use ambient_std::{mesh::Mesh, shapes::AABB};
+use glam::{Mat3, Mat4};
+use serde::{Deserialize, Serialize};
+
+use super::{morph_target_bounds, morph_target_weights};
+
+/// The bounds of a node's meshes in their rest pose, together with the bounds of the displacements of each
+/// of their morph targets. Used to keep `local_bounding_aabb` up to date when the weights change, without
+/// having to evaluate the morph targets for each vertex.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MorphTargetBounds {
+    pub base: AABB,
+    pub deltas: Vec<Option<AABB>>,
+}
+impl MorphTargetBounds {
+    /// Returns None if none of the meshes have any morph targets
+    pub fn from_meshes<'a>(base: AABB, meshes: impl IntoIterator<Item = &'a Mesh>) -> Option<Self> {
+        let mut deltas: Vec<Option<AABB>> = Vec::new();
+        for mesh in meshes {
+            if deltas.len() < mesh.morph_targets.len() {
+                deltas.resize(mesh.morph_targets.len(), None);
+            }
+            for (bounds, target) in deltas.iter_mut().zip(&mesh.morph_targets) {
+                *bounds = match (*bounds, target.delta_aabb()) {
+                    (Some(a), Some(b)) => Some(a.union(&b)),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+        if deltas.is_empty() {
+            None
+        } else {
+            Some(Self { base, deltas })
+        }
+    }
+    pub fn transform(&self, transform: &Mat4) -> Self {
+        // The deltas are displacements, so they are only affected by the linear part of the transform
+        let linear = Mat4::from_mat3(Mat3::from_mat4(*transform));
+        Self {
+            base: self.base.transform(transform).to_aabb(),
+            deltas: self.deltas.iter().map(|delta| delta.map(|delta| delta.transform(&linear).to_aabb())).collect(),
+        }
+    }
+    /// A conservative bound of the meshes with the morph targets applied with `weights`
+    pub fn evaluate(&self, weights: &[f32]) -> AABB {
+        let mut aabb = self.base;
+        for (delta, &weight) in self.deltas.iter().zip(weights) {
+            if let Some(delta) = delta {
+                let (a, b) = (delta.min * weight, delta.max * weight);
+                aabb.min += a.min(b);
+                aabb.max += a.max(b);
+            }
+        }
+        aabb
+    }
+}
+
+pub(crate) fn morph_target_bounds_system() -> DynSystem {
+    query((morph_target_weights().changed(), morph_target_bounds())).to_system(|q, world, qs, _| {
+        for (id, (weights, bounds)) in q.collect_cloned(world, qs) {
+            world.add_component(id, local_bounding_aabb(), bounds.evaluate(&weights)).ok();
+        }
+    })
+}
+
+#[test]
+fn test_morph_target_bounds() {
+    use ambient_std::mesh::MorphTarget;
+    use glam::{vec3, Vec3};
+
+    let mesh = Mesh {
+        positions: Some(vec![Vec3::ZERO, Vec3::ONE]),
+        morph_targets: vec![
+            MorphTarget { name: "up".to_string(), position_deltas: vec![Vec3::ZERO, vec3(0., 0., 2.)], ..Default::default() },
+            MorphTarget { name: "left".to_string(), position_deltas: vec![vec3(-1., 0., 0.), Vec3::ZERO], ..Default::default() },
+        ],
+        ..Default::default()
+    };
+    let bounds = MorphTargetBounds::from_meshes(mesh.aabb().unwrap(), [&mesh]).unwrap();
+    assert_eq!(bounds.evaluate(&[]), AABB { min: Vec3::ZERO, max: Vec3::ONE });
+    assert_eq!(bounds.evaluate(&[0.5, 1.]), AABB { min: vec3(-1., 0., 0.), max: vec3(1., 1., 2.) });
+
+    let mut morphed = mesh.clone();
+    morphed.apply_morph_targets(&[0.5, 1.]);
+    assert_eq!(morphed.positions.unwrap(), vec![vec3(-1., 0., 0.), vec3(1., 1., 2.)]);
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use ambient_animation::{animation_bind_id_from_name, AnimationClip, AnimationOutputs, AnimationTarget, AnimationTrack, Vec3Field};
 use ambient_core::transform::{euler_rotation, scale, translation};
+use ambient_model::morph_target_weights;
 use fbxcel::tree::v7400::NodeHandle;
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
@@ -98,11 +99,58 @@ pub fn get_animations(doc: &FbxDoc) -> HashMap<String, AnimationClip> {
                 },
             };
             clip.merge_field_tracks();
+            clip.tracks.extend(get_morph_target_tracks(doc, stack));
             (stack.name.clone(), clip)
         })
         .collect()
 }
 
+/// Creates one track per model with blend shapes, which animates all of the weights of its morph targets
+fn get_morph_target_tracks(doc: &FbxDoc, stack: &FbxAnimationStack) -> Vec<AnimationTrack> {
+    let channel_curves = stack
+        .layers
+        .iter()
+        .flat_map(|layer_id| doc.animation_layers.get(layer_id).unwrap().curve_nodes.iter())
+        .map(|curve_node_id| doc.animation_curve_nodes.get(curve_node_id).unwrap())
+        .flat_map(|curve_node| {
+            let curve = curve_node.curves.get("d|DeformPercent").and_then(|curve_id| doc.animation_curves.get(curve_id));
+            curve_node.blend_shape_channels.iter().filter_map(move |channel_id| Some((*channel_id, curve?)))
+        })
+        .collect::<HashMap<_, _>>();
+    if channel_curves.is_empty() {
+        return Vec::new();
+    }
+    doc.models
+        .values()
+        .filter_map(|model| {
+            let geometry = doc.geometries.get(model.geometries.first()?)?;
+            let channels = doc.morph_targets(geometry);
+            let curves = channels.iter().map(|(channel, _)| channel_curves.get(&channel.id)).collect_vec();
+            let mut times = curves.iter().flatten().flat_map(|curve| curve.key_time.iter().copied()).collect_vec();
+            if times.is_empty() {
+                return None;
+            }
+            times.sort();
+            times.dedup();
+            let data = times
+                .iter()
+                .map(|&time| {
+                    channels
+                        .iter()
+                        .zip(curves.iter())
+                        .map(|((channel, _), curve)| curve.map(|curve| curve.sample(time)).unwrap_or(channel.deform_percent) / 100.)
+                        .collect()
+                })
+                .collect();
+            Some(AnimationTrack {
+                target: AnimationTarget::BinderId(animation_bind_id_from_name(&model.node_name)),
+                inputs: times.iter().map(|time| *time as f32 / FBX_TIME).collect(),
+                outputs: AnimationOutputs::Weights { component: morph_target_weights(), data },
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct FbxAnimationStack {
     pub id: i64,
@@ -139,11 +187,12 @@ pub struct FbxAnimationCurveNode {
     pub id: i64,
     pub curves: HashMap<String, i64>,
     pub outputs: Vec<(i64, String)>,
+    pub blend_shape_channels: Vec<i64>,
 }
 impl FbxAnimationCurveNode {
     pub fn from_node(node: NodeHandle) -> Self {
         let id = node.attributes()[0].get_i64().unwrap();
-        Self { id, curves: HashMap::new(), outputs: Vec::new() }
+        Self { id, curves: HashMap::new(), outputs: Vec::new(), blend_shape_channels: Vec::new() }
     }
 }
 #[derive(Debug)]
@@ -163,4 +212,17 @@ impl FbxAnimationCurve {
             key_value_float: key_value_float.attributes()[0].get_arr_f32().unwrap().to_vec(),
         }
     }
+    /// Linearly interpolates the value of the curve at `time`
+    pub fn sample(&self, time: i64) -> f32 {
+        let i = self.key_time.partition_point(|&t| t <= time);
+        if i == 0 {
+            return self.key_value_float.first().copied().unwrap_or_default();
+        }
+        if i == self.key_time.len() {
+            return self.key_value_float.last().copied().unwrap_or_default();
+        }
+        let (t0, t1) = (self.key_time[i - 1], self.key_time[i]);
+        let p = (time - t0) as f64 / (t1 - t0) as f64;
+        self.key_value_float[i - 1] + (self.key_value_float[i] - self.key_value_float[i - 1]) * p as f32
+    }
 }
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
-use ambient_std::mesh::Mesh;
+use ambient_std::mesh::{Mesh, MorphTarget};
+use anyhow::Context;
 use fbxcel::tree::v7400::NodeHandle;
 use glam::{uvec4, vec2, vec3, vec4, Mat4, Vec2, Vec3};
 use indexmap::IndexMap;
@@ -103,6 +104,7 @@ pub struct FbxGeometry {
     uvs: Vec<FbxLayerElementUV>,
     materials: Option<FbxLayerElementMaterial>,
     pub skin: Option<i64>,
+    pub blend_shapes: Vec<i64>,
 }
 impl FbxGeometry {
     pub fn from_node(node: NodeHandle, _: &FbxGlobalSettings) -> Self {
@@ -140,9 +142,15 @@ impl FbxGeometry {
             uvs: node.children().filter_map(FbxLayerElementUV::from_node).sorted_by_key(|x| x.channel).collect(),
             materials: materials_container_node.map(FbxLayerElementMaterial::from_node),
             skin: None,
+            blend_shapes: Vec::new(),
         }
     }
-    pub fn to_cpu_meshes(&self, skins: &IndexMap<i64, FbxSkin>, clusters: &HashMap<i64, FbxCluster>) -> Vec<Mesh> {
+    pub fn to_cpu_meshes(
+        &self,
+        skins: &IndexMap<i64, FbxSkin>,
+        clusters: &HashMap<i64, FbxCluster>,
+        morph_targets: &[(&FbxBlendShapeChannel, &FbxShape)],
+    ) -> Vec<Mesh> {
         // FBX is a bit complicated; there is a "merged" list of vertices in the self.vertices field (positions),
         // but other properties (such as normals) may require them to be unmerged, since one corner can have multiple
         // normals. This code handles both cases; when a vertex can be shared by multiple faces it will be, and when
@@ -163,6 +171,8 @@ impl FbxGeometry {
             }
         }
 
+        let morph_target_deltas = morph_targets.iter().map(|(_, shape)| shape.deltas(self.vertices.len())).collect_vec();
+
         // The polygon_vertices represent all vertices for all polygons, so they may be reduntant. For instance, a triangluated
         // quad will have 3+3=6 polygon vertices, but at a later step they may get merged into just 4 vertices
         let polygon_vertices = self
@@ -172,6 +182,7 @@ impl FbxGeometry {
             .map(|(polygon_vertex_index, &vertex_index)| {
                 let vertex_index = if vertex_index >= 0 { vertex_index } else { -vertex_index - 1 } as usize;
                 IntermediateVertex {
+                    vertex_index,
                     position: self.vertices[vertex_index],
                     normal: self.normals.as_ref().map(|normals| match normals.info_type {
                         FbxMappingInformationType::ByPolygonVertex => normals.normals[polygon_vertex_index],
@@ -316,6 +327,18 @@ impl FbxGeometry {
                         None
                     },
                     indices: Some(indices),
+                    morph_targets: morph_targets
+                        .iter()
+                        .zip(morph_target_deltas.iter())
+                        .map(|((channel, _), (position_deltas, normal_deltas))| MorphTarget {
+                            name: channel.name.clone(),
+                            position_deltas: final_vertices.iter().map(|v| position_deltas[v.vertex_index]).collect(),
+                            normal_deltas: normal_deltas
+                                .as_ref()
+                                .map(|deltas| final_vertices.iter().map(|v| deltas[v.vertex_index]).collect()),
+                            tangent_deltas: None,
+                        })
+                        .collect(),
                 };
                 mesh.try_ensure_tangents();
                 mesh
@@ -330,6 +353,8 @@ fn read_vec3(p: &[f64]) -> Vec3 {
 
 #[derive(PartialEq, Clone, Default, Debug)]
 struct IntermediateVertex {
+    /// The index of the control point this vertex was created from
+    vertex_index: usize,
     position: Vec3,
     normal: Option<Vec3>,
     tangent: Option<Vec3>,
@@ -439,3 +464,90 @@ impl FbxCluster {
         }
     }
 }
+
+/// The displacements of a blend shape, stored sparsely as a list of control point indexes and their deltas
+#[derive(Debug)]
+pub struct FbxShape {
+    pub id: i64,
+    indexes: Vec<i32>,
+    vertices: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+}
+impl FbxShape {
+    pub fn from_node(node: NodeHandle) -> anyhow::Result<Self> {
+        let id = node.attributes().first().and_then(|id| id.get_i64()).context("Shape without an id")?;
+        let child = |name: &str| node.children().find(|node| node.name() == name);
+        let indexes = child("Indexes")
+            .and_then(|node| node.attributes().first()?.get_arr_i32())
+            .with_context(|| format!("Shape {id} has no indexes"))?;
+        let vertices = child("Vertices")
+            .and_then(|node| node.attributes().first()?.get_arr_f64())
+            .with_context(|| format!("Shape {id} has no vertices"))?;
+        let normals = match child("Normals") {
+            Some(normals) => Some(
+                normals
+                    .attributes()
+                    .first()
+                    .and_then(|normals| normals.get_arr_f64())
+                    .with_context(|| format!("Shape {id} has invalid normals"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            id,
+            indexes: indexes.to_vec(),
+            vertices: vertices.chunks_exact(3).map(read_vec3).collect(),
+            normals: normals.map(|normals| normals.chunks_exact(3).map(read_vec3).collect()),
+        })
+    }
+    /// Returns one position (and normal) delta per control point of the geometry
+    fn deltas(&self, n_vertices: usize) -> (Vec<Vec3>, Option<Vec<Vec3>>) {
+        let mut positions = vec![Vec3::ZERO; n_vertices];
+        for (&index, &delta) in self.indexes.iter().zip(self.vertices.iter()) {
+            if let Some(position) = positions.get_mut(index as usize) {
+                *position = delta;
+            }
+        }
+        let normals = self.normals.as_ref().filter(|normals| normals.len() == self.indexes.len()).map(|deltas| {
+            let mut normals = vec![Vec3::ZERO; n_vertices];
+            for (&index, &delta) in self.indexes.iter().zip(deltas.iter()) {
+                if let Some(normal) = normals.get_mut(index as usize) {
+                    *normal = delta;
+                }
+            }
+            normals
+        });
+        (positions, normals)
+    }
+}
+
+#[derive(Debug)]
+pub struct FbxBlendShape {
+    pub id: i64,
+    pub channels: Vec<i64>,
+}
+impl FbxBlendShape {
+    pub fn from_node(node: NodeHandle) -> Self {
+        let id = node.attributes()[0].get_i64().unwrap();
+        Self { id, channels: Vec::new() }
+    }
+}
+
+/// A single morph target of a blend shape. Channels can have several in-between shapes, but only
+/// the first one is currently used
+#[derive(Debug)]
+pub struct FbxBlendShapeChannel {
+    pub id: i64,
+    pub name: String,
+    /// The default weight, in percent
+    pub deform_percent: f32,
+    pub shapes: Vec<i64>,
+}
+impl FbxBlendShapeChannel {
+    pub fn from_node(node: NodeHandle) -> Self {
+        let id = node.attributes()[0].get_i64().unwrap();
+        let name = node.attributes()[1].get_string().unwrap().split('\u{0}').next().unwrap().to_string();
+        let deform_percent = node.children().find(|node| node.name() == "DeformPercent").and_then(|node| node.attributes()[0].get_f64());
+        Self { id, name, deform_percent: deform_percent.unwrap_or(0.) as f32, shapes: Vec::new() }
+    }
+}
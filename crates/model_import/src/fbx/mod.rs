@@ -26,7 +26,7 @@ use relative_path::RelativePathBuf;
 use self::{
     animation::{FbxAnimationCurve, FbxAnimationCurveNode, FbxAnimationLayer, FbxAnimationStack},
     material::{FbxMaterial, FbxTexture, FbxVideo},
-    mesh::{FbxCluster, FbxGeometry, FbxSkin},
+    mesh::{FbxBlendShape, FbxBlendShapeChannel, FbxCluster, FbxGeometry, FbxShape, FbxSkin},
     model::FbxModel,
 };
 use crate::{model_crate::ModelCrate, TextureResolver};
@@ -61,7 +61,7 @@ pub async fn import_from_fbx_reader(
             let mut n_meshes = HashMap::new();
 
             for (id, geo) in doc.geometries.iter() {
-                let meshes = geo.to_cpu_meshes(&doc.skins, &doc.clusters);
+                let meshes = geo.to_cpu_meshes(&doc.skins, &doc.clusters, &doc.morph_targets(geo));
                 n_meshes.insert(*id, meshes.len());
                 for (index, mesh) in meshes.into_iter().enumerate() {
                     asset_crate.meshes.insert(format!("{id}_{index}"), mesh);
@@ -139,6 +139,10 @@ pub struct FbxDoc {
     pub skins: IndexMap<i64, FbxSkin>,
     pub clusters: HashMap<i64, FbxCluster>,
 
+    pub shapes: HashMap<i64, FbxShape>,
+    pub blend_shapes: HashMap<i64, FbxBlendShape>,
+    pub blend_shape_channels: HashMap<i64, FbxBlendShapeChannel>,
+
     pub animation_stacks: HashMap<i64, FbxAnimationStack>,
     pub animation_layers: HashMap<i64, FbxAnimationLayer>,
     pub animation_curve_nodes: HashMap<i64, FbxAnimationCurveNode>,
@@ -168,6 +172,10 @@ impl FbxDoc {
             skins: IndexMap::new(),
             clusters: HashMap::new(),
 
+            shapes: HashMap::new(),
+            blend_shapes: HashMap::new(),
+            blend_shape_channels: HashMap::new(),
+
             animation_stacks: HashMap::new(),
             animation_layers: HashMap::new(),
             animation_curve_nodes: HashMap::new(),
@@ -198,6 +206,13 @@ impl FbxDoc {
                     doc.videos.insert(video.id, video);
                 }
 
+                "Geometry" if is_shape(node) => match FbxShape::from_node(node) {
+                    Ok(shape) => {
+                        doc.shapes.insert(shape.id, shape);
+                    }
+                    // Only the morph target is lost
+                    Err(err) => log::warn!("Skipping invalid blend shape: {err:#}"),
+                },
                 "Geometry" => {
                     let geo = FbxGeometry::from_node(node, &doc.global_settings);
                     doc.geometries.insert(geo.id, geo);
@@ -211,6 +226,14 @@ impl FbxDoc {
                         let cluster = FbxCluster::from_node(node);
                         doc.clusters.insert(cluster.id, cluster);
                     }
+                    "BlendShape" => {
+                        let blend_shape = FbxBlendShape::from_node(node);
+                        doc.blend_shapes.insert(blend_shape.id, blend_shape);
+                    }
+                    "BlendShapeChannel" => {
+                        let channel = FbxBlendShapeChannel::from_node(node);
+                        doc.blend_shape_channels.insert(channel.id, channel);
+                    }
                     _ => panic!("Unrecognized type: {}", node.attributes()[2].get_string().unwrap()),
                 },
 
@@ -247,6 +270,7 @@ impl FbxDoc {
                     id,
                     match node.name() {
                         "Deformer" => node.attributes()[2].get_string().unwrap().to_string(),
+                        "Geometry" if is_shape(node) => "Shape".to_string(),
                         _ => node.name().to_string(),
                     },
                 )
@@ -274,6 +298,9 @@ impl FbxDoc {
                     ("Cluster", "Skin") => doc.skins.get_mut(&from).unwrap().clusters.push(to),
                     ("Skin", "Geometry") => doc.geometries.get_mut(&from).unwrap().skin = Some(to),
                     ("Model", "Cluster") => doc.clusters.get_mut(&from).unwrap().bone_id = Some(to),
+                    ("BlendShape", "Geometry") => doc.geometries.get_mut(&from).unwrap().blend_shapes.push(to),
+                    ("BlendShapeChannel", "BlendShape") => doc.blend_shapes.get_mut(&from).unwrap().channels.push(to),
+                    ("Shape", "BlendShapeChannel") => doc.blend_shape_channels.get_mut(&from).unwrap().shapes.push(to),
 
                     ("AnimationLayer", "AnimationStack") => doc.animation_stacks.get_mut(&from).unwrap().layers.push(to),
                     ("AnimationCurveNode", "AnimationLayer") => doc.animation_layers.get_mut(&from).unwrap().curve_nodes.push(to),
@@ -283,12 +310,29 @@ impl FbxDoc {
                     ("AnimationCurveNode", "Model") => {
                         doc.animation_curve_nodes.get_mut(&to).unwrap().outputs.push((from, property.as_ref().unwrap().to_string()));
                     }
+                    ("AnimationCurveNode", "BlendShapeChannel") => {
+                        doc.animation_curve_nodes.get_mut(&to).unwrap().blend_shape_channels.push(from);
+                    }
                     _ => {}
                 }
             }
         }
         doc
     }
+    /// The blend shape channels of a geometry, in the order of the mesh's morph targets, together with the shape they target
+    pub fn morph_targets(&self, geometry: &FbxGeometry) -> Vec<(&FbxBlendShapeChannel, &FbxShape)> {
+        geometry
+            .blend_shapes
+            .iter()
+            .filter_map(|id| self.blend_shapes.get(id))
+            .flat_map(|blend_shape| blend_shape.channels.iter().filter_map(|id| self.blend_shape_channels.get(id)))
+            .filter_map(|channel| Some((channel, self.shapes.get(channel.shapes.first()?)?)))
+            .collect()
+    }
+}
+
+fn is_shape(node: NodeHandle) -> bool {
+    node.attributes().get(2).and_then(|attr| attr.get_string()) == Some("Shape")
 }
 
 pub enum FbxConnectionType {
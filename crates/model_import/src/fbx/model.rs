@@ -9,7 +9,7 @@ use ambient_core::{
     },
 };
 use ambient_ecs::{Entity, EntityId, World};
-use ambient_model::{model_skin_ix, pbr_renderer_primitives_from_url, PbrRenderPrimitiveFromUrl};
+use ambient_model::{model_skin_ix, morph_target_names, morph_target_weights, pbr_renderer_primitives_from_url, PbrRenderPrimitiveFromUrl};
 use ambient_renderer::double_sided;
 use fbxcel::tree::v7400::NodeHandle;
 use glam::{vec3, EulerRot, Mat4, Quat, Vec3};
@@ -176,6 +176,10 @@ impl FbxModel {
             if let Some(skin) = doc.geometries.get(&geo).and_then(|geo| geo.skin).and_then(|id| doc.skins.get_index_of(&id)) {
                 out_node.set(model_skin_ix(), skin);
             }
+            if let Some(morph_targets) = doc.geometries.get(&geo).map(|geo| doc.morph_targets(geo)).filter(|x| !x.is_empty()) {
+                out_node.set(morph_target_weights(), morph_targets.iter().map(|(channel, _)| channel.deform_percent / 100.).collect());
+                out_node.set(morph_target_names(), morph_targets.iter().map(|(channel, _)| channel.name.clone()).collect());
+            }
             if self.geometric_translation.is_some() || self.geometric_rotation.is_some() || self.geometric_scale.is_some() {
                 out_node.set(
                     mesh_to_local(),
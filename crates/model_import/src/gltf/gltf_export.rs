@@ -0,0 +1,723 @@
+use std::collections::HashMap;
+
+use ambient_animation::{AnimationOutputs, AnimationTarget};
+use ambient_core::{
+    hierarchy::{children, parent},
+    name,
+    transform::{fbx_complex_transform, get_fbx_transform, mesh_to_local, rotation, scale, translation},
+};
+use ambient_ecs::{query, EntityId, World};
+use ambient_model::{
+    animation_bind_id, model_from_url, model_skin_ix, model_skins, morph_target_names, morph_target_weights,
+    pbr_renderer_primitives_from_url, ModelSkin, PbrRenderPrimitiveFromUrl,
+};
+use ambient_std::{asset_url::AssetUrl, mesh::Mesh};
+use anyhow::Context;
+use glam::{Mat4, Quat, Vec3, Vec4};
+use itertools::Itertools;
+use serde_json::{json, Map, Value};
+
+use crate::model_crate::ModelCrate;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+/// Ambient is Z-up while glTF is Y-up; this is the same swap that the importer applies, so it's its own inverse
+const ZUP_TO_YUP: Mat4 = Mat4::from_cols(Vec4::X, Vec4::Z, Vec4::Y, Vec4::W);
+
+/// Writes the content of a [ModelCrate] to a binary glTF 2.0 (.glb) file.
+///
+/// Meshes, PBR materials (with their textures embedded as png), skins, morph targets, the node hierarchy and the
+/// animations are exported. Nodes with fbx pivots are exported with their rest transform baked into their TRS,
+/// so animations of those nodes will only be approximate. Only the first LOD of each node is exported.
+pub struct GltfExporter<'a> {
+    model_crate: &'a ModelCrate,
+    nodes: Vec<Value>,
+    meshes: Vec<Value>,
+    materials: Vec<Value>,
+    textures: Vec<Value>,
+    images: Vec<Value>,
+    skins: Vec<Value>,
+    animations: Vec<Value>,
+    accessors: Vec<Value>,
+    buffer_views: Vec<Value>,
+    bin: Vec<u8>,
+    mesh_cache: HashMap<Vec<(String, Option<String>)>, (usize, usize)>,
+    material_cache: HashMap<String, Option<usize>>,
+    texture_cache: HashMap<String, Option<usize>>,
+}
+impl<'a> GltfExporter<'a> {
+    pub fn new(model_crate: &'a ModelCrate) -> Self {
+        Self {
+            model_crate,
+            nodes: Vec::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            textures: Vec::new(),
+            images: Vec::new(),
+            skins: Vec::new(),
+            animations: Vec::new(),
+            accessors: Vec::new(),
+            buffer_views: Vec::new(),
+            bin: Vec::new(),
+            mesh_cache: HashMap::new(),
+            material_cache: HashMap::new(),
+            texture_cache: HashMap::new(),
+        }
+    }
+
+    /// Adds an instance of the crate's main model, and returns the index of its root node.
+    /// The animations of the crate are bound to the first instance which is added.
+    pub fn add_model(&mut self) -> anyhow::Result<usize> {
+        let model_crate = self.model_crate;
+        let model = model_crate.models.content.get(ModelCrate::MAIN).context("The model crate doesn't have a main model")?;
+        let world = &model.0;
+        let mut node_ids = HashMap::new();
+        let mut mesh_nodes = HashMap::new();
+        let roots = world.resource_opt(children()).cloned().unwrap_or_default();
+        let mut roots =
+            roots.into_iter().map(|id| self.add_node(world, id, &mut node_ids, &mut mesh_nodes)).collect::<anyhow::Result<Vec<_>>>()?;
+
+        let skins = world.resource_opt(model_skins()).cloned().unwrap_or_default();
+        let skins = skins.iter().map(|skin| self.add_skin(world, skin, &node_ids, &mut roots)).collect_vec();
+        for (id, &node) in &mesh_nodes {
+            if let Some(&skin) = world.get(*id, model_skin_ix()).ok().and_then(|ix| skins.get(ix)) {
+                self.nodes[node]["skin"] = skin.into();
+            }
+        }
+        if self.animations.is_empty() {
+            self.add_animations(world, &node_ids, &mesh_nodes);
+        }
+
+        let root = self.nodes.len();
+        self.nodes.push(json!({
+            "name": "model",
+            "matrix": model.get_transform().unwrap_or_default().to_cols_array(),
+            "children": roots,
+        }));
+        Ok(root)
+    }
+
+    /// Adds the entities of the crate's main prefab, and returns the indices of the root nodes. Entities using the
+    /// crate's own model get an instance of it; models from other crates can't be resolved here and are skipped.
+    pub fn add_prefab(&mut self) -> anyhow::Result<Vec<usize>> {
+        let model_crate = self.model_crate;
+        let world = model_crate.prefabs.content.get(ModelCrate::MAIN).context("The model crate doesn't have a main prefab")?;
+        let roots = world.resource_opt(children()).cloned().unwrap_or_default();
+        roots.into_iter().map(|id| self.add_prefab_node(world, id)).collect()
+    }
+
+    /// Builds the .glb file, with `roots` as the nodes of the scene
+    pub fn to_glb(mut self, roots: Vec<usize>) -> Vec<u8> {
+        let root = self.nodes.len();
+        self.nodes.push(json!({ "name": "root", "matrix": ZUP_TO_YUP.to_cols_array(), "children": roots }));
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "Ambient" },
+            "scene": 0,
+            "scenes": [{ "nodes": [root] }],
+            "nodes": self.nodes,
+        });
+        if !self.bin.is_empty() {
+            document["buffers"] = json!([{ "byteLength": self.bin.len() }]);
+        }
+        if !self.textures.is_empty() {
+            document["samplers"] = json!([{}]);
+        }
+        for (key, values) in [
+            ("meshes", self.meshes),
+            ("materials", self.materials),
+            ("textures", self.textures),
+            ("images", self.images),
+            ("skins", self.skins),
+            ("animations", self.animations),
+            ("accessors", self.accessors),
+            ("bufferViews", self.buffer_views),
+        ] {
+            if !values.is_empty() {
+                document[key] = values.into();
+            }
+        }
+        write_glb(&serde_json::to_vec(&document).unwrap(), &self.bin)
+    }
+
+    fn add_node(
+        &mut self,
+        world: &World,
+        id: EntityId,
+        node_ids: &mut HashMap<EntityId, usize>,
+        mesh_nodes: &mut HashMap<EntityId, usize>,
+    ) -> anyhow::Result<usize> {
+        let ix = self.nodes.len();
+        self.nodes.push(Value::Null);
+        node_ids.insert(id, ix);
+
+        let mut node = Map::new();
+        let node_name = world.get_ref(id, name()).cloned().unwrap_or_else(|_| format!("node_{ix}"));
+        node.insert("name".to_string(), node_name.clone().into());
+        let (scl, rot, pos) = if world.has_component(id, fbx_complex_transform()) {
+            get_fbx_transform(world, id).to_scale_rotation_translation()
+        } else {
+            (
+                world.get(id, scale()).unwrap_or(Vec3::ONE),
+                world.get(id, rotation()).unwrap_or(Quat::IDENTITY),
+                world.get(id, translation()).unwrap_or(Vec3::ZERO),
+            )
+        };
+        node.insert("translation".to_string(), json!(pos.to_array()));
+        node.insert("rotation".to_string(), json!(rot.normalize().to_array()));
+        node.insert("scale".to_string(), json!(scl.to_array()));
+
+        let mut node_children = Vec::new();
+        if let Ok(primitives) = world.get_ref(id, pbr_renderer_primitives_from_url()) {
+            let names = world.get_ref(id, morph_target_names()).cloned().unwrap_or_default();
+            if let Some((mesh, n_targets)) = self.add_mesh(&node_name, primitives, &names)? {
+                let mut mesh_node = Map::new();
+                mesh_node.insert("mesh".to_string(), mesh.into());
+                if n_targets > 0 {
+                    let mut weights = world.get_ref(id, morph_target_weights()).cloned().unwrap_or_default();
+                    weights.resize(n_targets, 0.);
+                    mesh_node.insert("weights".to_string(), weights.into());
+                }
+                match world.get(id, mesh_to_local()) {
+                    Ok(mesh_to_local) if mesh_to_local != Mat4::IDENTITY => {
+                        // glTF doesn't have geometric transforms, so the mesh is put on a child node instead
+                        mesh_node.insert("name".to_string(), format!("{node_name}_mesh").into());
+                        mesh_node.insert("matrix".to_string(), json!(mesh_to_local.to_cols_array()));
+                        let mesh_ix = self.nodes.len();
+                        self.nodes.push(mesh_node.into());
+                        mesh_nodes.insert(id, mesh_ix);
+                        node_children.push(mesh_ix);
+                    }
+                    _ => {
+                        node.extend(mesh_node);
+                        mesh_nodes.insert(id, ix);
+                    }
+                }
+            }
+        }
+        for child in world.get_ref(id, children()).cloned().unwrap_or_default() {
+            node_children.push(self.add_node(world, child, node_ids, mesh_nodes)?);
+        }
+        if !node_children.is_empty() {
+            node.insert("children".to_string(), node_children.into());
+        }
+        self.nodes[ix] = node.into();
+        Ok(ix)
+    }
+
+    fn add_prefab_node(&mut self, world: &World, id: EntityId) -> anyhow::Result<usize> {
+        let ix = self.nodes.len();
+        self.nodes.push(Value::Null);
+        let mut node = json!({
+            "name": world.get_ref(id, name()).cloned().unwrap_or_else(|_| format!("entity_{ix}")),
+            "translation": world.get(id, translation()).unwrap_or(Vec3::ZERO).to_array(),
+            "rotation": world.get(id, rotation()).unwrap_or(Quat::IDENTITY).normalize().to_array(),
+            "scale": world.get(id, scale()).unwrap_or(Vec3::ONE).to_array(),
+        });
+        let mut node_children = Vec::new();
+        if let Ok(url) = world.get_ref(id, model_from_url()) {
+            let is_own_model =
+                !url.contains("://") && self.model_crate.models.loc.id_from_path(url.as_str()).as_deref() == Some(ModelCrate::MAIN);
+            if is_own_model {
+                node_children.push(self.add_model()?);
+            } else {
+                log::warn!("Skipping model {url} when exporting prefab; only the model crate's own model can be exported");
+                node["extras"] = json!({ "model_from_url": url });
+            }
+        }
+        for child in world.get_ref(id, children()).cloned().unwrap_or_default() {
+            node_children.push(self.add_prefab_node(world, child)?);
+        }
+        if !node_children.is_empty() {
+            node["children"] = node_children.into();
+        }
+        self.nodes[ix] = node;
+        Ok(ix)
+    }
+
+    /// Returns the index of the mesh and its number of morph targets
+    fn add_mesh(
+        &mut self,
+        name: &str,
+        primitives: &[PbrRenderPrimitiveFromUrl],
+        target_names: &[String],
+    ) -> anyhow::Result<Option<(usize, usize)>> {
+        let primitives = primitives.iter().filter(|p| p.lod == 0).collect_vec();
+        let key = primitives.iter().map(|p| (p.mesh.to_string(), p.material.as_ref().map(|x| x.to_string()))).collect_vec();
+        if let Some(mesh) = self.mesh_cache.get(&key) {
+            return Ok(Some(*mesh));
+        }
+        let model_crate = self.model_crate;
+        let mut gltf_primitives = Vec::new();
+        let mut n_targets = None;
+        for primitive in primitives {
+            let mesh =
+                model_crate.meshes.get_by_path(primitive.mesh.path()).with_context(|| format!("Mesh not found: {}", primitive.mesh))?;
+            if mesh.positions.is_none() {
+                continue;
+            }
+            // All primitives of a glTF mesh must have the same number of morph targets
+            if *n_targets.get_or_insert(mesh.morph_targets.len()) != mesh.morph_targets.len() {
+                log::warn!("Skipping primitive {} of {name}; it has a different number of morph targets", mesh.name);
+                continue;
+            }
+            let material = primitive.material.as_ref().and_then(|url| self.add_material(url));
+            gltf_primitives.push(self.add_primitive(mesh, material));
+        }
+        if gltf_primitives.is_empty() {
+            return Ok(None);
+        }
+        let n_targets = n_targets.unwrap_or_default();
+        let mut mesh = json!({ "name": name, "primitives": gltf_primitives });
+        if n_targets > 0 && target_names.len() == n_targets {
+            mesh["extras"] = json!({ "targetNames": target_names });
+        }
+        let res = (self.meshes.len(), n_targets);
+        self.meshes.push(mesh);
+        self.mesh_cache.insert(key, res);
+        Ok(Some(res))
+    }
+
+    fn add_primitive(&mut self, mesh: &Mesh, material: Option<usize>) -> Value {
+        let vec3s = |data: &[Vec3]| data.iter().flat_map(|x| x.to_array()).collect_vec();
+        let vec4s = |data: &[Vec4]| data.iter().flat_map(|x| x.to_array()).collect_vec();
+
+        let mut attributes = Map::new();
+        let positions = mesh.positions.as_ref().unwrap();
+        attributes.insert("POSITION".to_string(), self.push_f32_accessor(&vec3s(positions), "VEC3", Some(ARRAY_BUFFER), true).into());
+        if let Some(normals) = &mesh.normals {
+            let normals = normals.iter().map(|x| x.try_normalize().unwrap_or(Vec3::Z)).collect_vec();
+            attributes.insert("NORMAL".to_string(), self.push_f32_accessor(&vec3s(&normals), "VEC3", Some(ARRAY_BUFFER), false).into());
+        }
+        if let Some(tangents) = &mesh.tangents {
+            let tangents = tangents.iter().map(|x| x.try_normalize().unwrap_or(Vec3::X).extend(1.)).collect_vec();
+            attributes.insert("TANGENT".to_string(), self.push_f32_accessor(&vec4s(&tangents), "VEC4", Some(ARRAY_BUFFER), false).into());
+        }
+        for (i, texcoords) in mesh.texcoords.iter().enumerate() {
+            let data = texcoords.iter().flat_map(|x| x.to_array()).collect_vec();
+            attributes.insert(format!("TEXCOORD_{i}"), self.push_f32_accessor(&data, "VEC2", Some(ARRAY_BUFFER), false).into());
+        }
+        if let Some(colors) = &mesh.colors {
+            attributes.insert("COLOR_0".to_string(), self.push_f32_accessor(&vec4s(colors), "VEC4", Some(ARRAY_BUFFER), false).into());
+        }
+        if let Some(joints) = &mesh.joint_indices {
+            let data = joints.iter().flat_map(|x| x.to_array().map(|x| x as u16)).collect_vec();
+            let view = self.push_buffer_view(bytemuck::cast_slice(&data), Some(ARRAY_BUFFER));
+            attributes.insert("JOINTS_0".to_string(), self.push_accessor(view, UNSIGNED_SHORT, "VEC4", joints.len(), None).into());
+        }
+        if let Some(weights) = &mesh.joint_weights {
+            attributes.insert("WEIGHTS_0".to_string(), self.push_f32_accessor(&vec4s(weights), "VEC4", Some(ARRAY_BUFFER), false).into());
+        }
+
+        let mut primitive = json!({ "attributes": attributes, "mode": 4 });
+        if let Some(indices) = &mesh.indices {
+            // The importer flips the winding order, so flip it back
+            let data = indices.chunks(3).flat_map(|x| if x.len() == 3 { vec![x[0], x[2], x[1]] } else { x.to_vec() }).collect_vec();
+            let view = self.push_buffer_view(bytemuck::cast_slice(&data), Some(ELEMENT_ARRAY_BUFFER));
+            primitive["indices"] = self.push_accessor(view, UNSIGNED_INT, "SCALAR", data.len(), None).into();
+        }
+        if let Some(material) = material {
+            primitive["material"] = material.into();
+        }
+        if !mesh.morph_targets.is_empty() {
+            // The accessors of the targets must have one delta per vertex; missing deltas are zero
+            let deltas = |data: &[Vec3]| {
+                let mut data = vec3s(data);
+                data.resize(positions.len() * 3, 0.);
+                data
+            };
+            let targets = mesh
+                .morph_targets
+                .iter()
+                .map(|target| {
+                    let mut attributes = Map::new();
+                    let position = self.push_f32_accessor(&deltas(&target.position_deltas), "VEC3", Some(ARRAY_BUFFER), true);
+                    attributes.insert("POSITION".to_string(), position.into());
+                    if let Some(normals) = target.normal_deltas.as_ref().filter(|normals| !normals.is_empty()) {
+                        attributes.insert(
+                            "NORMAL".to_string(),
+                            self.push_f32_accessor(&deltas(normals), "VEC3", Some(ARRAY_BUFFER), false).into(),
+                        );
+                    }
+                    if let Some(tangents) = target.tangent_deltas.as_ref().filter(|tangents| !tangents.is_empty()) {
+                        attributes.insert(
+                            "TANGENT".to_string(),
+                            self.push_f32_accessor(&deltas(tangents), "VEC3", Some(ARRAY_BUFFER), false).into(),
+                        );
+                    }
+                    Value::from(attributes)
+                })
+                .collect_vec();
+            primitive["targets"] = targets.into();
+        }
+        primitive
+    }
+
+    fn add_material(&mut self, url: &AssetUrl) -> Option<usize> {
+        if let Some(material) = self.material_cache.get(&url.to_string()) {
+            return *material;
+        }
+        let model_crate = self.model_crate;
+        let res = model_crate.materials.get_by_path(url.path()).map(|mat| {
+            let mut pbr = json!({ "metallicFactor": mat.metallic, "roughnessFactor": mat.roughness });
+            if let Some(factor) = mat.base_color_factor {
+                pbr["baseColorFactor"] = json!(factor.to_array());
+            }
+            if let Some(texture) = mat.base_color.as_ref().and_then(|x| self.add_texture(x)) {
+                pbr["baseColorTexture"] = json!({ "index": texture });
+            }
+            if let Some(texture) = mat.metallic_roughness.as_ref().and_then(|x| self.add_texture(x)) {
+                pbr["metallicRoughnessTexture"] = json!({ "index": texture });
+            }
+            let mut material = json!({ "pbrMetallicRoughness": pbr, "doubleSided": mat.double_sided.unwrap_or(false) });
+            if let Some(name) = &mat.name {
+                material["name"] = name.clone().into();
+            }
+            if let Some(texture) = mat.normalmap.as_ref().and_then(|x| self.add_texture(x)) {
+                material["normalTexture"] = json!({ "index": texture });
+            }
+            if let Some(factor) = mat.emissive_factor {
+                material["emissiveFactor"] = json!(factor.truncate().to_array());
+            }
+            if mat.transparent == Some(true) {
+                material["alphaMode"] = "BLEND".into();
+            } else if let Some(cutoff) = mat.alpha_cutoff {
+                material["alphaMode"] = "MASK".into();
+                material["alphaCutoff"] = cutoff.into();
+            }
+            self.materials.push(material);
+            self.materials.len() - 1
+        });
+        self.material_cache.insert(url.to_string(), res);
+        res
+    }
+
+    fn add_texture(&mut self, url: &AssetUrl) -> Option<usize> {
+        if let Some(texture) = self.texture_cache.get(&url.to_string()) {
+            return *texture;
+        }
+        let images = &self.model_crate.images;
+        let res = images.get_by_path(url.path()).map(|image| {
+            let png = (images.serialize)(image);
+            let view = self.push_buffer_view(&png, None);
+            self.images.push(json!({ "bufferView": view, "mimeType": "image/png" }));
+            self.textures.push(json!({ "source": self.images.len() - 1, "sampler": 0 }));
+            self.textures.len() - 1
+        });
+        if res.is_none() {
+            log::warn!("Image not found when exporting glTF: {url}");
+        }
+        self.texture_cache.insert(url.to_string(), res);
+        res
+    }
+
+    /// Joints that aren't exported get an empty node under their closest exported ancestor, or among `roots` when none
+    /// is, since the JOINTS_0 of the meshes index into the joints of the skin, and glTF joints must be unique
+    fn add_skin(&mut self, world: &World, skin: &ModelSkin, node_ids: &HashMap<EntityId, usize>, roots: &mut Vec<usize>) -> usize {
+        let mut joints = Vec::with_capacity(skin.joints.len());
+        let mut data = Vec::with_capacity(skin.joints.len() * 16);
+        for (i, &id) in skin.joints.iter().enumerate() {
+            let joint = match node_ids.get(&id) {
+                Some(&node) => node,
+                None => {
+                    let node = self.nodes.len();
+                    let joint_name = world.get_ref(id, name()).cloned().unwrap_or_else(|_| format!("joint_{i}"));
+                    self.nodes.push(json!({ "name": joint_name }));
+                    match exported_ancestor(world, id, node_ids) {
+                        Some(ancestor) => push_child(&mut self.nodes[ancestor], node),
+                        None => roots.push(node),
+                    }
+                    node
+                }
+            };
+            joints.push(joint);
+            data.extend(skin.inverse_bind_matrices.get(i).copied().unwrap_or(Mat4::IDENTITY).to_cols_array());
+        }
+        let inverse_bind_matrices = self.push_f32_accessor(&data, "MAT4", None, false);
+        self.skins.push(json!({ "inverseBindMatrices": inverse_bind_matrices, "joints": joints }));
+        self.skins.len() - 1
+    }
+
+    fn add_animations(&mut self, world: &World, node_ids: &HashMap<EntityId, usize>, mesh_nodes: &HashMap<EntityId, usize>) {
+        let bind_ids: HashMap<String, EntityId> =
+            query(animation_bind_id()).iter(world, None).map(|(id, bind_id)| (bind_id.clone(), id)).collect();
+        let model_crate = self.model_crate;
+        for (id, clip) in model_crate.animations.content.iter().sorted_by_key(|(id, _)| *id) {
+            let mut samplers = Vec::new();
+            let mut channels = Vec::new();
+            for track in &clip.tracks {
+                let entity = match &track.target {
+                    AnimationTarget::BinderId(bind_id) => bind_ids.get(bind_id).copied(),
+                    AnimationTarget::Entity(id) => Some(*id),
+                };
+                let (path, data, type_) = match &track.outputs {
+                    AnimationOutputs::Vec3 { component, data } if *component == translation() => {
+                        ("translation", data.iter().flat_map(|x| x.to_array()).collect_vec(), "VEC3")
+                    }
+                    AnimationOutputs::Vec3 { component, data } if *component == scale() => {
+                        ("scale", data.iter().flat_map(|x| x.to_array()).collect_vec(), "VEC3")
+                    }
+                    AnimationOutputs::Quat { component, data } if *component == rotation() => {
+                        ("rotation", data.iter().flat_map(|x| x.normalize().to_array()).collect_vec(), "VEC4")
+                    }
+                    AnimationOutputs::Weights { component, data } if *component == morph_target_weights() => {
+                        ("weights", data.iter().flatten().copied().collect_vec(), "SCALAR")
+                    }
+                    _ => {
+                        log::warn!(
+                            "Skipping animation track of {:?} when exporting glTF; it can't be represented",
+                            track.outputs.component().path()
+                        );
+                        continue;
+                    }
+                };
+                let nodes = if path == "weights" { mesh_nodes } else { node_ids };
+                let node = match entity.and_then(|id| nodes.get(&id)) {
+                    Some(node) => *node,
+                    None => continue,
+                };
+                let input = self.push_f32_accessor(&track.inputs, "SCALAR", None, true);
+                let output = self.push_f32_accessor(&data, type_, None, false);
+                samplers.push(json!({ "input": input, "output": output, "interpolation": "LINEAR" }));
+                channels.push(json!({ "sampler": samplers.len() - 1, "target": { "node": node, "path": path } }));
+            }
+            if !channels.is_empty() {
+                self.animations.push(json!({ "name": id, "samplers": samplers, "channels": channels }));
+            }
+        }
+    }
+
+    fn push_buffer_view(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        self.bin.resize(align4(self.bin.len()), 0);
+        let mut view = json!({ "buffer": 0, "byteOffset": self.bin.len(), "byteLength": data.len() });
+        if let Some(target) = target {
+            view["target"] = target.into();
+        }
+        self.bin.extend_from_slice(data);
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+    fn push_accessor(
+        &mut self,
+        view: usize,
+        component_type: u32,
+        type_: &str,
+        count: usize,
+        bounds: Option<(Vec<f32>, Vec<f32>)>,
+    ) -> usize {
+        let mut accessor = json!({ "bufferView": view, "componentType": component_type, "type": type_, "count": count });
+        if let Some((min, max)) = bounds {
+            accessor["min"] = min.into();
+            accessor["max"] = max.into();
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+    fn push_f32_accessor(&mut self, data: &[f32], type_: &str, target: Option<u32>, with_bounds: bool) -> usize {
+        let n_components = match type_ {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT4" => 16,
+            _ => unreachable!(),
+        };
+        let bounds = if with_bounds {
+            let mut min = vec![f32::MAX; n_components];
+            let mut max = vec![f32::MIN; n_components];
+            for chunk in data.chunks(n_components) {
+                for (i, &v) in chunk.iter().enumerate() {
+                    min[i] = min[i].min(v);
+                    max[i] = max[i].max(v);
+                }
+            }
+            if data.is_empty() {
+                min.fill(0.);
+                max.fill(0.);
+            }
+            Some((min, max))
+        } else {
+            None
+        };
+        let view = self.push_buffer_view(bytemuck::cast_slice(data), target);
+        self.push_accessor(view, FLOAT, type_, data.len() / n_components, bounds)
+    }
+}
+
+/// The node of the closest ancestor of `id` which is exported
+fn exported_ancestor(world: &World, id: EntityId, node_ids: &HashMap<EntityId, usize>) -> Option<usize> {
+    let mut id = world.get(id, parent()).ok()?;
+    loop {
+        if let Some(&node) = node_ids.get(&id) {
+            return Some(node);
+        }
+        id = world.get(id, parent()).ok()?;
+    }
+}
+
+fn push_child(node: &mut Value, child: usize) {
+    match node["children"].as_array_mut() {
+        Some(children) => children.push(child.into()),
+        None => node["children"] = json!([child]),
+    }
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Packs a glTF json document and its binary buffer into a .glb file
+pub fn write_glb(json: &[u8], bin: &[u8]) -> Vec<u8> {
+    let mut json = json.to_vec();
+    json.resize(align4(json.len()), b' ');
+    let mut bin = bin.to_vec();
+    bin.resize(align4(bin.len()), 0);
+
+    let length = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+    let mut glb = Vec::with_capacity(length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+    }
+    glb
+}
+
+#[test]
+fn test_export_glb() {
+    use ambient_ecs::Entity;
+    use ambient_model::Model;
+    use ambient_std::mesh::MorphTarget;
+    use glam::vec3;
+
+    use crate::dotdot_path;
+
+    ambient_ecs::init_components();
+    ambient_core::init_all_components();
+    ambient_model::init_components();
+
+    let mut model_crate = ModelCrate::new();
+    // A target without deltas still needs one (zero) delta per vertex
+    let empty_target =
+        MorphTarget { name: "empty".to_string(), position_deltas: Vec::new(), normal_deltas: Some(Vec::new()), tangent_deltas: None };
+    let mesh = model_crate.meshes.insert(
+        "0",
+        Mesh {
+            positions: Some(vec![Vec3::ZERO, Vec3::X, Vec3::Y]),
+            indices: Some(vec![0, 1, 2]),
+            morph_targets: vec![empty_target],
+            ..Default::default()
+        },
+    );
+    let mut world = World::new("model");
+    let node = Entity::new()
+        .with(name(), "triangle".to_string())
+        .with(translation(), vec3(1., 2., 3.))
+        .with(
+            pbr_renderer_primitives_from_url(),
+            vec![PbrRenderPrimitiveFromUrl { mesh: dotdot_path(mesh.path).into(), material: None, lod: 0 }],
+        )
+        .spawn(&mut world);
+    world.add_resource(children(), vec![node]);
+    model_crate.models.insert(ModelCrate::MAIN, Model(world));
+    model_crate.create_prefab_from_model();
+
+    let glb = model_crate.prefab_to_glb().unwrap();
+    assert_eq!(&glb[0..4], b"glTF");
+    assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+
+    let (document, buffers, _) = gltf::import_slice(&glb).unwrap();
+    let scene = document.default_scene().unwrap();
+    let root = scene.nodes().next().unwrap();
+    assert_eq!(root.name(), Some("root"));
+    let prefab = root.children().next().unwrap();
+    let model = prefab.children().next().unwrap();
+    let triangle = model.children().next().unwrap();
+    assert_eq!(triangle.name(), Some("triangle"));
+    assert_eq!(triangle.transform().decomposed().0, [1., 2., 3.]);
+
+    let primitive = triangle.mesh().unwrap().primitives().next().unwrap();
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    assert_eq!(reader.read_positions().unwrap().collect_vec(), vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]);
+    assert_eq!(reader.read_indices().unwrap().into_u32().collect_vec(), vec![0, 2, 1]);
+    let (positions, normals, _) = reader.read_morph_targets().next().unwrap();
+    assert_eq!(positions.unwrap().collect_vec(), vec![[0.; 3]; 3]);
+    assert!(normals.is_none());
+}
+
+#[test]
+fn test_export_skin_with_unexported_joints() {
+    use std::sync::Arc;
+
+    use ambient_ecs::Entity;
+    use ambient_model::Model;
+    use glam::{uvec4, vec3};
+
+    use crate::dotdot_path;
+
+    ambient_ecs::init_components();
+    ambient_core::init_all_components();
+    ambient_model::init_components();
+
+    let mut model_crate = ModelCrate::new();
+    let mesh = model_crate.meshes.insert(
+        "0",
+        Mesh {
+            positions: Some(vec![Vec3::ZERO, Vec3::X, Vec3::Y]),
+            indices: Some(vec![0, 1, 2]),
+            joint_indices: Some(vec![uvec4(0, 0, 0, 0), uvec4(1, 0, 0, 0), uvec4(2, 0, 0, 0)]),
+            joint_weights: Some(vec![Vec4::X; 3]),
+            ..Default::default()
+        },
+    );
+    let mut world = World::new("model");
+    let hips = Entity::new().with(name(), "hips".to_string()).spawn(&mut world);
+    // Neither joint is in the hierarchy of the model, but the spine still has an exported ancestor
+    let spine = Entity::new().with(name(), "spine".to_string()).with(parent(), hips).spawn(&mut world);
+    let orphan = Entity::new().spawn(&mut world);
+    let body = Entity::new()
+        .with(name(), "body".to_string())
+        .with(
+            pbr_renderer_primitives_from_url(),
+            vec![PbrRenderPrimitiveFromUrl { mesh: dotdot_path(mesh.path).into(), material: None, lod: 0 }],
+        )
+        .with(model_skin_ix(), 0)
+        .spawn(&mut world);
+    world.add_resource(children(), vec![hips, body]);
+    let inverse_bind_matrices = (0..3).map(|i| Mat4::from_translation(vec3(i as f32, 0., 0.))).collect_vec();
+    world.add_resource(
+        model_skins(),
+        vec![ModelSkin { inverse_bind_matrices: Arc::new(inverse_bind_matrices.clone()), joints: vec![hips, spine, orphan] }],
+    );
+    model_crate.models.insert(ModelCrate::MAIN, Model(world));
+
+    let glb = model_crate.to_glb().unwrap();
+    let (document, buffers, _) = gltf::import_slice(&glb).unwrap();
+    let skin = document.skins().next().unwrap();
+    let joints = skin.joints().collect_vec();
+    assert_eq!(joints.iter().map(|joint| joint.name()).collect_vec(), vec![Some("hips"), Some("spine"), Some("joint_2")]);
+    assert_eq!(joints.iter().map(|joint| joint.index()).unique().count(), 3);
+    assert!(joints[0].children().any(|child| child.index() == joints[1].index()));
+    let model = document.nodes().find(|node| node.name() == Some("model")).unwrap();
+    assert!(model.children().any(|child| child.index() == joints[2].index()));
+
+    let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+    let matrices = reader.read_inverse_bind_matrices().unwrap().map(|x| Mat4::from_cols_array_2d(&x)).collect_vec();
+    assert_eq!(matrices, inverse_bind_matrices);
+
+    let body = document.nodes().find(|node| node.name() == Some("body")).unwrap();
+    assert_eq!(body.skin().unwrap().index(), skin.index());
+    let primitive = body.mesh().unwrap().primitives().next().unwrap();
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let joint_indices = reader.read_joints(0).unwrap().into_u16().map(|x| x[0]).collect_vec();
+    assert_eq!(joint_indices, vec![0, 1, 2]);
+}
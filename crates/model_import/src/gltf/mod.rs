@@ -8,17 +8,27 @@ use ambient_core::{
     transform::{local_to_parent, local_to_world, rotation, scale, translation},
 };
 use ambient_ecs::{Entity, World};
-use ambient_model::{model_skin_ix, model_skins, pbr_renderer_primitives_from_url, Model, ModelSkin, PbrRenderPrimitiveFromUrl};
+use ambient_model::{
+    model_skin_ix, model_skins, morph_target_names, morph_target_weights, pbr_renderer_primitives_from_url, Model, ModelSkin,
+    PbrRenderPrimitiveFromUrl,
+};
 use ambient_renderer::materials::pbr_material::PbrMaterialDesc;
-use ambient_std::{asset_cache::AssetCache, asset_url::AbsAssetUrl, mesh::Mesh, shapes::AABB};
+use ambient_std::{
+    asset_cache::AssetCache,
+    asset_url::AbsAssetUrl,
+    mesh::{Mesh, MorphTarget},
+    shapes::AABB,
+};
 use glam::{uvec4, Mat4, Quat, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use gltf::animation::util::ReadOutputs;
 use itertools::Itertools;
 use relative_path::RelativePathBuf;
 
+pub use self::gltf_export::*;
 use self::gltf_import::GltfImport;
 use crate::{dotdot_path, model_crate::ModelCrate};
 
+mod gltf_export;
 mod gltf_import;
 
 pub async fn import_url(assets: &AssetCache, url: &AbsAssetUrl, asset_crate: &mut ModelCrate) -> anyhow::Result<RelativePathBuf> {
@@ -32,6 +42,7 @@ pub async fn import(import: &GltfImport, asset_crate: &mut ModelCrate) -> anyhow
 
     let mut meshes = import.document.meshes().map(|mesh| mesh.primitives().map(|_| RelativePathBuf::new()).collect_vec()).collect_vec();
     for (mesh_i, mesh) in import.document.meshes().enumerate() {
+        let target_names = target_names(&mesh);
         for (prim_i, primitive) in mesh.primitives().enumerate() {
             let reader = primitive.reader(|buffer| Some(&import.buffers[buffer.index()]));
 
@@ -67,6 +78,16 @@ pub async fn import(import: &GltfImport, asset_crate: &mut ModelCrate) -> anyhow
                         v.into_u32().collect::<Vec<u32>>()
                     }
                 }),
+                morph_targets: reader
+                    .read_morph_targets()
+                    .enumerate()
+                    .map(|(i, (positions, normals, tangents))| MorphTarget {
+                        name: target_names.get(i).cloned().unwrap_or_else(|| i.to_string()),
+                        position_deltas: positions.map(|v| v.map(|x| x.into()).collect()).unwrap_or_default(),
+                        normal_deltas: normals.map(|v| v.map(|x| x.into()).collect()),
+                        tangent_deltas: tangents.map(|v| v.map(|x| x.into()).collect()),
+                    })
+                    .collect(),
             };
             cpu_mesh.try_ensure_tangents();
             let path = asset_crate.meshes.insert(&format!("{}{}_{}", name_(mesh.name()), mesh.index(), primitive.index()), cpu_mesh).path;
@@ -106,6 +127,17 @@ pub async fn import(import: &GltfImport, asset_crate: &mut ModelCrate) -> anyhow
                             data: data.into_f32().map(|v| Quat::from_slice(&v)).collect(),
                         },
                     },
+                    Some(ReadOutputs::MorphTargetWeights(data)) => {
+                        let n_targets = channel.target().node().mesh().map(|mesh| n_morph_targets(&mesh)).unwrap_or_default().max(1);
+                        AnimationTrack {
+                            target,
+                            inputs,
+                            outputs: AnimationOutputs::Weights {
+                                component: morph_target_weights(),
+                                data: data.into_f32().chunks(n_targets).into_iter().map(|chunk| chunk.collect()).collect(),
+                            },
+                        }
+                    }
                     _ => unimplemented!(),
                 }
             })
@@ -237,6 +269,14 @@ pub async fn import(import: &GltfImport, asset_crate: &mut ModelCrate) -> anyhow
                 if let Some(aabb) = AABB::unions(&aabbs) {
                     ed.set(local_bounding_aabb(), aabb);
                 }
+
+                let n_targets = n_morph_targets(&mesh_);
+                if n_targets > 0 {
+                    let weights = node.weights().or_else(|| mesh_.weights()).map(|w| w.to_vec()).unwrap_or_else(|| vec![0.; n_targets]);
+                    ed.set(morph_target_weights(), weights);
+                    let names = target_names(&mesh_);
+                    ed.set(morph_target_names(), (0..n_targets).map(|i| names.get(i).cloned().unwrap_or_else(|| i.to_string())).collect());
+                }
             }
 
             if let Some(skin) = node.skin() {
@@ -277,3 +317,28 @@ pub async fn import(import: &GltfImport, asset_crate: &mut ModelCrate) -> anyhow
 
     Ok(asset_crate.models.insert(ModelCrate::MAIN, Model(world)).path)
 }
+
+/// The names of the morph targets of a mesh; glTF has no place for them, so exporters write them in the `targetNames` extra
+/// of the mesh. Targets without a name are named after their index
+fn target_names(mesh: &gltf::Mesh) -> Vec<String> {
+    let extras = match mesh.extras() {
+        Some(extras) => extras,
+        None => return Vec::new(),
+    };
+    match serde_json::from_str::<serde_json::Value>(extras.get()) {
+        Ok(extras) => match extras.get("targetNames").cloned().map(serde_json::from_value::<Vec<String>>) {
+            Some(Ok(names)) => names,
+            Some(Err(err)) => {
+                log::warn!("Invalid morph target names of mesh {}: {err}", mesh.index());
+                Vec::new()
+            }
+            None => Vec::new(),
+        },
+        Err(_) => Vec::new(),
+    }
+}
+
+/// All primitives of a mesh have the same number of morph targets
+fn n_morph_targets(mesh: &gltf::Mesh) -> usize {
+    mesh.primitives().next().map(|primitive| primitive.morph_targets().count()).unwrap_or_default()
+}
@@ -1,4 +1,4 @@
-use std::{f32::consts::PI, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, f32::consts::PI, path::PathBuf, sync::Arc};
 
 use ambient_animation::AnimationOutputs;
 use ambient_core::{bounding::local_bounding_aabb, transform::translation};
@@ -12,7 +12,7 @@ use ambient_std::{
 use anyhow::{anyhow, Context};
 use async_recursion::async_recursion;
 use futures::FutureExt;
-use glam::{Mat4, Vec3, Vec4};
+use glam::{Mat4, Vec2, Vec3, Vec4};
 use image::RgbaImage;
 use model_crate::{ModelCrate, ModelNodeRef};
 use relative_path::RelativePathBuf;
@@ -111,6 +111,7 @@ pub enum ModelImportTransform {
     ImportModelFromUrl { url: AbsAssetUrl, normalize: bool, force_assimp: bool },
     MergeMeshLods { lods: Vec<ModelImportPipeline>, lod_cutoffs: Option<Vec<f32>> },
     MergeUnityMeshLods { url: AbsAssetUrl, lod_cutoffs: Option<Vec<f32>> },
+    GenerateMeshLods { ratios: Vec<f32>, lod_cutoffs: Option<Vec<f32>> },
     SetName { name: String },
     Transform(ModelTransform),
     OverrideMaterial { filter: MaterialFilter, material: Box<PbrMaterialDesc> },
@@ -141,6 +142,9 @@ impl ModelImportTransform {
                 let source = ModelImportPipeline::model(url.clone()).produce_crate(assets).await?;
                 model_crate.merge_unity_style_mesh_lods(&source, lod_cutoffs.clone());
             }
+            ModelImportTransform::GenerateMeshLods { ratios, lod_cutoffs } => {
+                model_crate.generate_mesh_lods(ratios, lod_cutoffs.clone())?;
+            }
             ModelImportTransform::SetName { name } => {
                 model_crate.model_world_mut().add_resource(ambient_core::name(), name.clone());
             }
@@ -259,7 +263,7 @@ impl ModelTransform {
                                 AnimationOutputs::Vec3 { component, data } => {
                                     AnimationOutputs::Vec3 { component: *component, data: data.iter().map(|x| *x * *anim_scale).collect() }
                                 }
-                                AnimationOutputs::Quat { component: _, data: _ } => unreachable!(),
+                                AnimationOutputs::Quat { .. } | AnimationOutputs::Weights { .. } => unreachable!(),
                                 AnimationOutputs::Vec3Field { component, field, data } => AnimationOutputs::Vec3Field {
                                     component: *component,
                                     field: *field,
@@ -284,6 +288,57 @@ impl ModelTransform {
     }
 }
 
+fn default_ragdoll_radius() -> f32 {
+    0.25
+}
+fn default_ragdoll_density() -> f32 {
+    1.
+}
+fn default_ragdoll_swing_limit() -> Vec2 {
+    Vec2::splat(45.)
+}
+fn default_ragdoll_twist_limit() -> Vec2 {
+    Vec2::new(-30., 30.)
+}
+
+/// Generates a ragdoll from the skin of a model: one capsule body per bone, connected to the body of its parent bone by a joint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RagdollSettings {
+    /// If not empty, only the bones whose names contain one of these get a body.
+    /// Otherwise, all the bones of the skin do, except for the leaf bones, which have no length.
+    #[serde(default)]
+    pub bones: Vec<String>,
+    /// The radius of the capsules, as a fraction of the length of their bones. Defaults to 0.25.
+    #[serde(default = "default_ragdoll_radius")]
+    pub radius: f32,
+    /// The density of the bodies. Defaults to 1.
+    #[serde(default = "default_ragdoll_density")]
+    pub density: f32,
+    /// The swing limits of the joints in degrees, around the two axes perpendicular to the bone. Defaults to 45 degrees.
+    #[serde(default = "default_ragdoll_swing_limit")]
+    pub swing_limit: Vec2,
+    /// The twist limits (min, max) of the joints in degrees, around the bone. Defaults to -30 to 30 degrees.
+    #[serde(default = "default_ragdoll_twist_limit")]
+    pub twist_limit: Vec2,
+    /// Settings for individual bones, by bone name.
+    #[serde(default)]
+    pub overrides: HashMap<String, RagdollBoneOverride>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RagdollBoneOverride {
+    /// Don't create a body for this bone; its children are connected to the closest ancestor with a body instead.
+    #[serde(default)]
+    pub disabled: bool,
+    /// The radius of the capsule.
+    pub radius: Option<f32>,
+    pub density: Option<f32>,
+    /// The swing limits of the joint in degrees.
+    pub swing_limit: Option<Vec2>,
+    /// The twist limits of the joint in degrees.
+    pub twist_limit: Option<Vec2>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ElementEditor)]
 pub enum ModelTextureSize {
     /// Cap this model's textures to 128x128.
@@ -1,20 +1,31 @@
-use std::{collections::HashMap, io::Cursor, path::PathBuf, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    f32::consts::PI,
+    io::Cursor,
+    path::PathBuf,
+    sync::Arc,
+};
 
 use ambient_animation::{animation_bind_id_from_name, AnimationClip};
 use ambient_core::{
     bounding::local_bounding_aabb,
-    hierarchy::children,
+    hierarchy::{children, parent},
     name,
     transform::{local_to_parent, local_to_world, mesh_to_local, TransformSystem},
 };
 use ambient_ecs::{query, query_mut, Component, ComponentValue, Entity, EntityId, FrameEvent, System, World};
 use ambient_model::{
-    animation_bind_id, model_from_url, model_skin_ix, model_skins, pbr_renderer_primitives_from_url, Model, PbrRenderPrimitiveFromUrl,
+    animation_bind_id, model_from_url, model_skin_ix, model_skins, morph_target_bounds, morph_target_weights,
+    pbr_renderer_primitives_from_url, Model, MorphTargetBounds, PbrRenderPrimitiveFromUrl,
 };
 use ambient_physics::{
-    collider::{character_controller_height, character_controller_radius, collider, ColliderDef, ColliderFromUrls},
+    collider::{
+        capsule_collider, character_controller_height, character_controller_radius, collider, convex_collider, ColliderDef,
+        ColliderFromUrls,
+    },
     mesh::PhysxGeometryFromUrl,
     physx::PhysicsKey,
+    ragdoll::{ragdoll_bones, RagdollBone, RagdollChainNode},
 };
 use ambient_renderer::{
     double_sided,
@@ -30,14 +41,14 @@ use ambient_std::{
 };
 use anyhow::Context;
 use futures::FutureExt;
-use glam::{Mat4, Vec3};
+use glam::{vec2, Mat4, Quat, Vec3};
 use image::{ImageOutputFormat, RgbaImage};
 use itertools::Itertools;
 use ordered_float::Float;
-use physxx::{PxConvexFlag, PxConvexMeshDesc, PxDefaultMemoryOutputStream, PxMeshFlag, PxTriangleMeshDesc};
+use physxx::{PxConvexFlag, PxConvexMesh, PxConvexMeshDesc, PxDefaultMemoryOutputStream, PxMeshFlag, PxTriangleMeshDesc};
 use relative_path::RelativePathBuf;
 
-use crate::{dotdot_path, MaterialFilter, TextureResolver};
+use crate::{dotdot_path, gltf::GltfExporter, MaterialFilter, RagdollSettings, TextureResolver};
 
 #[derive(Debug, Clone)]
 pub struct AssetLoc {
@@ -188,6 +199,19 @@ impl ModelCrate {
         self.prefabs.content.get_mut(Self::MAIN).unwrap()
     }
 
+    /// Exports the main model and its animations as a binary glTF file
+    pub fn to_glb(&self) -> anyhow::Result<Vec<u8>> {
+        let mut exporter = GltfExporter::new(self);
+        let root = exporter.add_model()?;
+        Ok(exporter.to_glb(vec![root]))
+    }
+    /// Exports the main prefab as a binary glTF file, with the main model instantiated where the prefab uses it
+    pub fn prefab_to_glb(&self) -> anyhow::Result<Vec<u8>> {
+        let mut exporter = GltfExporter::new(self);
+        let roots = exporter.add_prefab()?;
+        Ok(exporter.to_glb(roots))
+    }
+
     pub async fn produce_local_model_url(&self, path: PathBuf) -> anyhow::Result<PathBuf> {
         self.write_to_fs(&path).await;
         let model_id = self.models.content.keys().next().unwrap();
@@ -242,9 +266,7 @@ impl ModelCrate {
         Ok(())
     }
     pub fn merge_mesh_lods(&mut self, cutoffs: Option<Vec<f32>>, lods: Vec<ModelNodeRef>) {
-        let default_min_screen_size = 0.04; // i.e. 4%
-        let lod_step = (1. / default_min_screen_size).powf(1. / (lods.len() - 1) as f32);
-        let cutoffs = cutoffs.unwrap_or_else(|| (0..lods.len()).map(|i| 1. / lod_step.powi(i as i32)).collect_vec());
+        let cutoffs = cutoffs.unwrap_or_else(|| default_lod_cutoffs(lods.len()));
 
         let lod_0_node = lods[0].get_node_id();
         let lod_0_world = lods[0].world();
@@ -303,6 +325,53 @@ impl ModelCrate {
         });
         self.merge_mesh_lods(cutoffs, lods.into_iter().map(|id| ModelNodeRef { model: source, root: Some(id) }).collect())
     }
+    /// Generates lower detail versions of the meshes by simplifying them down to each of `ratios` (of the original triangle
+    /// count), and adds them as extra LODs to the nodes using them. Nodes which already have LODs are left as they are.
+    /// `cutoffs` has one screen size per LOD, starting with the original mesh.
+    pub fn generate_mesh_lods(&mut self, ratios: &[f32], cutoffs: Option<Vec<f32>>) -> anyhow::Result<()> {
+        if let Some(cutoffs) = &cutoffs {
+            if cutoffs.len() != ratios.len() + 1 {
+                anyhow::bail!(
+                    "Expected {} LOD cutoffs (one for the original mesh and one per ratio), got {}",
+                    ratios.len() + 1,
+                    cutoffs.len()
+                );
+            }
+        }
+        let cutoffs = cutoffs.unwrap_or_else(|| default_lod_cutoffs(ratios.len() + 1));
+        let mut simplified = HashMap::new();
+        for (node, primitives) in query(pbr_renderer_primitives_from_url()).collect_cloned(self.model_world(), None) {
+            if primitives.iter().any(|p| p.lod != 0) {
+                continue;
+            }
+            let mut lods = primitives.clone();
+            for (i, &ratio) in ratios.iter().enumerate() {
+                let lod = i + 1;
+                for primitive in &primitives {
+                    let mesh_id = match self.meshes.loc.id_from_path(primitive.mesh.path()) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    let mesh_path = match simplified.entry((mesh_id.clone(), lod)) {
+                        Entry::Occupied(entry) => entry.get().clone(),
+                        Entry::Vacant(entry) => {
+                            let mesh = match self.meshes.content.get(&mesh_id) {
+                                Some(mesh) => mesh.simplify(ratio),
+                                None => continue,
+                            };
+                            entry.insert(self.meshes.insert(format!("{mesh_id}_lod{lod}"), mesh).path).clone()
+                        }
+                    };
+                    lods.push(PbrRenderPrimitiveFromUrl { mesh: dotdot_path(mesh_path).into(), material: primitive.material.clone(), lod });
+                }
+            }
+            let world = self.model_world_mut();
+            world.set(node, pbr_renderer_primitives_from_url(), lods).unwrap();
+            world.add_component(node, lod_cutoffs(), LodCutoffs::new(&cutoffs)).unwrap();
+            world.add_component(node, gpu_lod(), ()).unwrap();
+        }
+        Ok(())
+    }
     pub fn set_all_material(&mut self, material: PbrMaterialDesc) {
         self.materials.content.clear();
         let mat_path = dotdot_path(self.materials.insert("main".to_string(), material).path);
@@ -355,6 +424,11 @@ impl ModelCrate {
                 .collect_vec();
             if let Some(aabb) = AABB::unions(&aabbs) {
                 world.add_component(node, local_bounding_aabb(), aabb).unwrap();
+                let meshes =
+                    primitives.iter().filter_map(|p| self.meshes.get_by_path(&RelativePathBuf::from("materials").join(p.mesh.path())));
+                if let Some(bounds) = MorphTargetBounds::from_meshes(aabb, meshes) {
+                    world.add_component(node, morph_target_bounds(), bounds).unwrap();
+                }
             }
         }
     }
@@ -461,14 +535,98 @@ impl ModelCrate {
         world.add_component(object, character_controller_radius(), radius.unwrap_or(0.5)).unwrap();
         world.add_component(object, character_controller_height(), height.unwrap_or(2.0)).unwrap();
     }
+    pub fn create_capsule_collider(&mut self, radius: Option<f32>, height: Option<f32>) {
+        self.add_component_to_prefab(capsule_collider(), vec2(radius.unwrap_or(0.5), height.unwrap_or(1.0)));
+    }
+    /// Creates a `convex_collider` from the convex hull of all the meshes of the model
+    pub fn create_convex_collider(&mut self, assets: &AssetCache) -> anyhow::Result<()> {
+        self.update_transforms();
+        let world_transform = self.model().get_transform().unwrap_or_default();
+        let entities = {
+            let world = self.model_world();
+            query(pbr_renderer_primitives_from_url()).collect_cloned(world, None)
+        };
+        let mut points = Vec::new();
+        for (id, prims) in entities {
+            let ltw = self.model_world().get(id, local_to_world()).unwrap_or_default();
+            let mtl = self.model_world().get(id, mesh_to_local()).unwrap_or_default();
+            if let Some(max_lod) = prims.iter().map(|x| x.lod).max() {
+                for primitive in prims.into_iter().filter(|x| x.lod == max_lod) {
+                    let transform = world_transform * ltw * mtl;
+                    let mesh_id = self.meshes.loc.id_from_path(primitive.mesh.path()).unwrap();
+                    let mesh = self.meshes.content.get(&mesh_id).unwrap();
+                    points.extend(mesh.positions.iter().flatten().map(|&p| transform.transform_point3(p)));
+                }
+            }
+        }
+        if points.is_empty() {
+            anyhow::bail!("Model has no vertices to create a convex collider from");
+        }
+        let physics = PhysicsKey.get(assets);
+        let desc = PxConvexMeshDesc { points, indices: None, vertex_limit: Some(64), flags: Some(PxConvexFlag::COMPUTE_CONVEX) };
+        let hull = PxConvexMesh::from_desc(physics.physics, physics.cooking, desc)
+            .map_err(|err| anyhow::anyhow!("Failed to compute the convex hull: {err:?}"))?;
+        self.add_component_to_prefab(convex_collider(), hull.get_vertices());
+        Ok(())
+    }
+    /// Creates the `ragdoll_bones` of the model from the joints of its skins; the bodies are spawned from them on the server
+    pub fn create_ragdoll(&mut self, settings: &RagdollSettings) -> anyhow::Result<()> {
+        self.update_transforms();
+        let world_transform = self.model().get_transform().unwrap_or_default();
+        let joints: HashSet<EntityId> = self.model().skins().into_iter().flatten().flat_map(|skin| skin.joints.iter().copied()).collect();
+        if joints.is_empty() {
+            anyhow::bail!("Model has no skin to create a ragdoll from");
+        }
+        let world = self.model_world();
+
+        // Depth first, so that the parent of a node always comes before it
+        let mut ids = Vec::new();
+        let mut stack = self.model().roots().into_iter().rev().collect_vec();
+        while let Some(id) = stack.pop() {
+            ids.push(id);
+            stack.extend(world.get_ref(id, children()).cloned().unwrap_or_default().into_iter().rev());
+        }
+        let indices: HashMap<EntityId, usize> = ids.iter().enumerate().map(|(index, &id)| (id, index)).collect();
+        let nodes = ids
+            .iter()
+            .map(|&id| {
+                let ltw = world.get(id, local_to_world()).unwrap_or_default();
+                let parent_ltw = world
+                    .get(id, parent())
+                    .ok()
+                    .filter(|parent| indices.contains_key(parent))
+                    .map(|parent| world.get(parent, local_to_world()).unwrap_or_default());
+                // The bones are built in model space, without scale
+                let (_, rotation, position) = (world_transform * ltw).to_scale_rotation_translation();
+                RagdollNode {
+                    name: world.get_ref(id, name()).cloned().unwrap_or_default(),
+                    rotation,
+                    position,
+                    local: parent_ltw.map(|parent_ltw| parent_ltw.inverse() * ltw).unwrap_or(ltw),
+                    joint: joints.contains(&id),
+                    children: world
+                        .get_ref(id, children())
+                        .map(|children| children.iter().filter_map(|child| indices.get(child).copied()).collect())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect_vec();
+
+        let bones = ragdoll_bones_from_nodes(&nodes, world_transform, settings);
+        if bones.is_empty() {
+            anyhow::bail!("None of the bones of the model can be used for a ragdoll");
+        }
+        self.add_component_to_prefab(ragdoll_bones(), bones);
+        Ok(())
+    }
     pub fn create_collider_from_model(&mut self, assets: &AssetCache, flip_normals: bool, reverse_indices: bool) -> anyhow::Result<()> {
         self.update_transforms();
         let physics = PhysicsKey.get(assets);
-        let create_triangle_mesh = |asset_crate: &mut ModelCrate, id: &str| -> bool {
+        let create_triangle_mesh = |asset_crate: &mut ModelCrate, id: &str, mesh: Option<&Mesh>| -> bool {
             if asset_crate.px_triangle_meshes.content.contains_key(id) {
                 return true;
             }
-            let mesh = asset_crate.meshes.content.get(id).unwrap();
+            let mesh = mesh.unwrap_or_else(|| asset_crate.meshes.content.get(id).unwrap());
             if let Some(desc) = physx_triangle_mesh_desc_from_mesh(mesh, flip_normals, reverse_indices) {
                 let stream = PxDefaultMemoryOutputStream::new();
                 let mut res = physxx::PxTriangleMeshCookingResult::Success;
@@ -482,31 +640,32 @@ impl ModelCrate {
                 false
             }
         };
-        let create_convex_mesh = |asset_crate: &mut ModelCrate, id: &str, scale_signum: Vec3| -> Option<RelativePathBuf> {
-            // Physx doesn't support negative scaling on Convex meshes, so we need to generate a mesh with the right
-            // scale signum first, and then scale that with the absolute scale
-            let to_sign = |v| if v >= 0. { "p" } else { "n" }.to_string();
-            let full_id = format!("{id}_{}{}{}", to_sign(scale_signum.x), to_sign(scale_signum.y), to_sign(scale_signum.z));
-            if asset_crate.px_convex_meshes.content.contains_key(&full_id) {
-                return Some(asset_crate.px_convex_meshes.loc.path(&full_id));
-            }
-            let mesh = asset_crate.meshes.content.get(id).unwrap();
+        let create_convex_mesh =
+            |asset_crate: &mut ModelCrate, id: &str, mesh: Option<&Mesh>, scale_signum: Vec3| -> Option<RelativePathBuf> {
+                // Physx doesn't support negative scaling on Convex meshes, so we need to generate a mesh with the right
+                // scale signum first, and then scale that with the absolute scale
+                let to_sign = |v| if v >= 0. { "p" } else { "n" }.to_string();
+                let full_id = format!("{id}_{}{}{}", to_sign(scale_signum.x), to_sign(scale_signum.y), to_sign(scale_signum.z));
+                if asset_crate.px_convex_meshes.content.contains_key(&full_id) {
+                    return Some(asset_crate.px_convex_meshes.loc.path(&full_id));
+                }
+                let mesh = mesh.unwrap_or_else(|| asset_crate.meshes.content.get(id).unwrap());
 
-            let desc = PxConvexMeshDesc {
-                // Apply the correct mirroring according to the base scale
-                points: mesh.positions.as_ref().unwrap().iter().map(|&p| p * scale_signum).collect_vec(),
-                indices: mesh.indices.clone(),
-                vertex_limit: None,
-                flags: Some(PxConvexFlag::COMPUTE_CONVEX),
+                let desc = PxConvexMeshDesc {
+                    // Apply the correct mirroring according to the base scale
+                    points: mesh.positions.as_ref().unwrap().iter().map(|&p| p * scale_signum).collect_vec(),
+                    indices: mesh.indices.clone(),
+                    vertex_limit: None,
+                    flags: Some(PxConvexFlag::COMPUTE_CONVEX),
+                };
+                let stream = PxDefaultMemoryOutputStream::new();
+                let mut res = physxx::PxConvexMeshCookingResult::Success;
+                if !physics.cooking.cook_convex_mesh(&desc, &stream, &mut res) {
+                    log::error!("Failed to cook convex mesh: {:?}", res);
+                    return None;
+                }
+                Some(asset_crate.px_convex_meshes.insert(full_id, stream.get_data()).path)
             };
-            let stream = PxDefaultMemoryOutputStream::new();
-            let mut res = physxx::PxConvexMeshCookingResult::Success;
-            if !physics.cooking.cook_convex_mesh(&desc, &stream, &mut res) {
-                log::error!("Failed to cook convex mesh: {:?}", res);
-                return None;
-            }
-            Some(asset_crate.px_convex_meshes.insert(full_id, stream.get_data()).path)
-        };
         let mut convex = Vec::new();
         let mut triangle = Vec::new();
         let world_transform = self.model().get_transform().unwrap_or_default();
@@ -516,15 +675,25 @@ impl ModelCrate {
         };
         for (id, prims) in entities {
             let ltw = self.model_world().get(id, local_to_world()).unwrap_or_default();
+            // Colliders are cooked with the default weights of the morph targets applied
+            let morph_weights = self.model_world().get_ref(id, morph_target_weights()).ok().filter(|w| w.iter().any(|&w| w != 0.)).cloned();
             if let Some(max_lod) = prims.iter().map(|x| x.lod).max() {
                 let mtl = self.model_world().get(id, mesh_to_local()).unwrap_or_default();
                 // Only use the "max" lod for colliders
                 for primitive in prims.into_iter().filter(|x| x.lod == max_lod) {
                     let transform = world_transform * ltw * mtl;
                     let (scale, rot, pos) = transform.to_scale_rotation_translation();
-                    let mesh_id = self.meshes.loc.id_from_path(primitive.mesh.path()).unwrap();
-                    if create_triangle_mesh(self, &mesh_id) {
-                        if let Some(convex_path) = create_convex_mesh(self, &mesh_id, scale.signum()) {
+                    let mut mesh_id = self.meshes.loc.id_from_path(primitive.mesh.path()).unwrap();
+                    let morphed = morph_weights.as_ref().map(|weights| {
+                        let mut mesh = self.meshes.content.get(&mesh_id).unwrap().clone();
+                        mesh.apply_morph_targets(weights);
+                        mesh
+                    });
+                    if morphed.is_some() {
+                        mesh_id = format!("{mesh_id}_morphed_{id}");
+                    }
+                    if create_triangle_mesh(self, &mesh_id, morphed.as_ref()) {
+                        if let Some(convex_path) = create_convex_mesh(self, &mesh_id, morphed.as_ref(), scale.signum()) {
                             let convex_path = dotdot_path(convex_path);
                             let triangle_path = dotdot_path(self.px_triangle_meshes.loc.path(mesh_id));
                             convex.push((
@@ -554,6 +723,13 @@ pub struct AssetItem {
     pub data: Arc<Vec<u8>>,
 }
 
+/// Screen sizes at which to switch between `n_lods` LODs, spread out evenly (on a log scale) from the full screen down to 4% of it
+fn default_lod_cutoffs(n_lods: usize) -> Vec<f32> {
+    let min_screen_size = 0.04;
+    let lod_step = (1. / min_screen_size).powf(1. / (n_lods - 1) as f32);
+    (0..n_lods).map(|i| 1. / lod_step.powi(i as i32)).collect_vec()
+}
+
 pub fn cap_texture_size(image: &mut RgbaImage, max_size: u32) {
     if image.width() > max_size || image.height() > max_size {
         let (width, height) = if image.width() >= image.height() {
@@ -595,3 +771,126 @@ pub fn physx_triangle_mesh_desc_from_mesh(mesh: &Mesh, flip_normals: bool, rever
     }
     Some(desc)
 }
+
+/// A node of a model that a ragdoll is built from, in model space without scale
+struct RagdollNode {
+    name: String,
+    rotation: Quat,
+    position: Vec3,
+    /// The transform of the node relative to its parent node, or to the model for the roots, with scale
+    local: Mat4,
+    /// Whether the node is a joint of one of the skins of the model
+    joint: bool,
+    children: Vec<usize>,
+}
+
+/// Creates one bone per joint that has child joints, along the line to the average position of its child joints. `nodes` are
+/// sorted depth first, with each node before its children; the parent of a bone is its closest ancestor that has a bone.
+/// `model_transform` is the transform of the model that the roots are relative to.
+fn ragdoll_bones_from_nodes(nodes: &[RagdollNode], model_transform: Mat4, settings: &RagdollSettings) -> Vec<RagdollBone> {
+    let included = |name: &str| settings.bones.is_empty() || settings.bones.iter().any(|bone| name.contains(bone.as_str()));
+
+    let mut bones = Vec::new();
+    // The bone of the closest ancestor of each node that has one
+    let mut parent_bones: Vec<Option<usize>> = vec![None; nodes.len()];
+    // The nodes below the bone of the closest ancestor of each node, or below the model for the nodes without one
+    let mut parent_chains: Vec<Option<Vec<RagdollChainNode>>> = vec![None; nodes.len()];
+    for (index, node) in nodes.iter().enumerate() {
+        let parent = parent_bones[index];
+        let mut chain =
+            parent_chains[index].take().unwrap_or_else(|| vec![RagdollChainNode { bind_id: None, local_pose: model_transform }]);
+        chain.push(RagdollChainNode { bind_id: Some(animation_bind_id_from_name(&node.name)), local_pose: node.local });
+        let overrides = settings.overrides.get(&node.name).cloned().unwrap_or_default();
+        let mut children_parent = parent;
+        let child_joints =
+            node.children.iter().map(|&child| &nodes[child]).filter(|child| child.joint).map(|child| child.position).collect_vec();
+        if node.joint && !overrides.disabled && included(&node.name) && !child_joints.is_empty() {
+            let (rot, pos) = (node.rotation, node.position);
+            let end = child_joints.iter().sum::<Vec3>() / child_joints.len() as f32;
+            let length = pos.distance(end);
+            if length > f32::EPSILON {
+                let direction = rot.inverse() * (end - pos) / length;
+                let radius = overrides.radius.unwrap_or(length * settings.radius);
+                bones.push(RagdollBone {
+                    bind_id: animation_bind_id_from_name(&node.name),
+                    name: node.name.clone(),
+                    parent,
+                    bind_pose: Mat4::from_rotation_translation(rot, pos),
+                    body_offset: Mat4::from_rotation_translation(Quat::from_rotation_arc(Vec3::Z, direction), direction * length / 2.),
+                    radius,
+                    length,
+                    density: overrides.density.unwrap_or(settings.density),
+                    swing_limit: overrides.swing_limit.unwrap_or(settings.swing_limit) * PI / 180.,
+                    twist_limit: overrides.twist_limit.unwrap_or(settings.twist_limit) * PI / 180.,
+                    chain: std::mem::take(&mut chain),
+                });
+                children_parent = Some(bones.len() - 1);
+            }
+        }
+        for &child in &node.children {
+            parent_bones[child] = children_parent;
+            parent_chains[child] = Some(chain.clone());
+        }
+    }
+    bones
+}
+
+#[test]
+fn test_ragdoll_bones_from_nodes() {
+    use glam::vec3;
+
+    let node = |name: &str, position: Vec3, joint: bool, children: Vec<usize>| RagdollNode {
+        name: name.to_string(),
+        rotation: Quat::IDENTITY,
+        position,
+        local: Mat4::from_translation(position),
+        joint,
+        children,
+    };
+    // root (not a joint) -> hips -> spine -> (head, left arm -> left hand), and hips -> leg -> foot
+    let mut nodes = vec![
+        node("root", Vec3::ZERO, false, vec![1]),
+        node("hips", vec3(0., 0., 1.), true, vec![2, 6]),
+        node("spine", vec3(0., 0., 2.), true, vec![3, 4]),
+        node("head", vec3(0., 0., 3.), true, vec![]),
+        node("left_arm", vec3(1., 0., 2.), true, vec![5]),
+        node("left_hand", vec3(2., 0., 2.), true, vec![]),
+        node("leg", vec3(1., 0., 0.), true, vec![7]),
+        node("foot", vec3(1., 0., -1.), true, vec![]),
+    ];
+    // Make the transforms of the nodes relative to their parents
+    let positions = nodes.iter().map(|node| node.position).collect_vec();
+    for index in 0..nodes.len() {
+        for child in nodes[index].children.clone() {
+            nodes[child].local = Mat4::from_translation(positions[child] - positions[index]);
+        }
+    }
+    let settings: RagdollSettings = serde_json::from_str("{}").unwrap();
+    let bones = ragdoll_bones_from_nodes(&nodes, Mat4::IDENTITY, &settings);
+    let summary = bones.iter().map(|bone| (bone.name.as_str(), bone.parent.map(|parent| bones[parent].name.as_str()))).collect_vec();
+    // The leaves have no length, and don't get a bone
+    assert_eq!(summary, vec![("hips", None), ("spine", Some("hips")), ("left_arm", Some("spine")), ("leg", Some("hips"))]);
+    // The spine points at the average of its child joints
+    let spine = &bones[1];
+    assert!((spine.length - vec3(0.5, 0., 0.5).length()).abs() < 0.0001);
+    assert!((spine.bind_pose.transform_point3(spine.body_offset.transform_point3(Vec3::ZERO)) - vec3(0.25, 0., 2.25)).length() < 0.0001);
+    assert!((bones[3].radius - 0.25).abs() < 0.0001);
+    // The chain of a bone runs from below its parent bone, or from the model, down to its node
+    let chain = |bone: &RagdollBone| bone.chain.iter().map(|node| node.bind_id.as_deref()).collect_vec();
+    assert_eq!(chain(&bones[0]), vec![None, Some("Root"), Some("Hips")]);
+    assert_eq!(chain(&bones[1]), vec![Some("Spine")]);
+    let hips_pose = bones[0].chain.iter().fold(Mat4::IDENTITY, |pose, node| pose * node.local_pose);
+    assert!(hips_pose.abs_diff_eq(bones[0].bind_pose, 0.0001));
+
+    // Disabled bones are skipped, and their children attach to the closest ancestor with a bone
+    let settings: RagdollSettings = serde_json::from_str(r#"{ "overrides": { "spine": { "disabled": true } } }"#).unwrap();
+    let bones = ragdoll_bones_from_nodes(&nodes, Mat4::IDENTITY, &settings);
+    let summary = bones.iter().map(|bone| (bone.name.as_str(), bone.parent.map(|parent| bones[parent].name.as_str()))).collect_vec();
+    assert_eq!(summary, vec![("hips", None), ("left_arm", Some("hips")), ("leg", Some("hips"))]);
+
+    // Only the listed bones
+    let settings: RagdollSettings = serde_json::from_str(r#"{ "bones": ["arm", "leg"] }"#).unwrap();
+    let bones = ragdoll_bones_from_nodes(&nodes, Mat4::IDENTITY, &settings);
+    let summary = bones.iter().map(|bone| (bone.name.as_str(), bone.parent)).collect_vec();
+    assert_eq!(summary, vec![("left_arm", None), ("leg", None)]);
+}
@@ -0,0 +1,426 @@
+//! Administration of a running server.
+//!
+//! An [`AdminConsole`] runs [`AdminCommand`]s against the state of a [`GameServer`](crate::server::GameServer): listing,
+//! kicking and banning players, broadcasting messages, and listing and forking instances. The commands are lines of text,
+//! read from the standard input or a Unix socket ([`AdminConsole::serve_stdin`], [`AdminConsole::serve_unix_socket`]), or
+//! handed to [`AdminConsole::execute`] by another interface, like an HTTP endpoint.
+//!
+//! Modules add their own commands by spawning an entity with the `admin_command` component in the main instance; running
+//! one of them sends the `AdminCommand` message, with the arguments of the command, to the server modules.
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use ambient_core::player::get_by_user_id;
+use ambient_ecs::{
+    generated::{components::core::network::admin_command, messages},
+    query, world_events, Entity, WorldEventsExt,
+};
+use ambient_sys::time::SystemTime;
+use anyhow::Context;
+use async_trait::async_trait;
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::{
+    auth::{Authenticator, ConnectionRejection, Credentials},
+    rpc::fork_instance,
+    server::{player_connection, ServerState, SharedServerState, MAIN_INSTANCE_ID},
+};
+
+/// A command of the admin console, parsed from a line like `kick alice spamming`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    Help,
+    Players,
+    Kick {
+        user_id: String,
+        reason: Option<String>,
+    },
+    Ban {
+        user_id: String,
+        reason: Option<String>,
+    },
+    Unban {
+        user_id: String,
+    },
+    Bans,
+    Say {
+        message: String,
+    },
+    Instances,
+    Fork {
+        instance_id: String,
+        new_id: Option<String>,
+    },
+    /// A command registered by a module with the `admin_command` component
+    Custom {
+        command: String,
+        args: Vec<String>,
+    },
+}
+
+const HELP: &str = "\
+players                       List the players
+kick <user id> [reason]       Disconnect a player
+ban <user id> [reason]        Disconnect a player, and keep them from joining again
+unban <user id>               Let a banned player join again
+bans                          List the banned players
+say <message>                 Send a message to the server modules of every instance
+instances                     List the instances
+fork <instance id> [new id]   Create a copy of an instance, without its players
+help                          Show this help";
+
+impl FromStr for AdminCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let command = words.next().context("Empty command")?;
+        let args = words.collect_vec();
+        let arg =
+            |index: usize, what: &str| args.get(index).map(|arg| arg.to_string()).with_context(|| format!("Missing {what}, see `help`"));
+        let rest = |from: usize| (args.len() > from).then(|| args[from..].join(" "));
+
+        Ok(match command {
+            "help" => AdminCommand::Help,
+            "players" => AdminCommand::Players,
+            "kick" => AdminCommand::Kick { user_id: arg(0, "user id")?, reason: rest(1) },
+            "ban" => AdminCommand::Ban { user_id: arg(0, "user id")?, reason: rest(1) },
+            "unban" => AdminCommand::Unban { user_id: arg(0, "user id")? },
+            "bans" => AdminCommand::Bans,
+            "say" => AdminCommand::Say { message: rest(0).context("Missing message, see `help`")? },
+            "instances" => AdminCommand::Instances,
+            "fork" => AdminCommand::Fork { instance_id: arg(0, "instance id")?, new_id: args.get(1).map(|id| id.to_string()) },
+            _ => AdminCommand::Custom { command: command.to_string(), args: args.iter().map(|arg| arg.to_string()).collect() },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the player was banned, in seconds since the Unix epoch
+    pub since: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BanFile {
+    #[serde(default)]
+    bans: BTreeMap<String, Ban>,
+}
+
+/// The banned players, by user id. If the list has a path, it's saved there as TOML whenever it changes:
+///
+/// ```toml
+/// [bans.alice]
+/// reason = "griefing"
+/// since = 1681000000
+/// ```
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    bans: BTreeMap<String, Ban>,
+}
+impl BanList {
+    /// Loads the list from `path`, which doesn't have to exist yet
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let bans = match std::fs::read_to_string(&path) {
+            Ok(file) => toml::from_str::<BanFile>(&file).with_context(|| format!("Failed to parse the ban list {path:?}"))?.bans,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read the ban list {path:?}")),
+        };
+        Ok(Self { path: Some(path), bans })
+    }
+    pub fn get(&self, user_id: &str) -> Option<&Ban> {
+        self.bans.get(user_id)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Ban)> {
+        self.bans.iter()
+    }
+    pub fn ban(&mut self, user_id: &str, reason: Option<String>) -> anyhow::Result<()> {
+        let since = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        self.bans.insert(user_id.to_string(), Ban { reason, since });
+        self.save()
+    }
+    /// Returns false if the player wasn't banned
+    pub fn unban(&mut self, user_id: &str) -> anyhow::Result<bool> {
+        let banned = self.bans.remove(user_id).is_some();
+        self.save()?;
+        Ok(banned)
+    }
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(path) = &self.path {
+            let file = toml::to_string(&BanFile { bans: self.bans.clone() })?;
+            std::fs::write(path, file).with_context(|| format!("Failed to save the ban list {path:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Rejects the banned players, and authenticates the others with another authenticator.
+pub struct BanCheck {
+    inner: Arc<dyn Authenticator>,
+    bans: Arc<Mutex<BanList>>,
+}
+impl BanCheck {
+    pub fn new(inner: Arc<dyn Authenticator>, bans: Arc<Mutex<BanList>>) -> Self {
+        Self { inner, bans }
+    }
+}
+
+#[async_trait]
+impl Authenticator for BanCheck {
+    async fn authenticate(&self, credentials: &Credentials) -> Result<String, ConnectionRejection> {
+        let user_id = self.inner.authenticate(credentials).await?;
+        match self.bans.lock().get(&user_id) {
+            Some(ban) => Err(ConnectionRejection::Banned(ban.reason.clone())),
+            None => Ok(user_id),
+        }
+    }
+}
+
+/// Whether `given` is the admin token `expected`, compared in constant time
+pub fn verify_token(expected: &str, given: &str) -> bool {
+    ring::constant_time::verify_slices_are_equal(expected.as_bytes(), given.as_bytes()).is_ok()
+}
+
+/// Runs admin commands against a server; see [`crate::admin`].
+///
+/// The console is given to the server with [`GameServer::admin`](crate::server::GameServer::admin), and can be cloned to
+/// serve several interfaces.
+#[derive(Clone, Default)]
+pub struct AdminConsole {
+    state: Arc<OnceCell<SharedServerState>>,
+    bans: Arc<Mutex<BanList>>,
+}
+impl AdminConsole {
+    pub fn new(bans: BanList) -> Self {
+        Self { state: Default::default(), bans: Arc::new(Mutex::new(bans)) }
+    }
+    pub fn bans(&self) -> &Arc<Mutex<BanList>> {
+        &self.bans
+    }
+    pub(crate) fn attach(&self, state: SharedServerState) {
+        if self.state.set(state).is_err() {
+            log::warn!("The admin console is already attached to a server");
+        }
+    }
+
+    /// Parses and runs a command, returning its output
+    pub fn execute(&self, line: &str) -> anyhow::Result<String> {
+        self.run(line.parse()?)
+    }
+
+    pub fn run(&self, command: AdminCommand) -> anyhow::Result<String> {
+        let state = self.state.get().context("The server is not running yet")?;
+        let mut state = state.lock();
+        match command {
+            AdminCommand::Help => {
+                let main = &state.instances[MAIN_INSTANCE_ID].world;
+                let custom = query(admin_command()).iter(main, None).map(|(_, command)| command.clone()).sorted().dedup().collect_vec();
+                if custom.is_empty() {
+                    Ok(HELP.to_string())
+                } else {
+                    Ok(format!("{HELP}\n\nCommands of the modules: {}", custom.join(", ")))
+                }
+            }
+            AdminCommand::Players => {
+                if state.players.is_empty() {
+                    return Ok("No players".to_string());
+                }
+                Ok(state
+                    .players
+                    .iter()
+                    .sorted_by_key(|(user_id, _)| *user_id)
+                    .map(|(user_id, player)| {
+                        let status = if player.session.detached.is_some() { "connection lost" } else { "connected" };
+                        format!("{user_id}  instance: {}  {status}", player.instance)
+                    })
+                    .join("\n"))
+            }
+            AdminCommand::Kick { user_id, reason } => {
+                kick(&mut state, &user_id, reason.as_deref().unwrap_or("Kicked by an admin"))?;
+                Ok(format!("Kicked {user_id}"))
+            }
+            AdminCommand::Ban { user_id, reason } => {
+                self.bans.lock().ban(&user_id, reason.clone())?;
+                if state.players.contains_key(&user_id) {
+                    kick(&mut state, &user_id, reason.as_deref().unwrap_or("Banned by an admin"))?;
+                }
+                Ok(format!("Banned {user_id}"))
+            }
+            AdminCommand::Unban { user_id } => {
+                anyhow::ensure!(self.bans.lock().unban(&user_id)?, "{user_id} is not banned");
+                Ok(format!("Unbanned {user_id}"))
+            }
+            AdminCommand::Bans => {
+                let bans = self.bans.lock();
+                if bans.iter().next().is_none() {
+                    return Ok("No banned players".to_string());
+                }
+                Ok(bans
+                    .iter()
+                    .map(|(user_id, ban)| match &ban.reason {
+                        Some(reason) => format!("{user_id}: {reason}"),
+                        None => user_id.clone(),
+                    })
+                    .join("\n"))
+            }
+            AdminCommand::Say { message } => {
+                for instance in state.instances.values_mut() {
+                    instance.world.resource_mut(world_events()).add_message(messages::AdminBroadcast::new(message.clone()));
+                }
+                Ok(format!("Sent to {} instances", state.instances.len()))
+            }
+            AdminCommand::Instances => Ok(state
+                .instances
+                .iter()
+                .sorted_by_key(|(id, _)| *id)
+                .map(|(id, instance)| format!("{id}  players: {}", instance.player_count()))
+                .join("\n")),
+            AdminCommand::Fork { instance_id, new_id } => {
+                anyhow::ensure!(state.instances.contains_key(&instance_id), "No instance {instance_id:?}");
+                if let Some(new_id) = &new_id {
+                    anyhow::ensure!(!state.instances.contains_key(new_id), "There already is an instance {new_id:?}");
+                }
+                let new_id = fork_instance(&mut state, &instance_id, Entity::new(), Entity::new(), new_id);
+                Ok(format!("Forked {instance_id} into {new_id}"))
+            }
+            AdminCommand::Custom { command, args } => {
+                let main = state.instances.get_mut(MAIN_INSTANCE_ID).unwrap();
+                let registered = query(admin_command()).iter(&main.world, None).any(|(_, registered)| registered == &command);
+                anyhow::ensure!(registered, "Unknown command {command:?}, see `help`");
+                main.world.resource_mut(world_events()).add_message(messages::AdminCommand::new(args, command.clone()));
+                Ok(format!("Sent {command} to the server modules"))
+            }
+        }
+    }
+
+    /// Runs the commands read line by line from `reader`, and writes their output to `writer`
+    pub async fn serve(&self, reader: impl AsyncRead + Unpin, mut writer: impl AsyncWrite + Unpin) -> io::Result<()> {
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let output = match tokio::task::block_in_place(|| self.execute(&line)) {
+                Ok(output) => output,
+                Err(err) => format!("Error: {err:#}"),
+            };
+            writer.write_all(output.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Runs the commands typed in the terminal
+    pub async fn serve_stdin(&self) -> io::Result<()> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Runs the commands sent to a Unix socket at `path`, for instance with `socat - UNIX-CONNECT:<path>`
+    #[cfg(unix)]
+    pub async fn serve_unix_socket(&self, path: &Path) -> io::Result<()> {
+        use std::os::unix::fs::FileTypeExt;
+
+        // Binding fails if a previous server left its socket behind
+        if std::fs::metadata(path).map(|metadata| metadata.file_type().is_socket()).unwrap_or(false) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(path)?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let console = self.clone();
+            tokio::spawn(async move {
+                let (reader, writer) = stream.into_split();
+                if let Err(err) = console.serve(reader, writer).await {
+                    log::warn!("Admin console connection failed: {err}");
+                }
+            });
+        }
+    }
+}
+
+/// Disconnects the player. The player is removed even if the session could otherwise be resumed.
+fn kick(state: &mut ServerState, user_id: &str, reason: &str) -> anyhow::Result<()> {
+    let player = state.players.get(user_id).with_context(|| format!("No player {user_id:?}"))?;
+    if player.session.detached.is_some() {
+        state.remove_player(user_id);
+        return Ok(());
+    }
+    if let Some(world) = state.get_player_world(user_id) {
+        if let Some(conn) = get_by_user_id(world, user_id).and_then(|id| world.get_ref(id, player_connection()).ok()) {
+            conn.close(reason);
+        }
+    }
+    // The player is removed when its connection task ends
+    if let Some(handle) = player.abort_handle.get() {
+        handle.abort();
+    }
+    log::info!("[{}] Kicked: {}", user_id, reason);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        let parse = |line: &str| line.parse::<AdminCommand>().unwrap();
+        assert_eq!(parse("players"), AdminCommand::Players);
+        assert_eq!(parse("  kick   alice  "), AdminCommand::Kick { user_id: "alice".to_string(), reason: None });
+        assert_eq!(
+            parse("ban alice griefing the  spawn"),
+            AdminCommand::Ban { user_id: "alice".to_string(), reason: Some("griefing the spawn".to_string()) }
+        );
+        assert_eq!(parse("say hello everyone"), AdminCommand::Say { message: "hello everyone".to_string() });
+        assert_eq!(parse("fork main"), AdminCommand::Fork { instance_id: "main".to_string(), new_id: None });
+        assert_eq!(parse("fork main copy"), AdminCommand::Fork { instance_id: "main".to_string(), new_id: Some("copy".to_string()) });
+        assert_eq!(
+            parse("teleport alice 0 0"),
+            AdminCommand::Custom { command: "teleport".to_string(), args: vec!["alice".to_string(), "0".to_string(), "0".to_string()] }
+        );
+
+        assert!("".parse::<AdminCommand>().is_err());
+        assert!("kick".parse::<AdminCommand>().is_err());
+        assert!("say".parse::<AdminCommand>().is_err());
+        assert!("fork".parse::<AdminCommand>().is_err());
+    }
+
+    #[test]
+    fn ban_list_roundtrip() {
+        let path = std::env::temp_dir().join(format!("ambient_bans_{}.toml", std::process::id()));
+        std::fs::remove_file(&path).ok();
+
+        let mut bans = BanList::load(&path).unwrap();
+        assert!(bans.iter().next().is_none());
+        bans.ban("alice", Some("griefing".to_string())).unwrap();
+        bans.ban("bob", None).unwrap();
+        assert!(!bans.unban("carol").unwrap());
+
+        let loaded = BanList::load(&path).unwrap();
+        assert_eq!(loaded.iter().map(|(user_id, _)| user_id.as_str()).collect_vec(), vec!["alice", "bob"]);
+        assert_eq!(loaded.get("alice"), bans.get("alice"));
+        assert_eq!(loaded.get("alice").unwrap().reason.as_deref(), Some("griefing"));
+        assert_eq!(loaded.get("bob").unwrap().reason, None);
+
+        bans.unban("alice").unwrap();
+        assert!(BanList::load(&path).unwrap().get("alice").is_none());
+
+        std::fs::write(&path, "not toml [").unwrap();
+        assert!(BanList::load(&path).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+}
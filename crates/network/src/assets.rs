@@ -0,0 +1,258 @@
+//! Serves the files of the project over the game connection, so that a server only needs its QUIC port.
+//!
+//! The server advertises [`ambient_std::asset_url::AbsAssetUrl::asset_server_root`] as its content base url. For every file it needs, the client opens a
+//! bi-directional stream with [`ASSETS_BISTREAM_ID`] and sends an [`AssetRequest`]; the server answers with an [`AssetResponse`], followed
+//! by the bytes of the file in frames of at most [`CHUNK_SIZE`] bytes. The server sends the streams of the files of higher priority first.
+//! If a transfer is interrupted, the client requests the rest of the file from where it stopped, and starts over if the file changed
+//! in the meantime.
+//!
+//! Only the files of a local project are served; the requested paths must be plain relative paths inside its `build` directory.
+
+use std::{
+    io::SeekFrom,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use ambient_std::{
+    asset_cache::{AssetCache, SyncAssetKeyExt},
+    download_asset::{AssetServerClient, AssetServerClientKey, DownloadPriority},
+};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use quinn::{Connection, RecvStream, SendStream};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::{
+    log_network_result, open_bincode_bi_stream_with_id, server::BiStreamHandlers, IncomingStream, NetworkError, OutgoingStream,
+    ASSETS_BISTREAM_ID,
+};
+
+/// The largest frame of file bytes the server sends
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// How many times the client tries to download a file before giving up
+const MAX_ATTEMPTS: usize = 5;
+
+/// Sent by the client to download a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetRequest {
+    /// The path of the file, relative to the content root of the server
+    pub path: String,
+    /// Where to start sending the file from, to resume an interrupted transfer
+    pub offset: u64,
+    pub priority: DownloadPriority,
+}
+
+/// What the server answers to an [`AssetRequest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AssetResponse {
+    /// The file is `size` bytes long; the bytes after the offset of the request follow
+    Found {
+        size: u64,
+        /// When the file was last modified, in milliseconds since the Unix epoch, if the platform knows
+        modified: Option<u64>,
+    },
+    NotFound,
+    Failed(String),
+}
+
+/// The priority of the QUIC stream of a file; streams of higher priority are sent first
+fn stream_priority(priority: DownloadPriority) -> i32 {
+    match priority {
+        DownloadPriority::Low => -1,
+        DownloadPriority::Normal => 0,
+        DownloadPriority::High => 1,
+    }
+}
+
+/// Serves the files in the directory `content_root` to the clients that request them
+pub fn register_assets_bi_stream_handler(handlers: &mut BiStreamHandlers, content_root: PathBuf) {
+    handlers.insert(
+        ASSETS_BISTREAM_ID,
+        Arc::new(move |_state, _assets, _user_id, send, recv| {
+            let content_root = content_root.clone();
+            tokio::spawn(async move {
+                log_network_result!(serve_file(&content_root, send, recv).await);
+            });
+        }),
+    );
+}
+
+/// The file at the requested `path` in `content_root`, or `None` if `path` is not a plain relative path to a file in it.
+///
+/// The path comes from the client, so anything that could be interpreted as a url, an escape, or a path outside of the content root is
+/// refused
+fn resolve_request_path(content_root: &Path, path: &str) -> Option<PathBuf> {
+    if path.is_empty() || path.starts_with('/') || path.contains([':', '%', '\\', '?', '#']) {
+        return None;
+    }
+    if !Path::new(path).components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    let resolved = content_root.join(path);
+    resolved.starts_with(content_root).then_some(resolved)
+}
+
+async fn serve_file(content_root: &Path, send: SendStream, recv: RecvStream) -> anyhow::Result<()> {
+    let mut rx = IncomingStream::new(recv);
+    let mut tx = OutgoingStream::new(send);
+    let request: AssetRequest = rx.next().await?;
+    tx.stream.get_ref().set_priority(stream_priority(request.priority)).ok();
+
+    // Symlinks are followed, so check where the file really is too
+    let path = match resolve_request_path(content_root, &request.path) {
+        Some(path) => match (tokio::fs::canonicalize(&path).await, tokio::fs::canonicalize(content_root).await) {
+            (Ok(path), Ok(root)) if path.starts_with(&root) => Some(path),
+            _ => None,
+        },
+        None => None,
+    };
+    let file = match path {
+        Some(path) => tokio::fs::File::open(&path).await,
+        None => Err(std::io::ErrorKind::NotFound.into()),
+    };
+    let mut file = match file {
+        Ok(file) if file.metadata().await.map(|metadata| metadata.is_file()).unwrap_or(false) => file,
+        Ok(_) => {
+            tx.send(&AssetResponse::NotFound).await?;
+            return Ok(tx.stream.close().await?);
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tx.send(&AssetResponse::NotFound).await?;
+            return Ok(tx.stream.close().await?);
+        }
+        Err(err) => {
+            tx.send(&AssetResponse::Failed(err.to_string())).await?;
+            return Ok(tx.stream.close().await?);
+        }
+    };
+
+    let metadata = file.metadata().await?;
+    let size = metadata.len();
+    let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|time| time.as_millis() as u64);
+    tx.send(&AssetResponse::Found { size, modified }).await?;
+    let mut offset = request.offset.min(size);
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    // Don't send more than announced if the file grows in the meantime
+    while offset < size {
+        let read = file.read(&mut buf[..CHUNK_SIZE.min((size - offset) as usize)]).await?;
+        if read == 0 {
+            break;
+        }
+        offset += read as u64;
+        tx.send_bytes(buf[..read].to_vec()).await?;
+    }
+    Ok(tx.stream.close().await?)
+}
+
+/// Downloads the files of the server the client is connected to over the game connection
+#[derive(Debug, Clone)]
+pub struct AssetServerConnection {
+    conn: Connection,
+}
+
+impl AssetServerConnection {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Downloads the files of the server over `conn` from now on; called again when the connection changes
+    pub fn install(assets: &AssetCache, conn: Connection) {
+        AssetServerClientKey.insert(assets, Some(Arc::new(Self::new(conn))));
+    }
+
+    /// Requests the rest of the file at `path`, and appends the bytes received to `data`. `version` is the size and modification
+    /// time of the file that `data` was received from; if the file changed since, `data` is cleared and `Ok(false)` is returned,
+    /// so that it is downloaded again from the start.
+    async fn request(
+        &self,
+        path: &str,
+        priority: DownloadPriority,
+        data: &mut Vec<u8>,
+        version: &mut Option<(u64, Option<u64>)>,
+    ) -> anyhow::Result<bool> {
+        let (mut tx, mut rx) = open_bincode_bi_stream_with_id(&self.conn, ASSETS_BISTREAM_ID).await?;
+        tx.send(&AssetRequest { path: path.to_string(), offset: data.len() as u64, priority }).await?;
+
+        let (size, modified) = match rx.next().await? {
+            AssetResponse::Found { size, modified } => (size, modified),
+            AssetResponse::NotFound => anyhow::bail!("The server has no file at {path}"),
+            AssetResponse::Failed(err) => anyhow::bail!("The server failed to read {path}: {err}"),
+        };
+        if version.map_or(false, |version| version != (size, modified)) {
+            data.clear();
+            *version = None;
+            return Ok(false);
+        }
+        *version = Some((size, modified));
+        while (data.len() as u64) < size {
+            let chunk = rx.stream.next().await.ok_or(NetworkError::EndOfStream)?.map_err(|_| NetworkError::ConnectionClosed)?;
+            data.extend_from_slice(&chunk);
+        }
+        if data.len() as u64 > size {
+            anyhow::bail!("The server sent {} bytes of {path}, which is only {size} bytes long", data.len());
+        }
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl AssetServerClient for AssetServerConnection {
+    async fn download(&self, path: &str, priority: DownloadPriority) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut version = None;
+        let mut attempt = 1;
+        loop {
+            match self.request(path, priority, &mut data, &mut version).await {
+                Ok(true) => return Ok(data),
+                Ok(false) if attempt < MAX_ATTEMPTS => {
+                    log::warn!("{path} changed on the server during its download, downloading it again");
+                    attempt += 1;
+                }
+                Ok(false) => anyhow::bail!("{path} kept changing on the server during its download"),
+                // Resume interrupted transfers, unless the connection is gone
+                Err(err) if err.is::<NetworkError>() && attempt < MAX_ATTEMPTS && self.conn.close_reason().is_none() => {
+                    log::warn!("Download of {path} interrupted after {} bytes, resuming: {err:#}", data.len());
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_plain_paths() {
+        let root = Path::new("/project/build");
+        assert_eq!(resolve_request_path(root, "assets/a b.png"), Some(PathBuf::from("/project/build/assets/a b.png")));
+        assert_eq!(resolve_request_path(root, "ambient.toml"), Some(PathBuf::from("/project/build/ambient.toml")));
+    }
+
+    #[test]
+    fn refuse_paths_outside_of_the_root() {
+        let root = Path::new("/project/build");
+        for path in [
+            "",
+            "../ambient.toml",
+            "assets/../../ambient.toml",
+            "./assets/a.png",
+            "/etc/passwd",
+            "%2e%2e/%2e%2e/%2e%2e/etc/passwd",
+            "file:///etc/passwd",
+            "http://169.254.169.254/latest/meta-data",
+            "C:/Windows/win.ini",
+            "assets\\..\\..\\secret",
+            "assets/a.png?x=1",
+            "assets/a.png#x",
+        ] {
+            assert_eq!(resolve_request_path(root, path), None, "{path}");
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! Authentication of the players joining a server.
+//!
+//! During the handshake, the client presents [`Credentials`], and the server turns them into the user id of the player with
+//! its [`Authenticator`], or rejects the connection with a [`ConnectionRejection`], which is sent back to the client.
+//!
+//! Three authenticators are built in:
+//! - [`InsecureAuthenticator`] trusts the user id the client asks for; this is the default, for local development.
+//! - [`JwtAuthenticator`] verifies JSON Web Tokens signed with HMAC-SHA256 by a service that shares a secret with the server.
+//! - [`FileAuthenticator`] looks the token up in a local file; a stand-in for an authentication service during development.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use ambient_sys::time::SystemTime;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use ring::{constant_time, hmac};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::protocol::ResumeRequest;
+
+/// What the client presents to the server to join.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    /// The user id the client asks for. Only [`InsecureAuthenticator`] uses it; the others take the user id from the token.
+    pub user_id: String,
+    pub token: Option<String>,
+    /// Set when the client lost its connection and tries to resume its session
+    pub resume: Option<ResumeRequest>,
+}
+
+/// Why the server refused a connection. Sent to the client before the connection is closed.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum ConnectionRejection {
+    #[error("The server requires an authentication token")]
+    MissingToken,
+    #[error("The authentication token is invalid: {0}")]
+    InvalidToken(String),
+    #[error("The authentication token has expired")]
+    TokenExpired,
+    #[error("The server could not authenticate the player: {0}")]
+    AuthenticationUnavailable(String),
+    #[error("You are banned from the server{}", .0.as_ref().map(|reason| format!(": {reason}")).unwrap_or_default())]
+    Banned(Option<String>),
+    #[error("The server is full")]
+    ServerFull,
+}
+
+/// Validates the credentials of the players joining a server; see [`crate::server::GameServer::authenticator`].
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    /// Returns the user id of the player presenting `credentials`.
+    async fn authenticate(&self, credentials: &Credentials) -> Result<String, ConnectionRejection>;
+}
+
+/// Trusts the user id the client asks for. Anyone can join as anyone, so this should only be used for local development.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InsecureAuthenticator;
+
+#[async_trait]
+impl Authenticator for InsecureAuthenticator {
+    async fn authenticate(&self, credentials: &Credentials) -> Result<String, ConnectionRejection> {
+        Ok(credentials.user_id.clone())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum JwtAudience {
+    One(String),
+    Many(Vec<String>),
+}
+impl JwtAudience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            JwtAudience::One(aud) => aud == audience,
+            JwtAudience::Many(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+/// The registered claims of a token that the server checks; the user id is the subject (`sub`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JwtClaims {
+    sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbf: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aud: Option<JwtAudience>,
+}
+
+/// Verifies JSON Web Tokens signed with HMAC-SHA256 (`HS256`) with a secret shared with the service that issues them.
+///
+/// The user id of the player is the subject (`sub`) of the token. Tokens must have an expiry time (`exp`); the not-before
+/// time (`nbf`) is checked if present, and the issuer (`iss`) and audience (`aud`) if the authenticator expects them.
+#[derive(Debug, Clone)]
+pub struct JwtAuthenticator {
+    key: hmac::Key,
+    issuer: Option<String>,
+    audience: Option<String>,
+    /// Allowance for the clocks of the server and of the issuer not being in sync
+    leeway: Duration,
+}
+impl JwtAuthenticator {
+    pub fn new(secret: &[u8]) -> Self {
+        Self { key: hmac::Key::new(hmac::HMAC_SHA256, secret), issuer: None, audience: None, leeway: Duration::from_secs(30) }
+    }
+    /// Only accept tokens from this issuer
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+    /// Only accept tokens issued for this audience
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Issues a token for `user_id` that is valid for `valid_for`; for tools and tests that stand in for the issuing service.
+    pub fn issue(&self, user_id: &str, valid_for: Duration) -> String {
+        let claims = JwtClaims {
+            sub: user_id.to_string(),
+            exp: Some((now() + valid_for).as_secs()),
+            nbf: None,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone().map(JwtAudience::One),
+        };
+        let header = JwtHeader { alg: "HS256".to_string(), typ: Some("JWT".to_string()) };
+        let signed = format!("{}.{}", encode_part(&header), encode_part(&claims));
+        let signature = hmac::sign(&self.key, signed.as_bytes());
+        format!("{signed}.{}", base64::encode_config(signature.as_ref(), base64::URL_SAFE_NO_PAD))
+    }
+
+    fn verify(&self, token: &str) -> Result<JwtClaims, ConnectionRejection> {
+        let invalid = |reason: &str| ConnectionRejection::InvalidToken(reason.to_string());
+        let (signed, signature) = token.rsplit_once('.').ok_or_else(|| invalid("not a JSON Web Token"))?;
+        let (header, claims) = signed.split_once('.').ok_or_else(|| invalid("not a JSON Web Token"))?;
+
+        let header: JwtHeader = decode_part(header).ok_or_else(|| invalid("malformed header"))?;
+        if header.alg != "HS256" {
+            return Err(invalid(&format!("unsupported algorithm {}", header.alg)));
+        }
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).map_err(|_| invalid("malformed signature"))?;
+        hmac::verify(&self.key, signed.as_bytes(), &signature).map_err(|_| invalid("bad signature"))?;
+
+        let claims: JwtClaims = decode_part(claims).ok_or_else(|| invalid("malformed claims"))?;
+        let now = now();
+        // Tokens without an expiry would be valid forever if they leaked
+        let exp = claims.exp.ok_or_else(|| invalid("no expiry time"))?;
+        if Duration::from_secs(exp).saturating_add(self.leeway) < now {
+            return Err(ConnectionRejection::TokenExpired);
+        }
+        if claims.nbf.map(|nbf| Duration::from_secs(nbf) > now.saturating_add(self.leeway)).unwrap_or(false) {
+            return Err(invalid("not valid yet"));
+        }
+        if self.issuer.is_some() && claims.iss != self.issuer {
+            return Err(invalid("wrong issuer"));
+        }
+        if let Some(audience) = &self.audience {
+            if !claims.aud.as_ref().map(|aud| aud.contains(audience)).unwrap_or(false) {
+                return Err(invalid("wrong audience"));
+            }
+        }
+        if claims.sub.is_empty() {
+            return Err(invalid("no subject"));
+        }
+        Ok(claims)
+    }
+}
+
+#[async_trait]
+impl Authenticator for JwtAuthenticator {
+    async fn authenticate(&self, credentials: &Credentials) -> Result<String, ConnectionRejection> {
+        let token = credentials.token.as_deref().ok_or(ConnectionRejection::MissingToken)?;
+        Ok(self.verify(token)?.sub)
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap()
+}
+
+fn encode_part<T: Serialize>(value: &T) -> String {
+    base64::encode_config(serde_json::to_vec(value).unwrap(), base64::URL_SAFE_NO_PAD)
+}
+
+fn decode_part<T: serde::de::DeserializeOwned>(part: &str) -> Option<T> {
+    let json = base64::decode_config(part, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct UsersFile {
+    /// The token of each user, by user id
+    #[serde(default)]
+    users: HashMap<String, String>,
+}
+impl UsersFile {
+    /// Returns the user whose token is `token`. Tokens are compared in constant time, so that the time taken doesn't tell
+    /// how much of a token was guessed right
+    fn find_user(&self, token: &str) -> Option<&str> {
+        self.users
+            .iter()
+            .find(|(_, user_token)| constant_time::verify_slices_are_equal(user_token.as_bytes(), token.as_bytes()).is_ok())
+            .map(|(user_id, _)| user_id.as_str())
+    }
+}
+
+/// Looks the token up in a TOML file that lists the token of each user:
+///
+/// ```toml
+/// [users]
+/// alice = "a token only alice knows"
+/// bob = "another token"
+/// ```
+///
+/// The file is read again when it is modified, so users can be added while the server runs. This is a stand-in for an
+/// authentication service during development.
+#[derive(Debug, Clone)]
+pub struct FileAuthenticator {
+    path: PathBuf,
+    /// The users of the file, and when the file was modified when they were read
+    loaded: Arc<Mutex<Option<(std::time::SystemTime, Arc<UsersFile>)>>>,
+}
+impl FileAuthenticator {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf(), loaded: Default::default() }
+    }
+
+    /// The users of the file, which is only read if it changed since it was last read
+    async fn users(&self) -> anyhow::Result<Arc<UsersFile>> {
+        let modified = tokio::fs::metadata(&self.path).await?.modified()?;
+        if let Some((loaded_modified, users)) = &*self.loaded.lock() {
+            if *loaded_modified == modified {
+                return Ok(users.clone());
+            }
+        }
+        let file = tokio::fs::read_to_string(&self.path).await?;
+        let users = Arc::new(toml::from_str::<UsersFile>(&file)?);
+        *self.loaded.lock() = Some((modified, users.clone()));
+        Ok(users)
+    }
+}
+
+#[async_trait]
+impl Authenticator for FileAuthenticator {
+    async fn authenticate(&self, credentials: &Credentials) -> Result<String, ConnectionRejection> {
+        let token = credentials.token.as_deref().ok_or(ConnectionRejection::MissingToken)?;
+        let users = self.users().await.map_err(|err| {
+            log::error!("Failed to read the users file {:?}: {err:#}", self.path);
+            ConnectionRejection::AuthenticationUnavailable("the users file could not be read".to_string())
+        })?;
+        users
+            .find_user(token)
+            .map(|user_id| user_id.to_string())
+            .ok_or_else(|| ConnectionRejection::InvalidToken("unknown token".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(authenticator: &JwtAuthenticator, header: &JwtHeader, claims: &JwtClaims) -> String {
+        let signed = format!("{}.{}", encode_part(header), encode_part(claims));
+        let signature = hmac::sign(&authenticator.key, signed.as_bytes());
+        format!("{signed}.{}", base64::encode_config(signature.as_ref(), base64::URL_SAFE_NO_PAD))
+    }
+    fn hs256() -> JwtHeader {
+        JwtHeader { alg: "HS256".to_string(), typ: None }
+    }
+    fn claims(exp: Option<u64>) -> JwtClaims {
+        JwtClaims { sub: "alice".to_string(), exp, nbf: None, iss: None, aud: None }
+    }
+    fn in_an_hour() -> Option<u64> {
+        Some((now() + Duration::from_secs(3600)).as_secs())
+    }
+    fn invalid(reason: &str) -> Result<JwtClaims, ConnectionRejection> {
+        Err(ConnectionRejection::InvalidToken(reason.to_string()))
+    }
+
+    #[test]
+    fn verify_signature() {
+        let authenticator = JwtAuthenticator::new(b"secret");
+        let token = authenticator.issue("alice", Duration::from_secs(60));
+        assert_eq!(authenticator.verify(&token).unwrap().sub, "alice");
+
+        assert_eq!(JwtAuthenticator::new(b"other secret").verify(&token), invalid("bad signature"));
+        // Claims can't be changed without the secret
+        let (header, rest) = token.split_once('.').unwrap();
+        let (_, signature) = rest.split_once('.').unwrap();
+        let forged = format!("{header}.{}.{signature}", encode_part(&JwtClaims { sub: "bob".to_string(), ..claims(in_an_hour()) }));
+        assert_eq!(authenticator.verify(&forged), invalid("bad signature"));
+        assert_eq!(authenticator.verify("not a token"), invalid("not a JSON Web Token"));
+    }
+
+    #[test]
+    fn require_hs256() {
+        let authenticator = JwtAuthenticator::new(b"secret");
+        for alg in ["none", "HS512", "RS256"] {
+            let header = JwtHeader { alg: alg.to_string(), typ: None };
+            let token = sign(&authenticator, &header, &claims(in_an_hour()));
+            assert_eq!(authenticator.verify(&token), invalid(&format!("unsupported algorithm {alg}")));
+        }
+        // An unsigned token is refused whatever its claims
+        let unsigned =
+            format!("{}.{}.", encode_part(&JwtHeader { alg: "none".to_string(), typ: None }), encode_part(&claims(in_an_hour())));
+        assert!(authenticator.verify(&unsigned).is_err());
+    }
+
+    #[test]
+    fn require_unexpired_tokens() {
+        let authenticator = JwtAuthenticator::new(b"secret");
+        let expired = sign(&authenticator, &hs256(), &claims(Some((now() - Duration::from_secs(3600)).as_secs())));
+        assert_eq!(authenticator.verify(&expired), Err(ConnectionRejection::TokenExpired));
+        // Within the leeway for clock differences
+        let just_expired = sign(&authenticator, &hs256(), &claims(Some((now() - Duration::from_secs(5)).as_secs())));
+        assert!(authenticator.verify(&just_expired).is_ok());
+        let forever = sign(&authenticator, &hs256(), &claims(None));
+        assert_eq!(authenticator.verify(&forever), invalid("no expiry time"));
+        let far_future = sign(&authenticator, &hs256(), &claims(Some(u64::MAX)));
+        assert!(authenticator.verify(&far_future).is_ok());
+    }
+
+    #[test]
+    fn check_issuer_and_audience() {
+        let authenticator = JwtAuthenticator::new(b"secret").with_issuer("auth").with_audience("game");
+        assert!(authenticator.verify(&authenticator.issue("alice", Duration::from_secs(60))).is_ok());
+
+        let token = |iss: Option<&str>, aud: Option<JwtAudience>| {
+            sign(&authenticator, &hs256(), &JwtClaims { iss: iss.map(|x| x.to_string()), aud, ..claims(in_an_hour()) })
+        };
+        let one = |aud: &str| Some(JwtAudience::One(aud.to_string()));
+        assert_eq!(authenticator.verify(&token(Some("auth"), one("other game"))), invalid("wrong audience"));
+        assert_eq!(authenticator.verify(&token(Some("auth"), None)), invalid("wrong audience"));
+        assert_eq!(authenticator.verify(&token(Some("someone"), one("game"))), invalid("wrong issuer"));
+        assert_eq!(authenticator.verify(&token(None, one("game"))), invalid("wrong issuer"));
+        let many = JwtAudience::Many(vec!["other game".to_string(), "game".to_string()]);
+        assert!(authenticator.verify(&token(Some("auth"), Some(many))).is_ok());
+    }
+
+    #[test]
+    fn find_users_by_token() {
+        let users: UsersFile = toml::from_str("[users]\nalice = \"a token\"\nbob = \"another token\"").unwrap();
+        assert_eq!(users.find_user("a token"), Some("alice"));
+        assert_eq!(users.find_user("another token"), Some("bob"));
+        assert_eq!(users.find_user("a toke"), None);
+        assert_eq!(users.find_user(""), None);
+    }
+}
@@ -22,6 +22,7 @@ use ambient_std::{
     fps_counter::FpsSample,
     friendly_id, to_byte_unit, CallbackFn, Cb,
 };
+use ambient_sys::task::RuntimeHandle;
 use ambient_ui_native::{
     Button, Centered, FlowColumn, FlowRow, Image, MeasureSize, Text, Throbber,
 };
@@ -31,14 +32,18 @@ use glam::{uvec2, UVec2};
 use parking_lot::Mutex;
 use quinn::{Connection, RecvStream, SendStream};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{debug_span, Instrument};
 
 use crate::{
+    assets::AssetServerConnection,
+    auth::Credentials,
     client_game_state::ClientGameState,
     create_client_endpoint_random_port, is_remote_entity, log_network_result,
-    protocol::{ClientInfo, ClientProtocol, ServerInfo},
-    rpc_request, server, NetworkError,
+    protocol::{ClientInfo, ClientProtocol, ResumeRequest, ServerInfo},
+    rpc_request, server,
+    tls::ServerVerification,
+    NetworkError, LEAVE_UNISTREAM_ID,
 };
 
 components!("network::client", {
@@ -159,7 +164,13 @@ pub type InitCallback = Box<dyn FnOnce(&mut World, Arc<RenderTarget>) + Send + S
 #[derive(Debug)]
 pub struct GameClientView {
     pub server_addr: SocketAddr,
+    /// The host name of the server, which its certificate is verified against
+    pub server_name: String,
+    /// How the certificate of the server is verified; see [`crate::tls`]
+    pub server_verification: ServerVerification,
     pub user_id: String,
+    /// Presented to the server along with the user id; see [`crate::auth`]
+    pub auth_token: Option<String>,
     pub systems_and_resources: Cb<dyn Fn() -> (SystemGroup, Entity) + Sync + Send>,
     pub init_world: Cb<UseOnce<InitCallback>>,
     pub error_view: Cb<dyn Fn(String) -> Element + Sync + Send>,
@@ -183,7 +194,10 @@ impl Clone for GameClientView {
     fn clone(&self) -> Self {
         Self {
             server_addr: self.server_addr,
+            server_name: self.server_name.clone(),
+            server_verification: self.server_verification.clone(),
             user_id: self.user_id.clone(),
+            auth_token: self.auth_token.clone(),
             systems_and_resources: self.systems_and_resources.clone(),
             init_world: self.init_world.clone(),
             error_view: self.error_view.clone(),
@@ -202,7 +216,10 @@ impl ElementComponent for GameClientView {
     fn render(self: Box<Self>, hooks: &mut Hooks) -> Element {
         let Self {
             server_addr,
+            server_name,
+            server_verification,
             user_id,
+            auth_token,
             init_world,
             error_view,
             systems_and_resources,
@@ -250,11 +267,12 @@ impl ElementComponent for GameClientView {
         // Subscribe to window close events
         hooks.use_runtime_message::<messages::WindowClose>({
             let game_client = game_client.clone();
-            move |_, _| {
+            move |world, _| {
                 if let Some(game_client) = game_client.as_ref() {
-                    game_client
-                        .connection
-                        .close(0u32.into(), b"User window was closed");
+                    let connection = game_client.connection.clone();
+                    world
+                        .resource(runtime())
+                        .spawn(async move { leave(&connection, b"User window was closed").await });
                 }
             }
         });
@@ -299,9 +317,34 @@ impl ElementComponent for GameClientView {
 
             hooks.use_memo_with((), move |_, ()| {
                 let task = runtime.spawn(async move {
+                    let mut on_resume = {
+                        let game_state = game_state.clone();
+                        let create_rpc_registry = create_rpc_registry.clone();
+                        let set_game_client = set_game_client.clone();
+                        move |conn: Connection, client_info: ClientInfo| {
+                            // The world is kept, only the connection changes
+                            let assets = game_state.lock().world.resource(asset_cache()).clone();
+                            AssetServerConnection::install(&assets, conn.clone());
+                            let game_client = GameClient::new(
+                                conn,
+                                Arc::new(create_rpc_registry()),
+                                game_state.clone(),
+                                client_info.user_id,
+                            );
+                            game_state
+                                .lock()
+                                .world
+                                .add_resource(self::game_client(), Some(game_client.clone()));
+                            set_game_client(Some(game_client));
+                        }
+                    };
+
                     let mut on_init = {
                         let game_state = game_state.clone();
                         move |conn, client_info: ClientInfo, server_info: ServerInfo| {
+                            // The server may know us by another id than the one we asked for
+                            game_state.lock().set_user_id(client_info.user_id.clone());
+
                             let game_client = GameClient::new(
                                 conn,
                                 Arc::new(create_rpc_registry()),
@@ -314,6 +357,7 @@ impl ElementComponent for GameClientView {
 
                             let assets = world.resource(asset_cache());
                             ContentBaseUrlKey.insert(assets, server_info.content_base_url);
+                            AssetServerConnection::install(assets, game_client.connection.clone());
 
                             // Update parent client
                             set_game_client(Some(game_client.clone()));
@@ -337,50 +381,12 @@ impl ElementComponent for GameClientView {
                     };
 
                     let on_bi_stream = |handler_id, tx, rx| {
-                        let _span = debug_span!("on_bi_stream").entered();
-                        let mut gs = game_state.lock();
-                        let handler = gs
-                            .world
-                            .resource(bi_stream_handlers())
-                            .get(&handler_id)
-                            .cloned();
-                        if let Some(handler) = handler {
-                            handler(&mut gs.world, assets.clone(), tx, rx);
-                        } else {
-                            log::error!("Unrecognized stream handler id: {}", handler_id);
-                        }
+                        handle_bi_stream(&game_state, &assets, handler_id, tx, rx)
                     };
-
-                    let on_uni_stream = |handler_id, rx| {
-                        let _span = debug_span!("on_uni_stream").entered();
-                        let mut gs = game_state.lock();
-                        let handler = gs
-                            .world
-                            .resource(uni_stream_handlers())
-                            .get(&handler_id)
-                            .cloned();
-                        if let Some(handler) = handler {
-                            handler(&mut gs.world, assets.clone(), rx);
-                        } else {
-                            log::error!("Unrecognized stream handler id: {}", handler_id);
-                        }
-                    };
-
-                    let on_datagram = |handler_id: u32, bytes: Bytes| {
-                        let mut gs = game_state.lock();
-                        let handler = gs
-                            .world
-                            .resource(datagram_handlers())
-                            .get(&handler_id)
-                            .cloned();
-                        match handler {
-                            Some(handler) => {
-                                handler(&mut gs.world, assets.clone(), bytes);
-                            }
-                            None => {
-                                log::error!("No such datagram handler: {:?}", handler_id);
-                            }
-                        }
+                    let on_uni_stream =
+                        |handler_id, rx| handle_uni_stream(&game_state, &assets, handler_id, rx);
+                    let on_datagram = |handler_id, bytes| {
+                        handle_datagram(&game_state, &assets, handler_id, bytes)
                     };
 
                     let mut on_server_stats = |stats| {
@@ -394,8 +400,12 @@ impl ElementComponent for GameClientView {
                     let client_loop = ClientInstance {
                         set_connection_status,
                         server_addr,
+                        server_name,
+                        server_verification,
                         user_id,
+                        auth_token,
                         on_init: &mut on_init,
+                        on_resume: &mut on_resume,
                         on_diff: &mut on_diff,
                         on_bi_stream: &on_bi_stream,
                         on_uni_stream: &on_uni_stream,
@@ -411,6 +421,8 @@ impl ElementComponent for GameClientView {
                             if let Some(err) = err.downcast_ref::<NetworkError>() {
                                 if let NetworkError::ConnectionClosed = err {
                                     log::info!("Connection closed by peer");
+                                } else if let NetworkError::ConnectionRejected(rejection) = err {
+                                    log::error!("The server refused the connection: {rejection}");
                                 } else {
                                     log::error!("Network error: {:?}", err);
                                 }
@@ -455,6 +467,73 @@ impl ElementComponent for GameClientView {
         }
     }
 }
+
+/// Runs the handler of a bi-directional stream the server opened
+fn handle_bi_stream(
+    game_state: &Mutex<ClientGameState>,
+    assets: &AssetCache,
+    handler_id: u32,
+    tx: SendStream,
+    rx: RecvStream,
+) {
+    let _span = debug_span!("on_bi_stream").entered();
+    let mut gs = game_state.lock();
+    let handler = gs
+        .world
+        .resource(bi_stream_handlers())
+        .get(&handler_id)
+        .cloned();
+    if let Some(handler) = handler {
+        handler(&mut gs.world, assets.clone(), tx, rx);
+    } else {
+        log::error!("Unrecognized stream handler id: {}", handler_id);
+    }
+}
+
+/// Runs the handler of a uni-directional stream the server opened
+fn handle_uni_stream(
+    game_state: &Mutex<ClientGameState>,
+    assets: &AssetCache,
+    handler_id: u32,
+    rx: RecvStream,
+) {
+    let _span = debug_span!("on_uni_stream").entered();
+    let mut gs = game_state.lock();
+    let handler = gs
+        .world
+        .resource(uni_stream_handlers())
+        .get(&handler_id)
+        .cloned();
+    if let Some(handler) = handler {
+        handler(&mut gs.world, assets.clone(), rx);
+    } else {
+        log::error!("Unrecognized stream handler id: {}", handler_id);
+    }
+}
+
+/// Runs the handler of a datagram the server sent
+fn handle_datagram(
+    game_state: &Mutex<ClientGameState>,
+    assets: &AssetCache,
+    handler_id: u32,
+    bytes: Bytes,
+) {
+    let mut gs = game_state.lock();
+    let handler = gs
+        .world
+        .resource(datagram_handlers())
+        .get(&handler_id)
+        .cloned();
+    match handler {
+        Some(handler) => {
+            handler(&mut gs.world, assets.clone(), bytes);
+        }
+        None => {
+            log::error!("No such datagram handler: {:?}", handler_id);
+        }
+    }
+}
+
 #[element_component]
 pub fn GameClientWorld(hooks: &mut Hooks) -> Element {
     let (render_target, set_render_target) =
@@ -481,10 +560,150 @@ pub fn GameClientWorld(hooks: &mut Hooks) -> Element {
     )
 }
 
+/// A client that joins a server without a window or a GPU: it performs the handshake, keeps its
+/// world in sync with the server and runs its systems (and so the client-side WASM modules), but
+/// renders nothing. Used to simulate players, e.g. to load test a server.
+pub struct HeadlessGameClient {
+    pub server_addr: SocketAddr,
+    /// The host name of the server, which its certificate is verified against
+    pub server_name: String,
+    /// How the certificate of the server is verified; see [`crate::tls`]
+    pub server_verification: ServerVerification,
+    pub user_id: String,
+    /// Presented to the server along with the user id; see [`crate::auth`]
+    pub auth_token: Option<String>,
+    /// How often the systems of the world run
+    pub frame_time: Duration,
+    pub systems_and_resources: Cb<dyn Fn() -> (SystemGroup, Entity) + Sync + Send>,
+    pub create_rpc_registry: Cb<dyn Fn() -> RpcRegistry<server::RpcArgs> + Sync + Send>,
+}
+
+impl HeadlessGameClient {
+    /// Connects to the server and runs the client until the connection closes.
+    ///
+    /// `init_world` sets up the world before connecting, `on_frame` is called before the systems
+    /// run on every frame once the world is received, and `on_network_stats` receives the stats of
+    /// the connection every few seconds.
+    pub async fn run(
+        self,
+        assets: AssetCache,
+        init_world: impl FnOnce(&mut World) + Send,
+        mut on_frame: impl FnMut(&mut World) + Send,
+        mut on_network_stats: impl FnMut(GameClientNetworkStats) + Send + Sync,
+    ) -> anyhow::Result<()> {
+        let Self {
+            server_addr,
+            server_name,
+            server_verification,
+            user_id,
+            auth_token,
+            frame_time,
+            systems_and_resources,
+            create_rpc_registry,
+        } = self;
+
+        let game_state = {
+            let (systems, resources) = systems_and_resources();
+            let mut state = ClientGameState::new_headless(
+                assets.clone(),
+                RuntimeHandle::current(),
+                user_id.clone(),
+                systems,
+                resources,
+            );
+            init_world(&mut state.world);
+            Arc::new(Mutex::new(state))
+        };
+
+        let set_game_client = |conn, user_id| {
+            let game_client = GameClient::new(
+                conn,
+                Arc::new(create_rpc_registry()),
+                game_state.clone(),
+                user_id,
+            );
+            game_state
+                .lock()
+                .world
+                .add_resource(self::game_client(), Some(game_client));
+        };
+        let mut on_init = |conn: Connection, client_info: ClientInfo, server_info: ServerInfo| {
+            game_state.lock().set_user_id(client_info.user_id.clone());
+            ContentBaseUrlKey.insert(&assets, server_info.content_base_url);
+            AssetServerConnection::install(&assets, conn.clone());
+            set_game_client(conn, client_info.user_id);
+            anyhow::Ok(Box::new(|| {}) as Box<dyn FnOnce() + Sync + Send>)
+        };
+        let mut on_resume = |conn: Connection, client_info: ClientInfo| {
+            AssetServerConnection::install(&assets, conn.clone());
+            set_game_client(conn, client_info.user_id)
+        };
+        let mut on_diff = |diff: WorldDiff| {
+            let mut gs = game_state.lock();
+            diff.apply(
+                &mut gs.world,
+                Entity::new().with(is_remote_entity(), ()),
+                false,
+            );
+        };
+        let on_bi_stream =
+            |handler_id, tx, rx| handle_bi_stream(&game_state, &assets, handler_id, tx, rx);
+        let on_uni_stream =
+            |handler_id, rx| handle_uni_stream(&game_state, &assets, handler_id, rx);
+        let on_datagram =
+            |handler_id, bytes| handle_datagram(&game_state, &assets, handler_id, bytes);
+
+        let client_loop = ClientInstance {
+            set_connection_status: cb({
+                let user_id = user_id.clone();
+                move |status| log::debug!("[{user_id}] {status}")
+            }),
+            server_addr,
+            server_name,
+            server_verification,
+            user_id,
+            auth_token,
+            on_init: &mut on_init,
+            on_resume: &mut on_resume,
+            on_diff: &mut on_diff,
+            on_bi_stream: &on_bi_stream,
+            on_uni_stream: &on_uni_stream,
+            on_datagram: &on_datagram,
+            on_server_stats: &mut |_: GameClientServerStats| {},
+            on_client_stats: &mut on_network_stats,
+            on_disconnect: cb(|| {}),
+            init_destructor: None,
+        };
+
+        let frames = async {
+            let mut interval = tokio::time::interval(frame_time);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let mut gs = game_state.lock();
+                // The world is only there once the client is connected
+                if !matches!(gs.world.resource_opt(game_client()), Some(Some(_))) {
+                    continue;
+                }
+                on_frame(&mut gs.world);
+                gs.update();
+            }
+        };
+
+        tokio::select! {
+            result = client_loop.run() => result,
+            _ = frames => unreachable!(),
+        }
+    }
+}
+
 struct ClientInstance<'a> {
     set_connection_status: CallbackFn<String>,
     server_addr: SocketAddr,
+    server_name: String,
+    server_verification: ServerVerification,
     user_id: String,
+    auth_token: Option<String>,
 
     /// Called when the client connected and received the world.
     on_init: &'a mut (dyn FnMut(
@@ -494,6 +713,9 @@ struct ClientInstance<'a> {
     ) -> anyhow::Result<Box<dyn FnOnce() + Sync + Send>>
                  + Send
                  + Sync),
+    /// Called when the client resumed its session over a new connection after losing the previous
+    /// one.
+    on_resume: &'a mut (dyn FnMut(Connection, ClientInfo) + Send + Sync),
     on_diff: &'a mut (dyn FnMut(WorldDiff) + Send + Sync),
     on_datagram: &'a (dyn Fn(u32, Bytes) + Send + Sync),
     on_bi_stream: &'a (dyn Fn(u32, SendStream, RecvStream) + Send + Sync),
@@ -519,21 +741,12 @@ impl<'a> ClientInstance<'a> {
     async fn run(mut self) -> anyhow::Result<()> {
         log::info!("Connecting to server at {}", self.server_addr);
         (self.set_connection_status)(format!("Connecting to {}", self.server_addr));
-        let conn = open_connection(self.server_addr).await?;
-
-        (self.set_connection_status)("Waiting for server to respond".to_string());
-
-        // Set up the protocol.
-        let mut protocol = ClientProtocol::new(conn, self.user_id.clone()).await?;
-
-        let stats_interval = 5;
-        let mut stats_timer = tokio::time::interval(Duration::from_secs_f32(stats_interval as f32));
-        let mut prev_stats = protocol.connection().stats();
+        let mut protocol = self.connect(None).await?;
 
         // The first WorldDiff initializes the world, so wait for that until we say things are "ready"
         (self.set_connection_status)("Receiving world".to_string());
 
-        let msg = protocol.diff_stream.next().await?;
+        let (mut last_diff, msg) = protocol.next_diff().await?;
         (self.on_diff)(msg);
         self.init_destructor = Some(
             (self.on_init)(
@@ -544,12 +757,103 @@ impl<'a> ClientInstance<'a> {
             .context("Client initialization failed")?,
         );
 
-        // The server
+        loop {
+            let err = match self.serve(&mut protocol, &mut last_diff).await {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            // The server keeps the player for a while after losing the connection, so try to get back
+            // to it
+            let session = protocol.client_info().session.clone();
+            let token = match session.resume_token {
+                Some(token) if connection_lost(&protocol.conn) => token,
+                _ => return Err(err),
+            };
+            log::warn!("Lost the connection to the server, trying to resume the session: {err:?}");
+            protocol = match self.resume(token, last_diff, session.grace_period).await {
+                Some(protocol) => protocol,
+                None => return Err(err),
+            };
+            log::info!("Resumed the session");
+            (self.on_resume)(protocol.connection(), protocol.client_info().clone());
+        }
+    }
+
+    /// Opens a connection to the server and presents the credentials of the player
+    async fn connect(&self, resume: Option<ResumeRequest>) -> anyhow::Result<ClientProtocol> {
+        let conn = open_connection(
+            self.server_addr,
+            &self.server_name,
+            &self.server_verification,
+        )
+        .await?;
+
+        (self.set_connection_status)("Waiting for server to respond".to_string());
+
+        // Set up the protocol.
+        let credentials = Credentials {
+            user_id: self.user_id.clone(),
+            token: self.auth_token.clone(),
+            resume,
+        };
+        let set_connection_status = self.set_connection_status.clone();
+        let on_queued = move |position| {
+            set_connection_status(format!(
+                "The server is full, waiting in the join queue (position {position})"
+            ))
+        };
+        Ok(ClientProtocol::new(conn, credentials, &on_queued).await?)
+    }
+
+    /// Tries to resume the session until the grace period of the server runs out
+    async fn resume(
+        &self,
+        token: String,
+        last_diff: u64,
+        grace_period: Duration,
+    ) -> Option<ClientProtocol> {
+        let deadline = tokio::time::Instant::now() + grace_period;
+        while tokio::time::Instant::now() < deadline {
+            let resume = ResumeRequest {
+                token: token.clone(),
+                last_diff,
+            };
+            match self.connect(Some(resume)).await {
+                Ok(protocol) if protocol.client_info().session.resumed => return Some(protocol),
+                Ok(_) => {
+                    log::warn!("The server no longer has the session");
+                    return None;
+                }
+                Err(err) => match err.downcast_ref::<NetworkError>() {
+                    Some(NetworkError::ConnectionRejected(rejection)) => {
+                        log::error!("The server refused to resume the session: {rejection}");
+                        return None;
+                    }
+                    _ => log::debug!("Failed to resume the session: {err:?}"),
+                },
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        None
+    }
+
+    /// Handles the messages of the server until the connection fails
+    async fn serve(
+        &mut self,
+        protocol: &mut ClientProtocol,
+        last_diff: &mut u64,
+    ) -> anyhow::Result<()> {
+        let stats_interval = 5;
+        let mut stats_timer = tokio::time::interval(Duration::from_secs_f32(stats_interval as f32));
+        let mut prev_stats = protocol.connection().stats();
+
         loop {
             tokio::select! {
                 msg = protocol.diff_stream.next() => {
                     ambient_profiling::scope!("game_in_entities");
-                    let msg: WorldDiff  = msg?;
+                    let (seq, msg): (u64, WorldDiff) = msg?;
+                    *last_diff = seq;
                     (self.on_diff)(msg);
                 }
                 _ = stats_timer.tick() => {
@@ -592,6 +896,39 @@ impl<'a> ClientInstance<'a> {
     }
 }
 
+/// Whether the connection broke, as opposed to being closed by either side
+fn connection_lost(conn: &Connection) -> bool {
+    matches!(
+        conn.close_reason(),
+        Some(
+            quinn::ConnectionError::TimedOut
+                | quinn::ConnectionError::Reset
+                | quinn::ConnectionError::TransportError(_)
+        )
+    )
+}
+
+/// Tells the server that the player is leaving, then closes the connection. Servers can't tell
+/// proxied connections that were closed from lost ones, and would otherwise keep the session of the
+/// player for it to resume
+pub async fn leave(conn: &Connection, reason: &[u8]) {
+    let goodbye = async {
+        let mut stream = conn.open_uni().await?;
+        stream.write_u32(LEAVE_UNISTREAM_ID).await?;
+        // Wait for the server to receive it, as closing the connection drops what wasn't sent
+        stream.finish().await?;
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(Duration::from_secs(1), goodbye).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            log::debug!("Failed to tell the server that the player is leaving: {err:?}")
+        }
+        Err(_) => log::debug!("Timed out telling the server that the player is leaving"),
+    }
+    conn.close(0u32.into(), reason);
+}
+
 /// Set up and manage a connection to the server
 #[derive(Debug, Clone, Default)]
 pub struct GameClientNetworkStats {
@@ -618,14 +955,25 @@ pub struct GameClientServerStats(pub FpsSample);
 /// Connnect to the server endpoint.
 /// Does not handle a protocol.
 #[tracing::instrument(level = "debug")]
-pub async fn open_connection(server_addr: SocketAddr) -> anyhow::Result<Connection> {
+pub async fn open_connection(
+    server_addr: SocketAddr,
+    server_name: &str,
+    verification: &ServerVerification,
+) -> anyhow::Result<Connection> {
     log::debug!("Connecting to world instance: {server_addr:?}");
 
-    let endpoint =
-        create_client_endpoint_random_port().context("Failed to create client endpoint")?;
+    let endpoint = create_client_endpoint_random_port(verification)
+        .context("Failed to create client endpoint")?;
+
+    // The name only matters if the certificate is checked against it; it may be an IP address otherwise
+    let server_name = if verification.checks_server_name() {
+        server_name
+    } else {
+        "localhost"
+    };
 
     log::debug!("Got endpoint");
-    let conn = endpoint.connect(server_addr, "localhost")?.await?;
+    let conn = endpoint.connect(server_addr, server_name)?.await?;
 
     log::debug!("Got connection");
     Ok(conn)
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use ambient_proxy::client::ProxiedConnection;
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -12,6 +14,34 @@ pub enum ClientConnection {
     Proxied(ProxiedConnection),
 }
 
+impl ClientConnection {
+    /// Returns true if the client closed the connection, as opposed to the connection being lost
+    pub fn closed_by_peer(&self) -> bool {
+        match self {
+            ClientConnection::Direct(conn) => matches!(conn.close_reason(), Some(quinn::ConnectionError::ApplicationClosed(_))),
+            // The proxy doesn't tell why the connection was closed; clients that leave say so with `LEAVE_UNISTREAM_ID` instead
+            ClientConnection::Proxied(_) => false,
+        }
+    }
+
+    /// The statistics and round trip time of the connection, if it's direct; proxied connections are the proxy's
+    pub fn stats(&self) -> Option<(quinn::ConnectionStats, Duration)> {
+        match self {
+            ClientConnection::Direct(conn) => Some((conn.stats(), conn.rtt())),
+            ClientConnection::Proxied(_) => None,
+        }
+    }
+
+    /// Closes the connection, telling the client why
+    pub fn close(&self, reason: &str) {
+        match self {
+            ClientConnection::Direct(conn) => conn.close(1u32.into(), reason.as_bytes()),
+            // Proxied connections are closed when they are dropped
+            ClientConnection::Proxied(_) => {}
+        }
+    }
+}
+
 impl From<Connection> for ClientConnection {
     fn from(value: Connection) -> Self {
         Self::Direct(value)
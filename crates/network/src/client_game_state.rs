@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
 use ambient_app::{
-    gpu_world_sync_systems, world_instance_resources, world_instance_systems, AppResources,
+    gpu_world_sync_systems, headless_world_instance_resources, headless_world_instance_systems,
+    world_instance_resources, world_instance_systems, AppResources,
 };
 use ambient_core::{
     camera::{get_active_camera, projection_view},
@@ -21,6 +22,7 @@ use ambient_std::{
     math::interpolate,
     shapes::Ray,
 };
+use ambient_sys::task::RuntimeHandle;
 use glam::{vec2, Mat4, Vec2, Vec3, Vec3Swizzles};
 
 use ambient_core::player::{player, user_id};
@@ -35,11 +37,18 @@ pub struct ClientGameState {
     pub world: World,
     systems: SystemGroup,
     temporary_systems: Vec<TempSystem>,
+    /// `None` for headless clients, which don't render the world
+    pub rendering: Option<ClientRendering>,
+    assets: AssetCache,
+    user_id: String,
+}
+
+/// Renders the world of a [`ClientGameState`]
+#[derive(Debug)]
+pub struct ClientRendering {
     gpu_world_sync_systems: SystemGroup<GpuWorldSyncEvent>,
     pub renderer: Renderer,
     pub ui_renderer: Renderer,
-    assets: AssetCache,
-    user_id: String,
 }
 struct TempSystem(Box<dyn FnMut(&mut World) -> bool + Sync + Send>);
 impl std::fmt::Debug for TempSystem {
@@ -98,21 +107,82 @@ impl ClientGameState {
             world: game_world,
             systems,
             temporary_systems: Default::default(),
-            gpu_world_sync_systems: gpu_world_sync_systems(),
-            renderer,
-            ui_renderer,
+            rendering: Some(ClientRendering {
+                gpu_world_sync_systems: gpu_world_sync_systems(),
+                renderer,
+                ui_renderer,
+            }),
             assets,
             user_id: player_id,
         }
     }
-    #[ambient_profiling::function]
-    pub fn on_frame(&mut self, target: &RenderTarget) {
+    /// Creates the state of a client that doesn't render the world, and so needs no window or GPU
+    pub fn new_headless(
+        assets: AssetCache,
+        runtime: RuntimeHandle,
+        player_id: String,
+        client_systems: SystemGroup,
+        client_resources: Entity,
+    ) -> Self {
+        // Nothing shows the cursor or the window title; the messages are dropped
+        let (ctl_tx, ctl_rx) = flume::unbounded();
+        runtime.spawn(async move { while ctl_rx.recv_async().await.is_ok() {} });
+
+        let mut game_world = World::new("client_game_world");
+        let local_resources = headless_world_instance_resources(assets.clone(), runtime, ctl_tx)
+            .with(ambient_core::player::local_user_id(), player_id.clone())
+            .with_merge(client_resources);
+        game_world
+            .add_components(game_world.resource_entity(), local_resources)
+            .unwrap();
+
+        let systems = SystemGroup::new(
+            "game",
+            vec![
+                Box::new(client_systems),
+                Box::new(headless_world_instance_systems()),
+            ],
+        );
+
+        Self {
+            world: game_world,
+            systems,
+            temporary_systems: Default::default(),
+            rendering: None,
+            assets,
+            user_id: player_id,
+        }
+    }
+    /// Sets the user id of the local player to the one the server assigned during the handshake,
+    /// which may differ from the one that was asked for
+    pub fn set_user_id(&mut self, user_id: String) {
+        self.world
+            .set(
+                self.world.resource_entity(),
+                ambient_core::player::local_user_id(),
+                user_id.clone(),
+            )
+            .unwrap();
+        self.user_id = user_id;
+    }
+    /// Runs the systems of the world
+    pub fn update(&mut self) {
         self.world.next_frame();
         self.systems.run(&mut self.world, &FrameEvent);
         self.temporary_systems
             .retain_mut(|system| !(system.0)(&mut self.world));
+    }
+    /// Runs the systems of the world and renders it to `target`, unless the client is headless
+    #[ambient_profiling::function]
+    pub fn on_frame(&mut self, target: &RenderTarget) {
+        self.update();
 
-        self.gpu_world_sync_systems
+        let rendering = match &mut self.rendering {
+            Some(rendering) => rendering,
+            None => return,
+        };
+        rendering
+            .gpu_world_sync_systems
             .run(&mut self.world, &GpuWorldSyncEvent);
         let gpu = GpuKey.get(&self.assets);
         let mut encoder = gpu
@@ -121,14 +191,14 @@ impl ClientGameState {
                 label: Some("GameState.render"),
             });
         let mut post_submit = Vec::new();
-        self.renderer.render(
+        rendering.renderer.render(
             &mut self.world,
             &mut encoder,
             &mut post_submit,
             RendererTarget::Target(target),
             Some(Color::rgba(0., 0., 0., 1.)),
         );
-        self.ui_renderer.render(
+        rendering.ui_renderer.render(
             &mut self.world,
             &mut encoder,
             &mut post_submit,
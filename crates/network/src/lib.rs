@@ -8,33 +8,46 @@ use ambient_ecs::{query, Component, ComponentValue, EntityId, Networked, Seriali
 
 use ambient_rpc::{RpcError, RpcRegistry};
 use ambient_std::log_error;
+use auth::ConnectionRejection;
 use bytes::Bytes;
 use connection::Connection;
 use futures::{Future, SinkExt, StreamExt};
 use quinn::{ClientConfig, ConnectionClose, ConnectionError::ConnectionClosed, Endpoint, ServerConfig, TransportConfig};
 use rand::Rng;
-use rustls::{Certificate, PrivateKey, RootCertStore};
+use rustls::{Certificate, RootCertStore};
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
+use tls::{ServerCertificate, ServerVerification};
 use tokio::io::AsyncWriteExt;
 use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 
 pub use ambient_ecs::generated::components::core::network::{is_remote_entity, persistent_resources, synced_resources};
 
 pub type AsyncMutex<T> = tokio::sync::Mutex<T>;
+pub mod admin;
+pub mod assets;
+pub mod auth;
 pub mod client;
 pub mod client_connection;
 pub mod client_game_state;
 pub mod connection;
 pub mod hooks;
+pub mod limits;
+pub mod metrics;
 pub mod protocol;
+pub mod recording;
 pub mod rpc;
 pub mod server;
+pub mod tls;
 
 pub const RPC_BISTREAM_ID: u32 = 1;
 pub const WASM_BISTREAM_ID: u32 = 2;
+/// See [`assets`]
+pub const ASSETS_BISTREAM_ID: u32 = 3;
 
 pub const WASM_UNISTREAM_ID: u32 = 1;
+/// Opened by a client that leaves the server, so that the server doesn't keep its session for it to resume
+pub const LEAVE_UNISTREAM_ID: u32 = 2;
 
 pub const PLAYER_INPUT_DATAGRAM_ID: u32 = 5;
 pub const WASM_DATAGRAM_ID: u32 = 6;
@@ -143,6 +156,8 @@ pub enum NetworkError {
     RpcError(#[from] RpcError),
     #[error(transparent)]
     ProxyError(#[from] ambient_proxy::Error),
+    #[error("Connection rejected: {0}")]
+    ConnectionRejected(#[from] ConnectionRejection),
 }
 
 impl NetworkError {
@@ -267,7 +282,8 @@ pub async fn send_datagram<C: Connection>(conn: &C, id: u32, mut payload: Vec<u8
     Ok(())
 }
 
-pub fn create_client_endpoint_random_port() -> Option<Endpoint> {
+pub fn create_client_endpoint_random_port(verification: &ServerVerification) -> anyhow::Result<Endpoint> {
+    let crypto = Arc::new(verification.client_crypto()?);
     for _ in 0..10 {
         let client_port = {
             let mut rng = rand::thread_rng();
@@ -276,10 +292,6 @@ pub fn create_client_endpoint_random_port() -> Option<Endpoint> {
         let client_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), client_port);
 
         if let Ok(mut endpoint) = Endpoint::client(client_addr) {
-            let cert = Certificate(CERT.to_vec());
-            let mut roots = RootCertStore::empty();
-            roots.add(&cert).unwrap();
-            let crypto = rustls::ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
             let mut transport = TransportConfig::default();
             transport.keep_alive_interval(Some(Duration::from_secs_f32(1.)));
             if std::env::var("AMBIENT_DISABLE_TIMEOUT").is_ok() {
@@ -287,20 +299,18 @@ pub fn create_client_endpoint_random_port() -> Option<Endpoint> {
             } else {
                 transport.max_idle_timeout(Some(Duration::from_secs_f32(60.).try_into().unwrap()));
             }
-            let mut client_config = ClientConfig::new(Arc::new(crypto));
+            let mut client_config = ClientConfig::new(crypto.clone());
             client_config.transport_config(Arc::new(transport));
 
             endpoint.set_default_client_config(client_config);
-            return Some(endpoint);
+            return Ok(endpoint);
         }
     }
-    None
+    anyhow::bail!("Failed to find a free port for the client endpoint")
 }
 
-fn create_server(server_addr: SocketAddr) -> anyhow::Result<Endpoint> {
-    let cert = Certificate(CERT.to_vec());
-    let cert_key = PrivateKey(CERT_KEY.to_vec());
-    let mut server_conf = ServerConfig::with_single_cert(vec![cert.clone()], cert_key)?;
+fn create_server(server_addr: SocketAddr, certificate: &ServerCertificate) -> anyhow::Result<Endpoint> {
+    let mut server_conf = ServerConfig::with_single_cert(certificate.cert_chain.clone(), certificate.key.clone())?;
     let mut transport = TransportConfig::default();
     if std::env::var("AMBIENT_DISABLE_TIMEOUT").is_ok() {
         transport.max_idle_timeout(None);
@@ -313,9 +323,10 @@ fn create_server(server_addr: SocketAddr) -> anyhow::Result<Endpoint> {
 
     let mut endpoint = Endpoint::server(server_conf, server_addr)?;
 
-    // Create client config for the server endpoint for proxying and hole punching
+    // Create client config for the server endpoint for proxying and hole punching.
+    // The proxy presents the certificate that Ambient servers used to share, so that one is trusted here.
     let mut roots = RootCertStore::empty();
-    roots.add(&cert).unwrap();
+    roots.add(&Certificate(PROXY_CERT.to_vec())).unwrap();
     let crypto = rustls::ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
     let mut client_config = ClientConfig::new(Arc::new(crypto));
     client_config.transport_config(transport);
@@ -324,8 +335,8 @@ fn create_server(server_addr: SocketAddr) -> anyhow::Result<Endpoint> {
     Ok(endpoint)
 }
 
-pub const CERT: &[u8] = include_bytes!("./cert.der");
-pub const CERT_KEY: &[u8] = include_bytes!("./cert.key.der");
+/// The certificate of the proxy used for NAT traversal
+pub const PROXY_CERT: &[u8] = include_bytes!("./cert.der");
 
 #[macro_export]
 macro_rules! log_network_result {
@@ -0,0 +1,180 @@
+//! Player limits and the join queue.
+//!
+//! A server can cap how many players it has, and how many players each instance has; see [`PlayerLimits`]. The last
+//! [`PlayerLimits::reserved_slots`] slots are kept for the admins. When the server is full, the players joining wait in
+//! the [`JoinQueue`], and are told their position in it until a slot frees up. When the queue is full too, or if there is
+//! no queue, they are turned away with [`ConnectionRejection::ServerFull`]; admins are always let in the queue.
+//!
+//! Players who already have a slot, because they reconnect or resume their session, always get back in. The players
+//! whose connection was lost keep their slot until they resume their session or it expires, so they count against the
+//! limits like the connected players.
+use std::{collections::HashSet, time::Duration};
+
+use ambient_sys::time::Instant;
+
+use crate::auth::ConnectionRejection;
+
+/// How long a player waiting in the queue is kept without checking on its position
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the slot of an admitted player is kept until its player joins
+const ADMISSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many players can join a server; see [`crate::limits`]. There are no limits by default.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerLimits {
+    /// How many players the server has at most
+    pub max_players: Option<usize>,
+    /// How many players an instance has at most
+    pub max_instance_players: Option<usize>,
+    /// How many of the slots of the server, and of each instance, only the admins can take
+    pub reserved_slots: usize,
+    /// How many players can wait for a slot; the players joining a full server are turned away if this is zero
+    pub max_queue_length: usize,
+    /// The user ids of the admins, who can take the reserved slots and go first in the queue
+    pub admins: HashSet<String>,
+}
+impl PlayerLimits {
+    /// Whether a player can join an instance with `instance_players` players on a server with `players` players
+    pub fn has_room(&self, user_id: &str, players: usize, instance_players: usize) -> bool {
+        self.room(user_id, players, self.max_players) && self.has_instance_room(user_id, instance_players)
+    }
+    /// Whether a player can move to an instance with `instance_players` players
+    pub fn has_instance_room(&self, user_id: &str, instance_players: usize) -> bool {
+        self.room(user_id, instance_players, self.max_instance_players)
+    }
+    fn room(&self, user_id: &str, players: usize, max: Option<usize>) -> bool {
+        let reserved = if self.admins.contains(user_id) { 0 } else { self.reserved_slots };
+        max.map(|max| players + reserved < max).unwrap_or(true)
+    }
+}
+
+#[derive(Debug)]
+struct QueuedPlayer {
+    user_id: String,
+    admin: bool,
+    last_seen: Instant,
+}
+
+/// The players waiting to join a full server, and the players that were let in but didn't join yet
+#[derive(Debug, Default)]
+pub struct JoinQueue {
+    queue: Vec<QueuedPlayer>,
+    admitted: Vec<(String, Instant)>,
+}
+impl JoinQueue {
+    /// How many players wait in the queue
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    /// How many players were let in but didn't join yet; they hold a slot
+    pub fn admitted(&self) -> usize {
+        self.admitted.len()
+    }
+
+    /// Lets the player in if there is room (`has_room`) and nobody waits before it, and otherwise returns its position in the
+    /// queue, starting from 1. Players waiting in the queue must ask again at least every few seconds to keep their place.
+    pub fn admit(&mut self, limits: &PlayerLimits, user_id: &str, has_room: bool) -> Result<Option<usize>, ConnectionRejection> {
+        let now = Instant::now();
+        self.queue.retain(|queued| now.duration_since(queued.last_seen) < QUEUE_TIMEOUT);
+        self.admitted.retain(|(_, since)| now.duration_since(*since) < ADMISSION_TIMEOUT);
+
+        let admin = limits.admins.contains(user_id);
+        let position = match self.queue.iter().position(|queued| queued.user_id == user_id) {
+            Some(index) => {
+                self.queue[index].last_seen = now;
+                index
+            }
+            // Admins only wait for other admins
+            None if admin => self.queue.iter().take_while(|queued| queued.admin).count(),
+            None => self.queue.len(),
+        };
+        let queued = self.queue.get(position).map(|queued| queued.user_id == user_id).unwrap_or(false);
+        if has_room && position == 0 {
+            if queued {
+                self.queue.remove(0);
+            }
+            self.admitted.push((user_id.to_string(), now));
+            return Ok(None);
+        }
+
+        if !queued {
+            // The admins can always wait
+            if !admin && self.queue.len() >= limits.max_queue_length {
+                return Err(ConnectionRejection::ServerFull);
+            }
+            self.queue.insert(position, QueuedPlayer { user_id: user_id.to_string(), admin, last_seen: now });
+        }
+        Ok(Some(position + 1))
+    }
+
+    /// Frees the slot held for an admitted player once it joined, or failed to join
+    pub fn joined(&mut self, user_id: &str) {
+        self.admitted.retain(|(admitted, _)| admitted != user_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_queue_length: usize) -> PlayerLimits {
+        PlayerLimits { max_queue_length, admins: HashSet::from_iter(["admin".to_string()]), ..Default::default() }
+    }
+
+    #[test]
+    fn queued_players_wait_their_turn() {
+        let limits = limits(2);
+        let mut queue = JoinQueue::default();
+        assert_eq!(queue.admit(&limits, "a", false), Ok(Some(1)));
+        assert_eq!(queue.admit(&limits, "b", false), Ok(Some(2)));
+        // Admins go first
+        assert_eq!(queue.admit(&limits, "admin", false), Ok(Some(1)));
+        assert_eq!(queue.admit(&limits, "a", false), Ok(Some(2)));
+        assert_eq!(queue.len(), 3);
+
+        // Nobody skips the queue when a slot frees up
+        assert_eq!(queue.admit(&limits, "c", true), Err(ConnectionRejection::ServerFull));
+        assert_eq!(queue.admit(&limits, "b", true), Ok(Some(3)));
+        assert_eq!(queue.admit(&limits, "admin", true), Ok(None));
+        assert_eq!(queue.admit(&limits, "a", true), Ok(None));
+        assert_eq!(queue.admit(&limits, "b", true), Ok(None));
+        assert!(queue.is_empty());
+        assert_eq!(queue.admitted(), 3);
+
+        queue.joined("a");
+        assert_eq!(queue.admitted(), 2);
+    }
+
+    #[test]
+    fn full_queue_turns_players_away() {
+        let limits = limits(1);
+        let mut queue = JoinQueue::default();
+        assert_eq!(queue.admit(&limits, "a", false), Ok(Some(1)));
+        assert_eq!(queue.admit(&limits, "b", false), Err(ConnectionRejection::ServerFull));
+        // Admins can always wait
+        assert_eq!(queue.admit(&limits, "admin", false), Ok(Some(1)));
+
+        let mut queue = JoinQueue::default();
+        assert_eq!(queue.admit(&PlayerLimits::default(), "a", false), Err(ConnectionRejection::ServerFull));
+        assert_eq!(queue.admit(&PlayerLimits::default(), "a", true), Ok(None));
+    }
+
+    #[test]
+    fn stale_players_and_slots_are_dropped() {
+        let limits = limits(2);
+        let mut queue = JoinQueue::default();
+        assert_eq!(queue.admit(&limits, "a", true), Ok(None));
+        assert_eq!(queue.admit(&limits, "b", false), Ok(Some(1)));
+        assert_eq!(queue.admit(&limits, "c", false), Ok(Some(2)));
+
+        // "b" stopped asking, and "a" never joined
+        queue.queue[0].last_seen = Instant::now() - QUEUE_TIMEOUT;
+        queue.admitted[0].1 = Instant::now() - ADMISSION_TIMEOUT;
+        assert_eq!(queue.admit(&limits, "c", false), Ok(Some(1)));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.admitted(), 0);
+    }
+}
@@ -0,0 +1,315 @@
+//! Metrics of a running server, in the [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/).
+//!
+//! The server keeps the histograms of its ticks and of the diffs it broadcasts in [`ServerState::metrics`]; everything else
+//! (players, entities, the run times of the system groups and WASM modules, connections and assets) is read from the
+//! state when the metrics are rendered with [`render`]. The state is only locked while the values are collected, and the
+//! text is written once it's unlocked.
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use ambient_core::{asset_cache, player::user_id};
+use ambient_ecs::{query, run_timings};
+use once_cell::sync::OnceCell;
+
+use crate::server::{player_connection, ServerState, SharedServerState, MAIN_INSTANCE_ID};
+
+/// Counts the observations under each bound; the Prometheus buckets are cumulative.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self { bounds, counts: vec![0; bounds.len()], sum: 0., count: 0 }
+    }
+    pub fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+    fn write(&self, out: &mut String, name: &str) -> fmt::Result {
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}")?;
+        }
+        writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count)?;
+        writeln!(out, "{name}_sum {}", self.sum)?;
+        writeln!(out, "{name}_count {}", self.count)
+    }
+}
+
+/// The metrics the server records as it runs
+#[derive(Debug, Clone)]
+pub struct ServerMetrics {
+    /// How long the ticks take, in seconds
+    pub tick_duration: Histogram,
+    /// The size of the diffs broadcast to the players of each instance, in bytes
+    pub diff_size: Histogram,
+}
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self {
+            tick_duration: Histogram::new(&[0.001, 0.002, 0.004, 0.008, 0.016, 0.033, 0.066, 0.133]),
+            diff_size: Histogram::new(&[64., 256., 1024., 4096., 16384., 65536., 262144., 1048576.]),
+        }
+    }
+}
+impl ServerMetrics {
+    pub fn record_tick(&mut self, duration: Duration) {
+        self.tick_duration.observe(duration.as_secs_f64());
+    }
+    pub fn record_diff(&mut self, bytes: usize) {
+        self.diff_size.observe(bytes as f64);
+    }
+}
+
+/// A value of a metric that has labels
+#[derive(Debug, Clone, PartialEq)]
+struct Sample {
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+/// Samples of metrics that have labels, grouped by metric so that each is described once
+#[derive(Debug, Default)]
+struct Samples(BTreeMap<String, (&'static str, String, Vec<Sample>)>);
+impl Samples {
+    fn add(&mut self, name: &str, kind: &'static str, help: &str, labels: &[(&'static str, &str)], value: f64) {
+        let labels = labels.iter().map(|(key, value)| (*key, value.to_string())).collect();
+        let (_, _, samples) = self.0.entry(name.to_string()).or_insert_with(|| (kind, help.to_string(), Vec::new()));
+        samples.push(Sample { labels, value });
+    }
+    fn write(&self, out: &mut String) -> fmt::Result {
+        for (name, (kind, help, samples)) in &self.0 {
+            header(out, name, kind, help)?;
+            for Sample { labels, value } in samples {
+                let labels = labels.iter().map(|(key, value)| format!("{key}=\"{}\"", escape(value))).collect::<Vec<_>>().join(",");
+                writeln!(out, "{name}{{{labels}}} {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Renders the metrics of a server once it runs; it is given to the server with
+/// [`GameServer::metrics`](crate::server::GameServer::metrics), and can be cloned to serve the metrics.
+#[derive(Clone, Default)]
+pub struct MetricsEndpoint {
+    state: Arc<OnceCell<SharedServerState>>,
+}
+impl MetricsEndpoint {
+    pub(crate) fn attach(&self, state: SharedServerState) {
+        if self.state.set(state).is_err() {
+            log::warn!("The metrics endpoint is already attached to a server");
+        }
+    }
+    /// Renders the metrics of the server, or `None` if it isn't running yet
+    pub fn render(&self) -> Option<String> {
+        let snapshot = Snapshot::take(&self.state.get()?.lock());
+        Some(snapshot.render())
+    }
+}
+
+/// Renders the metrics of the server
+pub fn render(state: &ServerState) -> String {
+    Snapshot::take(state).render()
+}
+
+/// The memory used by the assets of the asset cache
+#[derive(Debug)]
+struct AssetsSnapshot {
+    loaded: usize,
+    cpu_bytes: u64,
+    gpu_bytes: u64,
+}
+
+/// The metrics of a server at one point in time
+#[derive(Debug)]
+struct Snapshot {
+    metrics: ServerMetrics,
+    players: usize,
+    join_queue_length: usize,
+    samples: Samples,
+    assets: Option<AssetsSnapshot>,
+}
+impl Snapshot {
+    fn take(state: &ServerState) -> Self {
+        let mut samples = Samples::default();
+        for (id, instance) in &state.instances {
+            let world = &instance.world;
+            let instance_label = ("instance", id.as_str());
+            samples.add("ambient_instance_entities", "gauge", "The entities of an instance.", &[instance_label], world.len() as f64);
+            samples.add(
+                "ambient_instance_archetypes",
+                "gauge",
+                "The archetypes of an instance.",
+                &[instance_label],
+                world.archetypes().len() as f64,
+            );
+            samples.add(
+                "ambient_instance_players",
+                "gauge",
+                "The players of an instance.",
+                &[instance_label],
+                instance.player_count() as f64,
+            );
+
+            for (kind, name, timing) in world.resource_opt(run_timings()).into_iter().flat_map(|timings| timings.iter()) {
+                let labels = [instance_label, ("name", name)];
+                samples.add(
+                    &format!("ambient_{kind}_run_seconds_total"),
+                    "counter",
+                    &format!("How long each {} ran for in total.", kind.replace('_', " ")),
+                    &labels,
+                    timing.total.as_secs_f64(),
+                );
+                samples.add(
+                    &format!("ambient_{kind}_runs_total"),
+                    "counter",
+                    &format!("How many times each {} ran.", kind.replace('_', " ")),
+                    &labels,
+                    timing.runs as f64,
+                );
+            }
+
+            for (_, (user_id, conn)) in query((user_id(), player_connection())).iter(world, None) {
+                let (stats, rtt) = match conn.stats() {
+                    Some(stats) => stats,
+                    None => continue,
+                };
+                let labels = [("user_id", user_id.as_str())];
+                samples.add(
+                    "ambient_connection_sent_bytes_total",
+                    "counter",
+                    "The bytes sent to a player.",
+                    &labels,
+                    stats.udp_tx.bytes as f64,
+                );
+                samples.add(
+                    "ambient_connection_received_bytes_total",
+                    "counter",
+                    "The bytes received from a player.",
+                    &labels,
+                    stats.udp_rx.bytes as f64,
+                );
+                samples.add(
+                    "ambient_connection_rtt_seconds",
+                    "gauge",
+                    "The round trip time of the connection of a player.",
+                    &labels,
+                    rtt.as_secs_f64(),
+                );
+            }
+        }
+
+        // The instances share the asset cache
+        let assets = state.instances.get(MAIN_INSTANCE_ID).and_then(|instance| instance.world.resource_opt(asset_cache())).map(|assets| {
+            let timeline = assets.timeline.lock();
+            let alive = timeline.assets.values().filter(|asset| asset.is_alive).collect::<Vec<_>>();
+            AssetsSnapshot {
+                loaded: alive.len(),
+                cpu_bytes: alive.iter().filter_map(|asset| asset.cpu_size).sum(),
+                gpu_bytes: alive.iter().filter_map(|asset| asset.gpu_size).sum(),
+            }
+        });
+
+        Self { metrics: state.metrics.clone(), players: state.players.len(), join_queue_length: state.join_queue.len(), samples, assets }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out).expect("Writing to a string can't fail");
+        out
+    }
+
+    fn write(&self, out: &mut String) -> fmt::Result {
+        header(out, "ambient_server_tick_duration_seconds", "histogram", "How long the server ticks take.")?;
+        self.metrics.tick_duration.write(out, "ambient_server_tick_duration_seconds")?;
+        header(out, "ambient_server_diff_size_bytes", "histogram", "The size of the world diffs broadcast to the players of an instance.")?;
+        self.metrics.diff_size.write(out, "ambient_server_diff_size_bytes")?;
+        header(out, "ambient_server_players", "gauge", "The players on the server, including the ones whose connection was lost.")?;
+        writeln!(out, "ambient_server_players {}", self.players)?;
+        header(out, "ambient_server_join_queue_length", "gauge", "The players waiting in the join queue for a slot.")?;
+        writeln!(out, "ambient_server_join_queue_length {}", self.join_queue_length)?;
+
+        self.samples.write(out)?;
+
+        if let Some(assets) = &self.assets {
+            header(out, "ambient_assets_loaded", "gauge", "The assets in the asset cache.")?;
+            writeln!(out, "ambient_assets_loaded {}", assets.loaded)?;
+            header(out, "ambient_assets_cpu_bytes", "gauge", "The memory used by the assets in the asset cache that report their size.")?;
+            writeln!(out, "ambient_assets_cpu_bytes {}", assets.cpu_bytes)?;
+            header(
+                out,
+                "ambient_assets_gpu_bytes",
+                "gauge",
+                "The GPU memory used by the assets in the asset cache that report their size.",
+            )?;
+            writeln!(out, "ambient_assets_gpu_bytes {}", assets.gpu_bytes)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[1., 2., 4.]);
+        for value in [0.5, 1., 3., 10.] {
+            histogram.observe(value);
+        }
+        let mut out = String::new();
+        histogram.write(&mut out, "test").unwrap();
+        assert_eq!(
+            out,
+            "test_bucket{le=\"1\"} 2\n\
+             test_bucket{le=\"2\"} 2\n\
+             test_bucket{le=\"4\"} 3\n\
+             test_bucket{le=\"+Inf\"} 4\n\
+             test_sum 14.5\n\
+             test_count 4\n"
+        );
+    }
+
+    #[test]
+    fn samples_are_grouped_and_escaped() {
+        let mut samples = Samples::default();
+        samples.add("b_total", "counter", "B.", &[("name", "x")], 1.);
+        samples.add("a", "gauge", "A.", &[("name", "quote\" and \\ and\nline"), ("instance", "main")], 0.25);
+        samples.add("b_total", "counter", "B.", &[("name", "y")], 2.);
+        let mut out = String::new();
+        samples.write(&mut out).unwrap();
+        assert_eq!(
+            out,
+            "# HELP a A.\n\
+             # TYPE a gauge\n\
+             a{name=\"quote\\\" and \\\\ and\\nline\",instance=\"main\"} 0.25\n\
+             # HELP b_total B.\n\
+             # TYPE b_total counter\n\
+             b_total{name=\"x\"} 1\n\
+             b_total{name=\"y\"} 2\n"
+        );
+    }
+}
@@ -1,14 +1,20 @@
+use std::time::Duration;
+
 use ambient_ecs::{ComponentRegistry, ExternalComponentDesc, WorldDiff};
 use ambient_std::asset_url::AbsAssetUrl;
 use anyhow::{Context, Result};
-use futures::io::BufReader;
+use futures::{io::BufReader, SinkExt};
 use quinn::{Connection, RecvStream};
 
 use crate::{
-    client_connection::ClientConnection, next_bincode_bi_stream, open_bincode_bi_stream, IncomingStream, NetworkError, OutgoingStream,
+    auth::{Authenticator, ConnectionRejection, Credentials},
+    client_connection::ClientConnection,
+    next_bincode_bi_stream, open_bincode_bi_stream, IncomingStream, NetworkError, OutgoingStream,
 };
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How often the clients waiting in the join queue are told their position
+const QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct ClientProtocol {
@@ -21,15 +27,21 @@ pub struct ClientProtocol {
 }
 
 impl ClientProtocol {
-    pub async fn new(conn: Connection, player_id: String) -> Result<Self> {
+    /// `on_queued` is called with the position of the client in the join queue while the server is full; see [`crate::limits`]
+    pub async fn new(conn: Connection, credentials: Credentials, on_queued: &(dyn Fn(usize) + Send + Sync)) -> Result<Self> {
         // Say who we are
-        // The server will respond appropriately and return things such as
-        // username (TODO)
+        // The server will authenticate us and respond with the user id it knows us by,
+        // or with the reason it refused the connection
         let (mut tx, mut rx) = open_bincode_bi_stream(&conn).await?;
-        tx.send(&player_id).await?;
-
-        // The server will acknowledge and send the credentials back
-        let client_info: ClientInfo = rx.next().await?;
+        tx.send(&credentials).await?;
+
+        let client_info = loop {
+            match rx.next().await? {
+                JoinResponse::Queued(position) => on_queued(position),
+                JoinResponse::Accepted(client_info) => break client_info,
+                JoinResponse::Rejected(rejection) => return Err(NetworkError::from(rejection).into()),
+            }
+        };
         ComponentRegistry::get_mut().add_external(client_info.external_components.clone());
 
         let server_info: ServerInfo = rx.next().await?;
@@ -50,8 +62,9 @@ impl ClientProtocol {
         Ok(Self { conn, diff_stream, stat_stream, client_info, server_info })
     }
 
-    pub async fn next_diff(&mut self) -> anyhow::Result<WorldDiff> {
-        self.diff_stream.next::<WorldDiff>().await.context("Failed to read world diff")
+    /// Returns the next world diff, along with its sequence number in the session
+    pub async fn next_diff(&mut self) -> anyhow::Result<(u64, WorldDiff)> {
+        self.diff_stream.next::<(u64, WorldDiff)>().await.context("Failed to read world diff")
     }
 
     pub async fn next_event(&mut self) -> anyhow::Result<BufReader<RecvStream>> {
@@ -80,20 +93,55 @@ pub struct ServerProtocol {
 }
 
 impl ServerProtocol {
-    pub async fn new(conn: ClientConnection, server_info: ServerInfo) -> Result<Self, NetworkError> {
-        // The client now sends the player id
+    /// `admit` lets the player with the user id in, or returns its position in the join queue (see [`crate::limits`]), and
+    /// `start_session` decides whether the client resumes a previous session, given its user id and its resume request
+    pub async fn new(
+        conn: ClientConnection,
+        server_info: ServerInfo,
+        authenticator: &dyn Authenticator,
+        admit: &(dyn Fn(&str) -> Result<Option<usize>, ConnectionRejection> + Send + Sync),
+        start_session: &(dyn Fn(&str, Option<&ResumeRequest>) -> Session + Send + Sync),
+    ) -> Result<Self, NetworkError> {
+        // The client now sends its credentials
         let (mut tx, mut rx) = next_bincode_bi_stream(&conn).await?;
 
-        let user_id: String = rx.next().await?;
-
-        log::debug!("Received handshake from {user_id:?}");
+        let credentials: Credentials = rx.next().await?;
+
+        log::debug!("Received handshake from {:?}", credentials.user_id);
+
+        let user_id = match authenticator.authenticate(&credentials).await {
+            Ok(user_id) => user_id,
+            Err(rejection) => {
+                // Tell the client why before hanging up
+                tx.send(&JoinResponse::Rejected(rejection.clone())).await?;
+                tx.stream.close().await?;
+                return Err(rejection.into());
+            }
+        };
+
+        // Wait for a slot if the server is full; sending the position also notices clients that gave up
+        loop {
+            match admit(&user_id) {
+                Ok(None) => break,
+                Ok(Some(position)) => {
+                    tx.send(&JoinResponse::Queued(position)).await?;
+                    tokio::time::sleep(QUEUE_UPDATE_INTERVAL).await;
+                }
+                Err(rejection) => {
+                    tx.send(&JoinResponse::Rejected(rejection.clone())).await?;
+                    tx.stream.close().await?;
+                    return Err(rejection.into());
+                }
+            }
+        }
 
+        let session = start_session(&user_id, credentials.resume.as_ref());
         let external_components = ComponentRegistry::get().all_external().map(|x| x.0).collect();
 
         // Respond
-        let client_info = ClientInfo { user_id, external_components };
+        let client_info = ClientInfo { user_id, session, external_components };
         log::debug!("Responding with {client_info:?}");
-        tx.send(&client_info).await?;
+        tx.send(&JoinResponse::Accepted(client_info.clone())).await?;
 
         // Send the project name to the client so it can title its window correctly
         tx.send(&server_info).await?;
@@ -113,16 +161,60 @@ impl ServerProtocol {
     }
 }
 
+/// What the server answers to the credentials of a client
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum JoinResponse {
+    /// The server is full, and the client waits in the join queue at this position
+    Queued(usize),
+    Accepted(ClientInfo),
+    Rejected(ConnectionRejection),
+}
+
 /// Contains things such as username (TODO) and user_id
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct ClientInfo {
     pub user_id: String,
+    pub session: Session,
     pub external_components: Vec<ExternalComponentDesc>,
 }
 
 impl std::fmt::Debug for ClientInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ClientInfo").field("user_id", &self.user_id).finish_non_exhaustive()
+        f.debug_struct("ClientInfo").field("user_id", &self.user_id).field("session", &self.session).finish_non_exhaustive()
+    }
+}
+
+/// The session of a client, which the client can resume if its connection is lost; see [`crate::server::GameServer::resume_grace_period`].
+///
+/// The world diffs sent to the client are numbered, so that the server can send the ones the client missed when it resumes the session.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    /// Presented by the client to resume the session; `None` if the server doesn't keep the sessions of lost connections
+    pub resume_token: Option<String>,
+    /// How long the server keeps the session after the connection is lost
+    pub grace_period: Duration,
+    /// Whether the connection resumed a previous session. The client then keeps its world, and only receives the diffs it missed
+    pub resumed: bool,
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session").field("grace_period", &self.grace_period).field("resumed", &self.resumed).finish_non_exhaustive()
+    }
+}
+
+/// Sent by a client that lost its connection to resume its session
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ResumeRequest {
+    /// The [`Session::resume_token`] of the session
+    pub token: String,
+    /// The sequence number of the last world diff the client received
+    pub last_diff: u64,
+}
+
+impl std::fmt::Debug for ResumeRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResumeRequest").field("last_diff", &self.last_diff).finish_non_exhaustive()
     }
 }
 
@@ -148,4 +240,4 @@ impl Default for ServerInfo {
             version: VERSION.into(),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,223 @@
+//! Records the ticks of a server world, so that a session can be replayed headless and checked
+//! against the original.
+//!
+//! A recording starts with a [`RecordingHeader`], followed by one [`RecordedTick`] per server tick.
+//! Each tick holds its inputs (the changes made to the world between ticks, such as players joining,
+//! RPCs and messages from clients), the changes made by the systems during the tick, and a checksum
+//! of the world after the tick. Only serializable components are recorded and checked.
+//!
+//! For a replay to reproduce a session, the session must have been run in deterministic mode: with a
+//! fixed time step, seeded entity ids and deterministic physics. State that lives outside of the
+//! ECS, such as the memory of WASM modules and assets loaded asynchronously, is reproduced by
+//! running the same project again, and is only checked through its effect on the world.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use ambient_ecs::{
+    ArchetypeFilter, Entity, EntityIdSequence, Serializable, World, WorldDiff, WorldStream,
+    WorldStreamFilter,
+};
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::server::WorldInstance;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    /// The seed the entity ids of the world were generated from, before it was initialized
+    pub entity_id_seed: u64,
+    pub app_start_time: Duration,
+    /// The checksum of the world when the recording started
+    pub checksum: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedTick {
+    pub tick: u64,
+    pub time: Duration,
+    /// Changes made to the world since the previous tick
+    pub inputs: WorldDiff,
+    /// The entity id sequence after the inputs were applied
+    pub entity_ids: Option<EntityIdSequence>,
+    /// Changes made to the world by the systems during this tick
+    pub outputs: WorldDiff,
+    /// The checksum of the world after this tick
+    pub checksum: u64,
+}
+
+fn recording_filter() -> WorldStreamFilter {
+    WorldStreamFilter::new(
+        ArchetypeFilter::new(),
+        Arc::new(|component, _| component.has_attribute::<Serializable>()),
+    )
+}
+
+/// FxHash; unlike `DefaultHasher`, it gives the same hash on every platform and with every
+/// version of Rust, so that a recording can be replayed anywhere
+#[derive(Default)]
+struct StableHasher(u64);
+impl StableHasher {
+    const SEED: u64 = 0x517c_c1b7_2722_0a95;
+
+    fn add(&mut self, word: u64) {
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+        self.add(bytes.len() as u64);
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes the serializable components of all entities, in a stable order
+pub fn world_checksum(world: &World) -> u64 {
+    let filter = recording_filter();
+    let mut hasher = StableHasher::default();
+    for id in filter.all_entities(world).sorted() {
+        hasher.write(&id.0.to_le_bytes());
+        for component in filter
+            .get_entity_components(world, id)
+            .into_iter()
+            .sorted_by_key(|component| component.path())
+        {
+            let entry = world.get_entry(id, component).unwrap();
+            hasher.write(&bincode::serialize(&entry).unwrap());
+        }
+    }
+    hasher.finish()
+}
+
+/// Writes the ticks of a world instance to a recording as they happen
+pub struct Recorder {
+    writer: BufWriter<File>,
+    stream: WorldStream,
+    tick: u64,
+}
+impl Recorder {
+    /// Starts a recording of `world`, which must have been created with `entity_id_seed`
+    pub fn new(path: &Path, world: &World, entity_id_seed: u64) -> anyhow::Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create recording {path:?}"))?;
+        let mut writer = BufWriter::new(file);
+        let header = RecordingHeader {
+            entity_id_seed,
+            app_start_time: *world.resource(ambient_core::app_start_time()),
+            checksum: world_checksum(world),
+        };
+        bincode::serialize_into(&mut writer, &header)?;
+        writer.flush()?;
+
+        let mut stream = WorldStream::new(recording_filter());
+        // Everything that happened before the recording started is reproduced by the replay itself
+        stream.next_diff(world);
+        Ok(Self {
+            writer,
+            stream,
+            tick: 0,
+        })
+    }
+
+    /// Steps `instance` and records the tick
+    pub fn step(&mut self, instance: &mut WorldInstance, time: Duration) -> anyhow::Result<()> {
+        let inputs = self.stream.next_diff(&instance.world);
+        let entity_ids = instance.world.entity_id_sequence();
+        instance.step(time);
+        let tick = RecordedTick {
+            tick: self.tick,
+            time,
+            inputs,
+            entity_ids,
+            outputs: self.stream.next_diff(&instance.world),
+            checksum: world_checksum(&instance.world),
+        };
+        self.tick += 1;
+        bincode::serialize_into(&mut self.writer, &tick)?;
+        // Flushed every tick so that the recording survives a crash
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads a recording back, and steps a world instance through it
+pub struct Replay {
+    reader: BufReader<File>,
+    pub header: RecordingHeader,
+}
+impl Replay {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open recording {path:?}"))?;
+        let mut reader = BufReader::new(file);
+        let header = bincode::deserialize_from(&mut reader)
+            .with_context(|| format!("Invalid recording {path:?}"))?;
+        Ok(Self { reader, header })
+    }
+
+    fn next_tick(&mut self) -> anyhow::Result<Option<RecordedTick>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(bincode::deserialize_from(&mut self.reader)?))
+    }
+
+    /// Applies the recorded inputs of every tick to `instance` and steps it, failing at the first
+    /// tick whose result differs from the recording. `instance` must have been set up like the
+    /// recorded one: the same project, with entity ids seeded from the header.
+    ///
+    /// Returns the number of ticks replayed
+    pub fn run(mut self, instance: &mut WorldInstance) -> anyhow::Result<u64> {
+        if world_checksum(&instance.world) != self.header.checksum {
+            bail!("The world differs from the recording before the first tick; was it created from the same project?");
+        }
+        let mut stream = WorldStream::new(recording_filter());
+        let mut ticks = 0;
+        while let Some(tick) = self.next_tick()? {
+            tick.inputs.apply(&mut instance.world, Entity::new(), false);
+            instance.world.set_entity_id_sequence(tick.entity_ids);
+            stream.next_diff(&instance.world);
+
+            instance.step(tick.time);
+            let checksum = world_checksum(&instance.world);
+            if checksum != tick.checksum {
+                log::error!("Recorded changes: {}", tick.outputs);
+                log::error!("Replayed changes: {}", stream.next_diff(&instance.world));
+                bail!(
+                    "The replay diverged from the recording at tick {}",
+                    tick.tick
+                );
+            }
+            ticks += 1;
+        }
+        Ok(ticks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_hasher_is_stable() {
+        let mut hasher = StableHasher::default();
+        hasher.write(b"ambient");
+        assert_eq!(hasher.finish(), 0x3b91_1386_17fe_c2df);
+
+        // The length is hashed too, so that trailing zeros make a difference
+        let mut other = StableHasher::default();
+        other.write(b"ambient\0");
+        assert_ne!(other.finish(), hasher.finish());
+    }
+}
@@ -7,8 +7,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     server::{
-        create_player_entity_data, player_entity_stream, player_stats_stream, ForkingEvent, RpcArgs as ServerRpcArgs, WorldInstance,
-        MAIN_INSTANCE_ID,
+        create_player_entity_data, player_entity_stream, player_stats_stream, ForkingEvent, RpcArgs as ServerRpcArgs, ServerState,
+        WorldInstance, MAIN_INSTANCE_ID,
     },
     ServerWorldExt,
 };
@@ -35,10 +35,17 @@ pub struct RpcForkInstance {
 /// This clones the current world instance of the player, and returns the id to the new instance.
 pub async fn rpc_fork_instance(args: ServerRpcArgs, RpcForkInstance { resources, synced_res, id }: RpcForkInstance) -> String {
     let mut state = args.state.lock();
+    let instance_id = state.players.get(&args.user_id).unwrap().instance.clone();
+    fork_instance(&mut state, &instance_id, resources, synced_res, id)
+}
+
+/// Clones the instance `instance_id` without its players, and returns the id of the new instance.
+/// Nothing is cloned if there already is an instance with the id.
+pub fn fork_instance(state: &mut ServerState, instance_id: &str, resources: Entity, synced_res: Entity, id: Option<String>) -> String {
     let id = id.unwrap_or(friendly_id());
     if !state.instances.contains_key(&id) {
         let new_instance = {
-            let instance = state.get_player_world_instance(&args.user_id).unwrap();
+            let instance = state.instances.get(instance_id).unwrap();
             let mut world = instance.world.clone();
 
             for (id, _) in query(user_id()).collect_cloned(&world, None) {
@@ -58,11 +65,21 @@ pub async fn rpc_fork_instance(args: ServerRpcArgs, RpcForkInstance { resources,
     }
     id
 }
-pub async fn rpc_join_instance(args: ServerRpcArgs, new_instance_id: String) {
+/// Moves the player to another instance; the error explains why the player can't join it
+pub async fn rpc_join_instance(args: ServerRpcArgs, new_instance_id: String) -> Result<(), String> {
     let mut state = args.state.lock();
     let old_instance_id = state.players.get(&args.user_id).unwrap().instance.clone();
     if old_instance_id == new_instance_id {
-        return;
+        return Ok(());
+    }
+
+    let new_instance_players = match state.instances.get(&new_instance_id) {
+        Some(instance) => instance.player_count(),
+        None => return Err(format!("There is no instance {new_instance_id}")),
+    };
+    if !state.limits.has_instance_room(&args.user_id, new_instance_players) {
+        log::warn!("[{}] Can't join instance {new_instance_id}: it is full", args.user_id);
+        return Err(format!("Instance {new_instance_id} is full"));
     }
 
     let instances = &mut state.instances;
@@ -97,6 +114,7 @@ pub async fn rpc_join_instance(args: ServerRpcArgs, new_instance_id: String) {
     if old_player_count == 1 && old_instance_id != MAIN_INSTANCE_ID {
         state.remove_instance(&old_instance_id);
     }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     net::{IpAddr, Ipv4Addr, SocketAddr},
     ops::Range,
     sync::Arc,
@@ -12,8 +12,9 @@ use ambient_core::{
     project_name,
 };
 use ambient_ecs::{
-    components, dont_store, query, ArchetypeFilter, ComponentDesc, Entity, EntityId, FrameEvent,
-    Resource, System, SystemGroup, World, WorldStream, WorldStreamCompEvent, WorldStreamFilter,
+    components, dont_store, query, run_timings, ArchetypeFilter, ComponentDesc, Entity, EntityId,
+    FrameEvent, Resource, RunTimings, System, SystemGroup, World, WorldStream,
+    WorldStreamCompEvent, WorldStreamFilter,
 };
 use ambient_proxy::client::AllocatedEndpoint;
 use ambient_rpc::RpcRegistry;
@@ -26,11 +27,13 @@ use ambient_std::{
 use ambient_sys::time::{Instant, SystemTime};
 use anyhow::bail;
 use bytes::Bytes;
-use flume::Sender;
+use flume::{Receiver, Sender};
 use futures::StreamExt;
 use once_cell::sync::OnceCell;
 use parking_lot::{Mutex, RwLock};
 use quinn::{Endpoint, RecvStream, SendStream};
+use rand::{distributions::Alphanumeric, Rng};
+use rustls::Certificate;
 use tokio::{
     io::AsyncReadExt,
     time::{interval, MissedTickBehavior},
@@ -38,11 +41,17 @@ use tokio::{
 use tracing::{debug_span, Instrument};
 
 use crate::{
+    admin::{AdminConsole, BanCheck},
+    auth::{Authenticator, ConnectionRejection, InsecureAuthenticator},
     client_connection::ClientConnection,
     connection::Connection,
     create_server,
-    protocol::{ClientInfo, ServerInfo, ServerProtocol},
-    NetworkError, RPC_BISTREAM_ID,
+    limits::{JoinQueue, PlayerLimits},
+    metrics::{MetricsEndpoint, ServerMetrics},
+    protocol::{ClientInfo, ResumeRequest, ServerInfo, ServerProtocol, Session},
+    recording::Recorder,
+    tls::{CertFingerprint, ServerCertificate},
+    NetworkError, LEAVE_UNISTREAM_ID, PROXY_CERT, RPC_BISTREAM_ID,
 };
 use colored::Colorize;
 
@@ -144,12 +153,14 @@ impl WorldInstance {
     pub fn despawn_player(&mut self, user_id: &str) -> Option<Entity> {
         self.world.despawn(get_by_user_id(&self.world, user_id)?)
     }
-    pub fn broadcast_diffs(&mut self) {
+    /// Returns the size of the diff that was broadcast, in bytes; 0 if nothing changed
+    pub fn broadcast_diffs(&mut self) -> usize {
         let diff = self.world_stream.next_diff(&self.world);
         if diff.is_empty() {
-            return;
+            return 0;
         }
         let msg = bincode::serialize(&diff).unwrap();
+        let size = msg.len();
 
         ambient_profiling::scope!("Send MsgEntities");
         for (_, (entity_stream,)) in query((player_entity_stream(),)).iter(&self.world, None) {
@@ -158,6 +169,7 @@ impl WorldInstance {
                 log::warn!("Failed to broadcast diff to player");
             }
         }
+        size
     }
     pub fn player_count(&self) -> usize {
         query((player(),)).iter(&self.world, None).count()
@@ -173,10 +185,14 @@ impl WorldInstance {
 
 pub const MAIN_INSTANCE_ID: &str = "main";
 
+/// The time the server advances by every tick
+pub const SIM_TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
 pub struct Player {
     pub instance: String,
     pub abort_handle: Arc<OnceCell<tokio::task::JoinHandle<()>>>,
     pub connection_id: String,
+    pub session: PlayerSession,
 }
 
 impl Player {
@@ -189,6 +205,7 @@ impl Player {
             instance,
             abort_handle,
             connection_id,
+            session: Default::default(),
         }
     }
 
@@ -197,10 +214,115 @@ impl Player {
             instance,
             abort_handle: Arc::new(OnceCell::new()),
             connection_id: friendly_id(),
+            session: Default::default(),
         }
     }
 }
 
+/// What the server keeps of the session of a player to let it resume the session; see
+/// [`GameServer::resume_grace_period`]
+#[derive(Default)]
+pub struct PlayerSession {
+    /// `None` if the session can't be resumed
+    pub resume_token: Option<String>,
+    pub sent_diffs: Arc<Mutex<SentDiffs>>,
+    /// Set while the connection is lost
+    pub detached: Option<DetachedSession>,
+}
+impl PlayerSession {
+    fn new(resume_token: Option<String>, grace_period: Duration) -> Self {
+        Self {
+            resume_token,
+            sent_diffs: Arc::new(Mutex::new(SentDiffs::new(grace_period))),
+            detached: None,
+        }
+    }
+}
+
+/// The session of a player whose connection was lost
+pub struct DetachedSession {
+    pub since: Instant,
+    /// The diffs that were not sent before the connection was lost, and the ones broadcast since
+    pub diffs_rx: Receiver<Vec<u8>>,
+}
+
+/// Numbers the world diffs sent to a player, and keeps the recent ones so that they can be sent
+/// again if the connection is lost before the player received them.
+///
+/// At most [`SentDiffs::MAX_COUNT`] diffs and [`SentDiffs::MAX_SIZE`] bytes are kept; a player that
+/// missed more than that can't resume its session.
+///
+/// A numbered diff is sent as a bincode-encoded `(u64, WorldDiff)`.
+#[derive(Default)]
+pub struct SentDiffs {
+    next: u64,
+    keep_for: Duration,
+    sent: VecDeque<(u64, Instant, Vec<u8>)>,
+    /// The size of the messages in `sent`
+    size: usize,
+}
+impl SentDiffs {
+    pub const MAX_COUNT: usize = 10_000;
+    pub const MAX_SIZE: usize = 32 * 1024 * 1024;
+
+    pub fn new(keep_for: Duration) -> Self {
+        Self {
+            keep_for,
+            ..Default::default()
+        }
+    }
+    /// Numbers the serialized `diff`, and returns the message to send
+    pub fn push(&mut self, diff: &[u8]) -> Vec<u8> {
+        let seq = self.next;
+        self.next += 1;
+        let mut msg = bincode::serialize(&seq).unwrap();
+        msg.extend_from_slice(diff);
+
+        if !self.keep_for.is_zero() {
+            let now = Instant::now();
+            self.size += msg.len();
+            self.sent.push_back((seq, now, msg.clone()));
+            while let Some((_, sent_at, front)) = self.sent.front() {
+                if now.duration_since(*sent_at) <= self.keep_for
+                    && self.sent.len() <= Self::MAX_COUNT
+                    && self.size <= Self::MAX_SIZE
+                {
+                    break;
+                }
+                self.size -= front.len();
+                self.sent.pop_front();
+            }
+        }
+        msg
+    }
+    /// The messages sent after the diff `last`, or `None` if some of them are no longer kept
+    pub fn sent_after(&self, last: u64) -> Option<Vec<Vec<u8>>> {
+        // No diff is numbered `u64::MAX`, so `last` can't be one that was sent
+        let next_after = last.checked_add(1)?;
+        if next_after >= self.next {
+            return Some(Vec::new());
+        }
+        match self.sent.front() {
+            Some((first, _, _)) if *first <= next_after => Some(
+                self.sent
+                    .iter()
+                    .filter(|(seq, _, _)| *seq > last)
+                    .map(|(_, _, msg)| msg.clone())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+fn new_resume_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
 pub type SharedServerState = Arc<Mutex<ServerState>>;
 pub struct ServerState {
     pub instances: HashMap<String, WorldInstance>,
@@ -208,6 +330,18 @@ pub struct ServerState {
     pub create_server_systems: Arc<dyn Fn(&mut World) -> SystemGroup + Sync + Send>,
     pub create_on_forking_systems: Arc<dyn Fn() -> SystemGroup<ForkingEvent> + Sync + Send>,
     pub create_shutdown_systems: Arc<dyn Fn() -> SystemGroup<ShutdownEvent> + Sync + Send>,
+    /// Advance the time of the instances by [`SIM_TIME_STEP`] every tick instead of following
+    /// the wall clock
+    pub fixed_time_step: bool,
+    /// Records the ticks of the main instance
+    pub recorder: Option<Recorder>,
+    /// See [`GameServer::resume_grace_period`]
+    pub resume_grace_period: Duration,
+    /// See [`crate::metrics`]
+    pub metrics: ServerMetrics,
+    /// See [`GameServer::player_limits`]
+    pub limits: PlayerLimits,
+    pub join_queue: JoinQueue,
 }
 impl ServerState {
     pub fn new_local() -> Self {
@@ -227,6 +361,12 @@ impl ServerState {
             create_server_systems: Arc::new(|_| SystemGroup::new("", vec![])),
             create_on_forking_systems: Arc::new(|| SystemGroup::new("", vec![])),
             create_shutdown_systems: Arc::new(|| SystemGroup::new("", vec![])),
+            fixed_time_step: false,
+            recorder: None,
+            resume_grace_period: Duration::ZERO,
+            metrics: Default::default(),
+            limits: Default::default(),
+            join_queue: Default::default(),
         }
     }
     pub fn new(
@@ -241,25 +381,63 @@ impl ServerState {
             create_server_systems,
             create_on_forking_systems,
             create_shutdown_systems,
+            fixed_time_step: false,
+            recorder: None,
+            resume_grace_period: Duration::ZERO,
+            metrics: Default::default(),
+            limits: Default::default(),
+            join_queue: Default::default(),
         }
     }
 
     pub fn step(&mut self) {
-        let time = SystemTime::now()
+        let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap();
-        for instance in self.instances.values_mut() {
-            instance.step(time);
+        for (id, instance) in self.instances.iter_mut() {
+            let time = if self.fixed_time_step {
+                *instance.world.resource(ambient_core::time()) + SIM_TIME_STEP
+            } else {
+                now
+            };
+            match &mut self.recorder {
+                Some(recorder) if id == MAIN_INSTANCE_ID => {
+                    if let Err(err) = recorder.step(instance, time) {
+                        log::error!("Stopped recording: {err:?}");
+                        self.recorder = None;
+                    }
+                }
+                _ => instance.step(time),
+            }
         }
     }
     pub fn broadcast_diffs(&mut self) {
         for instance in self.instances.values_mut() {
-            instance.broadcast_diffs();
+            let size = instance.broadcast_diffs();
+            if size > 0 {
+                self.metrics.record_diff(size);
+            }
         }
     }
     pub fn player_count(&self) -> usize {
         self.instances.values().map(|i| i.player_count()).sum()
     }
+    /// Lets the player in if there is room for it in the main instance, or returns its position
+    /// in the join queue; see [`crate::limits`]
+    pub fn admit(&mut self, user_id: &str) -> Result<Option<usize>, ConnectionRejection> {
+        // The slot of a reconnecting player is still taken by its player
+        if self.players.contains_key(user_id) {
+            return Ok(None);
+        }
+        let admitted = self.join_queue.admitted();
+        let instance_players = self.instances.get(MAIN_INSTANCE_ID).unwrap().player_count();
+        let has_room = self.limits.has_room(
+            user_id,
+            self.players.len() + admitted,
+            instance_players + admitted,
+        );
+        self.join_queue.admit(&self.limits, user_id, has_room)
+    }
     pub fn get_player_world_instance_mut(&mut self, user_id: &str) -> Option<&mut WorldInstance> {
         self.players
             .get(user_id)
@@ -277,6 +455,37 @@ impl ServerState {
     pub fn get_player_world(&self, user_id: &str) -> Option<&World> {
         self.get_player_world_instance(user_id).map(|i| &i.world)
     }
+    /// Removes the player and despawns its entity
+    pub fn remove_player(&mut self, user_id: &str) {
+        if let Some(player) = self.players.remove(user_id) {
+            self.instances
+                .get_mut(&player.instance)
+                .unwrap()
+                .despawn_player(user_id);
+        }
+    }
+    /// Removes the players whose connection was lost longer than
+    /// [`ServerState::resume_grace_period`] ago
+    pub fn remove_expired_sessions(&mut self) {
+        let now = Instant::now();
+        let expired = self
+            .players
+            .iter()
+            .filter(|(_, player)| {
+                player
+                    .session
+                    .detached
+                    .as_ref()
+                    .map(|detached| now.duration_since(detached.since) > self.resume_grace_period)
+                    .unwrap_or(false)
+            })
+            .map(|(user_id, _)| user_id.clone())
+            .collect::<Vec<_>>();
+        for user_id in expired {
+            self.remove_player(&user_id);
+            log::info!("[{}] Disconnected (session expired)", user_id);
+        }
+    }
     pub fn remove_instance(&mut self, instance_id: &str) {
         log::debug!("Removing server instance id={}", instance_id);
         let mut sys = (self.create_shutdown_systems)();
@@ -299,33 +508,64 @@ pub struct GameServer {
     pub port: u16,
     /// Shuts down the server if there are no players
     pub use_inactivity_shutdown: bool,
+    /// See [`ServerState::fixed_time_step`]
+    pub fixed_time_step: bool,
+    /// Records the ticks of the main instance; see [`crate::recording`]
+    pub recorder: Option<Recorder>,
+    /// Validates the credentials of the players joining; defaults to [`InsecureAuthenticator`]
+    pub authenticator: Arc<dyn Authenticator>,
+    /// How long the session of a player whose connection was lost is kept, so that the player can
+    /// resume it and keep its player entity. Zero disables resuming sessions.
+    pub resume_grace_period: Duration,
+    /// Runs the admin commands against the server once it runs, and keeps the banned players out
+    pub admin: AdminConsole,
+    /// Renders the metrics of the server once it runs; see [`crate::metrics`]
+    pub metrics: MetricsEndpoint,
+    /// How many players can join, and how many can wait in the join queue; see [`crate::limits`]
+    pub player_limits: PlayerLimits,
     proxy_settings: Option<ProxySettings>,
 }
 impl GameServer {
     pub async fn new_with_port(
         port: u16,
+        certificate: &ServerCertificate,
         use_inactivity_shutdown: bool,
         proxy_settings: Option<ProxySettings>,
     ) -> anyhow::Result<Self> {
         let server_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
 
-        let endpoint = create_server(server_addr)?;
+        let endpoint = create_server(server_addr, certificate)?;
 
         log::debug!("GameServer listening on port {}", port);
         Ok(Self {
             endpoint,
             port,
             use_inactivity_shutdown,
+            fixed_time_step: false,
+            recorder: None,
+            authenticator: Arc::new(InsecureAuthenticator),
+            resume_grace_period: Duration::from_secs(30),
+            admin: AdminConsole::default(),
+            metrics: MetricsEndpoint::default(),
+            player_limits: PlayerLimits::default(),
             proxy_settings,
         })
     }
     pub async fn new_with_port_in_range(
         port_range: Range<u16>,
+        certificate: &ServerCertificate,
         use_inactivity_shutdown: bool,
         proxy_settings: Option<ProxySettings>,
     ) -> anyhow::Result<Self> {
         for port in port_range {
-            match Self::new_with_port(port, use_inactivity_shutdown, proxy_settings.clone()).await {
+            match Self::new_with_port(
+                port,
+                certificate,
+                use_inactivity_shutdown,
+                proxy_settings.clone(),
+            )
+            .await
+            {
                 Ok(server) => {
                     return Ok(server);
                 }
@@ -348,12 +588,22 @@ impl GameServer {
         let Self {
             endpoint,
             proxy_settings,
+            fixed_time_step,
+            recorder,
+            authenticator,
+            resume_grace_period,
+            admin,
+            metrics,
+            player_limits,
             ..
         } = self;
+        let authenticator: Arc<dyn Authenticator> =
+            Arc::new(BanCheck::new(authenticator, admin.bans().clone()));
         let assets = world.resource(asset_cache()).clone();
+        world.add_resource(run_timings(), RunTimings::default());
         let world_stream_filter =
             WorldStreamFilter::new(ArchetypeFilter::new().excl(no_sync()), is_sync_component);
-        let state = Arc::new(Mutex::new(ServerState::new(
+        let mut state = ServerState::new(
             [(
                 MAIN_INSTANCE_ID.to_string(),
                 WorldInstance {
@@ -367,10 +617,17 @@ impl GameServer {
             create_server_systems,
             create_on_forking_systems,
             create_shutdown_systems,
-        )));
+        );
+        state.fixed_time_step = fixed_time_step;
+        state.recorder = recorder;
+        state.resume_grace_period = resume_grace_period;
+        state.limits = player_limits;
+        let state = Arc::new(Mutex::new(state));
+        admin.attach(state.clone());
+        metrics.attach(state.clone());
 
         let mut fps_counter = FpsCounter::new();
-        let mut sim_interval = interval(Duration::from_secs_f32(1. / 60.));
+        let mut sim_interval = interval(SIM_TIME_STEP);
         sim_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         let mut inactivity_interval = interval(Duration::from_secs_f32(5.));
@@ -381,6 +638,7 @@ impl GameServer {
             let state = state.clone();
             let world_stream_filter = world_stream_filter.clone();
             let assets = assets.clone();
+            let authenticator = authenticator.clone();
             tokio::spawn(async move {
                 start_proxy_connection(
                     endpoint.clone(),
@@ -388,6 +646,7 @@ impl GameServer {
                     state.clone(),
                     world_stream_filter.clone(),
                     assets.clone(),
+                    authenticator,
                 )
                 .await;
             });
@@ -409,7 +668,7 @@ impl GameServer {
 
 
                     log::debug!("Accepted connection");
-                    run_connection(conn.into(), state.clone(), world_stream_filter.clone(), assets.clone(), ServerBaseUrlKey.get(&assets));
+                    run_connection(conn.into(), state.clone(), world_stream_filter.clone(), assets.clone(), ServerBaseUrlKey.get(&assets), authenticator.clone());
                 }
                 _ = sim_interval.tick() => {
                     fps_counter.frame_start();
@@ -417,8 +676,11 @@ impl GameServer {
                     tokio::task::block_in_place(|| {
                         ambient_profiling::finish_frame!();
                         ambient_profiling::scope!("sim_tick");
+                        let tick_start = Instant::now();
                         state.step();
                         state.broadcast_diffs();
+                        state.metrics.record_tick(tick_start.elapsed());
+                        state.remove_expired_sessions();
                         if let Some(sample) = fps_counter.frame_end() {
                             for instance in state.instances.values() {
                                 for (_, (stream,)) in query((player_stats_stream(),)).iter(&instance.world, None) {
@@ -464,9 +726,11 @@ async fn start_proxy_connection(
     state: Arc<Mutex<ServerState>>,
     world_stream_filter: WorldStreamFilter,
     assets: AssetCache,
+    authenticator: Arc<dyn Authenticator>,
 ) {
     // start with content base url being the same as for direct connections
     let content_base_url = Arc::new(RwLock::new(ServerBaseUrlKey.get(&assets)));
+    let serves_assets = content_base_url.read().asset_server_path().is_some();
 
     let on_endpoint_allocated = {
         let content_base_url = content_base_url.clone();
@@ -480,11 +744,18 @@ async fn start_proxy_connection(
                   }: AllocatedEndpoint| {
                 log::debug!("Allocated proxy endpoint. Allocation id: {}", id);
                 log::info!("Proxy sees this server as {}", external_endpoint);
+                // Players connect to the proxy, so they verify its certificate
+                let proxy_fingerprint = CertFingerprint::of(&Certificate(PROXY_CERT.to_vec()));
                 log::info!(
                     "Proxy allocated an endpoint, use `{}` to join",
-                    format!("ambient join {}", allocated_endpoint).bright_green()
+                    format!("ambient join {allocated_endpoint} --pin {proxy_fingerprint}")
+                        .bright_green()
                 );
 
+                // The files served over the game connection go through the proxy with it
+                if serves_assets {
+                    return;
+                }
                 // set the content base url to point to proxy provided value
                 match AbsAssetUrl::parse(&assets_root) {
                     Ok(url) => {
@@ -511,6 +782,7 @@ async fn start_proxy_connection(
                     world_stream_filter.clone(),
                     assets.clone(),
                     content_base_url.read().clone(),
+                    authenticator.clone(),
                 );
             },
         )
@@ -565,6 +837,7 @@ fn run_connection(
     world_stream_filter: WorldStreamFilter,
     assets: AssetCache,
     content_base_url: AbsAssetUrl,
+    authenticator: Arc<dyn Authenticator>,
 ) {
     let connection_id = friendly_id();
     let handle = Arc::new(OnceCell::new());
@@ -577,10 +850,115 @@ fn run_connection(
 
                 let new_player_connection = connection.clone();
 
+                // The diffs the client missed, if it resumes its session
+                let missed_diffs = Mutex::new(Vec::new());
+
+                let start_session = |user_id: &str, resume: Option<&ResumeRequest>| {
+                    let mut state = state.lock();
+                    let state = &mut *state;
+                    let grace_period = state.resume_grace_period;
+                    if grace_period.is_zero() {
+                        return Session::default();
+                    }
+                    let resumed = match (state.players.get_mut(user_id), resume) {
+                        (Some(player), Some(resume))
+                            if player.session.resume_token.as_ref() == Some(&resume.token) =>
+                        {
+                            let sent_after = player
+                                .session
+                                .sent_diffs
+                                .lock()
+                                .sent_after(resume.last_diff);
+                            let spawned = state
+                                .instances
+                                .get(&player.instance)
+                                .map_or(false, |instance| {
+                                    get_by_user_id(&instance.world, user_id).is_some()
+                                });
+                            match (&mut player.session.detached, sent_after) {
+                                (Some(_), _) if !spawned => {
+                                    log::info!(
+                                        "[{}] Can't resume the session, the player was despawned",
+                                        user_id
+                                    );
+                                    false
+                                }
+                                (Some(detached), Some(sent_after)) => {
+                                    *missed_diffs.lock() = sent_after;
+                                    // Don't let the session expire before the client is initialized
+                                    detached.since = Instant::now();
+                                    true
+                                }
+                                (Some(_), None) => {
+                                    log::info!(
+                                        "[{}] Can't resume the session, missed diffs were dropped",
+                                        user_id
+                                    );
+                                    false
+                                }
+                                (None, _) => false,
+                            }
+                        }
+                        _ => false,
+                    };
+                    Session {
+                        resume_token: Some(new_resume_token()),
+                        grace_period,
+                        resumed,
+                    }
+                };
+
+                let admit = |user_id: &str| state.lock().admit(user_id);
+                let release = |user_id: &str| state.lock().join_queue.joined(user_id);
+
                 let on_init = |client: ClientInfo| {
                     let user_id = &client.user_id;
                     log::debug!("[{}] Locking world", user_id);
                     let mut state = state.lock();
+                    let grace_period = state.resume_grace_period;
+                    state.join_queue.joined(user_id);
+
+                    if client.session.resumed {
+                        let state = &mut *state;
+                        let player = match state.players.get_mut(user_id) {
+                            Some(player) => player,
+                            None => return None,
+                        };
+                        let instance = state.instances.get_mut(&player.instance).unwrap();
+                        // The player entity can be despawned after the session was accepted; the
+                        // client then starts a new session when it reconnects
+                        let entity = get_by_user_id(&instance.world, user_id)?;
+                        let detached = player.session.detached.take()?;
+                        player.abort_handle = handle.clone();
+                        player.connection_id = connection_id.clone();
+                        player.session.resume_token = client.session.resume_token.clone();
+                        let sent_diffs = player.session.sent_diffs.clone();
+
+                        // Send what was broadcast while the connection was lost,
+                        // and switch the player over to the new streams
+                        instance.broadcast_diffs();
+                        for diff in detached.diffs_rx.drain() {
+                            log_result!(diffs_tx.send(diff));
+                        }
+                        instance
+                            .world
+                            .set(entity, player_entity_stream(), diffs_tx.clone())
+                            .unwrap();
+                        instance
+                            .world
+                            .set(entity, player_stats_stream(), stats_tx.clone())
+                            .unwrap();
+                        instance
+                            .world
+                            .set(entity, player_connection(), new_player_connection.clone())
+                            .unwrap();
+                        log::info!("[{}] Player resumed its session", user_id);
+                        return Some((sent_diffs, std::mem::take(&mut *missed_diffs.lock())));
+                    }
+
+                    let session =
+                        PlayerSession::new(client.session.resume_token.clone(), grace_period);
+                    let sent_diffs = session.sent_diffs.clone();
                     // If there's an old player
                     let reconnecting = if let Some(player) = state.players.get_mut(user_id) {
                         if let Some(handle) = player.abort_handle.get() {
@@ -588,17 +966,17 @@ fn run_connection(
                         }
                         player.abort_handle = handle.clone();
                         player.connection_id = connection_id.clone();
+                        player.session = session;
                         log::debug!("[{}] Player reconnecting", user_id);
                         true
                     } else {
-                        state.players.insert(
-                            user_id.clone(),
-                            Player {
-                                instance: MAIN_INSTANCE_ID.to_string(),
-                                abort_handle: handle.clone(),
-                                connection_id: connection_id.clone(),
-                            },
+                        let mut player = Player::new(
+                            MAIN_INSTANCE_ID.to_string(),
+                            handle.clone(),
+                            connection_id.clone(),
                         );
+                        player.session = session;
+                        state.players.insert(user_id.clone(), player);
                         false
                     };
 
@@ -615,14 +993,11 @@ fn run_connection(
                     log_result!(diffs_tx.send(diff));
                     log::debug!("[{}] Init diff sent", user_id);
 
-                    if !reconnecting {
-                        instance.spawn_player(
-                            create_player_entity_data(user_id, diffs_tx.clone(), stats_tx.clone())
-                                .with(player_connection(), new_player_connection.clone()),
-                        );
-                        log::info!("[{}] Player spawned", user_id);
-                    } else {
-                        let entity = get_by_user_id(&instance.world, user_id).unwrap();
+                    // The entity of a reconnecting player can have been despawned in the meantime
+                    let entity = reconnecting
+                        .then(|| get_by_user_id(&instance.world, user_id))
+                        .flatten();
+                    if let Some(entity) = entity {
                         instance
                             .world
                             .set(entity, player_entity_stream(), diffs_tx.clone())
@@ -636,10 +1011,17 @@ fn run_connection(
                             .set(entity, player_connection(), new_player_connection.clone())
                             .unwrap();
                         log::info!("[{}] Player reconnected", user_id);
+                    } else {
+                        instance.spawn_player(
+                            create_player_entity_data(user_id, diffs_tx.clone(), stats_tx.clone())
+                                .with(player_connection(), new_player_connection.clone()),
+                        );
+                        log::info!("[{}] Player spawned", user_id);
                     }
+                    Some((sent_diffs, Vec::new()))
                 };
 
-                let on_disconnect = |user_id: &Option<String>| {
+                let on_disconnect = |user_id: &Option<String>, lost: Option<Receiver<Vec<u8>>>| {
                     if let Some(user_id) = user_id {
                         log::debug!("[{}] Disconnecting", user_id);
                         let mut state = state.lock();
@@ -652,13 +1034,25 @@ fn run_connection(
                             log::info!("[{}] Disconnected (reconnection)", user_id);
                             return;
                         }
-                        if let Some(player) = state.players.remove(user_id) {
-                            state
-                                .instances
-                                .get_mut(&player.instance)
-                                .unwrap()
-                                .despawn_player(user_id);
+                        let grace_period = state.resume_grace_period;
+                        let player = state.players.get_mut(user_id);
+                        if let (Some(player), Some(diffs_rx)) = (player, lost) {
+                            if player.session.resume_token.is_some() {
+                                // Keep the player, and the diffs it doesn't receive, in case it
+                                // resumes the session
+                                player.session.detached = Some(DetachedSession {
+                                    since: Instant::now(),
+                                    diffs_rx,
+                                });
+                                log::info!(
+                                    "[{}] Connection lost; the session can be resumed for {:?}",
+                                    user_id,
+                                    grace_period
+                                );
+                                return;
+                            }
                         }
+                        state.remove_player(user_id);
 
                         log::info!("[{}] Disconnected", user_id);
                     }
@@ -741,12 +1135,16 @@ fn run_connection(
                 let client = ClientInstance {
                     diffs_rx,
                     stats_rx,
+                    admit: &admit,
+                    release: &release,
+                    start_session: &start_session,
                     on_init: &on_init,
                     on_bi_stream: &on_bi_stream,
                     on_uni_stream: &on_uni_stream,
                     on_datagram: &on_datagram,
                     on_disconnect: &on_disconnect,
                     user_id: None,
+                    connection_lost: false,
                 };
 
                 let server_info = {
@@ -760,8 +1158,11 @@ fn run_connection(
                     }
                 };
 
-                match client.run(connection, server_info).await {
+                match client.run(connection, server_info, &*authenticator).await {
                     Ok(()) => {}
+                    Err(NetworkError::ConnectionRejected(rejection)) => {
+                        log::warn!("Rejected connection: {rejection}");
+                    }
                     Err(err) if err.is_closed() => {
                         log::info!("Connection closed by client");
                     }
@@ -782,24 +1183,36 @@ fn run_connection(
         .expect("Player handle set twice");
 }
 
+/// The numbering of the diffs of the session, and the diffs the client missed if it resumed the
+/// session
+type SessionStart = (Arc<Mutex<SentDiffs>>, Vec<Vec<u8>>);
+
 /// Manages the server side client communication
 struct ClientInstance<'a> {
     diffs_rx: flume::Receiver<Vec<u8>>,
     stats_rx: flume::Receiver<FpsSample>,
 
-    on_init: &'a (dyn Fn(ClientInfo) + Send + Sync),
+    admit: &'a (dyn Fn(&str) -> Result<Option<usize>, ConnectionRejection> + Send + Sync),
+    /// Frees the slot of a player that was let in, but didn't get to join
+    release: &'a (dyn Fn(&str) + Send + Sync),
+    start_session: &'a (dyn Fn(&str, Option<&ResumeRequest>) -> Session + Send + Sync),
+    /// Returns `None` if the session could not be resumed after all
+    on_init: &'a (dyn Fn(ClientInfo) -> Option<SessionStart> + Send + Sync),
     on_datagram: &'a (dyn Fn(&String, u32, Bytes) + Send + Sync),
     on_bi_stream: &'a (dyn Fn(&String, u32, SendStream, RecvStream) + Send + Sync),
     on_uni_stream: &'a (dyn Fn(&String, u32, RecvStream) + Send + Sync),
-    on_disconnect: &'a (dyn Fn(&Option<String>) + Send + Sync),
+    /// Called with the diffs that were not sent yet if the connection was lost, rather than closed
+    on_disconnect: &'a (dyn Fn(&Option<String>, Option<Receiver<Vec<u8>>>) + Send + Sync),
     user_id: Option<String>,
+    connection_lost: bool,
 }
 
 impl<'a> Drop for ClientInstance<'a> {
     fn drop(&mut self) {
         log::debug!("Closed server-side connection for {:?}", self.user_id);
+        let lost = self.connection_lost.then(|| self.diffs_rx.clone());
         tokio::task::block_in_place(|| {
-            (self.on_disconnect)(&self.user_id);
+            (self.on_disconnect)(&self.user_id, lost);
         })
     }
 }
@@ -810,24 +1223,62 @@ impl<'a> ClientInstance<'a> {
         mut self,
         conn: ClientConnection,
         server_info: ServerInfo,
+        authenticator: &dyn Authenticator,
+    ) -> Result<(), NetworkError> {
+        let result = self.serve(conn.clone(), server_info, authenticator).await;
+        if let Err(err) = &result {
+            self.connection_lost = !err.is_closed() && !conn.closed_by_peer();
+        }
+        result
+    }
+
+    async fn serve(
+        &mut self,
+        conn: ClientConnection,
+        server_info: ServerInfo,
+        authenticator: &dyn Authenticator,
     ) -> Result<(), NetworkError> {
         log::debug!("Connecting to client");
-        let mut proto = ServerProtocol::new(conn, server_info).await?;
+        let admitted = Mutex::new(None);
+        let admit = |user_id: &str| {
+            let result = (self.admit)(user_id);
+            if let Ok(None) = result {
+                *admitted.lock() = Some(user_id.to_string());
+            }
+            result
+        };
+        let proto =
+            ServerProtocol::new(conn, server_info, authenticator, &admit, self.start_session).await;
+        let mut proto = match proto {
+            Ok(proto) => proto,
+            Err(err) => {
+                // Don't keep the slot of the player until the admission times out
+                if let Some(user_id) = admitted.into_inner() {
+                    tokio::task::block_in_place(|| (self.release)(&user_id));
+                }
+                return Err(err);
+            }
+        };
 
         log::debug!("Client loop starting");
         let mut entities_rx = self.diffs_rx.stream();
         let mut stats_rx = self.stats_rx.stream();
 
-        tokio::task::block_in_place(|| {
-            (self.on_init)(proto.client_info().clone());
-        });
+        let (sent_diffs, missed_diffs) =
+            tokio::task::block_in_place(|| (self.on_init)(proto.client_info().clone()))
+                .ok_or(NetworkError::ConnectionClosed)?;
         let user_id = proto.client_info().user_id.clone();
         self.user_id = Some(user_id.clone());
 
+        for msg in missed_diffs {
+            proto.diff_stream.send_bytes(msg).await?;
+        }
+
         loop {
             tokio::select! {
                 Some(msg) = entities_rx.next() => {
                     let span = tracing::debug_span!("world diff");
+                    let msg = sent_diffs.lock().push(&msg);
                     proto.diff_stream.send_bytes(msg).instrument(span).await?;
                 }
                 Some(msg) = stats_rx.next() => {
@@ -851,6 +1302,11 @@ impl<'a> ClientInstance<'a> {
                 Ok(mut rx) = proto.conn.accept_uni() => {
                     let span = tracing::debug_span!("unistream");
                     let stream_id = rx.read_u32().instrument(span).await;
+                    // The client is leaving, so the connection is not lost even if it looks like it
+                    if let Ok(LEAVE_UNISTREAM_ID) = stream_id {
+                        log::info!("[{user_id}] Left the server");
+                        return Ok(());
+                    }
                     if let Ok(stream_id) = stream_id {
                         tokio::task::block_in_place(|| { (self.on_uni_stream)(&user_id, stream_id,  rx); })
                     }
@@ -859,3 +1315,63 @@ impl<'a> ClientInstance<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seqs(msgs: &[Vec<u8>]) -> Vec<u64> {
+        msgs.iter()
+            .map(|msg| bincode::deserialize(msg).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sent_diffs_are_numbered_and_kept() {
+        let mut diffs = SentDiffs::new(Duration::from_secs(60));
+        for i in 0..5u8 {
+            let msg = diffs.push(&[i]);
+            assert_eq!(msg.last(), Some(&i));
+        }
+        assert_eq!(seqs(&diffs.sent_after(1).unwrap()), vec![2, 3, 4]);
+        // The client got everything
+        assert_eq!(diffs.sent_after(4).unwrap(), Vec::<Vec<u8>>::new());
+        assert_eq!(diffs.sent_after(10).unwrap(), Vec::<Vec<u8>>::new());
+        // A diff that can't have been sent
+        assert!(diffs.sent_after(u64::MAX).is_none());
+    }
+
+    #[test]
+    fn sent_diffs_are_not_kept_without_grace_period() {
+        let mut diffs = SentDiffs::new(Duration::ZERO);
+        diffs.push(&[0]);
+        diffs.push(&[1]);
+        assert_eq!(diffs.sent_after(1).unwrap(), Vec::<Vec<u8>>::new());
+        assert!(diffs.sent_after(0).is_none());
+    }
+
+    #[test]
+    fn sent_diffs_are_capped() {
+        let mut diffs = SentDiffs::new(Duration::from_secs(60));
+        for _ in 0..SentDiffs::MAX_COUNT + 10 {
+            diffs.push(&[]);
+        }
+        assert_eq!(diffs.sent.len(), SentDiffs::MAX_COUNT);
+        // The oldest diffs are gone, so a client that missed them can't resume
+        assert!(diffs.sent_after(5).is_none());
+        assert_eq!(
+            diffs.sent_after(10).unwrap().len(),
+            SentDiffs::MAX_COUNT - 1
+        );
+
+        let mut diffs = SentDiffs::new(Duration::from_secs(60));
+        let big = vec![0; SentDiffs::MAX_SIZE / 2];
+        for _ in 0..3 {
+            diffs.push(&big);
+        }
+        assert_eq!(diffs.sent.len(), 1);
+        assert!(diffs.size <= SentDiffs::MAX_SIZE);
+        assert!(diffs.sent_after(0).is_none());
+        assert_eq!(seqs(&diffs.sent_after(1).unwrap()), vec![2]);
+    }
+}
@@ -0,0 +1,214 @@
+//! The TLS certificates of the QUIC connections between the clients and the server.
+//!
+//! The server presents a [`ServerCertificate`], either loaded from files (for instance one issued by a certificate authority
+//! for the host name of the server), or generated when the server starts. The client verifies it according to its
+//! [`ServerVerification`]: against the root certificates of the system, against a pinned [`CertFingerprint`] (which is what
+//! servers with a generated certificate print when they start), or not at all.
+use std::{fmt, fs::File, io::BufReader, path::Path, str::FromStr, sync::Arc, time::SystemTime};
+
+use anyhow::Context;
+use ring::digest;
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, PrivateKey, RootCertStore, ServerName,
+};
+
+/// The SHA-256 hash of a certificate. Written as colon-separated hexadecimal bytes, like `AB:CD:...`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CertFingerprint([u8; 32]);
+impl CertFingerprint {
+    pub fn of(cert: &Certificate) -> Self {
+        let mut fingerprint = [0; 32];
+        fingerprint.copy_from_slice(digest::digest(&digest::SHA256, &cert.0).as_ref());
+        Self(fingerprint)
+    }
+}
+impl fmt::Display for CertFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Debug for CertFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CertFingerprint({self})")
+    }
+}
+impl FromStr for CertFingerprint {
+    type Err = anyhow::Error;
+
+    /// Parses hexadecimal bytes, with or without colons between them
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().replace(':', "");
+        anyhow::ensure!(
+            hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            "A certificate fingerprint is 32 hexadecimal bytes, got {s:?}"
+        );
+        let mut fingerprint = [0; 32];
+        for (i, byte) in fingerprint.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).with_context(|| format!("Invalid certificate fingerprint {s:?}"))?;
+        }
+        Ok(Self(fingerprint))
+    }
+}
+
+/// The certificate chain and private key the server presents to the clients.
+#[derive(Debug, Clone)]
+pub struct ServerCertificate {
+    pub cert_chain: Vec<Certificate>,
+    pub key: PrivateKey,
+}
+impl ServerCertificate {
+    /// Generates a self-signed certificate for `names`. Clients can't verify it against their root certificates, so they
+    /// need to pin its [`ServerCertificate::fingerprint`].
+    pub fn self_signed(names: Vec<String>) -> anyhow::Result<Self> {
+        let cert = rcgen::generate_simple_self_signed(names).context("Failed to generate a certificate")?;
+        Ok(Self {
+            cert_chain: vec![Certificate(cert.serialize_der().context("Failed to serialize the certificate")?)],
+            key: PrivateKey(cert.serialize_private_key_der()),
+        })
+    }
+
+    /// Loads a certificate chain and its private key, each either PEM or DER encoded.
+    pub fn from_files(cert_path: &Path, key_path: &Path) -> anyhow::Result<Self> {
+        let cert_chain = read_pem_or_der(cert_path, |item| match item {
+            rustls_pemfile::Item::X509Certificate(cert) => Some(cert),
+            _ => None,
+        })
+        .with_context(|| format!("Failed to read the certificate {cert_path:?}"))?;
+        let key = read_pem_or_der(key_path, |item| match item {
+            rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .with_context(|| format!("Failed to read the private key {key_path:?}"))?;
+
+        anyhow::ensure!(!cert_chain.is_empty(), "No certificate found in {cert_path:?}");
+        let key = key.into_iter().next().with_context(|| format!("No private key found in {key_path:?}"))?;
+        Ok(Self { cert_chain: cert_chain.into_iter().map(Certificate).collect(), key: PrivateKey(key) })
+    }
+
+    /// The fingerprint of the certificate of the server, which clients can pin
+    pub fn fingerprint(&self) -> CertFingerprint {
+        CertFingerprint::of(&self.cert_chain[0])
+    }
+}
+
+/// Reads the items of a PEM file that `filter` keeps, or the whole file if it is not PEM encoded (assuming it is DER)
+fn read_pem_or_der(path: &Path, filter: impl Fn(rustls_pemfile::Item) -> Option<Vec<u8>>) -> anyhow::Result<Vec<Vec<u8>>> {
+    let items = rustls_pemfile::read_all(&mut BufReader::new(File::open(path)?))?;
+    if items.is_empty() {
+        Ok(vec![std::fs::read(path)?])
+    } else {
+        Ok(items.into_iter().filter_map(filter).collect())
+    }
+}
+
+/// How the client verifies the certificate of the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerVerification {
+    /// Verify the certificate against the root certificates of the system; the certificate has to be issued for the host
+    /// name the client connects to
+    SystemRoots,
+    /// Only accept the certificate with this fingerprint
+    Pinned(CertFingerprint),
+    /// Accept any certificate. The connection can then be intercepted, so this should only be used for development.
+    Insecure,
+}
+impl ServerVerification {
+    /// Whether the certificate has to be issued for the host name the client connects to
+    pub fn checks_server_name(&self) -> bool {
+        matches!(self, ServerVerification::SystemRoots)
+    }
+
+    pub(crate) fn client_crypto(&self) -> anyhow::Result<rustls::ClientConfig> {
+        let builder = rustls::ClientConfig::builder().with_safe_defaults();
+        Ok(match self {
+            ServerVerification::SystemRoots => {
+                let mut roots = RootCertStore::empty();
+                let certs = rustls_native_certs::load_native_certs().context("Failed to load the root certificates of the system")?;
+                let (_, ignored) = roots.add_parsable_certificates(&certs.into_iter().map(|cert| cert.0).collect::<Vec<_>>());
+                if ignored > 0 {
+                    log::debug!("Ignored {ignored} root certificates of the system that could not be parsed");
+                }
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            ServerVerification::Pinned(fingerprint) => {
+                builder.with_custom_certificate_verifier(Arc::new(PinnedVerifier(*fingerprint))).with_no_client_auth()
+            }
+            ServerVerification::Insecure => builder.with_custom_certificate_verifier(Arc::new(InsecureVerifier)).with_no_client_auth(),
+        })
+    }
+}
+
+/// Accepts the certificate with the pinned fingerprint, whoever it was issued by or for.
+///
+/// The handshake signatures are still verified, so the server has to hold the private key of the certificate.
+struct PinnedVerifier(CertFingerprint);
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = CertFingerprint::of(end_entity);
+        if fingerprint == self.0 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificateData(format!(
+                "The certificate of the server has the fingerprint {fingerprint}, but {} was pinned",
+                self.0
+            )))
+        }
+    }
+}
+
+struct InsecureVerifier;
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints_round_trip() {
+        let fingerprint = CertFingerprint::of(&Certificate(b"certificate".to_vec()));
+        let text = fingerprint.to_string();
+        assert_eq!(text.len(), 32 * 3 - 1);
+        assert_eq!(text.parse::<CertFingerprint>().unwrap(), fingerprint);
+        assert_eq!(text.replace(':', "").to_lowercase().parse::<CertFingerprint>().unwrap(), fingerprint);
+        assert_eq!(format!("  {text}\n").parse::<CertFingerprint>().unwrap(), fingerprint);
+    }
+
+    #[test]
+    fn invalid_fingerprints_are_rejected() {
+        let hex = "ab".repeat(32);
+        assert!(hex.parse::<CertFingerprint>().is_ok());
+        assert!(hex[2..].parse::<CertFingerprint>().is_err());
+        assert!(format!("{hex}ab").parse::<CertFingerprint>().is_err());
+        assert!(format!("+b{}", &hex[2..]).parse::<CertFingerprint>().is_err());
+        assert!(format!("xy{}", &hex[2..]).parse::<CertFingerprint>().is_err());
+        assert!(format!("é{}", &hex[2..]).parse::<CertFingerprint>().is_err());
+        assert!("".parse::<CertFingerprint>().is_err());
+    }
+}
@@ -23,20 +23,22 @@ use futures::future::try_join_all;
 use glam::{vec3, Mat4, Quat, Vec3};
 use itertools::Itertools;
 use physxx::{
-    AsPxActor, AsPxRigidActor, PxActor, PxActorFlag, PxBase, PxBoxGeometry, PxControllerDesc,
-    PxControllerShapeDesc, PxConvexMeshGeometry, PxGeometry, PxMaterial, PxMeshScale,
-    PxPlaneGeometry, PxRigidActor, PxRigidBody, PxRigidBodyFlag, PxRigidDynamicRef,
-    PxRigidStaticRef, PxShape, PxShapeFlag, PxSphereGeometry, PxTransform, PxTriangleMeshGeometry,
-    PxUserData,
+    AsPxActor, AsPxRigidActor, PxActor, PxActorFlag, PxBase, PxBoxGeometry, PxCapsuleGeometry,
+    PxControllerDesc, PxControllerShapeDesc, PxConvexFlag, PxConvexMesh, PxConvexMeshDesc,
+    PxConvexMeshGeometry, PxGeometry, PxMaterial, PxMeshScale, PxPlaneGeometry, PxRigidActor,
+    PxRigidBody, PxRigidBodyFlag, PxRigidDynamicRef, PxRigidStaticRef, PxShape, PxShapeFlag,
+    PxSphereGeometry, PxTransform, PxTriangleMeshGeometry, PxUserData,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    deterministic_physics,
+    layers::apply_collision_layer,
     main_controller_manager, make_physics_static,
     mesh::{PhysxGeometry, PhysxGeometryFromUrl},
     physx::{
         angular_velocity, character_controller, contact_offset, linear_velocity, physics,
-        physics_controlled, physics_shape, rest_offset, rigid_actor, Physics,
+        physics_controlled, physics_shape, rest_offset, rigid_actor, Physics, PhysicsKey,
     },
     wood_physics_material, ColliderScene, PxActorUserData, PxShapeUserData, PxWoodMaterialKey,
 };
@@ -144,6 +146,28 @@ pub fn server_systems() -> SystemGroup {
                         .unwrap();
                 }
             }),
+            query(capsule_collider().changed()).to_system(|q, world, qs, _| {
+                for (id, capsule) in changed_or_missing(q, world, qs, collider()) {
+                    world
+                        .add_component(
+                            id,
+                            collider(),
+                            ColliderDef::Capsule {
+                                radius: capsule.x,
+                                height: capsule.y,
+                                center: Vec3::ZERO,
+                            },
+                        )
+                        .unwrap();
+                }
+            }),
+            query(convex_collider().changed()).to_system(|q, world, qs, _| {
+                for (id, points) in changed_or_missing(q, world, qs, collider()) {
+                    world
+                        .add_component(id, collider(), ColliderDef::Convex { points })
+                        .unwrap();
+                }
+            }),
             query(collider_from_url().changed()).to_system(|q, world, qs, _| {
                 for (id, url) in changed_or_missing(q, world, qs, collider()) {
                     tracing::debug!("Loading collider: {url:#?}");
@@ -183,6 +207,16 @@ pub fn server_systems() -> SystemGroup {
                         .unwrap();
                 }
             }),
+            query(())
+                .incl(trigger_area())
+                .spawned()
+                .to_system(|q, world, qs, _| {
+                    for (id, _) in q.collect_cloned(world, qs) {
+                        world
+                            .add_component(id, collider_type(), ColliderType::TriggerArea)
+                            .unwrap();
+                    }
+                }),
             query((
                 character_controller_height().changed(),
                 character_controller_radius().changed(),
@@ -219,6 +253,7 @@ pub fn server_systems() -> SystemGroup {
                             density: 1.,
                             ..Default::default()
                         });
+                        apply_collision_layer(world, id, &actor.get_shapes()[0]);
                         world
                             .add_component(id, character_controller(), controller)
                             .unwrap();
@@ -304,6 +339,7 @@ pub fn server_systems() -> SystemGroup {
             ))
             .optional_changed(collider_type())
             .optional_changed(kinematic())
+            .optional_changed(collision_layer())
             .to_system(|q, world, qs, _| {
                 let physics = world.resource(physics()).clone();
                 let force_static = world
@@ -378,6 +414,7 @@ pub fn server_systems() -> SystemGroup {
                                 shape.set_rest_offset(roff);
                             }
                             shape.update_user_data::<PxShapeUserData>(&|ud| ud.entity = id);
+                            apply_collision_layer(world, id, shape);
                         }
                         if let Some(actor) = actor.to_rigid_dynamic() {
                             if !actor
@@ -423,7 +460,12 @@ pub fn server_systems() -> SystemGroup {
                             }
                         }
                     };
-                for (id, (convex, concave)) in q.collect_cloned(world, qs) {
+                let mut loaded = q.collect_cloned(world, qs);
+                if world.has_component(world.resource_entity(), deterministic_physics()) {
+                    // The order actors are added to the scene in affects the simulation
+                    loaded.sort_by_key(|(id, _)| *id);
+                }
+                for (id, (convex, concave)) in loaded {
                     build_actor(world, id, convex, concave);
                 }
             }),
@@ -434,6 +476,9 @@ pub fn server_systems() -> SystemGroup {
 fn one_value() -> f32 {
     1.
 }
+fn half_value() -> f32 {
+    0.5
+}
 fn vec3_zero_value() -> Vec3 {
     Vec3::ZERO
 }
@@ -456,6 +501,20 @@ pub enum ColliderDef {
         center: Vec3,
     },
     Plane,
+    /// A capsule along the Z axis
+    Capsule {
+        #[serde(default = "half_value")]
+        radius: f32,
+        /// The distance between the centers of the two caps
+        #[serde(default = "one_value")]
+        height: f32,
+        #[serde(default = "vec3_zero_value")]
+        center: Vec3,
+    },
+    /// The convex hull of a set of points
+    Convex {
+        points: Vec<Vec3>,
+    },
 }
 
 type ColliderSpawner = Box<dyn Fn(&Physics, Vec3) -> (Vec<PxShape>, Vec<PxShape>) + Sync + Send>;
@@ -531,6 +590,65 @@ impl ColliderDef {
                     (vec![shape.clone()], vec![shape])
                 }))
             }
+            ColliderDef::Capsule {
+                radius,
+                height,
+                center,
+            } => {
+                if radius <= 0. || height < 0. {
+                    return Err(anyhow::anyhow!(
+                        "Invalid capsule collider. radius={radius}, height={height}"
+                    )
+                    .into());
+                }
+                // PhysX capsules extend along the X axis
+                let rotation = Quat::from_rotation_y(-PI / 2.);
+                Ok(Box::new(move |physics, scale| {
+                    let geometry = PxCapsuleGeometry::new(
+                        radius * scale.x.max(scale.y),
+                        height / 2. * scale.z,
+                    );
+                    let shape =
+                        PxShape::new(physics.physics, &geometry, &[&material], Some(true), None);
+                    shape.set_local_pose(&PxTransform::new(center * scale, rotation));
+                    shape.set_user_data(PxShapeUserData {
+                        entity: EntityId::null(),
+                        density,
+                        base_pose: Mat4::from_scale_rotation_translation(
+                            vec3(height / 2., radius, radius),
+                            rotation,
+                            center * scale,
+                        ),
+                    });
+                    (vec![shape.clone()], vec![shape])
+                }))
+            }
+            ColliderDef::Convex { points } => {
+                let physics = PhysicsKey.get(&assets);
+                let desc = PxConvexMeshDesc {
+                    points,
+                    indices: None,
+                    vertex_limit: None,
+                    flags: Some(PxConvexFlag::COMPUTE_CONVEX),
+                };
+                let mesh = PxConvexMesh::from_desc(physics.physics, physics.cooking, desc)
+                    .map_err(|err| anyhow::anyhow!("Failed to cook convex collider: {err:?}"))?;
+                Ok(Box::new(move |physics, scale| {
+                    let geometry = PxConvexMeshGeometry::new(
+                        &mesh,
+                        Some(PxMeshScale::from_scale(scale.abs())),
+                        None,
+                    );
+                    let shape =
+                        PxShape::new(physics.physics, &geometry, &[&material], Some(true), None);
+                    shape.set_user_data(PxShapeUserData {
+                        entity: EntityId::null(),
+                        density,
+                        base_pose: Mat4::IDENTITY,
+                    });
+                    (vec![shape.clone()], vec![shape])
+                }))
+            }
             ColliderDef::Plane => Ok(Box::new(move |physics, _scale| {
                 let geometry = PxPlaneGeometry::new();
                 let shape =
@@ -0,0 +1,165 @@
+use ambient_ecs::{
+    generated::messages, query, with_component_registry, world_events, EntityId, FnSystem,
+    SystemGroup, World, WorldEventsExt,
+};
+use itertools::Itertools;
+use physxx::{
+    PxContactPairPoint, PxOverlapCallback, PxQueryFlag, PxRigidActor, PxRigidActorRef, PxUserData,
+};
+
+use crate::{
+    collision_layer, contact_events, contacts, layers::layer_query_filter_data, main_physics_scene,
+    physx::rigid_actor, trigger_area, trigger_area_filter, trigger_area_overlaps, PxShapeUserData,
+};
+
+/// Two entities that started touching during the last simulation step
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub a: EntityId,
+    pub b: EntityId,
+    pub points: Vec<PxContactPairPoint>,
+}
+
+pub fn server_systems() -> SystemGroup {
+    SystemGroup::new(
+        "physics/events",
+        vec![
+            Box::new(FnSystem::new(|world, _| {
+                ambient_profiling::scope!("contact_events");
+                let contacts = world.resource(contacts()).lock().clone();
+                for contact in contacts {
+                    if !world.has_component(contact.a, contact_events())
+                        && !world.has_component(contact.b, contact_events())
+                    {
+                        continue;
+                    }
+                    let points = &contact.points;
+                    world
+                        .resource_mut(world_events())
+                        .add_message(messages::Contact::new(
+                            contact.a,
+                            contact.b,
+                            points.iter().map(|p| p.impulse).collect(),
+                            points.iter().map(|p| p.normal).collect(),
+                            points.iter().map(|p| p.position).collect(),
+                        ));
+                }
+            })),
+            Box::new(FnSystem::new(|world, _| {
+                ambient_profiling::scope!("trigger_area_events");
+                for (id, (actor,)) in query((rigid_actor(),))
+                    .incl(trigger_area())
+                    .collect_cloned(world, None)
+                {
+                    let overlaps = trigger_area_overlapping_entities(world, id, actor);
+                    let previous = world.get_ref(id, trigger_area_overlaps()).cloned();
+                    let previous = previous.unwrap_or_default();
+                    let events = world.resource_mut(world_events());
+                    for (entity, change) in overlap_changes(&previous, &overlaps) {
+                        match change {
+                            OverlapChange::Enter => {
+                                events.add_message(messages::TriggerAreaEnter::new(entity, id))
+                            }
+                            OverlapChange::Stay => {
+                                events.add_message(messages::TriggerAreaStay::new(entity, id))
+                            }
+                            OverlapChange::Exit => {
+                                events.add_message(messages::TriggerAreaExit::new(entity, id))
+                            }
+                        }
+                    }
+                    if overlaps != previous {
+                        world
+                            .add_component(id, trigger_area_overlaps(), overlaps)
+                            .unwrap();
+                    }
+                }
+            })),
+        ],
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverlapChange {
+    Enter,
+    Stay,
+    Exit,
+}
+
+/// Compares the entities overlapping a trigger area during the last step with the ones that
+/// overlapped it during the step before
+fn overlap_changes(previous: &[EntityId], overlaps: &[EntityId]) -> Vec<(EntityId, OverlapChange)> {
+    let current = overlaps.iter().map(|&entity| {
+        if previous.contains(&entity) {
+            (entity, OverlapChange::Stay)
+        } else {
+            (entity, OverlapChange::Enter)
+        }
+    });
+    let exited = previous
+        .iter()
+        .filter(|entity| !overlaps.contains(entity))
+        .map(|&entity| (entity, OverlapChange::Exit));
+    current.chain(exited).collect()
+}
+
+/// Finds the dynamic entities in the main physics scene overlapping any of the shapes of the
+/// trigger area `id`, honouring its `trigger_area_filter`
+fn trigger_area_overlapping_entities(
+    world: &World,
+    id: EntityId,
+    actor: PxRigidActorRef,
+) -> Vec<EntityId> {
+    let filter_component = world
+        .get_ref(id, trigger_area_filter())
+        .ok()
+        .and_then(|path| with_component_registry(|r| r.get_by_path(path)));
+    let scene = world.resource(main_physics_scene());
+    let layer = world
+        .get_ref(id, collision_layer())
+        .ok()
+        .map(|x| x.as_str());
+    let mut filter_data = layer_query_filter_data(world, layer);
+    filter_data.set_flags(PxQueryFlag::DYNAMIC | PxQueryFlag::NO_BLOCK);
+
+    let mut entities = Vec::new();
+    for shape in actor.get_shapes() {
+        let mut hit_call = PxOverlapCallback::new(1000);
+        let pose = shape.get_global_pose(actor);
+        if scene.overlap(&shape.get_geometry(), pose, &mut hit_call, &filter_data) {
+            entities.extend(
+                hit_call
+                    .touches()
+                    .into_iter()
+                    .filter_map(|hit| hit.shape.get_user_data::<PxShapeUserData>())
+                    .map(|ud| ud.entity),
+            );
+        }
+    }
+    entities
+        .into_iter()
+        .filter(|&entity| entity != id && world.exists(entity))
+        .filter(|&entity| match filter_component {
+            Some(component) => world.has_component(entity, component),
+            None => true,
+        })
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+#[test]
+fn test_overlap_changes() {
+    let (a, b, c) = (EntityId(1), EntityId(2), EntityId(3));
+    assert_eq!(overlap_changes(&[], &[a]), vec![(a, OverlapChange::Enter)]);
+    assert_eq!(
+        overlap_changes(&[a, b], &[b, c]),
+        vec![
+            (b, OverlapChange::Stay),
+            (c, OverlapChange::Enter),
+            (a, OverlapChange::Exit)
+        ]
+    );
+    assert_eq!(overlap_changes(&[a], &[]), vec![(a, OverlapChange::Exit)]);
+    assert!(overlap_changes(&[], &[]).is_empty());
+}
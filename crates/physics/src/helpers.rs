@@ -6,9 +6,9 @@ use anyhow::{bail, Context};
 use glam::{vec3, Mat4, Vec3};
 use itertools::Itertools;
 use physxx::{
-    AsPxActor, AsPxRigidActor, PxActor, PxActorRef, PxActorTypeFlag, PxBase, PxBoxGeometry, PxConvexMeshGeometry, PxForceMode, PxJoint,
-    PxMeshScale, PxOverlapCallback, PxPhysicsRef, PxQueryFilterData, PxQueryFlag, PxRevoluteJointRef, PxRigidActor, PxRigidActorRef,
-    PxRigidBody, PxRigidBodyFlag, PxRigidDynamicRef, PxRigidStaticRef, PxSceneRef, PxShape, PxSphereGeometry, PxTransform,
+    AsPxActor, AsPxRigidActor, PxActor, PxActorRef, PxActorTypeFlag, PxBase, PxBoxGeometry, PxCapsuleGeometry, PxConvexMeshGeometry,
+    PxForceMode, PxJoint, PxMeshScale, PxOverlapCallback, PxPhysicsRef, PxQueryFilterData, PxQueryFlag, PxRevoluteJointRef, PxRigidActor,
+    PxRigidActorRef, PxRigidBody, PxRigidBodyFlag, PxRigidDynamicRef, PxRigidStaticRef, PxSceneRef, PxShape, PxSphereGeometry, PxTransform,
     PxTriangleMeshGeometry, PxUserData,
 };
 
@@ -170,6 +170,9 @@ pub fn scale_shape(shape: PxShape, scale: Vec3) {
     } else if let Some(_geo) = geo.as_box() {
         let new_geo = PxBoxGeometry::new(size.x, size.y, size.z);
         shape.set_geometry(&new_geo);
+    } else if let Some(_geo) = geo.as_capsule() {
+        let new_geo = PxCapsuleGeometry::new(size.y.abs().max(size.z.abs()), size.x.abs());
+        shape.set_geometry(&new_geo);
     } else {
         // TODO
     }
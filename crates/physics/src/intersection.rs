@@ -1,20 +1,20 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, f32::consts::FRAC_PI_2};
 
 use ambient_core::{asset_cache, transform::translation};
 use ambient_ecs::{query, ArchetypeFilter, EntityId, World};
 use ambient_meshes::cuboid::CuboidMesh;
 use ambient_network::server;
 use ambient_std::{asset_cache::SyncAssetKeyExt, mesh::Mesh, shapes::Ray};
-use glam::Vec3;
+use glam::{Quat, Vec3};
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
 use physxx::{
-    PxConvexFlag, PxConvexMesh, PxConvexMeshDesc, PxConvexMeshGeometry, PxOverlapCallback, PxQueryFilterData, PxRaycastCallback,
-    PxRigidActor, PxShape, PxTransform, PxUserData,
+    PxBoxGeometry, PxCapsuleGeometry, PxConvexFlag, PxConvexMesh, PxConvexMeshDesc, PxConvexMeshGeometry, PxGeometry, PxOverlapCallback,
+    PxQueryFilterData, PxQueryFlag, PxRaycastCallback, PxRigidActor, PxShape, PxSphereGeometry, PxTransform, PxUserData,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{main_physics_scene, physx::PhysicsKey, ColliderScene, PxShapeUserData};
+use crate::{layers::layer_query_filter_data, main_physics_scene, physx::PhysicsKey, ColliderScene, PxShapeUserData};
 
 pub fn get_entities_in_radius(world: &World, center: Vec3, radius: f32) -> Vec<EntityId> {
     query((translation(),))
@@ -23,25 +23,37 @@ pub fn get_entities_in_radius(world: &World, center: Vec3, radius: f32) -> Vec<E
         .collect_vec()
 }
 
-pub fn raycast_first(world: &World, ray: Ray) -> Option<(EntityId, f32)> {
-    raycast_first_px(world, ray).and_then(|(shape, dist)| shape.get_user_data::<PxShapeUserData>().map(|ud| (ud.entity, dist)))
+/// Returns the closest hit of `ray`; with a `collision_layer`, only the shapes that layer collides with are hit
+pub fn raycast_first(world: &World, ray: Ray, collision_layer: Option<&str>) -> Option<(EntityId, f32)> {
+    raycast_first_px(world, ray, collision_layer)
+        .and_then(|(shape, dist)| shape.get_user_data::<PxShapeUserData>().map(|ud| (ud.entity, dist)))
 }
 
-fn raycast_first_px(world: &World, ray: Ray) -> Option<(PxShape, f32)> {
+fn raycast_first_px(world: &World, ray: Ray, collision_layer: Option<&str>) -> Option<(PxShape, f32)> {
     (0..3)
-        .filter_map(|i| raycast_first_collider_type_px(world, ColliderScene::from_usize(i), ray))
+        .filter_map(|i| raycast_first_collider_type_px(world, ColliderScene::from_usize(i), ray, collision_layer))
         .sorted_by_key(|x| OrderedFloat(x.1))
         .next()
 }
 
-pub fn raycast_first_collider_type(world: &World, collider_type: ColliderScene, ray: Ray) -> Option<(EntityId, f32)> {
-    raycast_first_collider_type_px(world, collider_type, ray)
+pub fn raycast_first_collider_type(
+    world: &World,
+    collider_type: ColliderScene,
+    ray: Ray,
+    collision_layer: Option<&str>,
+) -> Option<(EntityId, f32)> {
+    raycast_first_collider_type_px(world, collider_type, ray, collision_layer)
         .and_then(|(shape, dist)| shape.get_user_data::<PxShapeUserData>().map(|ud| (ud.entity, dist)))
 }
-pub fn raycast_first_collider_type_px(world: &World, collider_type: ColliderScene, ray: Ray) -> Option<(PxShape, f32)> {
+pub fn raycast_first_collider_type_px(
+    world: &World,
+    collider_type: ColliderScene,
+    ray: Ray,
+    collision_layer: Option<&str>,
+) -> Option<(PxShape, f32)> {
     let mut hit = PxRaycastCallback::new(0);
     let scene = collider_type.get_scene(world);
-    let filter_data = PxQueryFilterData::new();
+    let filter_data = layer_query_filter_data(world, collision_layer);
     if scene.raycast(ray.origin, ray.dir, f32::MAX, &mut hit, None, &filter_data) {
         let block = hit.block().unwrap();
         if let Some(shape) = block.shape {
@@ -51,31 +63,39 @@ pub fn raycast_first_collider_type_px(world: &World, collider_type: ColliderScen
     None
 }
 
-pub fn raycast(world: &World, ray: Ray) -> Vec<(EntityId, f32)> {
-    raycast_px(world, ray)
+/// Returns all the hits of `ray`, closest first; with a `collision_layer`, only the shapes that layer collides with are hit
+pub fn raycast(world: &World, ray: Ray, collision_layer: Option<&str>) -> Vec<(EntityId, f32)> {
+    raycast_px(world, ray, collision_layer)
         .into_iter()
         .flat_map(|(shape, dist)| shape.get_user_data::<PxShapeUserData>().map(|ud| (ud.entity, dist)))
         .collect_vec()
 }
 
-fn raycast_px(world: &World, ray: Ray) -> Vec<(PxShape, f32)> {
+fn raycast_px(world: &World, ray: Ray, collision_layer: Option<&str>) -> Vec<(PxShape, f32)> {
     (0..3)
-        .flat_map(|i| raycast_collider_type_px(world, ColliderScene::from_usize(i), ray).into_iter())
+        .flat_map(|i| raycast_collider_type_px(world, ColliderScene::from_usize(i), ray, collision_layer).into_iter())
         .sorted_by_key(|x| OrderedFloat(x.1))
         .collect_vec()
 }
 
-pub fn raycast_collider_type(world: &World, collider_type: ColliderScene, ray: Ray) -> Vec<(EntityId, f32)> {
-    raycast_collider_type_px(world, collider_type, ray)
+pub fn raycast_collider_type(world: &World, collider_type: ColliderScene, ray: Ray, collision_layer: Option<&str>) -> Vec<(EntityId, f32)> {
+    raycast_collider_type_px(world, collider_type, ray, collision_layer)
         .into_iter()
         .filter_map(|(shape, dist)| shape.get_user_data::<PxShapeUserData>().map(|ud| (ud.entity, dist)))
         .collect()
 }
-pub fn raycast_collider_type_px(world: &World, collider_type: ColliderScene, ray: Ray) -> Vec<(PxShape, f32)> {
+pub fn raycast_collider_type_px(
+    world: &World,
+    collider_type: ColliderScene,
+    ray: Ray,
+    collision_layer: Option<&str>,
+) -> Vec<(PxShape, f32)> {
+    raycast_scene_px(world, collider_type, ray, &layer_query_filter_data(world, collision_layer))
+}
+fn raycast_scene_px(world: &World, collider_type: ColliderScene, ray: Ray, filter_data: &PxQueryFilterData) -> Vec<(PxShape, f32)> {
     let mut hit = PxRaycastCallback::new(100);
     let scene = collider_type.get_scene(world);
-    let filter_data = PxQueryFilterData::new();
-    if scene.raycast(ray.origin, ray.dir, f32::MAX, &mut hit, None, &filter_data) {
+    if scene.raycast(ray.origin, ray.dir, f32::MAX, &mut hit, None, filter_data) {
         return hit.touches().into_iter().filter_map(|hit| hit.shape.map(|shape| (shape, hit.distance))).collect_vec();
     }
     Vec::new()
@@ -122,16 +142,124 @@ pub async fn rpc_pick(args: server::RpcArgs, (ray, filter): (Ray, RaycastFilter)
 }
 
 pub fn raycast_filtered(world: &World, filter: RaycastFilter, ray: Ray) -> Option<(EntityId, f32)> {
-    let hits =
-        if let Some(collider_type) = filter.collider_type { raycast_collider_type(world, collider_type, ray) } else { raycast(world, ray) };
-    if let Some(filter) = &filter.entities {
-        hits.into_iter().filter(|(id, _)| filter.matches_entity(world, *id)).min_by_key(|(_, dist)| OrderedFloat(*dist))
-    } else {
-        hits.into_iter().min_by_key(|(_, dist)| OrderedFloat(*dist))
-    }
+    let filter_data = layer_query_filter_data(world, filter.collision_layer.as_deref());
+    filter_scenes(&filter)
+        .into_iter()
+        .flat_map(|collider_type| raycast_scene_px(world, collider_type, ray, &filter_data))
+        .filter_map(|(shape, dist)| shape.get_user_data::<PxShapeUserData>().map(|ud| (ud.entity, dist)))
+        .filter(|(id, _)| filter.entities.as_ref().map(|entities| entities.matches_entity(world, *id)).unwrap_or(true))
+        .min_by_key(|(_, dist)| OrderedFloat(*dist))
 }
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct RaycastFilter {
     pub entities: Option<ArchetypeFilter>,
     pub collider_type: Option<ColliderScene>,
+    /// Only hit the shapes this collision layer collides with
+    #[serde(default)]
+    pub collision_layer: Option<String>,
+}
+
+/// A shape used for sweeps and overlap tests
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum QueryShape {
+    Sphere {
+        radius: f32,
+    },
+    Cuboid {
+        half_extents: Vec3,
+    },
+    /// A capsule along the local Z axis. `half_height` is the distance from the center to the center of each cap
+    Capsule {
+        radius: f32,
+        half_height: f32,
+    },
+}
+impl QueryShape {
+    fn with_geometry<R>(&self, f: impl FnOnce(&dyn PxGeometry) -> R) -> R {
+        match *self {
+            QueryShape::Sphere { radius } => f(&PxSphereGeometry::new(radius)),
+            QueryShape::Cuboid { half_extents } => f(&PxBoxGeometry::new(half_extents.x, half_extents.y, half_extents.z)),
+            QueryShape::Capsule { radius, half_height } => f(&PxCapsuleGeometry::new(radius, half_height)),
+        }
+    }
+    fn pose(&self, position: Vec3, rotation: Quat) -> PxTransform {
+        match self {
+            // PhysX capsules extend along the X axis
+            QueryShape::Capsule { .. } => PxTransform::new(position, rotation * Quat::from_rotation_y(-FRAC_PI_2)),
+            _ => PxTransform::new(position, rotation),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueryHit {
+    pub entity: EntityId,
+    pub distance: f32,
+    pub position: Vec3,
+    pub normal: Vec3,
+}
+
+fn filter_scenes(filter: &RaycastFilter) -> Vec<ColliderScene> {
+    match filter.collider_type {
+        Some(collider_type) => vec![collider_type],
+        None => (0..3).map(ColliderScene::from_usize).collect(),
+    }
+}
+fn all_hits_filter_data(world: &World, filter: &RaycastFilter) -> PxQueryFilterData {
+    let mut filter_data = layer_query_filter_data(world, filter.collision_layer.as_deref());
+    filter_data.set_flags(PxQueryFlag::STATIC | PxQueryFlag::DYNAMIC | PxQueryFlag::NO_BLOCK);
+    filter_data
+}
+
+/// Sweeps `shape` from `position` along `direction` for at most `distance`, and returns all the hits along the way, closest
+/// first. Shapes which overlap at the start are reported with a distance of zero. Nothing is hit along a direction without length.
+pub fn sweep(
+    world: &World,
+    shape: QueryShape,
+    position: Vec3,
+    rotation: Quat,
+    direction: Vec3,
+    distance: f32,
+    filter: &RaycastFilter,
+) -> Vec<QueryHit> {
+    // PhysX requires a normalized direction
+    let direction = match direction.try_normalize() {
+        Some(direction) => direction,
+        None => return Vec::new(),
+    };
+    let pose = shape.pose(position, rotation);
+    filter_scenes(filter)
+        .into_iter()
+        .flat_map(|scene| {
+            let scene = scene.get_scene(world);
+            shape.with_geometry(|geometry| scene.sweep(geometry, &pose, direction, distance, all_hits_filter_data(world, filter))).touches()
+        })
+        .filter_map(|hit| {
+            let ud = hit.shape?.get_user_data::<PxShapeUserData>()?;
+            Some(QueryHit { entity: ud.entity, distance: hit.distance, position: hit.position, normal: hit.normal })
+        })
+        .filter(|hit| filter.entities.as_ref().map(|entities| entities.matches_entity(world, hit.entity)).unwrap_or(true))
+        .sorted_by_key(|hit| OrderedFloat(hit.distance))
+        .collect()
+}
+
+/// Returns the entities with a collider overlapping `shape` placed at `position` with `rotation`
+pub fn overlap(world: &World, shape: QueryShape, position: Vec3, rotation: Quat, filter: &RaycastFilter) -> Vec<EntityId> {
+    let pose = shape.pose(position, rotation);
+    filter_scenes(filter)
+        .into_iter()
+        .flat_map(|scene| {
+            let scene = scene.get_scene(world);
+            let mut hit_call = PxOverlapCallback::new(1000);
+            if shape.with_geometry(|geometry| scene.overlap(geometry, pose, &mut hit_call, &all_hits_filter_data(world, filter))) {
+                hit_call.touches()
+            } else {
+                Vec::new()
+            }
+        })
+        .filter_map(|hit| hit.shape.get_user_data::<PxShapeUserData>().map(|ud| ud.entity))
+        .filter(|&id| filter.entities.as_ref().map(|entities| entities.matches_entity(world, id)).unwrap_or(true))
+        .sorted()
+        .dedup()
+        .collect()
 }
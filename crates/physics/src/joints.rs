@@ -0,0 +1,344 @@
+use std::str::FromStr;
+
+use ambient_ecs::{components, query, EntityId, FnSystem, SystemGroup, World};
+use anyhow::bail;
+use glam::{Mat4, UVec3, Vec2, Vec3};
+use physxx::{
+    AsPxJoint, PxConstraintFlags, PxD6Axis, PxD6Drive, PxD6JointDrive, PxD6JointRef, PxD6Motion,
+    PxDistanceJointFlag, PxDistanceJointRef, PxFixedJointRef, PxJoint, PxJointAngularLimitPair,
+    PxJointLimitCone, PxJointLinearLimitPair, PxJointRef, PxPhysicsRef, PxPrismaticJointFlag,
+    PxPrismaticJointRef, PxRevoluteJointFlag, PxRevoluteJointRef, PxRigidActorRef,
+    PxSphericalJointFlag, PxSphericalJointRef, PxTransform, PxUserData,
+};
+
+use crate::{
+    joint_angular_motion, joint_body0, joint_body1, joint_break_force, joint_break_torque,
+    joint_broken, joint_cone_limit, joint_drive_damping, joint_drive_force_limit,
+    joint_drive_stiffness, joint_drive_target, joint_drive_velocity, joint_frame0, joint_frame1,
+    joint_limit, joint_linear_limit, joint_linear_motion, joint_type, physx::rigid_actor,
+};
+
+components!("physics", {
+    physx_joint: PhysxJoint,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JointType {
+    Fixed,
+    Revolute,
+    Spherical,
+    Prismatic,
+    Distance,
+    D6,
+}
+impl FromStr for JointType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "fixed" => Self::Fixed,
+            "revolute" => Self::Revolute,
+            "spherical" => Self::Spherical,
+            "prismatic" => Self::Prismatic,
+            "distance" => Self::Distance,
+            "d6" => Self::D6,
+            _ => bail!("Unknown joint type: {s}"),
+        })
+    }
+}
+
+/// Everything a joint is created from; the joint is recreated whenever this changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointDesc {
+    pub joint_type: JointType,
+    pub actors: [Option<PxRigidActorRef>; 2],
+    pub frames: [Mat4; 2],
+    pub limit: Option<Vec2>,
+    pub linear_limit: Option<Vec2>,
+    pub cone_limit: Option<Vec2>,
+    pub linear_motion: UVec3,
+    pub angular_motion: UVec3,
+    pub drive_velocity: Option<f32>,
+    pub drive_stiffness: Option<f32>,
+    pub drive_damping: f32,
+    pub drive_force_limit: f32,
+    pub drive_target: Mat4,
+    pub break_force: f32,
+    pub break_torque: f32,
+}
+impl JointDesc {
+    /// Reads the joint of `id` from its components. Returns `None` if it isn't a valid joint, or if
+    /// its bodies don't have a physics actor yet
+    pub fn from_world(world: &World, id: EntityId) -> Option<Self> {
+        let joint_type = match world.get_ref(id, joint_type()).ok()?.parse::<JointType>() {
+            Ok(joint_type) => joint_type,
+            Err(err) => {
+                log::warn!("Invalid joint {id}: {err}");
+                return None;
+            }
+        };
+        let actor = |body| match world.get(id, body) {
+            Ok(body) => world.get(body, rigid_actor()).ok().map(Some),
+            Err(_) => Some(None),
+        };
+        let actors = [actor(joint_body0())?, actor(joint_body1())?];
+        if actors == [None, None] {
+            return None;
+        }
+        Some(Self {
+            joint_type,
+            actors,
+            frames: [
+                world.get(id, joint_frame0()).unwrap_or(Mat4::IDENTITY),
+                world.get(id, joint_frame1()).unwrap_or(Mat4::IDENTITY),
+            ],
+            limit: world.get(id, joint_limit()).ok(),
+            linear_limit: world.get(id, joint_linear_limit()).ok(),
+            cone_limit: world.get(id, joint_cone_limit()).ok(),
+            linear_motion: world.get(id, joint_linear_motion()).unwrap_or(UVec3::ZERO),
+            angular_motion: world.get(id, joint_angular_motion()).unwrap_or(UVec3::ZERO),
+            drive_velocity: world.get(id, joint_drive_velocity()).ok(),
+            drive_stiffness: world.get(id, joint_drive_stiffness()).ok(),
+            drive_damping: world.get(id, joint_drive_damping()).unwrap_or(0.),
+            drive_force_limit: world.get(id, joint_drive_force_limit()).unwrap_or(f32::MAX),
+            drive_target: world
+                .get(id, joint_drive_target())
+                .unwrap_or(Mat4::IDENTITY),
+            break_force: world.get(id, joint_break_force()).unwrap_or(f32::MAX),
+            break_torque: world.get(id, joint_break_torque()).unwrap_or(f32::MAX),
+        })
+    }
+
+    pub fn create(&self) -> PxJointRef {
+        let physics = PxPhysicsRef::get();
+        let [actor0, actor1] = self.actors;
+        let [frame0, frame1] = self.frames.map(to_px_transform);
+        let joint = match self.joint_type {
+            JointType::Fixed => {
+                PxFixedJointRef::new(physics, actor0, &frame0, actor1, &frame1).as_joint()
+            }
+            JointType::Revolute => {
+                let joint = PxRevoluteJointRef::new(physics, actor0, &frame0, actor1, &frame1);
+                if let Some(limit) = self.limit {
+                    joint.set_limit(&PxJointAngularLimitPair::new(limit.x, limit.y, 0.1));
+                    joint.set_revolute_flag(PxRevoluteJointFlag::LIMIT_ENABLED, true);
+                }
+                if let Some(velocity) = self.drive_velocity {
+                    joint.set_drive_velocity(velocity, true);
+                    joint.set_drive_force_limit(self.drive_force_limit);
+                    joint.set_revolute_flag(PxRevoluteJointFlag::DRIVE_ENABLED, true);
+                }
+                joint.as_joint()
+            }
+            JointType::Spherical => {
+                let joint = PxSphericalJointRef::new(physics, actor0, &frame0, actor1, &frame1);
+                if let Some(limit) = self.cone_limit {
+                    joint.set_limit_cone(&PxJointLimitCone::new(limit.x, limit.y, 0.1));
+                    joint.set_spherical_flag(PxSphericalJointFlag::LIMIT_ENABLED, true);
+                }
+                joint.as_joint()
+            }
+            JointType::Prismatic => {
+                let joint = PxPrismaticJointRef::new(physics, actor0, &frame0, actor1, &frame1);
+                if let Some(limit) = self.limit {
+                    joint.set_limit(&PxJointLinearLimitPair::new(limit.x, limit.y, 0.01));
+                    joint.set_prismatic_flag(PxPrismaticJointFlag::LIMIT_ENABLED, true);
+                }
+                joint.as_joint()
+            }
+            JointType::Distance => {
+                let joint = PxDistanceJointRef::new(physics, actor0, &frame0, actor1, &frame1);
+                if let Some(limit) = self.limit {
+                    joint.set_min_distance(limit.x);
+                    joint.set_max_distance(limit.y);
+                    joint.set_distance_flag(PxDistanceJointFlag::MIN_DISTANCE_ENABLED, true);
+                    joint.set_distance_flag(PxDistanceJointFlag::MAX_DISTANCE_ENABLED, true);
+                }
+                if let Some(stiffness) = self.drive_stiffness {
+                    joint.set_stiffness(stiffness);
+                    joint.set_damping(self.drive_damping);
+                    joint.set_distance_flag(PxDistanceJointFlag::SPRING_ENABLED, true);
+                }
+                joint.as_joint()
+            }
+            JointType::D6 => self.create_d6(physics, &frame0, &frame1).as_joint(),
+        };
+        joint.set_break_force(self.break_force, self.break_torque);
+        joint
+    }
+
+    fn create_d6(
+        &self,
+        physics: PxPhysicsRef,
+        frame0: &PxTransform,
+        frame1: &PxTransform,
+    ) -> PxD6JointRef {
+        let [actor0, actor1] = self.actors;
+        let joint = PxD6JointRef::new(physics, actor0, frame0, actor1, frame1);
+        let linear = [PxD6Axis::X, PxD6Axis::Y, PxD6Axis::Z];
+        let angular = [PxD6Axis::Twist, PxD6Axis::Swing1, PxD6Axis::Swing2];
+        for (axes, motions) in [(linear, self.linear_motion), (angular, self.angular_motion)] {
+            for (axis, motion) in axes.into_iter().zip(motions.to_array()) {
+                joint.set_motion(axis, d6_motion(motion));
+            }
+        }
+        for (axis, limit) in linear.into_iter().zip(self.d6_linear_limits()) {
+            if let Some(limit) = limit {
+                joint.set_linear_limit(axis, &PxJointLinearLimitPair::new(limit.x, limit.y, 0.01));
+            }
+        }
+        if let Some(limit) = self.limit {
+            joint.set_twist_limit(&PxJointAngularLimitPair::new(limit.x, limit.y, 0.1));
+        }
+        if let Some(limit) = self.cone_limit {
+            joint.set_swing_limit(&PxJointLimitCone::new(limit.x, limit.y, 0.1));
+        }
+        if let Some(stiffness) = self.drive_stiffness {
+            let drive =
+                PxD6JointDrive::new(stiffness, self.drive_damping, self.drive_force_limit, false);
+            for axis in [PxD6Drive::X, PxD6Drive::Y, PxD6Drive::Z, PxD6Drive::Slerp] {
+                joint.set_drive(axis, &drive);
+            }
+            joint.set_drive_position(&to_px_transform(self.drive_target), true);
+            joint.set_drive_velocity(Vec3::ZERO, Vec3::ZERO, true);
+        }
+        joint
+    }
+    /// The limits of the X, Y and Z axes of a `d6` joint; only the limited axes have one
+    fn d6_linear_limits(&self) -> [Option<Vec2>; 3] {
+        self.linear_motion.to_array().map(|motion| {
+            self.linear_limit
+                .filter(|_| d6_motion(motion) == PxD6Motion::Limited)
+        })
+    }
+}
+
+/// A joint created from the joint components of an entity
+#[derive(Debug, Clone)]
+pub struct PhysxJoint {
+    pub joint: PxJointRef,
+    pub desc: JointDesc,
+}
+
+fn d6_motion(motion: u32) -> PxD6Motion {
+    match motion {
+        0 => PxD6Motion::Locked,
+        1 => PxD6Motion::Limited,
+        _ => PxD6Motion::Free,
+    }
+}
+
+fn to_px_transform(frame: Mat4) -> PxTransform {
+    let (_, rotation, translation) = frame.to_scale_rotation_translation();
+    PxTransform::new(translation, rotation)
+}
+
+fn release_joint(joint: PxJointRef) {
+    joint.remove_user_data::<EntityId>();
+    joint.release();
+}
+
+pub fn server_systems() -> SystemGroup {
+    SystemGroup::new(
+        "physics/joints",
+        vec![
+            query((physx_joint(),))
+                .despawned()
+                .to_system(|q, world, qs, _| {
+                    for (_, (joint,)) in q.iter(world, qs) {
+                        release_joint(joint.joint);
+                    }
+                }),
+            Box::new(FnSystem::new(|world, _| {
+                ambient_profiling::scope!("joints");
+                for (id, _) in query(()).incl(joint_type()).collect_cloned(world, None) {
+                    let desc = JointDesc::from_world(world, id);
+                    let current = world.get_ref(id, physx_joint()).ok().cloned();
+                    if current.as_ref().map(|x| &x.desc) == desc.as_ref() {
+                        continue;
+                    }
+                    if let Some(current) = current {
+                        release_joint(current.joint);
+                        world.remove_component(id, physx_joint()).unwrap();
+                    }
+                    world.remove_component(id, joint_broken()).ok();
+                    if let Some(desc) = desc {
+                        let joint = desc.create();
+                        joint.set_user_data(id);
+                        world
+                            .add_component(id, physx_joint(), PhysxJoint { joint, desc })
+                            .unwrap();
+                    }
+                }
+                // Joints whose components were removed
+                for (id, (joint,)) in query((physx_joint(),))
+                    .excl(joint_type())
+                    .collect_cloned(world, None)
+                {
+                    release_joint(joint.joint);
+                    world.remove_component(id, physx_joint()).unwrap();
+                }
+            })),
+            Box::new(FnSystem::new(|world, _| {
+                for (id, (joint,)) in query((physx_joint(),))
+                    .excl(joint_broken())
+                    .collect_cloned(world, None)
+                {
+                    if joint
+                        .joint
+                        .get_constraint_flags()
+                        .contains(PxConstraintFlags::BROKEN)
+                    {
+                        world.add_component(id, joint_broken(), ()).unwrap();
+                    }
+                }
+            })),
+        ],
+    )
+}
+
+#[test]
+fn test_joint_type() {
+    assert_eq!("d6".parse::<JointType>().unwrap(), JointType::D6);
+    assert_eq!(
+        "revolute".parse::<JointType>().unwrap(),
+        JointType::Revolute
+    );
+    assert!("hinge".parse::<JointType>().is_err());
+
+    assert_eq!(d6_motion(0), PxD6Motion::Locked);
+    assert_eq!(d6_motion(1), PxD6Motion::Limited);
+    assert_eq!(d6_motion(2), PxD6Motion::Free);
+    assert_eq!(d6_motion(7), PxD6Motion::Free);
+}
+
+#[test]
+fn test_d6_limits() {
+    let desc = JointDesc {
+        joint_type: JointType::D6,
+        actors: [None, None],
+        frames: [Mat4::IDENTITY; 2],
+        limit: Some(Vec2::new(-0.5, 0.5)),
+        linear_limit: Some(Vec2::new(-2., 2.)),
+        cone_limit: None,
+        linear_motion: UVec3::new(1, 0, 2),
+        angular_motion: UVec3::ONE,
+        drive_velocity: None,
+        drive_stiffness: None,
+        drive_damping: 0.,
+        drive_force_limit: f32::MAX,
+        drive_target: Mat4::IDENTITY,
+        break_force: f32::MAX,
+        break_torque: f32::MAX,
+    };
+    // The linear axes use their own limit, not the one of the twist
+    assert_eq!(
+        desc.d6_linear_limits(),
+        [Some(Vec2::new(-2., 2.)), None, None]
+    );
+    let desc = JointDesc {
+        linear_limit: None,
+        ..desc
+    };
+    assert_eq!(desc.d6_linear_limits(), [None, None, None]);
+}
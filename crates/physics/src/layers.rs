@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use ambient_ecs::{components, Debuggable, EntityId, Resource, World};
+use anyhow::{bail, Context};
+use physxx::{PxControllerFilters, PxFilterData, PxQueryFilterData, PxShape};
+
+use crate::collision_layer;
+
+components!("physics", {
+    @[Debuggable, Resource]
+    collision_layers: CollisionLayers,
+});
+
+/// The named collision layers of a project, and which of them collide with each other.
+///
+/// Two shapes only collide if each of their layers collides with the other one. Shapes without a
+/// filter (i.e. created before any layers were declared) collide with everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollisionLayers {
+    names: Vec<String>,
+    masks: Vec<u32>,
+}
+impl CollisionLayers {
+    pub const MAX_LAYERS: usize = 32;
+
+    /// `collides_with` lists the layers each layer collides with; layers that are not in it
+    /// collide with every layer
+    pub fn new(
+        names: Vec<String>,
+        collides_with: &BTreeMap<String, Vec<String>>,
+    ) -> anyhow::Result<Self> {
+        if names.len() > Self::MAX_LAYERS {
+            bail!(
+                "At most {} collision layers are supported, got {}",
+                Self::MAX_LAYERS,
+                names.len()
+            );
+        }
+        let index = |name: &String| {
+            names
+                .iter()
+                .position(|x| x == name)
+                .with_context(|| format!("Unknown collision layer: {name}"))
+        };
+        let mut masks = vec![u32::MAX; names.len()];
+        for (layer, others) in collides_with {
+            let mut mask = 0;
+            for other in others {
+                mask |= 1 << index(other)?;
+            }
+            masks[index(layer)?] = mask;
+        }
+        Ok(Self { names, masks })
+    }
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the index of `layer`, or of the first layer if it's unknown
+    fn index(&self, layer: &str) -> usize {
+        match self.names.iter().position(|x| x == layer) {
+            Some(index) => index,
+            None => {
+                log::warn!("Unknown collision layer: {layer}");
+                0
+            }
+        }
+    }
+    /// Returns the index of the layer of a shape; shapes without a layer are on the first layer
+    fn shape_index(&self, layer: Option<&str>) -> usize {
+        layer.map(|layer| self.index(layer)).unwrap_or(0)
+    }
+    /// The filter data used by the simulation to decide which shapes collide
+    pub fn simulation_filter_data(&self, layer: Option<&str>) -> PxFilterData {
+        if self.is_empty() {
+            return PxFilterData::default();
+        }
+        let index = self.shape_index(layer);
+        PxFilterData {
+            word0: 1 << index,
+            word1: self.masks[index],
+            ..Default::default()
+        }
+    }
+    /// The filter data of a shape on `layer` for scene queries
+    pub fn shape_query_filter_data(&self, layer: Option<&str>) -> PxFilterData {
+        if self.is_empty() {
+            return PxFilterData::default();
+        }
+        PxFilterData {
+            word0: 1 << self.shape_index(layer),
+            ..Default::default()
+        }
+    }
+    /// The filter data for scene queries (raycasts, sweeps and character controllers) which should
+    /// only report the shapes that `layer` collides with; queries without a layer report every shape
+    pub fn query_filter_data(&self, layer: Option<&str>) -> PxFilterData {
+        match layer {
+            Some(layer) if !self.is_empty() => PxFilterData {
+                word0: self.masks[self.index(layer)],
+                ..Default::default()
+            },
+            // PhysX doesn't filter queries whose filter data is all zeros
+            _ => PxFilterData::default(),
+        }
+    }
+}
+
+/// Applies the collision layer of `id` to `shape`
+pub fn apply_collision_layer(world: &World, id: EntityId, shape: &PxShape) {
+    if let Some(layers) = world.resource_opt(collision_layers()) {
+        let layer = world
+            .get_ref(id, collision_layer())
+            .ok()
+            .map(|x| x.as_str());
+        shape.set_simulation_filter_data(layers.simulation_filter_data(layer));
+        shape.set_query_filter_data(layers.shape_query_filter_data(layer));
+    }
+}
+
+/// The query filter data for scene queries made on behalf of `layer`
+pub fn layer_query_filter_data(world: &World, layer: Option<&str>) -> PxQueryFilterData {
+    let mut filter_data = PxQueryFilterData::new();
+    if let Some(layers) = world.resource_opt(collision_layers()) {
+        filter_data.set_data(layers.query_filter_data(layer));
+    }
+    filter_data
+}
+
+/// The filters used when moving the character controller of `id`, so that it only collides with
+/// the layers its own layer collides with
+pub fn character_controller_filters(world: &World, id: EntityId) -> PxControllerFilters {
+    match world.resource_opt(collision_layers()) {
+        Some(layers) => {
+            // Characters without a layer are on the first one, like every other shape
+            let layer = world
+                .get_ref(id, collision_layer())
+                .ok()
+                .map(|x| x.as_str())
+                .or_else(|| layers.names().first().map(|x| x.as_str()));
+            PxControllerFilters::with_filter_data(layers.query_filter_data(layer))
+        }
+        None => PxControllerFilters::new(),
+    }
+}
+
+#[test]
+fn test_collision_layers() {
+    let layers = CollisionLayers::new(
+        vec![
+            "default".to_string(),
+            "player".to_string(),
+            "projectile".to_string(),
+        ],
+        &BTreeMap::from_iter([("projectile".to_string(), vec!["default".to_string()])]),
+    )
+    .unwrap();
+    let collides = |a: Option<&str>, b: Option<&str>| {
+        let (a, b) = (
+            layers.simulation_filter_data(a),
+            layers.simulation_filter_data(b),
+        );
+        (a.word0 & b.word1) != 0 && (b.word0 & a.word1) != 0
+    };
+    assert!(collides(None, Some("player")));
+    assert!(collides(Some("projectile"), Some("default")));
+    assert!(!collides(Some("projectile"), Some("player")));
+    assert!(!collides(Some("projectile"), Some("projectile")));
+
+    // Queries without a layer hit everything, even the layers that collide with nothing
+    assert_eq!(layers.query_filter_data(None).word0, 0);
+    let query = layers.query_filter_data(Some("projectile"));
+    assert_ne!(query.word0 & layers.shape_query_filter_data(None).word0, 0);
+    assert_eq!(
+        query.word0 & layers.shape_query_filter_data(Some("player")).word0,
+        0
+    );
+
+    assert!(CollisionLayers::new(
+        vec!["default".to_string()],
+        &BTreeMap::from_iter([("default".to_string(), vec!["missing".to_string()])]),
+    )
+    .is_err());
+}
@@ -10,6 +10,7 @@ use ambient_std::asset_cache::{AssetCache, SyncAssetKey, SyncAssetKeyExt};
 use collider::{collider_shapes, collider_shapes_convex};
 use glam::{vec3, Mat4};
 use helpers::release_px_scene;
+use joints::physx_joint;
 use parking_lot::Mutex;
 use physx::{
     actor_aggregate, articulation_cache, articulation_link, articulation_reduce_coordinate,
@@ -22,13 +23,17 @@ use physxx::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::physx::PhysicsKey;
+use crate::{events::Contact, physx::PhysicsKey};
 
 pub mod collider;
+pub mod events;
 pub mod helpers;
 pub mod intersection;
+pub mod joints;
+pub mod layers;
 pub mod mesh;
 pub mod physx;
+pub mod ragdoll;
 pub mod rc_asset;
 pub mod visualization;
 
@@ -47,11 +52,18 @@ components!("physics", {
     wood_physics_material: PxMaterial,
     @[Debuggable, Resource]
     collisions: Arc<Mutex<Vec<(EntityId, EntityId)>>>,
+    @[Debuggable, Resource]
+    contacts: Arc<Mutex<Vec<Contact>>>,
+    @[Debuggable, Resource]
+    deterministic_physics: (),
 });
 pub fn init_all_components() {
     init_components();
     physx::init_components();
     collider::init_components();
+    joints::init_components();
+    layers::init_components();
+    ragdoll::init_components();
     visualization::init_components();
 }
 
@@ -59,15 +71,22 @@ pub const GRAVITY: f32 = 9.82;
 pub fn create_server_resources(assets: &AssetCache, server_resources: &mut Entity) {
     let physics = PhysicsKey.get(assets);
     server_resources.set(crate::physx::physics(), physics.clone());
+    let deterministic = DeterministicPhysicsKey.get(assets);
 
     let mut main_scene_desc = PxSceneDesc::new(physics.physics);
     main_scene_desc.set_cpu_dispatcher(&physics.dispatcher);
     main_scene_desc.set_gravity(vec3(0., 0., -GRAVITY));
     main_scene_desc.update_flags(|flags| flags | PxSceneFlags::ENABLE_CCD);
+    if deterministic {
+        main_scene_desc.update_flags(|flags| flags | PxSceneFlags::ENABLE_ENHANCED_DETERMINISM);
+        server_resources.set(self::deterministic_physics(), ());
+    }
     main_scene_desc.set_filter_shader(main_physx_scene_filter_shader, true);
     let collisions = Arc::new(Mutex::new(Vec::new()));
+    let contacts = Arc::new(Mutex::new(Vec::new()));
     {
         let collisions = collisions.clone();
+        let contacts = contacts.clone();
         main_scene_desc.set_simulation_event_callbacks(PxSimulationEventCallback {
             collision_callback: Some(Box::new(move |header: &PxContactPairHeader| {
                 if let (Some(a), Some(b)) = (header.actors[0], header.actors[1]) {
@@ -85,11 +104,25 @@ pub fn create_server_resources(assets: &AssetCache, server_resources: &mut Entit
                         collisions.lock().push((a, b));
                     }
                 }
+                for pair in &header.pairs {
+                    if let [Some(a), Some(b)] = &pair.shapes {
+                        let a = a.get_user_data::<PxShapeUserData>().map(|ud| ud.entity);
+                        let b = b.get_user_data::<PxShapeUserData>().map(|ud| ud.entity);
+                        if let (Some(a), Some(b)) = (a, b) {
+                            contacts.lock().push(Contact {
+                                a,
+                                b,
+                                points: pair.points.clone(),
+                            });
+                        }
+                    }
+                }
             })),
         });
     }
     let main_scene = PxSceneRef::new(&physics.physics, &main_scene_desc);
     server_resources.set(self::collisions(), collisions);
+    server_resources.set(self::contacts(), contacts);
     server_resources.set(self::collider_loads(), vec![]);
 
     main_scene.get_scene_pvd_client().set_scene_pvd_flags(
@@ -172,6 +205,17 @@ impl ColliderScene {
     }
 }
 
+/// Creates the main scene with enhanced determinism, and builds the actors of the colliders that
+/// finished loading in a stable order. Together with a fixed time step and seeded entity ids, this
+/// makes the simulation reproducible from its recorded inputs
+#[derive(Debug, Clone)]
+pub struct DeterministicPhysicsKey;
+impl SyncAssetKey<bool> for DeterministicPhysicsKey {
+    fn load(&self, _assets: AssetCache) -> bool {
+        false
+    }
+}
+
 #[derive(Debug)]
 pub struct PxWoodMaterialKey;
 impl SyncAssetKey<PxMaterial> for PxWoodMaterialKey {
@@ -184,11 +228,20 @@ impl SyncAssetKey<PxMaterial> for PxWoodMaterialKey {
 unsafe extern "C" fn main_physx_scene_filter_shader(
     mut info: *mut physxx::sys::FilterShaderCallbackInfo,
 ) -> u16 {
+    // See `layers::CollisionLayers`; shapes without a layer collide with everything
+    let (data0, data1) = (&(*info).filterData0, &(*info).filterData1);
+    if data0.word0 != 0
+        && data1.word0 != 0
+        && ((data0.word0 & data1.word1) == 0 || (data1.word0 & data0.word1) == 0)
+    {
+        return physxx::sys::PxFilterFlag::eSUPPRESS as u16;
+    }
     (*(*info).pairFlags).mBits |= (physxx::sys::PxPairFlag::eSOLVE_CONTACT
         | physxx::sys::PxPairFlag::eDETECT_DISCRETE_CONTACT
         | physxx::sys::PxPairFlag::eDETECT_CCD_CONTACT
         | physxx::sys::PxPairFlag::eCONTACT_DEFAULT
-        | physxx::sys::PxPairFlag::eNOTIFY_TOUCH_FOUND) as u16;
+        | physxx::sys::PxPairFlag::eNOTIFY_TOUCH_FOUND
+        | physxx::sys::PxPairFlag::eNOTIFY_CONTACT_POINTS) as u16;
     (physxx::sys::PxFilterFlag::eDEFAULT) as u16
 }
 
@@ -223,13 +276,22 @@ pub fn server_systems() -> SystemGroup {
                     }
                 }),
             Box::new(collider::server_systems()),
+            Box::new(joints::server_systems()),
+            Box::new(ragdoll::server_systems()),
+            Box::new(events::server_systems()),
             Box::new(visualization::server_systems()),
         ],
     )
 }
 
 pub fn client_systems() -> SystemGroup {
-    SystemGroup::new("physics", vec![Box::new(visualization::client_systems())])
+    SystemGroup::new(
+        "physics",
+        vec![
+            Box::new(ragdoll::client_systems()),
+            Box::new(visualization::client_systems()),
+        ],
+    )
 }
 
 /// Starts the physx simulation step concurrently.
@@ -251,6 +313,7 @@ pub fn fetch_simulation_system() -> DynSystem {
         ambient_profiling::scope!("fetch_simulation_system");
 
         world.resource(collisions()).lock().clear();
+        world.resource(contacts()).lock().clear();
         world.resource_mut(collider_loads()).clear();
         let scene = world.resource(main_physics_scene());
         // Ensure the previous simulation has completed
@@ -290,6 +353,9 @@ pub fn on_forking_systems() -> SystemGroup<ForkingEvent> {
             for (id, _) in query(()).incl(revolute_joint()).collect_cloned(world, None) {
                 world.remove_component(id, revolute_joint()).unwrap();
             }
+            for (id, _) in query(()).incl(physx_joint()).collect_cloned(world, None) {
+                world.remove_component(id, physx_joint()).unwrap();
+            }
             for (id, _) in query(())
                 .incl(articulation_reduce_coordinate())
                 .collect_cloned(world, None)
@@ -0,0 +1,447 @@
+//! Ragdolls generated from the skins of models.
+//!
+//! A model built with the `ragdoll` option of the models pipeline carries its [`ragdoll_bones`].
+//! On the server, one dynamic body is created per bone, with a capsule collider along the bone and
+//! a `d6` joint to the body of its parent bone. The bodies stay kinematic and follow the model
+//! until they are simulated, either all at once with `ragdoll` on the model, or one by one with
+//! `ragdoll_blend` on a body. On the client, the bones of the model are then blended towards
+//! their bodies through `animation_pose_override`.
+//!
+//! The server does not spawn the nodes of the model, so the kinematic bodies follow the pose
+//! sampled from the `animation_controller` of the model along the [`RagdollBone::chain`] of each
+//! bone, or the bind pose while there is no animation. A ragdoll that is activated starts from the
+//! animated pose.
+
+use std::collections::HashMap;
+
+use ambient_animation::{
+    animation_controller, animation_pose_override, animation_retargeting, PoseOverride, SampledPose,
+};
+use ambient_core::{
+    asset_cache,
+    hierarchy::parent,
+    name, time,
+    transform::{get_world_transform, local_to_world, rotation, translation},
+};
+use ambient_ecs::{
+    components, query, Debuggable, Entity, EntityId, Networked, Store, SystemGroup, World,
+};
+use ambient_model::animation_binder;
+use glam::{Mat4, Quat, UVec3, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capsule_collider, density, dynamic, joint_angular_motion, joint_body0, joint_body1,
+    joint_cone_limit, joint_frame0, joint_frame1, joint_limit, joint_type, kinematic, ragdoll,
+    ragdoll_blend, ragdoll_bodies,
+};
+
+components!("physics", {
+    /// The bones of the ragdoll of this model, generated from its skin
+    @[Debuggable, Networked, Store]
+    ragdoll_bones: Vec<RagdollBone>,
+    /// The model and bone this ragdoll body belongs to
+    @[Debuggable, Networked, Store]
+    ragdoll_body: RagdollBody,
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RagdollBone {
+    /// The `animation_bind_id` of the node of this bone
+    pub bind_id: String,
+    pub name: String,
+    /// The index of the closest ancestor bone that has a body
+    pub parent: Option<usize>,
+    /// The transform of the bone relative to the model, without scale
+    pub bind_pose: Mat4,
+    /// The transform of the body relative to the bone; the capsule of the body lies along its Z axis
+    pub body_offset: Mat4,
+    pub radius: f32,
+    pub length: f32,
+    pub density: f32,
+    /// The swing limits of the joint to the parent body, in radians
+    pub swing_limit: Vec2,
+    /// The twist limits (min, max) of the joint to the parent body, in radians
+    pub twist_limit: Vec2,
+    /// The nodes from below the node of the parent bone (or from the model) down to the node of
+    /// this bone, used to pose the bone from the animation
+    #[serde(default)]
+    pub chain: Vec<RagdollChainNode>,
+}
+impl RagdollBone {
+    /// The transform of the body relative to the model, in the bind pose
+    pub fn body_bind_pose(&self) -> Mat4 {
+        self.bind_pose * self.body_offset
+    }
+    /// The frame of the joint relative to the body; the joint sits at the origin of the bone, and
+    /// its X (twist) axis runs along the bone
+    fn joint_frame(&self) -> Mat4 {
+        Mat4::from_rotation_translation(
+            Quat::from_rotation_y(-std::f32::consts::FRAC_PI_2),
+            Vec3::new(0., 0., -self.length / 2.),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RagdollChainNode {
+    /// The `animation_bind_id` of the node, or `None` for the transform of the model
+    pub bind_id: Option<String>,
+    /// The transform of the node relative to its parent in the bind pose, with scale
+    pub local_pose: Mat4,
+}
+
+/// The transforms of the bones relative to the model, without scale, with the nodes animated by
+/// `pose` and the others in their bind pose. Bones without a chain keep their bind pose.
+pub fn animated_bone_poses(
+    bones: &[RagdollBone],
+    pose: &HashMap<String, SampledPose>,
+) -> Vec<Mat4> {
+    // The transforms of the nodes of the bones, with scale, as the chains of their children start from them
+    let mut node_transforms: Vec<Option<Mat4>> = Vec::with_capacity(bones.len());
+    let mut poses = Vec::with_capacity(bones.len());
+    for bone in bones {
+        let base = match bone.parent {
+            Some(parent) => node_transforms[parent],
+            None => Some(Mat4::IDENTITY),
+        };
+        let transform = base.filter(|_| !bone.chain.is_empty()).map(|base| {
+            bone.chain.iter().fold(base, |transform, node| {
+                let sampled = node.bind_id.as_ref().and_then(|bind_id| pose.get(bind_id));
+                let local_pose = match sampled {
+                    Some(sampled) => {
+                        let (scl, rot, pos) = node.local_pose.to_scale_rotation_translation();
+                        Mat4::from_scale_rotation_translation(
+                            scl,
+                            sampled.rotation.unwrap_or(rot),
+                            sampled.translation.unwrap_or(pos),
+                        )
+                    }
+                    None => node.local_pose,
+                };
+                transform * local_pose
+            })
+        });
+        poses.push(match transform {
+            Some(transform) => {
+                let (_, rot, pos) = transform.to_scale_rotation_translation();
+                Mat4::from_rotation_translation(rot, pos)
+            }
+            None => bone.bind_pose,
+        });
+        node_transforms.push(transform);
+    }
+    poses
+}
+
+/// The transforms of the bones of `owner` relative to it, animated if it has an `animation_controller`
+fn bone_poses(world: &World, owner: EntityId, bones: &[RagdollBone]) -> Vec<Mat4> {
+    let pose = world
+        .get_ref(owner, animation_controller())
+        .ok()
+        .and_then(|controller| {
+            controller.sample_pose(
+                world.resource(asset_cache()),
+                world
+                    .get(owner, animation_retargeting())
+                    .unwrap_or_default(),
+                *world.resource(time()),
+            )
+        });
+    match pose {
+        Some(pose) => animated_bone_poses(bones, &pose),
+        None => bones.iter().map(|bone| bone.bind_pose).collect(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RagdollBody {
+    pub owner: EntityId,
+    /// The index of the bone in the `ragdoll_bones` of the owner
+    pub bone: usize,
+}
+
+/// The world transform of `owner`, without scale; ragdolls are built in unscaled model space
+fn owner_transform(world: &World, owner: EntityId) -> Mat4 {
+    let (_, rot, pos) = get_world_transform(world, owner)
+        .unwrap_or(Mat4::IDENTITY)
+        .to_scale_rotation_translation();
+    Mat4::from_rotation_translation(rot, pos)
+}
+
+fn spawn_bodies(world: &mut World, owner: EntityId, bones: &[RagdollBone]) -> Vec<EntityId> {
+    let owner_transform = owner_transform(world, owner);
+    let poses = bone_poses(world, owner, bones);
+    let mut bodies: Vec<EntityId> = Vec::with_capacity(bones.len());
+    for (index, bone) in bones.iter().enumerate() {
+        let body_bind_pose = bone.body_bind_pose();
+        let (_, rot, pos) =
+            (owner_transform * poses[index] * bone.body_offset).to_scale_rotation_translation();
+        let mut body = Entity::new()
+            .with(name(), bone.name.clone())
+            .with(ragdoll_body(), RagdollBody { owner, bone: index })
+            .with(translation(), pos)
+            .with(rotation(), rot)
+            .with(
+                capsule_collider(),
+                Vec2::new(bone.radius, (bone.length - 2. * bone.radius).max(0.)),
+            )
+            .with(density(), bone.density)
+            .with(dynamic(), true)
+            .with_default(kinematic());
+        if let Some(parent) = bone.parent {
+            let frame1 = bone.joint_frame();
+            let frame0 = bones[parent].body_bind_pose().inverse() * body_bind_pose * frame1;
+            body = body
+                .with(joint_type(), "d6".to_string())
+                .with(joint_body0(), bodies[parent])
+                .with(joint_frame0(), frame0)
+                .with(joint_frame1(), frame1)
+                .with(joint_angular_motion(), UVec3::ONE)
+                .with(joint_limit(), bone.twist_limit)
+                .with(joint_cone_limit(), bone.swing_limit);
+        }
+        let id = body.spawn(world);
+        if bone.parent.is_some() {
+            world.add_component(id, joint_body1(), id).unwrap();
+        }
+        bodies.push(id);
+    }
+    bodies
+}
+
+fn is_simulated(world: &World, owner: EntityId, body: EntityId) -> bool {
+    world.has_component(owner, ragdoll()) || world.get(body, ragdoll_blend()).unwrap_or(0.) > 0.
+}
+
+pub fn server_systems() -> SystemGroup {
+    SystemGroup::new(
+        "ragdoll",
+        vec![
+            query((ragdoll_bones(),))
+                .excl(ragdoll_bodies())
+                .to_system(|q, world, qs, _| {
+                    for (owner, (bones,)) in q.collect_cloned(world, qs) {
+                        let bodies = spawn_bodies(world, owner, &bones);
+                        world
+                            .add_component(owner, ragdoll_bodies(), bodies)
+                            .unwrap();
+                    }
+                }),
+            query((ragdoll_bodies(),))
+                .despawned()
+                .to_system(|q, world, qs, _| {
+                    for (_, (bodies,)) in q.collect_cloned(world, qs) {
+                        for body in bodies {
+                            world.despawn(body);
+                        }
+                    }
+                }),
+            query((ragdoll_bones(), ragdoll_bodies())).to_system(|q, world, qs, _| {
+                for (owner, (bones, bodies)) in q.collect_cloned(world, qs) {
+                    let owner_transform = owner_transform(world, owner);
+                    let poses = bone_poses(world, owner, &bones);
+                    for ((bone, &body), pose) in bones.iter().zip(bodies.iter()).zip(poses) {
+                        if !world.exists(body) {
+                            continue;
+                        }
+                        let simulated = is_simulated(world, owner, body);
+                        if simulated == world.has_component(body, kinematic()) {
+                            // Toggling `kinematic` recreates the actor from the current pose
+                            if simulated {
+                                world.remove_component(body, kinematic()).unwrap();
+                            } else {
+                                world.add_component(body, kinematic(), ()).unwrap();
+                            }
+                        }
+                        if !simulated {
+                            let (_, rot, pos) = (owner_transform * pose * bone.body_offset)
+                                .to_scale_rotation_translation();
+                            world.set_if_changed(body, translation(), pos).unwrap();
+                            world.set_if_changed(body, rotation(), rot).unwrap();
+                        }
+                    }
+                }
+            }),
+        ],
+    )
+}
+
+/// The world pose of a bone, derived from its body
+fn bone_pose(world: &World, bone: &RagdollBone, body: EntityId) -> Option<(Quat, Vec3)> {
+    let body_rotation = world.get(body, rotation()).ok()?;
+    let body_translation = world.get(body, translation()).ok()?;
+    let (_, offset_rotation, offset_translation) = bone.body_offset.to_scale_rotation_translation();
+    let bone_rotation = body_rotation * offset_rotation.inverse();
+    Some((
+        bone_rotation,
+        body_translation - bone_rotation * offset_translation,
+    ))
+}
+
+pub fn client_systems() -> SystemGroup {
+    SystemGroup::new(
+        "ragdoll/client",
+        vec![
+            query((ragdoll_bones(), ragdoll_bodies(), animation_binder())).to_system(
+                |q, world, qs, _| {
+                    for (owner, (bones, bodies, binder)) in q.collect_cloned(world, qs) {
+                        let owner_blend = world
+                            .has_component(owner, ragdoll())
+                            .then(|| world.get(owner, ragdoll_blend()).unwrap_or(1.));
+                        let weights = bodies
+                            .iter()
+                            .map(|&body| {
+                                owner_blend.unwrap_or_else(|| {
+                                    world.get(body, ragdoll_blend()).unwrap_or(0.)
+                                })
+                            })
+                            .collect::<Vec<_>>();
+                        let poses = bones
+                            .iter()
+                            .zip(bodies.iter())
+                            .map(|(bone, &body)| bone_pose(world, bone, body))
+                            .collect::<Vec<_>>();
+
+                        for (index, bone) in bones.iter().enumerate() {
+                            let node = match binder.get(&bone.bind_id) {
+                                Some(&node) => node,
+                                None => continue,
+                            };
+                            let (bone_rotation, bone_translation) = match poses[index] {
+                                Some(pose) if weights[index] > 0. => pose,
+                                // The bone is animated, or its body is gone
+                                _ => {
+                                    if world.has_component(node, animation_pose_override()) {
+                                        world
+                                            .remove_component(node, animation_pose_override())
+                                            .unwrap();
+                                    }
+                                    continue;
+                                }
+                            };
+                            let weight = weights[index];
+                            let node_parent = world.get(node, parent()).ok();
+                            // Bones directly under a simulated bone are posed relative to its body;
+                            // the others are posed relative to their (animated) parent node
+                            let parent_body_pose = bone.parent.and_then(|p| {
+                                let parent_node = binder.get(&bones[p].bind_id).copied();
+                                (weights[p] > 0.
+                                    && parent_node.is_some()
+                                    && parent_node == node_parent)
+                                    .then_some(poses[p])
+                                    .flatten()
+                            });
+                            let pose = if let Some((parent_rotation, _)) = parent_body_pose {
+                                PoseOverride {
+                                    translation: None,
+                                    rotation: parent_rotation.inverse() * bone_rotation,
+                                    weight,
+                                }
+                            } else {
+                                let parent_transform = node_parent
+                                    .and_then(|p| world.get(p, local_to_world()).ok())
+                                    .unwrap_or(Mat4::IDENTITY);
+                                let (_, parent_rotation, _) =
+                                    parent_transform.to_scale_rotation_translation();
+                                PoseOverride {
+                                    translation: Some(
+                                        parent_transform
+                                            .inverse()
+                                            .transform_point3(bone_translation),
+                                    ),
+                                    rotation: parent_rotation.inverse() * bone_rotation,
+                                    weight,
+                                }
+                            };
+                            world
+                                .add_component(node, animation_pose_override(), pose)
+                                .unwrap();
+                        }
+                    }
+                },
+            ),
+        ],
+    )
+}
+
+#[test]
+fn test_animated_bone_poses() {
+    use glam::vec3;
+
+    let bone =
+        |name: &str, parent: Option<usize>, bind_pose: Mat4, chain: Vec<RagdollChainNode>| {
+            RagdollBone {
+                bind_id: name.to_string(),
+                name: name.to_string(),
+                parent,
+                bind_pose,
+                body_offset: Mat4::from_translation(vec3(0., 0., 0.5)),
+                radius: 0.1,
+                length: 1.,
+                density: 1.,
+                swing_limit: Vec2::ONE,
+                twist_limit: Vec2::ONE,
+                chain,
+            }
+        };
+    let node = |bind_id: Option<&str>, local_pose: Mat4| RagdollChainNode {
+        bind_id: bind_id.map(|bind_id| bind_id.to_string()),
+        local_pose,
+    };
+    // A model scaled down 100 times, with the hips 1m up and the spine 0.5m above them
+    let model = Mat4::from_scale(Vec3::splat(0.01));
+    let bones = vec![
+        bone(
+            "Hips",
+            None,
+            Mat4::from_translation(vec3(0., 0., 1.)),
+            vec![
+                node(None, model),
+                node(Some("Hips"), Mat4::from_translation(vec3(0., 0., 100.))),
+            ],
+        ),
+        bone(
+            "Spine",
+            Some(0),
+            Mat4::from_translation(vec3(0., 0., 1.5)),
+            vec![node(
+                Some("Spine"),
+                Mat4::from_translation(vec3(0., 0., 50.)),
+            )],
+        ),
+        // A bone built before the chains were stored keeps its bind pose
+        bone(
+            "Head",
+            Some(1),
+            Mat4::from_translation(vec3(0., 0., 2.)),
+            vec![],
+        ),
+    ];
+
+    // Without animation, the bones are in their bind pose
+    let poses = animated_bone_poses(&bones, &HashMap::new());
+    for (pose, bone) in poses.iter().zip(bones.iter()) {
+        assert!(pose.abs_diff_eq(bone.bind_pose, 0.0001));
+    }
+
+    // The hips are lowered and turned on their side; the spine follows them
+    let rotation = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    let pose = HashMap::from([(
+        "Hips".to_string(),
+        SampledPose {
+            translation: Some(vec3(0., 0., 20.)),
+            rotation: Some(rotation),
+        },
+    )]);
+    let poses = animated_bone_poses(&bones, &pose);
+    let hips = Mat4::from_rotation_translation(rotation, vec3(0., 0., 0.2));
+    assert!(poses[0].abs_diff_eq(hips, 0.0001));
+    let spine = Mat4::from_rotation_translation(rotation, vec3(0., -0.5, 0.2));
+    assert!(poses[1].abs_diff_eq(spine, 0.0001));
+    assert!(poses[2].abs_diff_eq(bones[2].bind_pose, 0.0001));
+
+    // The kinematic bodies follow the animated pose, so that activating the ragdoll keeps it
+    let body = (poses[1] * bones[1].body_offset).transform_point3(Vec3::ZERO);
+    assert!((body - vec3(0., -1., 0.2)).length() < 0.0001);
+}
@@ -1,5 +1,5 @@
 use crate::{
-    collider::{collider_shapes, collider_shapes_convex},
+    collider::{collider, collider_shapes, collider_shapes_convex, ColliderDef},
     main_physics_scene,
     physx::{physics_shape, rigid_actor},
     picking_scene, trigger_areas_scene,
@@ -119,10 +119,38 @@ pub fn server_systems() -> SystemGroup {
 
                     let mut current = Vec::new();
 
-                    let (_, _, pos) = ltw.to_scale_rotation_translation();
+                    let (scale, _, pos) = ltw.to_scale_rotation_translation();
 
                     current.push(GizmoPrimitive::sphere(pos, 0.15).with_color(Vec3::X));
 
+                    // Primitive colliders which aren't easy to make out from the PhysX debug lines
+                    match w.get_ref(id, collider()) {
+                        Ok(ColliderDef::Capsule {
+                            radius,
+                            height,
+                            center,
+                        }) => {
+                            let center = ltw.transform_point3(*center);
+                            let axis = ltw.transform_vector3(Vec3::Z * *height / 2.);
+                            let radius = *radius * scale.x.max(scale.y);
+                            current.extend(
+                                [
+                                    GizmoPrimitive::sphere(center - axis, radius),
+                                    GizmoPrimitive::sphere(center + axis, radius),
+                                    GizmoPrimitive::line(center - axis, center + axis, radius),
+                                ]
+                                .map(|x| x.with_color(Vec3::Z)),
+                            );
+                        }
+                        Ok(ColliderDef::Convex { points }) => {
+                            current.extend(points.iter().map(|&point| {
+                                GizmoPrimitive::sphere(ltw.transform_point3(point), 0.05)
+                                    .with_color(Vec3::Z)
+                            }));
+                        }
+                        _ => {}
+                    }
+
                     if let Ok(shape) = w.get_ref(id, physics_shape()) {
                         let actor = shape.get_actor().unwrap();
                         current.push(
@@ -0,0 +1,494 @@
+//! Asset archives: a built project in a single file, for distribution.
+//!
+//! The files are split into chunks of [`CHUNK_SIZE`] bytes, which are compressed with deflate and
+//! stored once per content hash, so identical files and chunks take no extra space. The archive
+//! starts with a fixed-size header pointing to the index at its end, which lists the files and
+//! their chunks. The index and every chunk are checked against their SHA-256 hash when read.
+//!
+//! Archives are read from disk, or over HTTP with range requests, through urls such as
+//! `archive+https://example.com/game.ambarc/build/assets/a.png`; see
+//! [`AbsAssetUrl::archive_entry`].
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_cache::{AssetCache, AsyncAssetKey, AsyncAssetKeyExt},
+    asset_url::AbsAssetUrl,
+    download_asset::{download_range, AssetResult},
+    path::path_to_unix_string,
+};
+
+pub const ARCHIVE_EXTENSION: &str = "ambarc";
+/// The files are split into chunks of this size, which are compressed and deduplicated separately
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+const MAGIC: &[u8; 8] = b"AMBARC\0\x01";
+/// The magic, the offset and size of the index, and its hash
+const HEADER_SIZE: u64 = 8 + 8 + 8 + 32;
+
+// Archives can come from anywhere, so everything read from them is bounded by these
+/// The largest index read
+const MAX_INDEX_SIZE: u64 = 64 << 20;
+/// The largest compressed chunk; deflate adds a few bytes to incompressible data
+const MAX_COMPRESSED_CHUNK_SIZE: u64 = 2 * CHUNK_SIZE as u64;
+/// The largest file read from an archive
+const MAX_FILE_SIZE: u64 = 1 << 30;
+/// The largest range read at once; adjacent chunks are read together up to this size
+const MAX_RANGE_SIZE: u64 = 16 << 20;
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .try_into()
+        .unwrap()
+}
+
+struct ArchiveHeader {
+    index_offset: u64,
+    index_size: u64,
+    index_hash: [u8; 32],
+}
+impl ArchiveHeader {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&self.index_offset.to_le_bytes());
+        bytes.extend_from_slice(&self.index_size.to_le_bytes());
+        bytes.extend_from_slice(&self.index_hash);
+        bytes
+    }
+    fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < HEADER_SIZE as usize || &bytes[0..8] != MAGIC {
+            anyhow::bail!("Not an asset archive");
+        }
+        let header = Self {
+            index_offset: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            index_size: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            index_hash: bytes[24..56].try_into().unwrap(),
+        };
+        if header.index_offset < HEADER_SIZE
+            || header.index_size > MAX_INDEX_SIZE
+            || header.index_offset.checked_add(header.index_size).is_none()
+        {
+            anyhow::bail!("The header of the archive is corrupted");
+        }
+        Ok(header)
+    }
+    fn index_range(&self) -> Range<u64> {
+        self.index_offset..self.index_offset + self.index_size
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    /// The files, by their path in the archive
+    pub files: BTreeMap<String, ArchiveFile>,
+    pub chunks: Vec<ArchiveChunk>,
+}
+impl ArchiveIndex {
+    fn parse(header: &ArchiveHeader, bytes: &[u8]) -> anyhow::Result<Self> {
+        if sha256(bytes) != header.index_hash {
+            anyhow::bail!("The index of the archive is corrupted");
+        }
+        let index: Self = bincode::deserialize(bytes)?;
+        index.validate(header)?;
+        Ok(index)
+    }
+    /// Checks that the chunks are between the header and the index, and that the files are made
+    /// of existing chunks, so that reading them is bounded
+    fn validate(&self, header: &ArchiveHeader) -> anyhow::Result<()> {
+        for chunk in &self.chunks {
+            let end = chunk.offset.checked_add(chunk.compressed_size);
+            if chunk.offset < HEADER_SIZE
+                || end.map(|end| end > header.index_offset).unwrap_or(true)
+                || chunk.size > CHUNK_SIZE as u64
+                || chunk.compressed_size > MAX_COMPRESSED_CHUNK_SIZE
+            {
+                anyhow::bail!("The chunk at {} of the archive is corrupted", chunk.offset);
+            }
+        }
+        for (path, file) in &self.files {
+            let mut size = 0u64;
+            for index in &file.chunks {
+                let chunk = self.chunks.get(*index).with_context(|| {
+                    format!("The file {path} of the archive has a missing chunk")
+                })?;
+                size = size.saturating_add(chunk.size);
+            }
+            if size != file.size || file.size > MAX_FILE_SIZE {
+                anyhow::bail!("The size of the file {path} of the archive is corrupted");
+            }
+        }
+        Ok(())
+    }
+    /// The size of the files, before compression and deduplication
+    pub fn files_size(&self) -> u64 {
+        self.files.values().map(|file| file.size).sum()
+    }
+    /// The size of the stored chunks
+    pub fn chunks_size(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.compressed_size).sum()
+    }
+    /// The ranges of the archive to read for the file at `path`, and the chunks in each range, or
+    /// `None` if there is no such file; adjacent chunks are read together
+    pub fn file_ranges(
+        &self,
+        path: &str,
+    ) -> anyhow::Result<Option<Vec<(Range<u64>, Vec<&ArchiveChunk>)>>> {
+        let file = match self.files.get(path) {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let mut ranges: Vec<(Range<u64>, Vec<&ArchiveChunk>)> = Vec::new();
+        for index in &file.chunks {
+            let chunk = self
+                .chunks
+                .get(*index)
+                .with_context(|| format!("The file {path} of the archive has a missing chunk"))?;
+            match ranges.last_mut() {
+                Some((range, chunks))
+                    if range.end == chunk.offset
+                        && range.end - range.start + chunk.compressed_size <= MAX_RANGE_SIZE =>
+                {
+                    range.end += chunk.compressed_size;
+                    chunks.push(chunk);
+                }
+                _ => ranges.push((chunk.range(), vec![chunk])),
+            }
+        }
+        Ok(Some(ranges))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    pub size: u64,
+    /// The indices of the chunks of the file, in order
+    pub chunks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveChunk {
+    pub offset: u64,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// The SHA-256 hash of the uncompressed chunk
+    pub hash: [u8; 32],
+}
+impl ArchiveChunk {
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.compressed_size
+    }
+    /// Decompresses the chunk, and checks it against its hash
+    pub fn decode(&self, compressed: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let size = self.size.min(CHUNK_SIZE as u64);
+        let mut data = Vec::with_capacity(size as usize);
+        // Stop right after the expected size, rather than inflating whatever the chunk holds
+        DeflateDecoder::new(compressed)
+            .take(size + 1)
+            .read_to_end(&mut data)
+            .context("Failed to decompress chunk")?;
+        if data.len() as u64 != self.size || sha256(&data) != self.hash {
+            anyhow::bail!("Chunk at {} is corrupted", self.offset);
+        }
+        Ok(data)
+    }
+}
+
+/// Writes an archive; the header is written by [`ArchiveWriter::finish`]
+pub struct ArchiveWriter<W: Write + Seek> {
+    out: W,
+    offset: u64,
+    index: ArchiveIndex,
+    chunks_by_hash: HashMap<[u8; 32], usize>,
+}
+impl<W: Write + Seek> ArchiveWriter<W> {
+    pub fn new(mut out: W) -> anyhow::Result<Self> {
+        out.write_all(&[0; HEADER_SIZE as usize])?;
+        Ok(Self {
+            out,
+            offset: HEADER_SIZE,
+            index: Default::default(),
+            chunks_by_hash: Default::default(),
+        })
+    }
+    pub fn add_file(&mut self, path: impl Into<String>, data: &[u8]) -> anyhow::Result<()> {
+        let mut chunks = Vec::new();
+        for data in data.chunks(CHUNK_SIZE) {
+            let hash = sha256(data);
+            let index = match self.chunks_by_hash.get(&hash) {
+                Some(index) => *index,
+                None => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(data)?;
+                    let compressed = encoder.finish()?;
+                    self.out.write_all(&compressed)?;
+                    self.index.chunks.push(ArchiveChunk {
+                        offset: self.offset,
+                        compressed_size: compressed.len() as u64,
+                        size: data.len() as u64,
+                        hash,
+                    });
+                    self.offset += compressed.len() as u64;
+                    let index = self.index.chunks.len() - 1;
+                    self.chunks_by_hash.insert(hash, index);
+                    index
+                }
+            };
+            chunks.push(index);
+        }
+        self.index.files.insert(
+            path.into(),
+            ArchiveFile {
+                size: data.len() as u64,
+                chunks,
+            },
+        );
+        Ok(())
+    }
+    pub fn finish(mut self) -> anyhow::Result<ArchiveIndex> {
+        let index = bincode::serialize(&self.index)?;
+        self.out.write_all(&index)?;
+        let header = ArchiveHeader {
+            index_offset: self.offset,
+            index_size: index.len() as u64,
+            index_hash: sha256(&index),
+        };
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header.to_bytes())?;
+        self.out.flush()?;
+        Ok(self.index)
+    }
+}
+
+/// Writes all the files in `dir` to an archive at `out`, with `prefix` prepended to their paths
+pub fn pack_directory(dir: &Path, prefix: &str, out: &Path) -> anyhow::Result<ArchiveIndex> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in
+            std::fs::read_dir(&current).context(format!("Failed to read dir: {current:?}"))?
+        {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_symlink() && path.is_dir() {
+                // Symlinked directories could point anywhere, including back up the tree
+                log::warn!("Not packaging the symlinked directory {path:?}");
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    let file = File::create(out).context(format!("Failed to create archive: {out:?}"))?;
+    let mut writer = ArchiveWriter::new(BufWriter::new(file))?;
+    for path in files {
+        let data = std::fs::read(&path).context(format!("Failed to read file: {path:?}"))?;
+        let relative_path = path_to_unix_string(path.strip_prefix(dir)?);
+        writer.add_file(format!("{prefix}{relative_path}"), &data)?;
+    }
+    writer.finish()
+}
+
+/// Reads a range of the archive at `url`, from disk or with an HTTP range request
+async fn read_range(
+    assets: &AssetCache,
+    url: &AbsAssetUrl,
+    range: Range<u64>,
+) -> anyhow::Result<Vec<u8>> {
+    if range.end < range.start || range.end - range.start > MAX_INDEX_SIZE.max(MAX_RANGE_SIZE) {
+        anyhow::bail!("Invalid range {range:?} of the archive {url}");
+    }
+    if let Some(path) = url.to_file_path()? {
+        return ambient_sys::task::spawn_blocking(move || {
+            let mut file =
+                File::open(&path).context(format!("Failed to open archive: {path:?}"))?;
+            if range.end > file.metadata()?.len() {
+                anyhow::bail!("The archive {path:?} is truncated");
+            }
+            file.seek(SeekFrom::Start(range.start))?;
+            let mut data = vec![0; (range.end - range.start) as usize];
+            file.read_exact(&mut data)
+                .context(format!("Failed to read archive: {path:?}"))?;
+            anyhow::Ok(data)
+        })
+        .await?;
+    }
+    download_range(assets, url.0.clone(), range).await
+}
+
+/// The index of the archive at the url
+#[derive(Debug, Clone)]
+pub struct ArchiveIndexFromUrl(pub AbsAssetUrl);
+#[async_trait]
+impl AsyncAssetKey<AssetResult<Arc<ArchiveIndex>>> for ArchiveIndexFromUrl {
+    async fn load(self, assets: AssetCache) -> AssetResult<Arc<ArchiveIndex>> {
+        let header = ArchiveHeader::parse(&read_range(&assets, &self.0, 0..HEADER_SIZE).await?)
+            .context(format!("Failed to read archive: {}", self.0))?;
+        let index = read_range(&assets, &self.0, header.index_range()).await?;
+        Ok(Arc::new(
+            ArchiveIndex::parse(&header, &index)
+                .context(format!("Failed to read archive: {}", self.0))?,
+        ))
+    }
+}
+
+/// Reads the file at `path` in the archive at `archive`
+pub async fn read_archive_file(
+    assets: &AssetCache,
+    archive: &AbsAssetUrl,
+    path: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let index = ArchiveIndexFromUrl(archive.clone()).get(assets).await?;
+    let ranges = index
+        .file_ranges(path)?
+        .with_context(|| format!("No file {path} in the archive {archive}"))?;
+    let size = index
+        .files
+        .get(path)
+        .map(|file| file.size)
+        .unwrap_or_default();
+    let mut data = Vec::with_capacity(size.min(MAX_FILE_SIZE) as usize);
+    for (range, chunks) in ranges {
+        let start = range.start;
+        let compressed = read_range(assets, archive, range).await?;
+        for chunk in chunks {
+            let chunk_range = chunk.range();
+            let compressed = compressed
+                .get((chunk_range.start - start) as usize..(chunk_range.end - start) as usize)
+                .with_context(|| format!("The archive {archive} is truncated"))?;
+            data.extend(
+                chunk
+                    .decode(compressed)
+                    .with_context(|| format!("Failed to read {path} from the archive {archive}"))?,
+            );
+        }
+    }
+    Ok(data)
+}
+
+#[test]
+fn test_archive_roundtrip() {
+    let big = (0..CHUNK_SIZE * 2 + 10)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let files: [(&str, &[u8]); 4] = [
+        ("a.txt", b"hello"),
+        ("copy_of_a.txt", b"hello"),
+        ("empty", b""),
+        ("big.bin", &big),
+    ];
+
+    let mut bytes = Vec::new();
+    let mut writer = ArchiveWriter::new(std::io::Cursor::new(&mut bytes)).unwrap();
+    for (path, data) in files {
+        writer.add_file(path, data).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let header = ArchiveHeader::parse(&bytes).unwrap();
+    let index_range = header.index_range();
+    let index = ArchiveIndex::parse(
+        &header,
+        &bytes[index_range.start as usize..index_range.end as usize],
+    )
+    .unwrap();
+    // The copy is deduplicated, and the big file takes three chunks
+    assert_eq!(index.chunks.len(), 4);
+    assert_eq!(index.files_size(), 10 + big.len() as u64);
+
+    let read = |bytes: &[u8], path: &str| -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for (_, chunks) in index.file_ranges(path)?.unwrap() {
+            for chunk in chunks {
+                let range = chunk.range();
+                data.extend(chunk.decode(&bytes[range.start as usize..range.end as usize])?);
+            }
+        }
+        Ok(data)
+    };
+    for (path, data) in files {
+        assert_eq!(read(&bytes, path).unwrap(), data);
+    }
+    assert!(index.file_ranges("missing").unwrap().is_none());
+
+    let mut corrupted = bytes.clone();
+    corrupted[index.chunks[0].offset as usize] ^= 0xff;
+    assert!(read(&corrupted, "a.txt").is_err());
+}
+
+#[test]
+fn test_archive_corrupted_index() {
+    let mut bytes = Vec::new();
+    let mut writer = ArchiveWriter::new(std::io::Cursor::new(&mut bytes)).unwrap();
+    writer.add_file("a.txt", b"hello").unwrap();
+    let index = writer.finish().unwrap();
+    let header = ArchiveHeader::parse(&bytes).unwrap();
+    assert!(index.validate(&header).is_ok());
+
+    // The header points outside of what can be read
+    let mut huge_index = bytes.clone();
+    huge_index[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(ArchiveHeader::parse(&huge_index).is_err());
+    let mut overflowing = bytes.clone();
+    overflowing[8..16].copy_from_slice(&(u64::MAX - 10).to_le_bytes());
+    assert!(ArchiveHeader::parse(&overflowing).is_err());
+
+    let corrupt = |f: &dyn Fn(&mut ArchiveIndex)| {
+        let mut index = index.clone();
+        f(&mut index);
+        index
+    };
+    // A missing chunk
+    let missing_chunk = corrupt(&|index| index.files.get_mut("a.txt").unwrap().chunks = vec![7]);
+    assert!(missing_chunk.validate(&header).is_err());
+    assert!(missing_chunk.file_ranges("a.txt").is_err());
+    // A chunk in the index, or past it
+    assert!(
+        corrupt(&|index| index.chunks[0].offset = header.index_offset)
+            .validate(&header)
+            .is_err()
+    );
+    assert!(corrupt(&|index| index.chunks[0].compressed_size = u64::MAX)
+        .validate(&header)
+        .is_err());
+    // Sizes that don't add up, or that are too large
+    assert!(
+        corrupt(&|index| index.files.get_mut("a.txt").unwrap().size = u64::MAX)
+            .validate(&header)
+            .is_err()
+    );
+    assert!(corrupt(&|index| index.chunks[0].size = u64::MAX)
+        .validate(&header)
+        .is_err());
+    let repeated = corrupt(&|index| {
+        index.chunks[0].size = CHUNK_SIZE as u64;
+        let file = index.files.get_mut("a.txt").unwrap();
+        file.chunks = vec![0; (MAX_FILE_SIZE / CHUNK_SIZE as u64 + 1) as usize];
+        file.size = CHUNK_SIZE as u64 * file.chunks.len() as u64;
+    });
+    assert!(repeated.validate(&header).is_err());
+
+    // A chunk that inflates to more than its size
+    let chunk = &index.chunks[0];
+    let compressed = &bytes[chunk.range().start as usize..chunk.range().end as usize];
+    let smaller = ArchiveChunk {
+        size: 2,
+        ..chunk.clone()
+    };
+    assert!(smaller.decode(compressed).is_err());
+}
@@ -16,14 +16,22 @@ use serde::{
 use url::Url;
 
 use crate::{
+    asset_archive::ARCHIVE_EXTENSION,
     asset_cache::{AssetCache, SyncAssetKey, SyncAssetKeyExt},
-    download_asset::{download, AssetsCacheDir},
+    download_asset::{download, download_without_http, AssetsCacheDir},
     Cb,
 };
 
 pub use url::ParseError;
 
 pub const ASSETS_PROTOCOL_SCHEME: &str = "ambient-assets";
+/// Prepended to the scheme of the url of an archive to point to the files in it, as in
+/// `archive+https://example.com/game.ambarc/build/assets/a.png`; see [`crate::asset_archive`]
+pub const ARCHIVE_SCHEME_PREFIX: &str = "archive+";
+/// The scheme of the files that the server the client is connected to sends over the game
+/// connection, as in `ambient-server:/assets/a.png`; see
+/// [`crate::download_asset::AssetServerClient`]
+pub const ASSET_SERVER_SCHEME: &str = "ambient-server";
 
 #[derive(Debug, Clone)]
 pub struct ServerBaseUrlKey;
@@ -110,6 +118,34 @@ impl AbsAssetUrl {
         ))?))
     }
 
+    /// The url of the root directory of the archive at `archive`
+    pub fn from_archive(archive: &AbsAssetUrl) -> Result<Self, ParseError> {
+        Ok(Self(Url::parse(&format!(
+            "{ARCHIVE_SCHEME_PREFIX}{}/",
+            archive.as_file()
+        ))?))
+    }
+    /// The url of the root directory of the files of the server the client is connected to
+    pub fn asset_server_root() -> Self {
+        Self(Url::parse(&format!("{ASSET_SERVER_SCHEME}:/")).unwrap())
+    }
+    /// If this url points to a file of the server the client is connected to, the path of the
+    /// file relative to the content root of the server
+    pub fn asset_server_path(&self) -> Option<String> {
+        if self.0.scheme() != ASSET_SERVER_SCHEME {
+            return None;
+        }
+        Some(self.path().as_str().trim_start_matches('/').to_string())
+    }
+    /// If this url points into an archive, the url of the archive and the path of the file in it
+    pub fn archive_entry(&self) -> Option<(AbsAssetUrl, String)> {
+        let url = self.0.as_str().strip_prefix(ARCHIVE_SCHEME_PREFIX)?;
+        let end = url.find(&format!(".{ARCHIVE_EXTENSION}/"))? + ARCHIVE_EXTENSION.len() + 1;
+        let archive = AbsAssetUrl::parse(&url[..end]).ok()?;
+        let path = percent_decode_str(&url[end + 1..]).decode_utf8().ok()?;
+        Some((archive, path.to_string()))
+    }
+
     pub fn relative_cache_path(&self) -> String {
         self.0.to_string().replace("://", "/").replace(':', "_")
     }
@@ -218,11 +254,17 @@ impl AbsAssetUrl {
     fn to_download_raw_url(&self, assets: &AssetCache) -> Result<Url, url::ParseError> {
         self.to_download_url_with_base(&ContentBaseUrlKey.get(assets))
     }
+    /// Reads the file if it's not downloaded over HTTP; see [`download_without_http`]
+    async fn read_without_http(&self, assets: &AssetCache) -> anyhow::Result<Option<Vec<u8>>> {
+        download_without_http(assets, &self.to_download_url(assets)?).await
+    }
     pub async fn download_bytes(&self, assets: &AssetCache) -> anyhow::Result<Vec<u8>> {
         if let Some(path) = self.to_file_path()? {
             Ok(ambient_sys::fs::read(path)
                 .await
                 .context(format!("Failed to read file at: {:}", self.0))?)
+        } else if let Some(data) = self.read_without_http(assets).await? {
+            Ok(data)
         } else {
             Ok(
                 download(assets, self.to_download_raw_url(assets)?, |resp| async {
@@ -238,6 +280,8 @@ impl AbsAssetUrl {
             Ok(ambient_sys::fs::read_to_string(path)
                 .await
                 .context(format!("Failed to read file at: {:}", self.0))?)
+        } else if let Some(data) = self.read_without_http(assets).await? {
+            Ok(String::from_utf8(data)?)
         } else {
             Ok(
                 download(assets, self.to_download_raw_url(assets)?, |resp| async {
@@ -256,6 +300,8 @@ impl AbsAssetUrl {
                 .await
                 .context(format!("Failed to read file at: {:}", self.0))?;
             Ok(serde_json::from_slice(&content)?)
+        } else if let Some(data) = self.read_without_http(assets).await? {
+            Ok(serde_json::from_slice(&data)?)
         } else {
             Ok(
                 download(assets, self.to_download_raw_url(assets)?, |resp| async {
@@ -393,6 +439,62 @@ fn test_abs_asset_url_to_download_url() {
     );
 }
 
+#[test]
+fn test_abs_asset_url_archive() {
+    let archive = AbsAssetUrl::parse("https://t.c/games/game.ambarc").unwrap();
+    let root = AbsAssetUrl::from_archive(&archive).unwrap();
+    assert_eq!(root.to_string(), "archive+https://t.c/games/game.ambarc/");
+    assert_eq!(root.archive_entry(), Some((archive.clone(), String::new())));
+
+    let file = root.push("build/assets/a b.png").unwrap();
+    assert_eq!(
+        file.archive_entry(),
+        Some((archive.clone(), "build/assets/a b.png".to_string()))
+    );
+    assert_eq!(
+        file.join("c.png").unwrap().archive_entry(),
+        Some((archive, "build/assets/c.png".to_string()))
+    );
+
+    let base_url = root.push("build/").unwrap();
+    assert_eq!(
+        AbsAssetUrl::parse(format!("{}:/a/b/c.png", ASSETS_PROTOCOL_SCHEME))
+            .unwrap()
+            .to_download_url_with_base(&base_url)
+            .unwrap()
+            .to_string(),
+        "archive+https://t.c/games/game.ambarc/build/a/b/c.png"
+    );
+    assert_eq!(
+        AbsAssetUrl::parse("https://t.c/games/game.ambarc/a.png")
+            .unwrap()
+            .archive_entry(),
+        None
+    );
+}
+
+#[test]
+fn test_abs_asset_url_asset_server() {
+    let root = AbsAssetUrl::asset_server_root();
+    assert_eq!(root.to_string(), "ambient-server:/");
+    assert_eq!(root.asset_server_path(), Some(String::new()));
+    assert_eq!(
+        AbsAssetUrl::parse(format!("{}:/a/b c.png", ASSETS_PROTOCOL_SCHEME))
+            .unwrap()
+            .to_download_url_with_base(&root.push("build/").unwrap())
+            .map(AbsAssetUrl)
+            .unwrap()
+            .asset_server_path(),
+        Some("build/a/b c.png".to_string())
+    );
+    assert_eq!(
+        AbsAssetUrl::parse("https://t.c/a.png")
+            .unwrap()
+            .asset_server_path(),
+        None
+    );
+}
+
 /// This is either an absolute url (which can also be an absolute file:// url),
 /// or a relative path which needs to be resolved
 ///
@@ -1,4 +1,4 @@
-use std::{marker::PhantomData, path::PathBuf, sync::Arc, time::Duration};
+use std::{marker::PhantomData, ops::Range, path::PathBuf, sync::Arc, time::Duration};
 
 use ambient_sys::task::wasm_nonsend;
 use anyhow::{anyhow, Context};
@@ -9,6 +9,7 @@ use thiserror::Error;
 use tokio::sync::Semaphore;
 
 use crate::{
+    asset_archive::read_archive_file,
     asset_cache::{
         AssetCache, AssetKeepalive, AsyncAssetKey, AsyncAssetKeyExt, SyncAssetKey, SyncAssetKeyExt,
     },
@@ -70,11 +71,135 @@ impl SyncAssetKey<reqwest::Client> for ReqwestClientKey {
     }
 }
 
+/// How urgently a file is needed. When several files are downloaded from the server at once, the
+/// ones of higher priority are sent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DownloadPriority {
+    Low,
+    Normal,
+    High,
+}
+impl DownloadPriority {
+    /// The priority of the file at `path`, from its extension: the modules and the metadata
+    /// needed to start the game come first, and the sounds last
+    pub fn of(path: &str) -> Self {
+        let extension = path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+        match extension.as_deref() {
+            Some("wasm" | "toml" | "json") => Self::High,
+            Some("ogg" | "wav" | "mp3" | "flac") => Self::Low,
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// Downloads the files under [`ASSET_SERVER_SCHEME`](crate::asset_url::ASSET_SERVER_SCHEME)
+/// urls from the server the client is connected to
+#[async_trait]
+pub trait AssetServerClient: Send + Sync {
+    /// Downloads the file at `path`, relative to the content root of the server
+    async fn download(&self, path: &str, priority: DownloadPriority) -> anyhow::Result<Vec<u8>>;
+}
+
+/// The connection to the server used to download its files, once the client is connected
+#[derive(Clone, Debug)]
+pub struct AssetServerClientKey;
+impl SyncAssetKey<Option<Arc<dyn AssetServerClient>>> for AssetServerClientKey {
+    fn load(&self, _assets: AssetCache) -> Option<Arc<dyn AssetServerClient>> {
+        None
+    }
+}
+
+/// Reads the files that aren't downloaded with a plain HTTP request: the files in archives, and
+/// the files of the server the client is connected to. Returns `None` for other urls
+pub(crate) async fn download_without_http(
+    assets: &AssetCache,
+    url: &AbsAssetUrl,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    if let Some((archive, path)) = url.archive_entry() {
+        return Ok(Some(read_archive_file(assets, &archive, &path).await?));
+    }
+    match url.asset_server_path() {
+        Some(path) => {
+            let client = AssetServerClientKey
+                .get(assets)
+                .context("Not connected to a server to download the file from")?;
+            let data = client
+                .download(&path, DownloadPriority::of(&path))
+                .await
+                .with_context(|| format!("Failed to download {url} from the server"))?;
+            Ok(Some(data))
+        }
+        None => Ok(None),
+    }
+}
+
 /// Download with retries and a global rate limiting sempahore
 pub(crate) async fn download<T: 'static + Send, F: Future<Output = anyhow::Result<T>>>(
     assets: &AssetCache,
     url: impl reqwest::IntoUrl,
     map: impl 'static + Send + Fn(reqwest::Response) -> F,
+) -> anyhow::Result<T> {
+    download_with_range(assets, url, None, map).await
+}
+
+/// Download a range of bytes with an HTTP range request. Fails if the server doesn't support range
+/// requests, rather than downloading the whole file for every range
+pub(crate) async fn download_range(
+    assets: &AssetCache,
+    url: impl reqwest::IntoUrl,
+    range: Range<u64>,
+) -> anyhow::Result<Vec<u8>> {
+    download_with_range(assets, url, Some(range.clone()), move |resp| {
+        let range = range.clone();
+        async move {
+            let len = range.end - range.start;
+            let body = read_body(resp, len).await?;
+            if body.len() as u64 != len {
+                return Err(anyhow!("The response doesn't match the range {range:?}"));
+            }
+            Ok(body)
+        }
+    })
+    .await
+}
+
+/// Reads the body of `resp`, failing once it is longer than `max_len`
+#[cfg(not(target_os = "unknown"))]
+async fn read_body(mut resp: reqwest::Response, max_len: u64) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        anyhow::ensure!(
+            body.len() as u64 <= max_len,
+            "The response is longer than {max_len} bytes"
+        );
+    }
+    Ok(body)
+}
+
+/// Reads the body of `resp`, failing if it is longer than `max_len`
+#[cfg(target_os = "unknown")]
+async fn read_body(resp: reqwest::Response, max_len: u64) -> anyhow::Result<Vec<u8>> {
+    // Responses can't be read in chunks on wasm, so only the announced length can be checked beforehand
+    anyhow::ensure!(
+        resp.content_length()
+            .map(|len| len <= max_len)
+            .unwrap_or(true),
+        "The response is longer than {max_len} bytes"
+    );
+    let body = resp.bytes().await?;
+    anyhow::ensure!(
+        body.len() as u64 <= max_len,
+        "The response is longer than {max_len} bytes"
+    );
+    Ok(body.to_vec())
+}
+
+async fn download_with_range<T: 'static + Send, F: Future<Output = anyhow::Result<T>>>(
+    assets: &AssetCache,
+    url: impl reqwest::IntoUrl,
+    range: Option<Range<u64>>,
+    map: impl 'static + Send + Fn(reqwest::Response) -> F,
 ) -> anyhow::Result<T> {
     let url_str = url.as_str().to_string();
     let url = url.into_url()?;
@@ -95,8 +220,14 @@ pub(crate) async fn download<T: 'static + Send, F: Future<Output = anyhow::Resul
             log::info!("download [pending ] {}", url_short);
             let _permit = semaphore.acquire().await.unwrap();
             log::info!("download [download] {}", url_short);
-            let resp = client
-                .get(url.clone())
+            let mut request = client.get(url.clone());
+            if let Some(range) = &range {
+                request = request.header(
+                    reqwest::header::RANGE,
+                    format!("bytes={}-{}", range.start, range.end - 1),
+                );
+            }
+            let resp = request
                 .send()
                 .await
                 .with_context(|| format!("Failed to download {url_str}"))?;
@@ -107,6 +238,13 @@ pub(crate) async fn download<T: 'static + Send, F: Future<Output = anyhow::Resul
                     resp.status()
                 ));
             }
+            // Servers without support for range requests respond with the whole file
+            if range.is_some() && resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                return Err(anyhow!(
+                    "Downloading {url_str} failed, the server doesn't support range requests (status code: {:?})",
+                    resp.status()
+                ));
+            }
             match map(resp).await {
                 Ok(res) => {
                     log::info!("download [complete] {}", url_short);
@@ -148,6 +286,15 @@ impl BytesFromUrl {
 #[async_trait]
 impl AsyncAssetKey<AssetResult<Arc<Vec<u8>>>> for BytesFromUrl {
     async fn load(self, assets: AssetCache) -> AssetResult<Arc<Vec<u8>>> {
+        let download_url = self
+            .url
+            .to_download_url(&assets)
+            .map_err(anyhow::Error::new)?;
+        // Archives and the files of the server are read in place, so they are not cached on disk
+        if let Some(data) = download_without_http(&assets, &download_url).await? {
+            return Ok(Arc::new(data));
+        }
+
         if self.cache_on_disk && AssetsCacheOnDisk.get(&assets) {
             let path = BytesFromUrlCachedPath {
                 url: self.url.clone(),
@@ -171,14 +318,9 @@ impl AsyncAssetKey<AssetResult<Arc<Vec<u8>>>> for BytesFromUrl {
             ));
         }
 
-        let body = download(
-            &assets,
-            self.url
-                .to_download_url(&assets)
-                .map_err(anyhow::Error::new)?
-                .0,
-            |resp| async { Ok(resp.bytes().await?) },
-        )
+        let body = download(&assets, download_url.0, |resp| async {
+            Ok(resp.bytes().await?)
+        })
         .await?
         .to_vec();
         assert!(!body.is_empty());
@@ -228,20 +370,24 @@ impl AsyncAssetKey<AssetResult<Arc<PathBuf>>> for BytesFromUrlCachedPath {
         }
 
         let path = self.url.absolute_cache_path(&assets);
+        let download_url = self
+            .url
+            .to_download_url(&assets)
+            .map_err(anyhow::Error::new)?;
         if !path.exists() {
-            use tokio::io::AsyncWriteExt;
             let mut dir = path.clone();
             dir.pop();
             std::fs::create_dir_all(&dir)
                 .context(format!("Failed to create asset dir: {dir:?}"))?;
+            // The file is written to a temporary path first, so that an interrupted download doesn't leave a partial file in
+            // the cache
             let tmp_path = path.with_extension(".downloading");
-            download(
-                &assets,
-                self.url
-                    .to_download_url(&assets)
-                    .map_err(anyhow::Error::new)?
-                    .0,
-                {
+            if let Some(data) = download_without_http(&assets, &download_url).await? {
+                std::fs::write(&tmp_path, data)
+                    .context(format!("Failed to write file: {tmp_path:?}"))?;
+            } else {
+                use tokio::io::AsyncWriteExt;
+                download(&assets, download_url.0, {
                     let tmp_path = tmp_path.clone();
                     move |mut resp| {
                         let tmp_path = tmp_path.clone();
@@ -261,9 +407,9 @@ impl AsyncAssetKey<AssetResult<Arc<PathBuf>>> for BytesFromUrlCachedPath {
                             Ok(())
                         }
                     }
-                },
-            )
-            .await?;
+                })
+                .await?;
+            }
             std::fs::rename(&tmp_path, &path).context(format!(
                 "Failed to rename tmp file, from: {tmp_path:?}, to: {path:?}"
             ))?;
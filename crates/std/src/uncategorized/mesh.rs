@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::shapes::AABB;
 
+mod simplify;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Mesh {
     pub name: String,
@@ -15,6 +17,27 @@ pub struct Mesh {
     pub joint_indices: Option<Vec<UVec4>>,
     pub joint_weights: Option<Vec<Vec4>>,
     pub indices: Option<Vec<u32>>,
+    pub morph_targets: Vec<MorphTarget>,
+}
+
+/// A morph target (aka blend shape) stores per vertex displacements, which are added to the
+/// base mesh scaled by the target's weight
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MorphTarget {
+    pub name: String,
+    pub position_deltas: Vec<Vec3>,
+    pub normal_deltas: Option<Vec<Vec3>>,
+    pub tangent_deltas: Option<Vec<Vec3>>,
+}
+impl MorphTarget {
+    /// The bounds of the position displacements of this target
+    pub fn delta_aabb(&self) -> Option<AABB> {
+        if self.position_deltas.is_empty() {
+            None
+        } else {
+            Some(AABB::from_points(&self.position_deltas))
+        }
+    }
 }
 
 impl std::fmt::Debug for Mesh {
@@ -61,6 +84,10 @@ impl std::fmt::Debug for Mesh {
                 "indices",
                 &self.indices.as_ref().map(|v| v.len()).unwrap_or_default(),
             )
+            .field(
+                "morph_targets",
+                &self.morph_targets.iter().map(|t| &t.name).collect_vec(),
+            )
             .finish()
     }
 }
@@ -77,6 +104,7 @@ impl Default for Mesh {
             joint_indices: None,
             joint_weights: None,
             indices: None,
+            morph_targets: Vec::new(),
         }
     }
 }
@@ -112,6 +140,30 @@ impl Mesh {
                     .normalize();
             }
         }
+        // Normals are transformed by the inverse transpose, so that they stay perpendicular to
+        // the surface under non-uniform scaling
+        let linear = Mat3::from_mat4(transform);
+        let normal_matrix = if linear.determinant().abs() > f32::EPSILON {
+            linear.inverse().transpose()
+        } else {
+            linear
+        };
+        for target in &mut self.morph_targets {
+            // Deltas are directions, so only the linear part of the transform applies
+            for d in &mut target.position_deltas {
+                *d = linear * *d;
+            }
+            if let Some(normal_deltas) = &mut target.normal_deltas {
+                for d in normal_deltas {
+                    *d = normal_matrix * *d;
+                }
+            }
+            if let Some(tangent_deltas) = &mut target.tangent_deltas {
+                for d in tangent_deltas {
+                    *d = linear * *d;
+                }
+            }
+        }
     }
     /// Flips indicies so that what was front facing will become back facing and vice versa
     pub fn invert_indicies(&mut self) {
@@ -139,6 +191,36 @@ impl Mesh {
         }
     }
 
+    /// Adds the weighted displacements of the morph targets to the base mesh, i.e. evaluates
+    /// the morph targets on the CPU. Weights beyond the number of targets are ignored.
+    pub fn apply_morph_targets(&mut self, weights: &[f32]) {
+        for (target, &weight) in self.morph_targets.iter().zip(weights) {
+            if weight == 0. {
+                continue;
+            }
+            if let Some(positions) = &mut self.positions {
+                for (p, d) in positions.iter_mut().zip(&target.position_deltas) {
+                    *p += *d * weight;
+                }
+            }
+            if let (Some(normals), Some(deltas)) = (&mut self.normals, &target.normal_deltas) {
+                for (n, d) in normals.iter_mut().zip(deltas) {
+                    *n += *d * weight;
+                }
+            }
+            if let (Some(tangents), Some(deltas)) = (&mut self.tangents, &target.tangent_deltas) {
+                for (t, d) in tangents.iter_mut().zip(deltas) {
+                    *t += *d * weight;
+                }
+            }
+        }
+        if let Some(normals) = &mut self.normals {
+            for n in normals {
+                *n = n.normalize_or_zero();
+            }
+        }
+    }
+
     #[ambient_profiling::function]
     pub fn append(&mut self, mut mesh: Mesh) {
         let indices_offset = self.positions.as_ref().unwrap().len() as u32;
@@ -160,6 +242,21 @@ impl Mesh {
         if let Some(x) = &mut self.joint_weights {
             x.extend(mesh.joint_weights.unwrap());
         }
+        if self.morph_targets.len() == mesh.morph_targets.len() {
+            for (target, other) in self.morph_targets.iter_mut().zip(mesh.morph_targets) {
+                target.position_deltas.extend(other.position_deltas);
+                if let Some(x) = &mut target.normal_deltas {
+                    x.extend(other.normal_deltas.unwrap_or_default());
+                }
+                if let Some(x) = &mut target.tangent_deltas {
+                    x.extend(other.tangent_deltas.unwrap_or_default());
+                }
+            }
+        } else {
+            // The targets can't be matched up, so drop them rather than ending up with deltas
+            // that don't line up with the vertices
+            self.morph_targets.clear();
+        }
         if let Some(x) = &mut self.indices {
             x.extend(
                 mesh.indices
@@ -211,6 +308,13 @@ impl Mesh {
                 .filter_map(|(i, v)| if used[i] { Some(v) } else { None })
                 .collect()
         });
+        self.tangents = self.tangents.as_mut().map(|tangents| {
+            tangents
+                .drain(..)
+                .enumerate()
+                .filter_map(|(i, v)| if used[i] { Some(v) } else { None })
+                .collect()
+        });
         self.joint_indices = self.joint_indices.as_mut().map(|joints| {
             joints
                 .drain(..)
@@ -225,6 +329,22 @@ impl Mesh {
                 .filter_map(|(i, v)| if used[i] { Some(v) } else { None })
                 .collect()
         });
+        let keep_used = |values: &mut Vec<Vec3>| {
+            *values = values
+                .drain(..)
+                .enumerate()
+                .filter_map(|(i, v)| if used[i] { Some(v) } else { None })
+                .collect();
+        };
+        for target in &mut self.morph_targets {
+            keep_used(&mut target.position_deltas);
+            if let Some(x) = &mut target.normal_deltas {
+                keep_used(x);
+            }
+            if let Some(x) = &mut target.tangent_deltas {
+                keep_used(x);
+            }
+        }
         self.texcoords = self
             .texcoords
             .drain(..)
@@ -327,5 +447,44 @@ impl Mesh {
                 .iter()
                 .map(|x| std::mem::size_of_val(&**x))
                 .sum::<usize>()
+            + self
+                .morph_targets
+                .iter()
+                .map(|x| {
+                    std::mem::size_of_val(&*x.position_deltas)
+                        + x.normal_deltas
+                            .as_ref()
+                            .map(|x| std::mem::size_of_val(&**x))
+                            .unwrap_or(0)
+                        + x.tangent_deltas
+                            .as_ref()
+                            .map(|x| std::mem::size_of_val(&**x))
+                            .unwrap_or(0)
+                })
+                .sum::<usize>()
     }
 }
+
+#[test]
+fn test_transform_morph_targets() {
+    let mut mesh = Mesh {
+        morph_targets: vec![MorphTarget {
+            name: "target".to_string(),
+            position_deltas: vec![vec3(1., 1., 0.)],
+            normal_deltas: Some(vec![vec3(1., 1., 0.)]),
+            tangent_deltas: Some(vec![vec3(1., 0., 0.)]),
+        }],
+        ..Default::default()
+    };
+    mesh.transform(Mat4::from_scale_rotation_translation(
+        vec3(2., 1., 1.),
+        Quat::IDENTITY,
+        vec3(5., 5., 5.),
+    ));
+    let target = &mesh.morph_targets[0];
+    assert_eq!(target.position_deltas, vec![vec3(2., 1., 0.)]);
+    // A surface along (1, -1, 0) with the normal (1, 1, 0) is stretched along X, so its normal
+    // turns towards Y
+    assert_eq!(target.normal_deltas, Some(vec![vec3(0.5, 1., 0.)]));
+    assert_eq!(target.tangent_deltas, Some(vec![vec3(2., 0., 0.)]));
+}
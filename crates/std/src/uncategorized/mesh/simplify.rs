@@ -0,0 +1,321 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use glam::{DVec3, Vec3};
+use itertools::Itertools;
+use ordered_float::OrderedFloat;
+
+use super::Mesh;
+
+impl Mesh {
+    /// Reduces the number of triangles to about `target_ratio` of the original by collapsing edges,
+    /// picking the collapses which move the surface the least (quadric error metric).
+    ///
+    /// Vertices are only ever merged into one of their neighbours, so all other vertex attributes
+    /// (uvs, skinning, morph targets) stay valid. Vertices on uv/normal seams and on open borders
+    /// are kept in place, so the outline of the mesh is preserved.
+    pub fn simplify(&self, target_ratio: f32) -> Mesh {
+        let (positions, indices) = match (&self.positions, &self.indices) {
+            (Some(positions), Some(indices)) => (positions, indices),
+            _ => return self.clone(),
+        };
+        let target = ((indices.len() / 3) as f32 * target_ratio.clamp(0., 1.)).round() as usize;
+        let mut simplifier = Simplifier::new(positions, indices);
+        simplifier.run(target);
+
+        let mut mesh = self.clone();
+        mesh.indices = Some(simplifier.indices());
+        mesh.remove_unused_vertices();
+        mesh
+    }
+}
+
+/// The sum of the squared distances to a set of planes, stored as a symmetric 4x4 matrix
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+impl Quadric {
+    fn from_plane(normal: DVec3, d: f64, weight: f64) -> Self {
+        let DVec3 { x: a, y: b, z: c } = normal;
+        let coefficients = [
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ];
+        Self(coefficients.map(|x| x * weight))
+    }
+    fn add(&self, other: &Quadric) -> Quadric {
+        let mut res = *self;
+        for (a, b) in res.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+        res
+    }
+    fn error(&self, p: DVec3) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let DVec3 { x, y, z } = p;
+        x * x * aa
+            + 2. * x * y * ab
+            + 2. * x * z * ac
+            + 2. * x * ad
+            + y * y * bb
+            + 2. * y * z * bc
+            + 2. * y * bd
+            + z * z * cc
+            + 2. * z * cd
+            + dd
+    }
+}
+
+/// A pending collapse of `from` into `to`; only valid if neither vertex changed since it was
+/// queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Collapse {
+    cost: OrderedFloat<f64>,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+struct Simplifier<'a> {
+    positions: &'a [Vec3],
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    n_triangles: usize,
+    vertex_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    locked: Vec<bool>,
+    versions: Vec<u32>,
+    queue: BinaryHeap<Reverse<Collapse>>,
+}
+impl<'a> Simplifier<'a> {
+    fn new(positions: &'a [Vec3], indices: &[u32]) -> Self {
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect_vec();
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        for (i, &triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|v| positions[v as usize].as_dvec3());
+            let normal = (b - a).cross(c - a);
+            let area = normal.length() / 2.;
+            let quadric = if area > 0. {
+                let normal = normal.normalize();
+                Quadric::from_plane(normal, -normal.dot(a), area)
+            } else {
+                Quadric::default()
+            };
+            for v in triangle {
+                vertex_triangles[v as usize].push(i);
+                quadrics[v as usize] = quadrics[v as usize].add(&quadric);
+            }
+        }
+
+        // Vertices that share their position with another vertex sit on a seam, and vertices on an
+        // edge with only one triangle sit on a border; moving either would open holes in the mesh
+        let mut locked = vec![false; positions.len()];
+        let mut by_position = (0..positions.len() as u32).collect_vec();
+        by_position.sort_by_key(|&v| positions[v as usize].to_array().map(f32::to_bits));
+        for (a, b) in by_position.iter().tuple_windows() {
+            if positions[*a as usize] == positions[*b as usize] {
+                locked[*a as usize] = true;
+                locked[*b as usize] = true;
+            }
+        }
+        let edges = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .counts();
+        for ((a, b), count) in edges {
+            if count == 1 {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+            }
+        }
+
+        let mut simplifier = Self {
+            positions,
+            removed: vec![false; triangles.len()],
+            n_triangles: triangles.len(),
+            triangles,
+            vertex_triangles,
+            quadrics,
+            locked,
+            versions: vec![0; positions.len()],
+            queue: BinaryHeap::new(),
+        };
+        for i in 0..simplifier.triangles.len() {
+            let [a, b, c] = simplifier.triangles[i];
+            for (from, to) in [(a, b), (b, a), (b, c), (c, b), (c, a), (a, c)] {
+                simplifier.push_collapse(from, to);
+            }
+        }
+        simplifier
+    }
+
+    fn push_collapse(&mut self, from: u32, to: u32) {
+        if from == to || self.locked[from as usize] {
+            return;
+        }
+        let quadric = self.quadrics[from as usize].add(&self.quadrics[to as usize]);
+        self.queue.push(Reverse(Collapse {
+            cost: OrderedFloat(quadric.error(self.positions[to as usize].as_dvec3())),
+            from,
+            to,
+            from_version: self.versions[from as usize],
+            to_version: self.versions[to as usize],
+        }));
+    }
+
+    fn run(&mut self, target: usize) {
+        while self.n_triangles > target {
+            let collapse = match self.queue.pop() {
+                Some(Reverse(collapse)) => collapse,
+                None => break,
+            };
+            if self.versions[collapse.from as usize] != collapse.from_version
+                || self.versions[collapse.to as usize] != collapse.to_version
+            {
+                continue;
+            }
+            if self.can_collapse(collapse.from, collapse.to) {
+                self.collapse(collapse.from, collapse.to);
+            }
+        }
+    }
+
+    fn alive_triangles(&self, v: u32) -> impl Iterator<Item = usize> + '_ {
+        self.vertex_triangles[v as usize]
+            .iter()
+            .copied()
+            .filter(|&t| !self.removed[t])
+    }
+    fn neighbours(&self, v: u32) -> Vec<u32> {
+        self.alive_triangles(v)
+            .flat_map(|t| self.triangles[t])
+            .filter(|&x| x != v)
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    fn can_collapse(&self, from: u32, to: u32) -> bool {
+        // The vertices may only share the neighbours of the triangles on their common edge,
+        // otherwise the collapse would pinch the surface into a non-manifold shape
+        let shared_triangles = self
+            .alive_triangles(from)
+            .filter(|&t| self.triangles[t].contains(&to))
+            .count();
+        if shared_triangles == 0 {
+            return false;
+        }
+        let to_neighbours = self.neighbours(to);
+        let shared_neighbours = self
+            .neighbours(from)
+            .into_iter()
+            .filter(|x| to_neighbours.contains(x))
+            .count();
+        if shared_neighbours > shared_triangles {
+            return false;
+        }
+        // Don't flip any of the remaining triangles around `from`
+        self.alive_triangles(from)
+            .filter(|&t| !self.triangles[t].contains(&to))
+            .all(|t| {
+                let before = self.triangles[t].map(|v| self.positions[v as usize]);
+                let after = self.triangles[t]
+                    .map(|v| self.positions[if v == from { to } else { v } as usize]);
+                let normal_before = (before[1] - before[0]).cross(before[2] - before[0]);
+                let normal_after = (after[1] - after[0]).cross(after[2] - after[0]);
+                normal_after.length_squared() > 0. && normal_before.dot(normal_after) > 0.
+            })
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        for t in std::mem::take(&mut self.vertex_triangles[from as usize]) {
+            if self.removed[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&to) {
+                self.removed[t] = true;
+                self.n_triangles -= 1;
+            } else {
+                for v in &mut self.triangles[t] {
+                    if *v == from {
+                        *v = to;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(t);
+            }
+        }
+        let removed = &self.removed;
+        self.vertex_triangles[to as usize].retain(|&t| !removed[t]);
+        self.quadrics[to as usize] = self.quadrics[to as usize].add(&self.quadrics[from as usize]);
+        self.versions[from as usize] += 1;
+        self.versions[to as usize] += 1;
+        for neighbour in self.neighbours(to) {
+            self.push_collapse(neighbour, to);
+            self.push_collapse(to, neighbour);
+        }
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        self.triangles
+            .iter()
+            .zip(&self.removed)
+            .filter(|(_, removed)| !**removed)
+            .flat_map(|(t, _)| *t)
+            .collect()
+    }
+}
+
+#[test]
+fn test_simplify() {
+    use glam::vec3;
+
+    // A flat 10x10 grid; all the interior vertices can be collapsed without changing the shape
+    let size = 10;
+    let positions = (0..=size)
+        .cartesian_product(0..=size)
+        .map(|(y, x)| vec3(x as f32, y as f32, 0.))
+        .collect_vec();
+    let indices = (0..size)
+        .cartesian_product(0..size)
+        .flat_map(|(y, x)| {
+            let i = y * (size + 1) + x;
+            [i, i + 1, i + size + 2, i, i + size + 2, i + size + 1]
+        })
+        .collect_vec();
+    let mesh = Mesh {
+        positions: Some(positions),
+        indices: Some(indices),
+        ..Default::default()
+    };
+
+    let simplified = mesh.simplify(0.25);
+    assert!(simplified.indices.as_ref().unwrap().len() / 3 <= 50);
+    assert_eq!(simplified.aabb(), mesh.aabb());
+    assert!(simplified
+        .positions
+        .as_ref()
+        .unwrap()
+        .iter()
+        .all(|p| p.z == 0.));
+    assert_eq!(
+        simplified
+            .indices
+            .unwrap()
+            .iter()
+            .max()
+            .map(|&i| i as usize),
+        simplified.positions.map(|p| p.len() - 1)
+    );
+}
@@ -1,6 +1,7 @@
 pub use ambient_asset_cache as asset_cache;
 pub use ambient_color as color;
 pub use ambient_math as math;
+pub mod asset_archive;
 pub mod asset_url;
 pub mod barc;
 pub mod disk_cache;
@@ -111,6 +111,28 @@ impl wit::server_physics::Host for Bindings {
     ) -> anyhow::Result<wit::server_physics::CharacterCollision> {
         unsupported()
     }
+
+    fn sweep(
+        &mut self,
+        _shape: wit::server_physics::PhysicsShape,
+        _position: wit::types::Vec3,
+        _rotation: wit::types::Quat,
+        _direction: wit::types::Vec3,
+        _distance: f32,
+        _filter: wit::server_physics::PhysicsFilter,
+    ) -> anyhow::Result<Vec<wit::server_physics::PhysicsHit>> {
+        unsupported()
+    }
+
+    fn overlap(
+        &mut self,
+        _shape: wit::server_physics::PhysicsShape,
+        _position: wit::types::Vec3,
+        _rotation: wit::types::Quat,
+        _filter: wit::server_physics::PhysicsFilter,
+    ) -> anyhow::Result<Vec<wit::types::EntityId>> {
+        unsupported()
+    }
 }
 impl wit::server_message::Host for Bindings {
     fn send(
@@ -3,12 +3,14 @@
 //! If implementing a trait that is also available on the client, it should go in [super].
 
 use ambient_core::player::{player, user_id};
-use ambient_ecs::{query, EntityId, World};
+use ambient_ecs::{query, with_component_registry, ArchetypeFilter, EntityId, World};
 use ambient_network::server::player_connection;
-use ambient_physics::physx::character_controller;
+use ambient_physics::{
+    intersection::RaycastFilter, layers::character_controller_filters, physx::character_controller,
+};
 use ambient_std::shapes::Ray;
 use anyhow::Context;
-use physxx::{PxControllerCollisionFlag, PxControllerFilters};
+use physxx::PxControllerCollisionFlag;
 
 use super::super::Bindings;
 use crate::shared::{
@@ -181,6 +183,7 @@ impl wit::server_physics::Host for Bindings {
         let result = ambient_physics::intersection::raycast_first(
             self.world(),
             Ray::new(origin.from_bindgen(), direction.from_bindgen()),
+            None,
         )
         .map(|t| (t.0.into_bindgen(), t.1.into_bindgen()));
 
@@ -195,6 +198,7 @@ impl wit::server_physics::Host for Bindings {
         let result = ambient_physics::intersection::raycast(
             self.world(),
             Ray::new(origin.from_bindgen(), direction.from_bindgen()),
+            None,
         )
         .into_iter()
         .map(|t| (t.0.into_bindgen(), t.1.into_bindgen()))
@@ -210,16 +214,14 @@ impl wit::server_physics::Host for Bindings {
         min_dist: f32,
         elapsed_time: f32,
     ) -> anyhow::Result<wit::server_physics::CharacterCollision> {
-        match self
-            .world()
-            .get(entity.from_bindgen(), character_controller())
-        {
+        let entity = entity.from_bindgen();
+        match self.world().get(entity, character_controller()) {
             Ok(controller) => {
                 let res = controller.move_controller(
                     displacement.from_bindgen(),
                     min_dist,
                     elapsed_time,
-                    &PxControllerFilters::new(),
+                    &character_controller_filters(self.world(), entity),
                     None,
                 );
                 Ok(wit::server_physics::CharacterCollision {
@@ -235,6 +237,88 @@ impl wit::server_physics::Host for Bindings {
             }),
         }
     }
+
+    fn sweep(
+        &mut self,
+        shape: wit::server_physics::PhysicsShape,
+        position: wit::types::Vec3,
+        rotation: wit::types::Quat,
+        direction: wit::types::Vec3,
+        distance: f32,
+        filter: wit::server_physics::PhysicsFilter,
+    ) -> anyhow::Result<Vec<wit::server_physics::PhysicsHit>> {
+        let direction = direction.from_bindgen();
+        if direction.try_normalize().is_none() {
+            anyhow::bail!("The direction of a sweep must have a length, got {direction}");
+        }
+        let result = ambient_physics::intersection::sweep(
+            self.world(),
+            shape.from_bindgen(),
+            position.from_bindgen(),
+            rotation.from_bindgen(),
+            direction,
+            distance,
+            &physics_filter(filter)?,
+        )
+        .into_iter()
+        .map(|hit| wit::server_physics::PhysicsHit {
+            entity: hit.entity.into_bindgen(),
+            distance: hit.distance,
+            position: hit.position.into_bindgen(),
+            normal: hit.normal.into_bindgen(),
+        })
+        .collect();
+
+        Ok(result)
+    }
+
+    fn overlap(
+        &mut self,
+        shape: wit::server_physics::PhysicsShape,
+        position: wit::types::Vec3,
+        rotation: wit::types::Quat,
+        filter: wit::server_physics::PhysicsFilter,
+    ) -> anyhow::Result<Vec<wit::types::EntityId>> {
+        let result = ambient_physics::intersection::overlap(
+            self.world(),
+            shape.from_bindgen(),
+            position.from_bindgen(),
+            rotation.from_bindgen(),
+            &physics_filter(filter)?,
+        )
+        .into_bindgen();
+
+        Ok(result)
+    }
+}
+
+fn physics_filter(filter: wit::server_physics::PhysicsFilter) -> anyhow::Result<RaycastFilter> {
+    let entities = if filter.include.is_empty() && filter.exclude.is_empty() {
+        None
+    } else {
+        Some(with_component_registry(|cr| {
+            let mut entities = ArchetypeFilter::new();
+            for index in &filter.include {
+                let component = cr
+                    .get_primitive_component(*index)
+                    .context("no primitive component")?;
+                entities = entities.incl_ref(component.as_component());
+            }
+            for index in &filter.exclude {
+                let component = cr
+                    .get_primitive_component(*index)
+                    .context("no primitive component")?;
+                entities = entities.excl_ref(component.as_component());
+            }
+            anyhow::Ok(entities)
+        })?)
+    };
+
+    Ok(RaycastFilter {
+        entities,
+        collider_type: filter.collider_scene.from_bindgen(),
+        collision_layer: filter.collision_layer,
+    })
 }
 
 impl wit::server_message::Host for Bindings {
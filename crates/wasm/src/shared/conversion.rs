@@ -2,6 +2,7 @@ use ambient_sys::time::SystemTime;
 
 use ambient_animation as animation;
 use ambient_ecs::EntityId;
+use ambient_physics::{intersection::QueryShape, ColliderScene};
 use ambient_std::asset_url::TypedAssetUrl;
 use ambient_std::shapes::Ray;
 use glam::{Mat4, Quat, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
@@ -312,3 +313,32 @@ impl FromBindgen for wit::entity::AnimationController {
         }
     }
 }
+
+impl FromBindgen for wit::server_physics::ColliderScene {
+    type Item = ColliderScene;
+    fn from_bindgen(self) -> Self::Item {
+        match self {
+            wit::server_physics::ColliderScene::Physics => ColliderScene::Physics,
+            wit::server_physics::ColliderScene::TriggerArea => ColliderScene::TriggerArea,
+            wit::server_physics::ColliderScene::Picking => ColliderScene::Picking,
+        }
+    }
+}
+
+impl FromBindgen for wit::server_physics::PhysicsShape {
+    type Item = QueryShape;
+    fn from_bindgen(self) -> Self::Item {
+        match self {
+            wit::server_physics::PhysicsShape::Sphere(radius) => QueryShape::Sphere { radius },
+            wit::server_physics::PhysicsShape::Cuboid(half_extents) => QueryShape::Cuboid {
+                half_extents: half_extents.from_bindgen(),
+            },
+            wit::server_physics::PhysicsShape::Capsule((radius, half_height)) => {
+                QueryShape::Capsule {
+                    radius,
+                    half_height,
+                }
+            }
+        }
+    }
+}
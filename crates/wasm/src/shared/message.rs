@@ -1,11 +1,13 @@
-use ambient_ecs::{components, Debuggable, EntityId, Resource, World};
+use ambient_ecs::{components, Debuggable, EntityId, Resource, Serializable, World};
+use serde::{Deserialize, Serialize};
 
 components!("wasm::message", {
-    @[Debuggable, Resource]
+    // Serializable so that messages are part of the inputs of a recorded session
+    @[Debuggable, Resource, Serializable]
     pending_messages: Vec<SerializedMessage>,
 });
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Source {
     Runtime,
     Server,
@@ -13,7 +15,7 @@ pub enum Source {
     Local(EntityId),
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct SerializedMessage {
     /// If unspecified, this will broadcast to all modules
     pub(super) module_id: Option<EntityId>,
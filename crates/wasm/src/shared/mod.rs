@@ -13,11 +13,12 @@ use std::sync::Arc;
 
 use ambient_core::async_ecs::async_run;
 use ambient_ecs::{
-    dont_despawn_on_unload, generated::messages, query, world_events, Entity, EntityId, FnSystem,
-    Message, SystemGroup, World, WorldEventReader,
+    dont_despawn_on_unload, generated::messages, query, run_timings, world_events, Entity,
+    EntityId, FnSystem, Message, SystemGroup, World, WorldEventReader,
 };
 use ambient_physics::{collider_loads, collisions};
 use ambient_project::Identifier;
+use ambient_sys::time::Instant;
 use itertools::Itertools;
 pub use module::*;
 
@@ -290,8 +291,15 @@ fn run(
         return;
     }
 
+    let start = Instant::now();
     let result =
         run_and_catch_panics(|| state.run(world, message_source, message_name, message_data));
+    if world.resource_opt(run_timings()).is_some() {
+        let name = get_module_name(world, id).to_string();
+        world
+            .resource_mut(run_timings())
+            .record("wasm_module", &name, start.elapsed());
+    }
 
     if let Err(message) = result {
         update_errors(world, &[(id, message)]);
@@ -58,6 +58,43 @@ export type Pipeline = {
       "TriggerArea" | 
       /// This object should only be present in the picking scene.
       "Picking",
+    /// If specified, a ragdoll is generated from the skin of this model: one capsule body per bone, jointed to its parent.
+    /// The bodies are spawned with the prefab, and are simulated when the `ragdoll` component is added to it.
+    ragdoll?: {
+      /// If not empty, only the bones whose names contain one of these get a body.
+      /// Otherwise, all the bones of the skin do, except for the leaf bones, which have no length.
+      bones?: string[],
+      /// The radius of the capsules, as a fraction of the length of their bones. Defaults to 0.25.
+      radius?: f32,
+      /// The density of the bodies. Defaults to 1.
+      density?: f32,
+      /// The swing limits of the joints in degrees, around the two axes perpendicular to the bone. Defaults to 45 degrees.
+      swing_limit?: Vec2,
+      /// The twist limits (min, max) of the joints in degrees, around the bone. Defaults to -30 to 30 degrees.
+      twist_limit?: Vec2,
+      /// Settings for individual bones, by bone name.
+      overrides?: {
+        [bone: string]: {
+          /// Don't create a body for this bone; its children are connected to the closest ancestor with a body instead.
+          disabled?: boolean,
+          /// The radius of the capsule.
+          radius?: f32,
+          density?: f32,
+          /// The swing limits of the joint in degrees.
+          swing_limit?: Vec2,
+          /// The twist limits of the joint in degrees.
+          twist_limit?: Vec2,
+        }
+      },
+    },
+    /// If specified, lower detail LODs will be generated for the meshes by simplifying them.
+    generate_lods?: {
+      /// The triangle count of each generated LOD, as a fraction of the original mesh. Defaults to `[0.5, 0.25, 0.1]`.
+      ratios?: f32[],
+      /// The screen sizes at which to switch to each LOD, starting with the original mesh, so one more than `ratios`.
+      /// If not specified, they will be spread out down to 4% of the screen.
+      lod_cutoffs?: f32[],
+    },
     /// Whether or not this mesh should have its texture sizes capped.
     cap_texture_sizes?: 
       /// Cap this model's textures to 128x128.
@@ -82,6 +119,8 @@ export type Pipeline = {
     output_prefabs?: boolean,
     /// Output the animations that belonged to this model.
     output_animations?: boolean,
+    /// Also output the model and its animations as a binary glTF file (`main.glb`), for use in external tools. Off by default.
+    output_glb?: boolean,
     /// If specified, these components will be added to the prefabs produced by `output_prefabs`.
     /// 
     /// This is a great way to specify additional information about your prefab that can be used by gameplay logic.
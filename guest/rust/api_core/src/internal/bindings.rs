@@ -8321,6 +8321,7 @@ pub mod asset{
                                   pub type EntityId = super::types::EntityId;
                                   pub type Vec3 = super::types::Vec3;
                                   pub type Mat4 = super::types::Mat4;
+                                  pub type Quat = super::types::Quat;
                                   #[repr(C)]
                                   #[derive(Copy, Clone)]
                                   pub struct CharacterCollision {
@@ -8333,6 +8334,78 @@ pub mod asset{
                                       f.debug_struct("CharacterCollision").field("side", &self.side).field("up", &self.up).field("down", &self.down).finish()
                                     }
                                   }
+                                  #[repr(u8)]
+                                  #[derive(Clone, Copy, PartialEq, Eq)]
+                                  pub enum ColliderScene {
+                                    Physics,
+                                    TriggerArea,
+                                    Picking,
+                                  }
+                                  impl core::fmt::Debug for ColliderScene {
+                                    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                                      match self {
+                                        ColliderScene::Physics => {
+                                          f.debug_tuple("ColliderScene::Physics").finish()
+                                        }
+                                        ColliderScene::TriggerArea => {
+                                          f.debug_tuple("ColliderScene::TriggerArea").finish()
+                                        }
+                                        ColliderScene::Picking => {
+                                          f.debug_tuple("ColliderScene::Picking").finish()
+                                        }
+                                      }
+                                    }
+                                  }
+                                  #[derive(Clone, Copy)]
+                                  pub enum PhysicsShape{
+                                    /// The radius of the sphere.
+                                    Sphere(f32),
+                                    /// The half extents of the box.
+                                    Cuboid(Vec3),
+                                    /// The radius and half height of a capsule along the Z axis.
+                                    Capsule((f32,f32,)),
+                                  }
+                                  impl core::fmt::Debug for PhysicsShape {
+                                    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                                      match self {
+                                        PhysicsShape::Sphere(e) => {
+                                          f.debug_tuple("PhysicsShape::Sphere").field(e).finish()
+                                        }
+                                        PhysicsShape::Cuboid(e) => {
+                                          f.debug_tuple("PhysicsShape::Cuboid").field(e).finish()
+                                        }
+                                        PhysicsShape::Capsule(e) => {
+                                          f.debug_tuple("PhysicsShape::Capsule").field(e).finish()
+                                        }
+                                      }
+                                    }
+                                  }
+                                  #[derive(Clone)]
+                                  pub struct PhysicsFilter<'a,> {
+                                    pub collider_scene: Option<ColliderScene>,
+                                    pub include: &'a [u32],
+                                    pub exclude: &'a [u32],
+                                    /// Only hit the shapes this collision layer collides with.
+                                    pub collision_layer: Option<&'a str>,
+                                  }
+                                  impl<'a,> core::fmt::Debug for PhysicsFilter<'a,> {
+                                    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                                      f.debug_struct("PhysicsFilter").field("collider-scene", &self.collider_scene).field("include", &self.include).field("exclude", &self.exclude).field("collision-layer", &self.collision_layer).finish()
+                                    }
+                                  }
+                                  #[repr(C)]
+                                  #[derive(Copy, Clone)]
+                                  pub struct PhysicsHit {
+                                    pub entity: EntityId,
+                                    pub distance: f32,
+                                    pub position: Vec3,
+                                    pub normal: Vec3,
+                                  }
+                                  impl core::fmt::Debug for PhysicsHit {
+                                    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                                      f.debug_struct("PhysicsHit").field("entity", &self.entity).field("distance", &self.distance).field("position", &self.position).field("normal", &self.normal).finish()
+                                    }
+                                  }
                                   #[allow(clippy::all)]
                                   pub fn add_force(entity: EntityId,force: Vec3,){
                                     
@@ -8730,6 +8803,209 @@ pub mod asset{
                                       }, }
                                     }
                                   }
+                                  #[allow(clippy::all)]
+                                  pub fn sweep(shape: PhysicsShape,position: Vec3,rotation: Quat,direction: Vec3,distance: f32,filter: PhysicsFilter<'_,>,) -> wit_bindgen::rt::vec::Vec::<PhysicsHit>{
+                                    
+                                    #[allow(unused_imports)]
+                                    use wit_bindgen::rt::{alloc, vec::Vec, string::String};
+                                    unsafe {
+                                      
+                                      #[repr(align(4))]
+                                      struct RetArea([u8; 92]);
+                                      let mut ret_area = core::mem::MaybeUninit::<RetArea>::uninit();
+                                      let ptr0 = ret_area.as_mut_ptr() as i32;match shape {
+                                        PhysicsShape::Sphere(e) => {
+                                          *((ptr0 + 0) as *mut u8) = (0i32) as u8;
+                                          *((ptr0 + 4) as *mut f32) = wit_bindgen::rt::as_f32(e);
+                                          
+                                        },
+                                        PhysicsShape::Cuboid(e) => {
+                                          *((ptr0 + 0) as *mut u8) = (1i32) as u8;
+                                          let super::types::Vec3{ x:x1, y:y1, z:z1, } = e;
+                                          *((ptr0 + 4) as *mut f32) = wit_bindgen::rt::as_f32(x1);
+                                          *((ptr0 + 8) as *mut f32) = wit_bindgen::rt::as_f32(y1);
+                                          *((ptr0 + 12) as *mut f32) = wit_bindgen::rt::as_f32(z1);
+                                          
+                                        },
+                                        PhysicsShape::Capsule(e) => {
+                                          *((ptr0 + 0) as *mut u8) = (2i32) as u8;
+                                          let (t2_0, t2_1, ) = e;
+                                          *((ptr0 + 4) as *mut f32) = wit_bindgen::rt::as_f32(t2_0);
+                                          *((ptr0 + 8) as *mut f32) = wit_bindgen::rt::as_f32(t2_1);
+                                          
+                                        },
+                                      };
+                                      let super::types::Vec3{ x:x3, y:y3, z:z3, } = position;
+                                      *((ptr0 + 16) as *mut f32) = wit_bindgen::rt::as_f32(x3);
+                                      *((ptr0 + 20) as *mut f32) = wit_bindgen::rt::as_f32(y3);
+                                      *((ptr0 + 24) as *mut f32) = wit_bindgen::rt::as_f32(z3);
+                                      let super::types::Quat{ x:x4, y:y4, z:z4, w:w4, } = rotation;
+                                      *((ptr0 + 28) as *mut f32) = wit_bindgen::rt::as_f32(x4);
+                                      *((ptr0 + 32) as *mut f32) = wit_bindgen::rt::as_f32(y4);
+                                      *((ptr0 + 36) as *mut f32) = wit_bindgen::rt::as_f32(z4);
+                                      *((ptr0 + 40) as *mut f32) = wit_bindgen::rt::as_f32(w4);
+                                      let super::types::Vec3{ x:x5, y:y5, z:z5, } = direction;
+                                      *((ptr0 + 44) as *mut f32) = wit_bindgen::rt::as_f32(x5);
+                                      *((ptr0 + 48) as *mut f32) = wit_bindgen::rt::as_f32(y5);
+                                      *((ptr0 + 52) as *mut f32) = wit_bindgen::rt::as_f32(z5);
+                                      *((ptr0 + 56) as *mut f32) = wit_bindgen::rt::as_f32(distance);
+                                      let PhysicsFilter{ collider_scene:collider_scene6, include:include6, exclude:exclude6, collision_layer:collision_layer6, } = filter;
+                                      match collider_scene6 {
+                                        Some(e) => {
+                                          *((ptr0 + 60) as *mut u8) = (1i32) as u8;
+                                          *((ptr0 + 61) as *mut u8) = (match e {
+                                            ColliderScene::Physics => 0,
+                                            ColliderScene::TriggerArea => 1,
+                                            ColliderScene::Picking => 2,
+                                          }) as u8;
+                                          
+                                        },
+                                        None => {
+                                          {
+                                            *((ptr0 + 60) as *mut u8) = (0i32) as u8;
+                                            
+                                          }
+                                        },
+                                      };
+                                      let vec7 = include6;
+                                      let ptr7 = vec7.as_ptr() as i32;
+                                      let len7 = vec7.len() as i32;
+                                      *((ptr0 + 68) as *mut i32) = len7;
+                                      *((ptr0 + 64) as *mut i32) = ptr7;
+                                      let vec8 = exclude6;
+                                      let ptr8 = vec8.as_ptr() as i32;
+                                      let len8 = vec8.len() as i32;
+                                      *((ptr0 + 76) as *mut i32) = len8;
+                                      *((ptr0 + 72) as *mut i32) = ptr8;
+                                      match collision_layer6 {
+                                        Some(e) => {
+                                          *((ptr0 + 80) as *mut u8) = (1i32) as u8;
+                                          let vec9 = e;
+                                          let ptr9 = vec9.as_ptr() as i32;
+                                          let len9 = vec9.len() as i32;
+                                          *((ptr0 + 88) as *mut i32) = len9;
+                                          *((ptr0 + 84) as *mut i32) = ptr9;
+                                          
+                                        },
+                                        None => {
+                                          {
+                                            *((ptr0 + 80) as *mut u8) = (0i32) as u8;
+                                            
+                                          }
+                                        },
+                                      };
+                                      let ptr10 = ret_area.as_mut_ptr() as i32;
+                                      #[link(wasm_import_module = "server-physics")]
+                                      extern "C" {
+                                        #[cfg_attr(target_arch = "wasm32", link_name = "sweep")]
+                                        #[cfg_attr(not(target_arch = "wasm32"), link_name = "server-physics_sweep")]
+                                        fn wit_import(
+                                        _: i32, _: i32, );
+                                      }
+                                      wit_import(ptr0, ptr10);
+                                      let len11 = *((ptr10 + 4) as *const i32) as usize;
+                                      Vec::from_raw_parts(*((ptr10 + 0) as *const i32) as *mut _, len11, len11)
+                                    }
+                                  }
+                                  #[allow(clippy::all)]
+                                  pub fn overlap(shape: PhysicsShape,position: Vec3,rotation: Quat,filter: PhysicsFilter<'_,>,) -> wit_bindgen::rt::vec::Vec::<EntityId>{
+                                    
+                                    #[allow(unused_imports)]
+                                    use wit_bindgen::rt::{alloc, vec::Vec, string::String};
+                                    unsafe {
+                                      
+                                      #[repr(align(4))]
+                                      struct RetArea([u8; 76]);
+                                      let mut ret_area = core::mem::MaybeUninit::<RetArea>::uninit();
+                                      let ptr0 = ret_area.as_mut_ptr() as i32;match shape {
+                                        PhysicsShape::Sphere(e) => {
+                                          *((ptr0 + 0) as *mut u8) = (0i32) as u8;
+                                          *((ptr0 + 4) as *mut f32) = wit_bindgen::rt::as_f32(e);
+                                          
+                                        },
+                                        PhysicsShape::Cuboid(e) => {
+                                          *((ptr0 + 0) as *mut u8) = (1i32) as u8;
+                                          let super::types::Vec3{ x:x1, y:y1, z:z1, } = e;
+                                          *((ptr0 + 4) as *mut f32) = wit_bindgen::rt::as_f32(x1);
+                                          *((ptr0 + 8) as *mut f32) = wit_bindgen::rt::as_f32(y1);
+                                          *((ptr0 + 12) as *mut f32) = wit_bindgen::rt::as_f32(z1);
+                                          
+                                        },
+                                        PhysicsShape::Capsule(e) => {
+                                          *((ptr0 + 0) as *mut u8) = (2i32) as u8;
+                                          let (t2_0, t2_1, ) = e;
+                                          *((ptr0 + 4) as *mut f32) = wit_bindgen::rt::as_f32(t2_0);
+                                          *((ptr0 + 8) as *mut f32) = wit_bindgen::rt::as_f32(t2_1);
+                                          
+                                        },
+                                      };
+                                      let super::types::Vec3{ x:x3, y:y3, z:z3, } = position;
+                                      *((ptr0 + 16) as *mut f32) = wit_bindgen::rt::as_f32(x3);
+                                      *((ptr0 + 20) as *mut f32) = wit_bindgen::rt::as_f32(y3);
+                                      *((ptr0 + 24) as *mut f32) = wit_bindgen::rt::as_f32(z3);
+                                      let super::types::Quat{ x:x4, y:y4, z:z4, w:w4, } = rotation;
+                                      *((ptr0 + 28) as *mut f32) = wit_bindgen::rt::as_f32(x4);
+                                      *((ptr0 + 32) as *mut f32) = wit_bindgen::rt::as_f32(y4);
+                                      *((ptr0 + 36) as *mut f32) = wit_bindgen::rt::as_f32(z4);
+                                      *((ptr0 + 40) as *mut f32) = wit_bindgen::rt::as_f32(w4);
+                                      let PhysicsFilter{ collider_scene:collider_scene5, include:include5, exclude:exclude5, collision_layer:collision_layer5, } = filter;
+                                      match collider_scene5 {
+                                        Some(e) => {
+                                          *((ptr0 + 44) as *mut u8) = (1i32) as u8;
+                                          *((ptr0 + 45) as *mut u8) = (match e {
+                                            ColliderScene::Physics => 0,
+                                            ColliderScene::TriggerArea => 1,
+                                            ColliderScene::Picking => 2,
+                                          }) as u8;
+                                          
+                                        },
+                                        None => {
+                                          {
+                                            *((ptr0 + 44) as *mut u8) = (0i32) as u8;
+                                            
+                                          }
+                                        },
+                                      };
+                                      let vec6 = include5;
+                                      let ptr6 = vec6.as_ptr() as i32;
+                                      let len6 = vec6.len() as i32;
+                                      *((ptr0 + 52) as *mut i32) = len6;
+                                      *((ptr0 + 48) as *mut i32) = ptr6;
+                                      let vec7 = exclude5;
+                                      let ptr7 = vec7.as_ptr() as i32;
+                                      let len7 = vec7.len() as i32;
+                                      *((ptr0 + 60) as *mut i32) = len7;
+                                      *((ptr0 + 56) as *mut i32) = ptr7;
+                                      match collision_layer5 {
+                                        Some(e) => {
+                                          *((ptr0 + 64) as *mut u8) = (1i32) as u8;
+                                          let vec8 = e;
+                                          let ptr8 = vec8.as_ptr() as i32;
+                                          let len8 = vec8.len() as i32;
+                                          *((ptr0 + 72) as *mut i32) = len8;
+                                          *((ptr0 + 68) as *mut i32) = ptr8;
+                                          
+                                        },
+                                        None => {
+                                          {
+                                            *((ptr0 + 64) as *mut u8) = (0i32) as u8;
+                                            
+                                          }
+                                        },
+                                      };
+                                      let ptr9 = ret_area.as_mut_ptr() as i32;
+                                      #[link(wasm_import_module = "server-physics")]
+                                      extern "C" {
+                                        #[cfg_attr(target_arch = "wasm32", link_name = "overlap")]
+                                        #[cfg_attr(not(target_arch = "wasm32"), link_name = "server-physics_overlap")]
+                                        fn wit_import(
+                                        _: i32, _: i32, );
+                                      }
+                                      wit_import(ptr0, ptr9);
+                                      let len10 = *((ptr9 + 4) as *const i32) as usize;
+                                      Vec::from_raw_parts(*((ptr9 + 0) as *const i32) as *mut _, len10, len10)
+                                    }
+                                  }
                                   
                                 }
                                 
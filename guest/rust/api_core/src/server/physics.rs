@@ -1,5 +1,6 @@
 use crate::{
-    global::{EntityId, Vec3},
+    ecs::ComponentsTuple,
+    global::{EntityId, Quat, Vec3},
     internal::{
         conversion::{FromBindgen, IntoBindgen},
         wit,
@@ -212,3 +213,165 @@ pub fn move_character(
         down: res.down,
     }
 }
+
+/// The shape used by [sweep] and [overlap].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryShape {
+    /// A sphere with the given `radius`.
+    Sphere {
+        /// The radius of the sphere.
+        radius: f32,
+    },
+    /// A box extending `half_extents` from its center along each axis.
+    Cuboid {
+        /// Half of the size of the box.
+        half_extents: Vec3,
+    },
+    /// A capsule along the Z axis.
+    Capsule {
+        /// The radius of the capsule.
+        radius: f32,
+        /// The distance from the center of the capsule to the center of each of its caps.
+        half_height: f32,
+    },
+}
+impl IntoBindgen for QueryShape {
+    type Item = wit::server_physics::PhysicsShape;
+    fn into_bindgen(self) -> Self::Item {
+        match self {
+            QueryShape::Sphere { radius } => wit::server_physics::PhysicsShape::Sphere(radius),
+            QueryShape::Cuboid { half_extents } => {
+                wit::server_physics::PhysicsShape::Cuboid(half_extents.into_bindgen())
+            }
+            QueryShape::Capsule {
+                radius,
+                half_height,
+            } => wit::server_physics::PhysicsShape::Capsule((radius, half_height)),
+        }
+    }
+}
+
+/// The physics scenes that can be queried with [sweep] and [overlap].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColliderScene {
+    /// The scene with all the static and dynamic colliders.
+    Physics,
+    /// The scene with the trigger areas.
+    TriggerArea,
+    /// The scene with the picking colliders.
+    Picking,
+}
+impl IntoBindgen for ColliderScene {
+    type Item = wit::server_physics::ColliderScene;
+    fn into_bindgen(self) -> Self::Item {
+        match self {
+            ColliderScene::Physics => wit::server_physics::ColliderScene::Physics,
+            ColliderScene::TriggerArea => wit::server_physics::ColliderScene::TriggerArea,
+            ColliderScene::Picking => wit::server_physics::ColliderScene::Picking,
+        }
+    }
+}
+
+/// Restricts which entities are returned by [sweep] and [overlap].
+///
+/// By default, all the physics scenes are queried and every entity is returned.
+#[derive(Clone, Debug, Default)]
+pub struct QueryFilter {
+    collider_scene: Option<ColliderScene>,
+    include: Vec<u32>,
+    exclude: Vec<u32>,
+    collision_layer: Option<String>,
+}
+impl QueryFilter {
+    /// Creates a filter that accepts everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Only query the given physics scene.
+    pub fn collider_scene(mut self, collider_scene: ColliderScene) -> Self {
+        self.collider_scene = Some(collider_scene);
+        self
+    }
+    /// Only return entities that have all of these components.
+    pub fn requires(mut self, requires: impl ComponentsTuple) -> Self {
+        self.include.extend_from_slice(&requires.as_indices());
+        self
+    }
+    /// Only return entities that have none of these components.
+    pub fn excludes(mut self, excludes: impl ComponentsTuple) -> Self {
+        self.exclude.extend_from_slice(&excludes.as_indices());
+        self
+    }
+    /// Only return entities on the layers that `collision_layer` collides with.
+    pub fn collision_layer(mut self, collision_layer: impl Into<String>) -> Self {
+        self.collision_layer = Some(collision_layer.into());
+        self
+    }
+    fn as_bindgen(&self) -> wit::server_physics::PhysicsFilter<'_> {
+        wit::server_physics::PhysicsFilter {
+            collider_scene: self.collider_scene.into_bindgen(),
+            include: &self.include,
+            exclude: &self.exclude,
+            collision_layer: self.collision_layer.as_deref(),
+        }
+    }
+}
+
+/// Where a [sweep] hit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SweepHit {
+    /// The entity that was hit.
+    pub entity: EntityId,
+    /// How far the shape travelled before hitting the entity.
+    pub distance: f32,
+    /// The position of the contact.
+    pub position: Vec3,
+    /// The surface normal of the entity at the contact.
+    pub normal: Vec3,
+}
+
+/// Sweeps `shape` from `position` with `rotation` along `direction` for at most `distance`, and returns the [SweepHit]s
+/// along the way, closest first. Entities that already overlap the shape at `position` are returned with a distance of zero.
+///
+/// `direction` doesn't need to be normalized, but it must have a length.
+pub fn sweep(
+    shape: QueryShape,
+    position: Vec3,
+    rotation: Quat,
+    direction: Vec3,
+    distance: f32,
+    filter: &QueryFilter,
+) -> Vec<SweepHit> {
+    wit::server_physics::sweep(
+        shape.into_bindgen(),
+        position.into_bindgen(),
+        rotation.into_bindgen(),
+        direction.into_bindgen(),
+        distance,
+        filter.as_bindgen(),
+    )
+    .into_iter()
+    .map(|hit| SweepHit {
+        entity: hit.entity.from_bindgen(),
+        distance: hit.distance,
+        position: hit.position.from_bindgen(),
+        normal: hit.normal.from_bindgen(),
+    })
+    .collect()
+}
+
+/// Returns all the entities with a collider overlapping `shape` at `position` with `rotation`.
+pub fn overlap(
+    shape: QueryShape,
+    position: Vec3,
+    rotation: Quat,
+    filter: &QueryFilter,
+) -> Vec<EntityId> {
+    wit::server_physics::overlap(
+        shape.into_bindgen(),
+        position.into_bindgen(),
+        rotation.into_bindgen(),
+        filter.as_bindgen(),
+    )
+    .from_bindgen()
+}
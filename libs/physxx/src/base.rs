@@ -1,5 +1,5 @@
 use crate::{
-    AsPxJoint, AsPxRigidActor, AsPxRigidBody, PxAggregateRef, PxArticulationLinkRef, PxConstraintRef, PxConvexMesh, PxFixedJointRef, PxHeightField, PxJointRef, PxMaterial, PxRevoluteJointRef, PxRigidActorRef, PxRigidBodyRef, PxRigidDynamicRef, PxRigidStaticRef, PxShape
+    AsPxJoint, AsPxRigidActor, AsPxRigidBody, PxAggregateRef, PxArticulationLinkRef, PxConstraintRef, PxConvexMesh, PxD6JointRef, PxDistanceJointRef, PxFixedJointRef, PxHeightField, PxJointRef, PxMaterial, PxPrismaticJointRef, PxRevoluteJointRef, PxRigidActorRef, PxRigidBodyRef, PxRigidDynamicRef, PxRigidStaticRef, PxShape, PxSphericalJointRef
 };
 
 pub trait AsPxBase: Sync + Send {
@@ -53,6 +53,10 @@ impl<T: AsPxBase + 'static> PxBase for T {
         match self.as_px_any() {
             PxAny::PxFixedJoint(o) => Some(o.as_joint()),
             PxAny::PxRevoluteJoint(o) => Some(o.as_joint()),
+            PxAny::PxSphericalJoint(o) => Some(o.as_joint()),
+            PxAny::PxPrismaticJoint(o) => Some(o.as_joint()),
+            PxAny::PxDistanceJoint(o) => Some(o.as_joint()),
+            PxAny::PxD6Joint(o) => Some(o.as_joint()),
             _ => None,
         }
     }
@@ -91,6 +95,10 @@ pub enum PxAny {
     PxShape(PxShape),
     PxFixedJoint(PxFixedJointRef),
     PxRevoluteJoint(PxRevoluteJointRef),
+    PxSphericalJoint(PxSphericalJointRef),
+    PxPrismaticJoint(PxPrismaticJointRef),
+    PxDistanceJoint(PxDistanceJointRef),
+    PxD6Joint(PxD6JointRef),
     PxConstraint(PxConstraintRef),
     PxArticulationLink(PxArticulationLinkRef),
 }
@@ -120,13 +128,13 @@ impl PxAny {
                 physx_sys::PxConcreteType::eTRIANGLE_MESH_BVH34 => panic!("PhysX object type is not supported"),
 
                 physx_sys::PxJointConcreteType::eCONTACT => panic!("PhysX object type is not supported"),
-                physx_sys::PxJointConcreteType::eD6 => panic!("PhysX object type is not supported"),
-                physx_sys::PxJointConcreteType::eDISTANCE => panic!("PhysX object type is not supported"),
+                physx_sys::PxJointConcreteType::eD6 => PxAny::PxD6Joint(PxD6JointRef(obj as _)),
+                physx_sys::PxJointConcreteType::eDISTANCE => PxAny::PxDistanceJoint(PxDistanceJointRef(obj as _)),
                 physx_sys::PxJointConcreteType::eFIXED => PxAny::PxFixedJoint(PxFixedJointRef(obj as _)),
                 physx_sys::PxJointConcreteType::eLast => panic!("PhysX object type is not supported"),
-                physx_sys::PxJointConcreteType::ePRISMATIC => panic!("PhysX object type is not supported"),
+                physx_sys::PxJointConcreteType::ePRISMATIC => PxAny::PxPrismaticJoint(PxPrismaticJointRef(obj as _)),
                 physx_sys::PxJointConcreteType::eREVOLUTE => PxAny::PxRevoluteJoint(PxRevoluteJointRef(obj as _)),
-                physx_sys::PxJointConcreteType::eSPHERICAL => panic!("PhysX object type is not supported"),
+                physx_sys::PxJointConcreteType::eSPHERICAL => PxAny::PxSphericalJoint(PxSphericalJointRef(obj as _)),
 
                 _ => panic!("Unknown type"),
             }
@@ -3,7 +3,7 @@ use std::ptr::null_mut;
 use glam::{DVec3, Vec3};
 use physx_sys::PxControllerCollisionFlag::*;
 
-use crate::{to_glam_vec3, to_glam_vec3_f64, to_physx_vec3, to_physx_vec3_f64, PxMaterial, PxRigidDynamicRef, PxSceneRef};
+use crate::{to_glam_vec3, to_glam_vec3_f64, to_physx_vec3, to_physx_vec3_f64, PxFilterData, PxMaterial, PxRigidDynamicRef, PxSceneRef};
 
 #[derive(Clone, Copy)]
 pub struct PxControllerManagerRef(*mut physx_sys::PxControllerManager);
@@ -143,10 +143,15 @@ unsafe impl Send for PxControllerDescRef {}
 //     }
 // }
 
-pub struct PxControllerFilters(physx_sys::PxControllerFilters);
+pub struct PxControllerFilters(physx_sys::PxControllerFilters, Option<Box<physx_sys::PxFilterData>>);
 impl PxControllerFilters {
     pub fn new() -> Self {
-        Self(unsafe { physx_sys::PxControllerFilters_new(null_mut(), null_mut(), null_mut()) })
+        Self(unsafe { physx_sys::PxControllerFilters_new(null_mut(), null_mut(), null_mut()) }, None)
+    }
+    /// Only collide with shapes whose query filter data has a bit in common with `data`
+    pub fn with_filter_data(data: PxFilterData) -> Self {
+        let data = Box::new(physx_sys::PxFilterData::from(data));
+        Self(unsafe { physx_sys::PxControllerFilters_new(&*data, null_mut(), null_mut()) }, Some(data))
     }
 }
 
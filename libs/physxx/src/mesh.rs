@@ -1,7 +1,8 @@
 use glam::Vec3;
 
 use crate::{
-    AsPxPtr, PxConvexMeshCookingResult, PxCookingRef, PxDefaultMemoryInputData, PxDefaultMemoryOutputStream, PxPhysicsRef, PxReferenceCounted
+    to_glam_vec3, AsPxPtr, PxConvexMeshCookingResult, PxCookingRef, PxDefaultMemoryInputData, PxDefaultMemoryOutputStream, PxPhysicsRef,
+    PxReferenceCounted,
 };
 
 bitflags! {
@@ -52,6 +53,16 @@ impl PxConvexMesh {
     pub(crate) fn acquire_reference(&mut self) {
         unsafe { physx_sys::PxConvexMesh_acquireReference_mut(self.0) }
     }
+    pub fn get_nb_vertices(&self) -> u32 {
+        unsafe { physx_sys::PxConvexMesh_getNbVertices(self.0) }
+    }
+    /// The vertices of the convex hull
+    pub fn get_vertices(&self) -> Vec<Vec3> {
+        unsafe {
+            let vertices = physx_sys::PxConvexMesh_getVertices(self.0);
+            std::slice::from_raw_parts(vertices, self.get_nb_vertices() as usize).iter().map(to_glam_vec3).collect()
+        }
+    }
 }
 impl PxReferenceCounted for PxConvexMesh {
     fn get_reference_count(&self) -> u32 {
@@ -6,8 +6,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     sweep::PxSweepHit, to_glam_vec3, to_physx_vec3, AsArticulationBase, AsPxActor, PxActorRef, PxAggregateRef, PxCollectionRef,
-    PxConstraintRef, PxDefaultCpuDispatcherRef, PxGeometry, PxHitFlags, PxPhysicsRef, PxPvdSceneClientRef, PxRaycastHit, PxRigidActorRef,
-    PxShape, PxTransform,
+    PxConstraintRef, PxDefaultCpuDispatcherRef, PxFilterData, PxGeometry, PxHitFlags, PxPhysicsRef, PxPvdSceneClientRef, PxRaycastHit,
+    PxRigidActorRef, PxShape, PxTransform,
 };
 
 pub struct PxSceneDesc(physx_sys::PxSceneDesc);
@@ -41,11 +41,12 @@ impl PxSceneDesc {
             unsafe extern "C" fn collision_callback_trampoline<C: FnMut(&PxContactPairHeader)>(
                 user_data: *mut std::ffi::c_void,
                 pair_header: *const physx_sys::PxContactPairHeader,
-                _pairs: *const physx_sys::PxContactPair,
-                _nb_pairs: u32,
+                pairs: *const physx_sys::PxContactPair,
+                nb_pairs: u32,
             ) {
                 let mut cb: Box<C> = Box::from_raw(user_data as _);
                 let pair_header_flags = PxContactPairHeaderFlag::from_bits((*pair_header).flags.mBits).unwrap();
+                let pairs = std::slice::from_raw_parts(pairs, nb_pairs as usize);
                 cb(&PxContactPairHeader {
                     actors: [
                         if pair_header_flags.contains(PxContactPairHeaderFlag::REMOVED_ACTOR_0) {
@@ -59,6 +60,7 @@ impl PxSceneDesc {
                             PxRigidActorRef::from_ptr((*pair_header).actors[1])
                         },
                     ],
+                    pairs: pairs.iter().map(|pair| PxContactPair::from_px(pair)).collect(),
                 });
                 Box::into_raw(cb);
             }
@@ -85,6 +87,45 @@ impl PxSceneDesc {
 
 pub struct PxContactPairHeader {
     pub actors: [Option<PxRigidActorRef>; 2],
+    pub pairs: Vec<PxContactPair>,
+}
+
+/// A pair of touching shapes. The contact points are only available if the pair was reported with `PxPairFlag::eNOTIFY_CONTACT_POINTS`
+pub struct PxContactPair {
+    pub shapes: [Option<PxShape>; 2],
+    pub points: Vec<PxContactPairPoint>,
+}
+impl PxContactPair {
+    unsafe fn from_px(pair: &physx_sys::PxContactPair) -> Self {
+        let flags = PxContactPairFlag::from_bits_truncate(pair.flags.mBits);
+        let mut points: Vec<physx_sys::PxContactPairPoint> = Vec::with_capacity(pair.contactCount as usize);
+        let count = physx_sys::PxContactPair_extractContacts(pair, points.as_mut_ptr(), pair.contactCount as u32);
+        points.set_len(count as usize);
+        Self {
+            shapes: [
+                if flags.contains(PxContactPairFlag::REMOVED_SHAPE_0) { None } else { Some(PxShape::from_ptr(pair.shapes[0])) },
+                if flags.contains(PxContactPairFlag::REMOVED_SHAPE_1) { None } else { Some(PxShape::from_ptr(pair.shapes[1])) },
+            ],
+            points: points
+                .iter()
+                .map(|point| PxContactPairPoint {
+                    position: to_glam_vec3(&point.position),
+                    separation: point.separation,
+                    normal: to_glam_vec3(&point.normal),
+                    impulse: to_glam_vec3(&point.impulse),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PxContactPairPoint {
+    pub position: Vec3,
+    pub separation: f32,
+    /// Points from the second shape towards the first one
+    pub normal: Vec3,
+    pub impulse: Vec3,
 }
 
 pub struct PxSimulationEventCallback<C: FnMut(&PxContactPairHeader)> {
@@ -98,6 +139,15 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct PxContactPairFlag: u16 {
+        const REMOVED_SHAPE_0 = physx_sys::PxContactPairFlag::eREMOVED_SHAPE_0 as u16;
+        const REMOVED_SHAPE_1 = physx_sys::PxContactPairFlag::eREMOVED_SHAPE_1 as u16;
+        const ACTOR_PAIR_HAS_FIRST_TOUCH = physx_sys::PxContactPairFlag::eACTOR_PAIR_HAS_FIRST_TOUCH as u16;
+        const ACTOR_PAIR_LOST_TOUCH = physx_sys::PxContactPairFlag::eACTOR_PAIR_LOST_TOUCH as u16;
+    }
+}
+
 bitflags! {
     pub struct PxSceneFlags: u32 {
         const ADAPTIVE_FORCE = physx_sys::PxSceneFlag::eADAPTIVE_FORCE;
@@ -357,6 +407,10 @@ impl PxQueryFilterData {
     pub fn set_flags(&mut self, flags: PxQueryFlag) {
         self.0.flags.mBits = flags.bits as u16;
     }
+    /// Shapes are only reported if their query filter data has a bit in common with `data`, unless `data` is all zero
+    pub fn set_data(&mut self, data: PxFilterData) {
+        self.0.data = data.into();
+    }
 }
 impl Default for PxQueryFilterData {
     fn default() -> Self {
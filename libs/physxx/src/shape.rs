@@ -103,6 +103,37 @@ impl PxShape {
     pub fn set_rest_offset(&self, offset: f32) {
         unsafe { physx_sys::PxShape_setRestOffset_mut(self.0, offset) }
     }
+    pub fn get_simulation_filter_data(&self) -> PxFilterData {
+        unsafe { physx_sys::PxShape_getSimulationFilterData(self.0) }.into()
+    }
+    pub fn set_simulation_filter_data(&self, data: PxFilterData) {
+        unsafe { physx_sys::PxShape_setSimulationFilterData_mut(self.0, &data.into()) }
+    }
+    pub fn get_query_filter_data(&self) -> PxFilterData {
+        unsafe { physx_sys::PxShape_getQueryFilterData(self.0) }.into()
+    }
+    pub fn set_query_filter_data(&self, data: PxFilterData) {
+        unsafe { physx_sys::PxShape_setQueryFilterData_mut(self.0, &data.into()) }
+    }
+}
+
+/// User data used to decide which shapes collide in the filter shader, and which shapes scene queries report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PxFilterData {
+    pub word0: u32,
+    pub word1: u32,
+    pub word2: u32,
+    pub word3: u32,
+}
+impl From<physx_sys::PxFilterData> for PxFilterData {
+    fn from(data: physx_sys::PxFilterData) -> Self {
+        Self { word0: data.word0, word1: data.word1, word2: data.word2, word3: data.word3 }
+    }
+}
+impl From<PxFilterData> for physx_sys::PxFilterData {
+    fn from(data: PxFilterData) -> Self {
+        physx_sys::PxFilterData { word0: data.word0, word1: data.word1, word2: data.word2, word3: data.word3 }
+    }
 }
 impl AsPxBase for PxShape {
     fn as_base(&self) -> PxBaseRef {
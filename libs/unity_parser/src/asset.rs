@@ -128,6 +128,7 @@ impl Asset {
                 joint_indices: None,
                 joint_weights: None,
                 indices: Some(indices),
+                morph_targets: Vec::new(),
             },
         }
     }
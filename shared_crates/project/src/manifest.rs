@@ -17,6 +17,8 @@ pub struct Manifest {
     pub concepts: BTreeMap<IdentifierPathBuf, NamespaceOr<Concept>>,
     #[serde(default)]
     pub messages: BTreeMap<IdentifierPathBuf, NamespaceOr<Message>>,
+    #[serde(default)]
+    pub physics: Physics,
 }
 impl Manifest {
     pub fn parse(manifest: &str) -> Result<Self, toml::de::Error> {
@@ -73,6 +75,18 @@ pub struct Build {
     pub rust: BuildRust,
 }
 
+#[derive(Deserialize, Clone, Debug, PartialEq, Default, Serialize)]
+pub struct Physics {
+    /// The collision layers of this project; the first one is used for colliders without a layer
+    #[serde(default)]
+    #[serde(rename = "collision-layers")]
+    pub collision_layers: Vec<Identifier>,
+    /// The layers each layer collides with; layers that are not listed collide with every layer
+    #[serde(default)]
+    #[serde(rename = "collides-with")]
+    pub collides_with: BTreeMap<Identifier, Vec<Identifier>>,
+}
+
 #[derive(Deserialize, Clone, Debug, PartialEq, Serialize)]
 pub struct BuildRust {
     #[serde(rename = "feature-multibuild")]
@@ -199,6 +213,7 @@ mod tests {
                     .into()
                 )]),
                 messages: BTreeMap::new(),
+                physics: Physics::default(),
             })
         )
     }
@@ -235,10 +250,34 @@ mod tests {
                 components: BTreeMap::new(),
                 concepts: BTreeMap::new(),
                 messages: BTreeMap::new(),
+                physics: Physics::default(),
             })
         )
     }
 
+    #[test]
+    fn can_parse_physics_settings() {
+        const TOML: &str = r#"
+        [project]
+        id = "shooter"
+        version = "0.0.1"
+
+        [physics]
+        collision-layers = ["default", "player", "projectile"]
+        collides-with = { projectile = ["default"] }
+        "#;
+
+        let physics = Manifest::parse(TOML).unwrap().physics;
+        let id = |id| Identifier::new(id).unwrap();
+        assert_eq!(
+            physics,
+            Physics {
+                collision_layers: vec![id("default"), id("player"), id("projectile")],
+                collides_with: BTreeMap::from_iter([(id("projectile"), vec![id("default")])]),
+            }
+        );
+    }
+
     #[test]
     fn can_parse_manifest_with_namespaces() {
         const TOML: &str = r#"
@@ -302,6 +341,7 @@ mod tests {
                 ]),
                 concepts: BTreeMap::new(),
                 messages: BTreeMap::new(),
+                physics: Physics::default(),
             })
         )
     }
@@ -437,6 +477,7 @@ mod tests {
                     )
                 ]),
                 messages: BTreeMap::new(),
+                physics: Physics::default(),
             })
         )
     }
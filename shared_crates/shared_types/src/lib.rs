@@ -815,3 +815,42 @@ impl From<MouseButton> for winit::event::MouseButton {
         }
     }
 }
+
+/// A button of a gamepad, named by its position on the gamepad rather than by its label.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, EnumString, Display, Serialize, Deserialize)]
+pub enum GamepadButton {
+    /// The bottom face button ("A" on Xbox controllers, "Cross" on PlayStation controllers).
+    South,
+    /// The right face button ("B" on Xbox controllers, "Circle" on PlayStation controllers).
+    East,
+    /// The top face button ("Y" on Xbox controllers, "Triangle" on PlayStation controllers).
+    North,
+    /// The left face button ("X" on Xbox controllers, "Square" on PlayStation controllers).
+    West,
+    /// The left shoulder button.
+    LeftBumper,
+    /// The left trigger.
+    LeftTrigger,
+    /// The right shoulder button.
+    RightBumper,
+    /// The right trigger.
+    RightTrigger,
+    /// The select (or back, or share) button.
+    Select,
+    /// The start (or menu, or options) button.
+    Start,
+    /// The mode (or guide, or home) button.
+    Mode,
+    /// Pressing the left stick.
+    LeftThumb,
+    /// Pressing the right stick.
+    RightThumb,
+    /// Up on the directional pad.
+    DPadUp,
+    /// Down on the directional pad.
+    DPadDown,
+    /// Left on the directional pad.
+    DPadLeft,
+    /// Right on the directional pad.
+    DPadRight,
+}
@@ -35,6 +35,7 @@ use parking_lot::Mutex;
 use crate::{
     default_theme::{cutout_color, primary_color, secondary_color},
     dropdown::Tooltip,
+    focus::Focusable,
     layout::{FlowColumn, FlowRow},
     text::Text,
     UIBase, UIElement, UIExt,
@@ -310,8 +311,15 @@ pub fn Button(
         .el();
 
     if disabled {
-        content
-    } else if let Some(hotkey) = hotkey {
+        return content;
+    }
+    let content = Focusable::new(content)
+        .on_activate({
+            to_owned![on_invoked, set_is_working];
+            move |world| on_invoked.invoke(world, set_is_working.clone())
+        })
+        .el();
+    if let Some(hotkey) = hotkey {
         Hotkey {
             hotkey,
             hotkey_modifier,
@@ -0,0 +1,485 @@
+//! Keyboard and gamepad navigation between focusable elements.
+//!
+//! Elements wrapped in a [Focusable] inside a [FocusRoot](crate::FocusRoot) can be focused with Tab and Shift+Tab, in
+//! reading order, or with the arrow keys and the directional pad of a gamepad, which move the focus to the closest element
+//! in that direction. The arrow keys and the directional pad only start navigating once an element has been focused, or
+//! inside a [FocusScope]. The focused element is activated with Enter or the South button of a gamepad ("A" on Xbox
+//! controllers). A ring is drawn around the focused element when it was focused by navigation.
+//!
+//! A [FocusScope] keeps the navigation within its elements while it is mounted; [DialogScreen](crate::screens::DialogScreen)
+//! and [PageScreen](crate::screens::PageScreen) are focus scopes.
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use ambient_cb::{cb, Cb};
+use ambient_element::{element_component, to_owned, Element, ElementComponentExt, Hooks};
+use ambient_guest_bridge::{
+    components::{
+        layout::{height, width},
+        rect::{background_color, border_color, border_radius, border_thickness},
+        transform::{local_to_world, translation},
+    },
+    ecs::{EntityId, World},
+    messages,
+};
+use ambient_shared_types::{GamepadButton, ModifiersState, VirtualKeyCode};
+use glam::{vec2, Vec2, Vec3Swizzles, Vec4};
+use itertools::Itertools;
+use parking_lot::Mutex;
+
+use crate::{default_theme::primary_color, with_rect, Focus, UIBase};
+
+const FOCUS_RING_THICKNESS: f32 = 2.;
+
+#[derive(Debug)]
+struct FocusEntry {
+    entity: Option<EntityId>,
+    scope: Option<String>,
+    tab_index: i32,
+    on_activate: Option<Cb<dyn Fn(&mut World) + Sync + Send>>,
+}
+
+#[derive(Debug)]
+struct FocusScopeEntry {
+    id: String,
+    depth: usize,
+    /// When the scope was mounted, relative to the other scopes
+    order: u64,
+}
+
+#[derive(Debug, Default)]
+struct FocusRegistryState {
+    /// The focusable elements, by instance id
+    entries: HashMap<String, FocusEntry>,
+    scopes: Vec<FocusScopeEntry>,
+    next_scope_order: u64,
+}
+
+/// A focusable element that can currently be navigated to.
+#[derive(Debug, Clone)]
+struct FocusTarget {
+    id: String,
+    tab_index: i32,
+    min: Vec2,
+    max: Vec2,
+    on_activate: Option<Cb<dyn Fn(&mut World) + Sync + Send>>,
+}
+
+/// Keeps track of the focusable elements of a [FocusRoot](crate::FocusRoot), and of their focus scopes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FocusRegistry(Arc<Mutex<FocusRegistryState>>);
+impl FocusRegistry {
+    fn active_scope(state: &FocusRegistryState) -> Option<String> {
+        state.scopes.iter().max_by_key(|scope| (scope.depth, scope.order)).map(|scope| scope.id.clone())
+    }
+    /// Returns the focusable elements of the active scope that are on screen, and whether there is an active scope.
+    fn targets(&self, world: &World) -> (Vec<FocusTarget>, bool) {
+        let state = self.0.lock();
+        let scope = Self::active_scope(&state);
+        let targets = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.scope == scope)
+            .filter_map(|(id, entry)| {
+                let (min, max, _) = entity_rect(world, entry.entity?)?;
+                Some(FocusTarget { id: id.clone(), tab_index: entry.tab_index, min, max, on_activate: entry.on_activate.clone() })
+            })
+            .collect();
+        (targets, scope.is_some())
+    }
+    /// Whether `id` is a [Focusable] element.
+    fn contains(&self, id: &str) -> bool {
+        self.0.lock().entries.contains_key(id)
+    }
+    fn entity(&self, id: &str) -> Option<EntityId> {
+        self.0.lock().entries.get(id).and_then(|entry| entry.entity)
+    }
+}
+
+/// The top-left corner, bottom-right corner and depth of a UI element on screen.
+fn entity_rect(world: &World, entity: EntityId) -> Option<(Vec2, Vec2, f32)> {
+    let (scale, _, position) = world.get(entity, local_to_world()).ok()?.to_scale_rotation_translation();
+    let size = vec2(world.get(entity, width()).unwrap_or(0.), world.get(entity, height()).unwrap_or(0.)) * scale.xy();
+    if size.x <= 0. || size.y <= 0. {
+        return None;
+    }
+    Some((position.xy(), position.xy() + size, position.z))
+}
+
+/// The focusable elements in the order Tab goes through them: by `tab_index`, then from top to bottom and left to right.
+fn tab_order(targets: &[FocusTarget]) -> Vec<usize> {
+    (0..targets.len())
+        .sorted_by(|&a, &b| {
+            let (a, b) = (&targets[a], &targets[b]);
+            a.tab_index.cmp(&b.tab_index).then(a.min.y.total_cmp(&b.min.y)).then(a.min.x.total_cmp(&b.min.x))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FocusDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+impl FocusDirection {
+    fn vector(self) -> Vec2 {
+        match self {
+            FocusDirection::Up => -Vec2::Y,
+            FocusDirection::Down => Vec2::Y,
+            FocusDirection::Left => -Vec2::X,
+            FocusDirection::Right => Vec2::X,
+        }
+    }
+}
+
+/// Returns the closest element in `direction` from the element `from`. Elements that are out of line are penalized,
+/// so that moving down a column stays in the column.
+fn spatial_neighbor(targets: &[FocusTarget], from: usize, direction: FocusDirection) -> Option<usize> {
+    let center = |target: &FocusTarget| (target.min + target.max) / 2.;
+    let from_center = center(&targets[from]);
+    let direction = direction.vector();
+    targets
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != from)
+        .filter_map(|(index, target)| {
+            let delta = center(target) - from_center;
+            let along = delta.dot(direction);
+            if along <= 0. {
+                return None;
+            }
+            let across = (delta - direction * along).length();
+            Some((index, along + 2. * across))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FocusAction {
+    Next,
+    Previous,
+    Move(FocusDirection),
+    Activate,
+}
+impl FocusAction {
+    fn from_key(key: VirtualKeyCode, modifiers: ModifiersState) -> Option<Self> {
+        Some(match key {
+            VirtualKeyCode::Tab if modifiers.shift() => Self::Previous,
+            VirtualKeyCode::Tab => Self::Next,
+            VirtualKeyCode::Up => Self::Move(FocusDirection::Up),
+            VirtualKeyCode::Down => Self::Move(FocusDirection::Down),
+            VirtualKeyCode::Left => Self::Move(FocusDirection::Left),
+            VirtualKeyCode::Right => Self::Move(FocusDirection::Right),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Self::Activate,
+            _ => return None,
+        })
+    }
+    fn from_gamepad_button(button: GamepadButton) -> Option<Self> {
+        Some(match button {
+            GamepadButton::DPadUp => Self::Move(FocusDirection::Up),
+            GamepadButton::DPadDown => Self::Move(FocusDirection::Down),
+            GamepadButton::DPadLeft => Self::Move(FocusDirection::Left),
+            GamepadButton::DPadRight => Self::Move(FocusDirection::Right),
+            GamepadButton::South => Self::Activate,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FocusChange {
+    Focus(usize),
+    Activate(usize),
+}
+
+/// Returns the element among `targets` to focus or activate for `action`, given the currently focused element.
+///
+/// Tab always moves the focus, but nothing is focused by the arrow keys or the directional pad, nor activated, unless
+/// something already is focused or the targets are in an active [FocusScope] (`scoped`), so that the input of a game
+/// doesn't drive the UI on top of it.
+fn navigate(targets: &[FocusTarget], current: Option<usize>, scoped: bool, action: FocusAction) -> Option<FocusChange> {
+    let order = tab_order(targets);
+    let next = match (action, current) {
+        (FocusAction::Activate, Some(current)) => return Some(FocusChange::Activate(current)),
+        (FocusAction::Activate, None) => return None,
+        (FocusAction::Next | FocusAction::Previous, Some(current)) => {
+            let position = order.iter().position(|&index| index == current)?;
+            let step = if action == FocusAction::Next { 1 } else { order.len() - 1 };
+            Some(order[(position + step) % order.len()])
+        }
+        (FocusAction::Next, None) => order.first().copied(),
+        (FocusAction::Previous, None) => order.last().copied(),
+        (FocusAction::Move(direction), Some(current)) => spatial_neighbor(targets, current, direction),
+        (FocusAction::Move(_), None) if scoped => order.first().copied(),
+        (FocusAction::Move(_), None) => None,
+    }?;
+    Some(FocusChange::Focus(next))
+}
+
+/// Listens to the keyboard and the gamepads to move the focus, and draws the focus ring.
+#[element_component]
+pub(crate) fn FocusNavigator(hooks: &mut Hooks) -> Element {
+    let (registry, _) = hooks.consume_context::<FocusRegistry>().unwrap();
+    let (focus, set_focus) = hooks.consume_context::<Focus>().unwrap();
+    // The ring is only shown while the focus is driven by the keyboard or a gamepad
+    let (ring_visible, set_ring_visible) = hooks.use_state(false);
+    let (ring, set_ring) = hooks.use_state(None::<(Vec2, Vec2, f32)>);
+
+    let focused = focus.0.as_ref().map(|(id, _)| id.clone());
+    let on_action: Cb<dyn Fn(&mut World, FocusAction) + Sync + Send> = cb({
+        to_owned![registry, focused, set_ring_visible];
+        move |world, action| {
+            // Elements that are focused without being `Focusable` (e.g. a text editor) handle the keys themselves; only
+            // Tab leaves them
+            let focused_elsewhere = focused.as_deref().map(|focused| registry.contains(focused)) == Some(false);
+            if focused_elsewhere && !matches!(action, FocusAction::Next | FocusAction::Previous) {
+                return;
+            }
+            let (targets, scoped) = registry.targets(world);
+            let current = focused.as_deref().and_then(|focused| targets.iter().position(|target| target.id == focused));
+            match navigate(&targets, current, scoped, action) {
+                Some(FocusChange::Focus(next)) => {
+                    set_focus(Focus::new(Some(targets[next].id.clone())));
+                    set_ring_visible(true);
+                }
+                Some(FocusChange::Activate(current)) => {
+                    if let Some(on_activate) = &targets[current].on_activate {
+                        on_activate(world);
+                    }
+                }
+                None => {}
+            }
+        }
+    });
+    hooks.use_runtime_message::<messages::WindowKeyboardInput>({
+        to_owned![on_action];
+        move |world, event| {
+            if !event.pressed {
+                return;
+            }
+            let modifiers = ModifiersState::from_bits(event.modifiers).unwrap_or_default();
+            if let Some(action) = event
+                .keycode
+                .as_deref()
+                .and_then(|key| VirtualKeyCode::from_str(key).ok())
+                .and_then(|key| FocusAction::from_key(key, modifiers))
+            {
+                on_action(world, action);
+            }
+        }
+    });
+    hooks.use_runtime_message::<messages::GamepadInput>({
+        to_owned![on_action];
+        move |world, event| {
+            if !event.pressed {
+                return;
+            }
+            if let Some(action) = GamepadButton::from_str(&event.button).ok().and_then(FocusAction::from_gamepad_button) {
+                on_action(world, action);
+            }
+        }
+    });
+    hooks.use_runtime_message::<messages::WindowMouseInput>({
+        to_owned![set_ring_visible];
+        move |_world, _event| set_ring_visible(false)
+    });
+    hooks.use_frame({
+        to_owned![registry, focused];
+        move |world| {
+            let new_ring = if ring_visible {
+                focused.as_deref().and_then(|id| registry.entity(id)).and_then(|entity| entity_rect(world, entity))
+            } else {
+                None
+            };
+            if new_ring != ring {
+                set_ring(new_ring);
+            }
+        }
+    });
+
+    if let Some((min, max, z)) = ring {
+        let offset = Vec2::ONE * FOCUS_RING_THICKNESS;
+        let size = max - min + offset * 2.;
+        with_rect(UIBase.el())
+            .with(translation(), (min - offset).extend(z - 0.01))
+            .with(width(), size.x)
+            .with(height(), size.y)
+            .with(background_color(), Vec4::ZERO)
+            .with(border_color(), primary_color().into())
+            .with(border_thickness(), FOCUS_RING_THICKNESS)
+            .with(border_radius(), Vec4::ONE * 3.)
+    } else {
+        Element::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FocusScopeId {
+    id: String,
+    depth: usize,
+}
+
+/// An element that can be focused, and activated, with the keyboard or a gamepad.
+///
+/// Must be used within a [FocusRoot](crate::FocusRoot); elsewhere, the element can't be focused.
+#[element_component]
+pub fn Focusable(
+    hooks: &mut Hooks,
+    /// The element to focus.
+    inner: Element,
+    /// Elements are focused with Tab in increasing order of `tab_index`, then from top to bottom and left to right.
+    tab_index: i32,
+    /// The callback to invoke when the element is activated with Enter or a gamepad.
+    on_activate: Option<Cb<dyn Fn(&mut World) + Sync + Send>>,
+) -> Element {
+    let instance_id = hooks.instance_id().to_owned();
+    let registry = hooks.consume_context::<FocusRegistry>().map(|(registry, _)| registry);
+    let scope = hooks.consume_context::<FocusScopeId>().map(|(scope, _)| scope.id);
+    let registry = match registry {
+        Some(registry) => registry,
+        None => return inner,
+    };
+    {
+        let mut state = registry.0.lock();
+        let entry =
+            state.entries.entry(instance_id.clone()).or_insert(FocusEntry { entity: None, scope: None, tab_index, on_activate: None });
+        entry.scope = scope;
+        entry.tab_index = tab_index;
+        entry.on_activate = on_activate;
+    }
+    hooks.use_spawn({
+        to_owned![registry, instance_id];
+        move |_| {
+            move |_| {
+                registry.0.lock().entries.remove(&instance_id);
+            }
+        }
+    });
+    inner.on_spawned(move |_, entity, _| {
+        if let Some(entry) = registry.0.lock().entries.get_mut(&instance_id) {
+            entry.entity = Some(entity);
+        }
+    })
+}
+impl Focusable {
+    /// Creates a new [Focusable] that does nothing when activated.
+    pub fn new(inner: Element) -> Self {
+        Self { inner, tab_index: 0, on_activate: None }
+    }
+    /// Sets the tab index of the element.
+    pub fn tab_index(mut self, tab_index: i32) -> Self {
+        self.tab_index = tab_index;
+        self
+    }
+    /// Sets the callback to invoke when the element is activated.
+    pub fn on_activate(mut self, on_activate: impl Fn(&mut World) + Sync + Send + 'static) -> Self {
+        self.on_activate = Some(cb(on_activate));
+        self
+    }
+}
+
+/// Keeps the keyboard and gamepad navigation within the [Focusable] elements inside of it while it is mounted.
+///
+/// When scopes are nested, the innermost one is active; otherwise, the last one to be mounted is. When the scope is
+/// unmounted, the focus goes back to the element that had it when the scope was mounted.
+#[element_component]
+pub fn FocusScope(
+    hooks: &mut Hooks,
+    /// The content of the scope.
+    inner: Element,
+) -> Element {
+    let id = hooks.instance_id().to_owned();
+    // Only the first render sees the parent scope, as the scope provides its own context from then on
+    let parent_depth = hooks.consume_context::<FocusScopeId>().map(|(scope, _)| scope.depth);
+    let (depth, _) = hooks.use_state(parent_depth.map(|depth| depth + 1).unwrap_or(0));
+    hooks.provide_context(|| FocusScopeId { id: id.clone(), depth });
+    let registry = hooks.consume_context::<FocusRegistry>().map(|(registry, _)| registry);
+    let focus = hooks.consume_context::<Focus>();
+    hooks.use_spawn(move |_| {
+        if let Some(registry) = &registry {
+            let mut state = registry.0.lock();
+            let order = state.next_scope_order;
+            state.next_scope_order += 1;
+            state.scopes.push(FocusScopeEntry { id: id.clone(), depth, order });
+        }
+        move |_| {
+            if let Some(registry) = &registry {
+                registry.0.lock().scopes.retain(|scope| scope.id != id);
+            }
+            if let Some((focus @ Focus(Some(_)), set_focus)) = focus {
+                set_focus(focus);
+            }
+        }
+    });
+    inner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(id: &str, tab_index: i32, position: Vec2) -> FocusTarget {
+        FocusTarget { id: id.to_string(), tab_index, min: position, max: position + vec2(10., 10.), on_activate: None }
+    }
+
+    /// A 2x2 grid of elements, listed out of order
+    fn grid() -> Vec<FocusTarget> {
+        vec![target("d", 0, vec2(20., 20.)), target("a", 0, vec2(0., 0.)), target("c", 0, vec2(0., 20.)), target("b", 0, vec2(20., 0.))]
+    }
+
+    fn ids(targets: &[FocusTarget], indices: impl IntoIterator<Item = usize>) -> Vec<&str> {
+        indices.into_iter().map(|index| targets[index].id.as_str()).collect()
+    }
+
+    #[test]
+    fn tab_order_reads_rows_after_tab_index() {
+        let mut targets = grid();
+        assert_eq!(ids(&targets, tab_order(&targets)), ["a", "b", "c", "d"]);
+        targets[0].tab_index = -1;
+        targets[3].tab_index = 1;
+        assert_eq!(ids(&targets, tab_order(&targets)), ["d", "a", "c", "b"]);
+    }
+
+    #[test]
+    fn spatial_neighbor_prefers_elements_in_line() {
+        let targets = grid();
+        let a = 1;
+        assert_eq!(ids(&targets, spatial_neighbor(&targets, a, FocusDirection::Right)), ["b"]);
+        assert_eq!(ids(&targets, spatial_neighbor(&targets, a, FocusDirection::Down)), ["c"]);
+        assert_eq!(spatial_neighbor(&targets, a, FocusDirection::Up), None);
+        assert_eq!(spatial_neighbor(&targets, a, FocusDirection::Left), None);
+        assert_eq!(ids(&targets, spatial_neighbor(&targets, 0, FocusDirection::Up)), ["b"]);
+
+        // Further along, but in the same row
+        let targets = vec![target("a", 0, vec2(0., 0.)), target("b", 0, vec2(100., 0.)), target("c", 0, vec2(20., 50.))];
+        assert_eq!(ids(&targets, spatial_neighbor(&targets, 0, FocusDirection::Right)), ["b"]);
+    }
+
+    #[test]
+    fn navigate_cycles_with_tab() {
+        let targets = grid();
+        let (a, b, d) = (1, 3, 0);
+        assert_eq!(navigate(&targets, None, false, FocusAction::Next), Some(FocusChange::Focus(a)));
+        assert_eq!(navigate(&targets, None, false, FocusAction::Previous), Some(FocusChange::Focus(d)));
+        assert_eq!(navigate(&targets, Some(a), false, FocusAction::Next), Some(FocusChange::Focus(b)));
+        assert_eq!(navigate(&targets, Some(d), false, FocusAction::Next), Some(FocusChange::Focus(a)));
+        assert_eq!(navigate(&targets, Some(a), false, FocusAction::Previous), Some(FocusChange::Focus(d)));
+        assert_eq!(navigate(&[], None, false, FocusAction::Next), None);
+    }
+
+    #[test]
+    fn navigate_needs_a_focus_or_a_scope_to_move_and_activate() {
+        let targets = grid();
+        let (a, c) = (1, 2);
+        let down = FocusAction::Move(FocusDirection::Down);
+        assert_eq!(navigate(&targets, None, false, down), None);
+        assert_eq!(navigate(&targets, None, false, FocusAction::Activate), None);
+        assert_eq!(navigate(&targets, None, true, down), Some(FocusChange::Focus(a)));
+        assert_eq!(navigate(&targets, None, true, FocusAction::Activate), None);
+        assert_eq!(navigate(&targets, Some(a), false, down), Some(FocusChange::Focus(c)));
+        assert_eq!(navigate(&targets, Some(c), false, down), None);
+        assert_eq!(navigate(&targets, Some(a), false, FocusAction::Activate), Some(FocusChange::Activate(a)));
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! The layout is roughly based on [Windows Forms](https://docs.microsoft.com/en-us/dotnet/desktop/winforms/controls/layout?view=netdesktop-6.0#container-flow-layout).
 //!
-//! There are two major layout components, [Dock] and [Flow] (which includes [FlowColumn] and [FlowRow]).
+//! There are two major layout components, [Dock] and [Flow] (which includes [FlowColumn] and [FlowRow]), and a CSS-style [Grid].
 use crate::{use_window_logical_resolution, UIBase, UIExt};
 use ambient_cb::Cb;
 use ambient_color::Color;
@@ -13,8 +13,9 @@ use ambient_guest_bridge::components::{
     ecs::children,
     layout::{
         align_horizontal_begin, align_horizontal_center, align_vertical_begin, align_vertical_center, fit_horizontal_children,
-        fit_horizontal_none, fit_horizontal_parent, fit_vertical_children, fit_vertical_none, fit_vertical_parent, height, is_book_file,
-        layout_bookcase, layout_dock, layout_flow, orientation_horizontal, orientation_vertical, width,
+        fit_horizontal_none, fit_horizontal_parent, fit_vertical_children, fit_vertical_none, fit_vertical_parent, grid_columns, grid_gap,
+        grid_rows, height, is_book_file, layout_bookcase, layout_dock, layout_flow, layout_grid, orientation_horizontal,
+        orientation_vertical, width,
     },
     transform::{local_to_parent, translation},
 };
@@ -179,6 +180,31 @@ pub fn FixedGrid(
     )
 }
 
+/// A CSS-style grid layout. Each child is placed in the cell given by its `grid_cell` component, or in the
+/// next free cell row by row, and can cover several cells with `grid_span`.
+#[element_component]
+pub fn Grid(
+    _: &mut Hooks,
+    /// The items to put in the grid.
+    items: Vec<Element>,
+    /// The sizes of the columns, such as `"100 1fr 2fr auto"`: a number is a fixed size, `fr` is a share of the
+    /// space left by the other columns, and `auto` fits the widest item in the column.
+    columns: String,
+    /// The sizes of the rows, in the same format as `columns`. Rows beyond the listed ones are `auto`.
+    rows: String,
+    /// The space between the columns and the rows.
+    gap: Vec2,
+) -> Element {
+    UIBase
+        .el()
+        .init_default(layout_grid())
+        .init_default(children())
+        .with(grid_columns(), columns)
+        .with(grid_rows(), rows)
+        .with(grid_gap(), gap)
+        .children(items)
+}
+
 /// Measures the size of its inner element and calls the callback when it changes.
 #[element_component]
 pub fn MeasureSize(
@@ -52,7 +52,9 @@ pub mod clickarea;
 pub mod default_theme;
 pub mod dropdown;
 pub mod editor;
+pub mod focus;
 pub mod layout;
+pub mod list;
 pub mod prelude;
 pub mod prompt;
 pub mod screens;
@@ -177,10 +179,28 @@ pub fn use_focus_for_instance_id(
 }
 
 #[derive(Debug, Clone)]
-/// Provides a context for focusable UI elements.
+/// Provides a context for focusable UI elements, and lets the keyboard and gamepads move the focus between the
+/// [Focusable](focus::Focusable) elements within it.
 pub struct FocusRoot(pub Vec<Element>);
 define_el_function_for_vec_element_newtype!(FocusRoot);
 impl ElementComponent for FocusRoot {
+    fn render(self: Box<Self>, hooks: &mut Hooks) -> Element {
+        hooks.provide_context(|| Focus::new(None));
+        hooks.provide_context(focus::FocusRegistry::default);
+        let mut children = self.0;
+        children.push(FocusResetter.el());
+        children.push(focus::FocusNavigator.el());
+        Element::new().children(children)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Provides a context for focusable UI elements, like [FocusRoot], but without the keyboard and gamepad navigation.
+///
+/// For UIs that share the keyboard and gamepads with a game, so that the input of the game doesn't move the focus.
+pub struct FocusRootWithoutNavigation(pub Vec<Element>);
+define_el_function_for_vec_element_newtype!(FocusRootWithoutNavigation);
+impl ElementComponent for FocusRootWithoutNavigation {
     fn render(self: Box<Self>, hooks: &mut Hooks) -> Element {
         hooks.provide_context(|| Focus::new(None));
         let mut children = self.0;
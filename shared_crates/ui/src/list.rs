@@ -0,0 +1,243 @@
+//! Virtualized lists and tables, which only create elements for the rows that are visible.
+//!
+//! This keeps lists of thousands of rows (server browsers, inventories) as cheap as the handful of rows that fit
+//! on the screen. All the rows of a list have the same height, which is what makes it possible to know which rows
+//! are visible without creating them.
+use std::{cmp::Ordering, ops::Range, sync::Arc};
+
+use ambient_cb::{cb, Cb};
+use ambient_element::{element_component, to_owned, Element, ElementComponentExt, Hooks};
+use ambient_guest_bridge::{
+    components::{
+        app::cursor_position,
+        ecs::children,
+        layout::{docking_fill, docking_top, height, width},
+        transform::translation,
+    },
+    messages,
+    window::set_cursor,
+};
+use ambient_shared_types::CursorIcon;
+use glam::{vec3, Vec2};
+use itertools::Itertools;
+
+use crate::{
+    button::{Button, ButtonStyle},
+    layout::{Dock, FlowRow, MeasureSize},
+    scroll_area::use_scroll_offset,
+    text::Text,
+    UIBase, UIExt,
+};
+
+/// The number of rows created above and below the visible ones, so that scrolling doesn't show empty space.
+const OVERSCAN: usize = 2;
+const RESIZE_HANDLE_WIDTH: f32 = 6.;
+const MIN_COLUMN_WIDTH: f32 = 20.;
+
+/// Returns the rows that are at least partly visible when the content is scrolled down by `scroll`, in a viewport of
+/// `viewport_height`, with `overscan` more rows on each side.
+pub fn visible_rows(scroll: f32, viewport_height: f32, row_height: f32, row_count: usize, overscan: usize) -> Range<usize> {
+    if row_height <= 0. || row_count == 0 {
+        return 0..0;
+    }
+    let first = (scroll / row_height).floor().max(0.) as usize;
+    let end = ((scroll + viewport_height) / row_height).ceil().max(0.) as usize;
+    first.saturating_sub(overscan).min(row_count)..(end + overscan).min(row_count)
+}
+
+/// A vertical list of items of the same height, which only creates the elements of the items that are visible.
+///
+/// The list is scrolled with the mouse wheel. It takes the size it is given, so it should be given a `height`, or be
+/// sized by its parent (e.g. by docking it).
+#[element_component]
+pub fn VirtualList(
+    hooks: &mut Hooks,
+    /// The number of items in the list.
+    item_count: usize,
+    /// The height of every item.
+    item_height: f32,
+    /// Creates the element of the item at the given index.
+    render_item: Cb<dyn Fn(usize) -> Element + Sync + Send>,
+) -> Element {
+    let (size, set_size) = hooks.use_state(Vec2::ZERO);
+    let scroll = use_scroll_offset(hooks, Some(item_count as f32 * item_height - size.y));
+    let rows = visible_rows(scroll, size.y, item_height, item_count, OVERSCAN);
+    MeasureSize::el(
+        UIBase.el().init_default(children()).children(
+            rows.map(|index| {
+                render_item(index)
+                    .key(index.to_string())
+                    .with(translation(), vec3(0., index as f32 * item_height - scroll, 0.))
+                    .with(width(), size.x)
+                    .with(height(), item_height)
+            })
+            .collect(),
+        ),
+        cb(move |size| set_size(size)),
+    )
+}
+
+/// A column of a [Table].
+#[derive(Debug, Clone)]
+pub struct TableColumn {
+    /// The title of the column, shown in the header.
+    pub title: String,
+    /// The initial width of the column. Columns can be resized by dragging the right edge of their header.
+    pub width: f32,
+    /// Whether the rows can be sorted by this column, by clicking on its header.
+    pub sortable: bool,
+}
+impl TableColumn {
+    /// Creates a new column that can't be sorted by.
+    pub fn new(title: impl Into<String>, width: f32) -> Self {
+        Self { title: title.into(), width, sortable: false }
+    }
+    /// Makes it possible to sort the rows by this column.
+    pub fn sortable(mut self) -> Self {
+        self.sortable = true;
+        self
+    }
+}
+
+/// The column the rows of a [Table] are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSort {
+    /// The index of the column.
+    pub column: usize,
+    /// Whether the rows are sorted from the largest to the smallest value.
+    pub descending: bool,
+}
+
+/// A table with a header of sortable, resizable columns, whose rows are virtualized like a [VirtualList].
+///
+/// Like a [VirtualList], the table takes the size it is given.
+#[element_component]
+pub fn Table(
+    hooks: &mut Hooks,
+    /// The columns of the table.
+    columns: Vec<TableColumn>,
+    /// The number of rows.
+    row_count: usize,
+    /// The height of the header and of every row.
+    row_height: f32,
+    /// Creates the element of a cell, given its row and column. Rows are indexed in the order of the data, not in
+    /// the order they are displayed in.
+    render_cell: Cb<dyn Fn(usize, usize) -> Element + Sync + Send>,
+    /// Compares two rows (by their index in the data) on the values of a column. Used to sort the rows by the
+    /// sortable columns; the rows are sorted again when the sort or `row_count` change.
+    compare_rows: Cb<dyn Fn(usize, usize, usize) -> Ordering + Sync + Send>,
+) -> Element {
+    let (widths, set_widths) = hooks.use_state_with(|_| columns.iter().map(|column| column.width).collect_vec());
+    let widths = columns.iter().enumerate().map(|(i, column)| widths.get(i).copied().unwrap_or(column.width)).collect_vec();
+    let (sort, set_sort) = hooks.use_state(None::<TableSort>);
+
+    // The column being resized, with the cursor position and the width of the column when the drag started
+    let resizing = hooks.use_ref_with(|_| None::<(usize, f32, f32)>);
+    hooks.use_runtime_message::<messages::WindowMouseInput>({
+        to_owned![resizing];
+        move |_, event| {
+            if !event.pressed {
+                *resizing.lock() = None;
+            }
+        }
+    });
+    hooks.use_runtime_message::<messages::WindowMouseMotion>({
+        to_owned![resizing, widths, set_widths];
+        move |world, _| {
+            if let Some((column, start_x, start_width)) = *resizing.lock() {
+                let mut widths = widths.clone();
+                widths[column] = (start_width + world.resource(cursor_position()).x - start_x).max(MIN_COLUMN_WIDTH);
+                set_widths(widths);
+            }
+        }
+    });
+
+    // Sorting every row is too slow to do on every render, so only sort again when the sort or the rows change
+    let order = hooks.use_memo_with((sort.filter(|sort| sort.column < columns.len()), row_count), |_, &(sort, row_count)| {
+        let mut order = (0..row_count).collect_vec();
+        if let Some(sort) = sort {
+            order.sort_by(|&a, &b| {
+                let ordering = compare_rows(sort.column, a, b);
+                if sort.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        Arc::new(order)
+    });
+
+    let header = FlowRow::el(columns.into_iter().enumerate().map(|(i, column)| {
+        let column_width = widths[i];
+        let sorted = sort.filter(|sort| sort.column == i);
+        let title = match sorted {
+            Some(TableSort { descending: false, .. }) => format!("{} \u{25b2}", column.title),
+            Some(TableSort { descending: true, .. }) => format!("{} \u{25bc}", column.title),
+            None => column.title,
+        };
+        let title = if column.sortable {
+            let set_sort = set_sort.clone();
+            let descending = sorted.map(|sort| !sort.descending).unwrap_or(false);
+            Button::new(title, move |_| set_sort(Some(TableSort { column: i, descending }))).style(ButtonStyle::Flat).el()
+        } else {
+            Text::el(title)
+        };
+        let resize_handle = UIBase
+            .el()
+            .with(width(), RESIZE_HANDLE_WIDTH)
+            .with(height(), row_height)
+            .with(translation(), vec3(column_width - RESIZE_HANDLE_WIDTH, 0., -0.01))
+            .with_clickarea()
+            .on_mouse_enter(|world, _| set_cursor(world, CursorIcon::ColResize))
+            .on_mouse_leave(|world, _| set_cursor(world, CursorIcon::Default))
+            .on_mouse_down({
+                to_owned![resizing];
+                move |world, _, _| *resizing.lock() = Some((i, world.resource(cursor_position()).x, column_width))
+            })
+            .el();
+        UIBase.el().with(width(), column_width).with(height(), row_height).children(vec![title, resize_handle])
+    }))
+    .with_default(docking_top());
+
+    let rows = VirtualList::el(
+        row_count,
+        row_height,
+        cb(move |index| {
+            let row = order[index];
+            FlowRow::el(widths.iter().enumerate().map(|(column, &column_width)| {
+                UIBase.el().with(width(), column_width).with(height(), row_height).children(vec![render_cell(row, column)])
+            }))
+        }),
+    )
+    .with_default(docking_fill());
+
+    Dock::el([header, rows])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_rows_cover_the_viewport() {
+        assert_eq!(visible_rows(0., 100., 20., 100, 0), 0..5);
+        assert_eq!(visible_rows(30., 100., 20., 100, 0), 1..7);
+        assert_eq!(visible_rows(30., 100., 20., 100, 2), 0..9);
+    }
+
+    #[test]
+    fn visible_rows_are_clamped_to_the_list() {
+        assert_eq!(visible_rows(0., 100., 20., 3, 2), 0..3);
+        assert_eq!(visible_rows(1900., 100., 20., 100, 3), 92..100);
+        assert_eq!(visible_rows(5000., 100., 20., 100, 3), 100..100);
+        assert_eq!(visible_rows(-50., 100., 20., 100, 1), 0..4);
+        assert_eq!(visible_rows(0., 100., 20., 0, 2), 0..0);
+    }
+
+    #[test]
+    fn visible_rows_without_row_height_are_empty() {
+        assert_eq!(visible_rows(0., 100., 0., 100, 2), 0..0);
+        assert_eq!(visible_rows(0., 100., -20., 100, 2), 0..0);
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::{
     default_theme::app_background_color,
+    focus::FocusScope,
     layout::{Dock, WindowSized},
     UIBase, UIExt,
 };
@@ -31,30 +32,38 @@ impl ElementComponent for ScreenContainer {
 
 #[derive(Clone, Debug)]
 /// Contains several elements with a background color and click area.
+///
+/// The screen is a [FocusScope]: keyboard and gamepad navigation stays within it.
 pub struct PageScreen(pub Vec<Element>);
 define_el_function_for_vec_element_newtype!(PageScreen);
 impl ElementComponent for PageScreen {
     #[allow(clippy::clone_on_copy)]
     fn render(self: Box<Self>, _: &mut Hooks) -> Element {
-        WindowSized(vec![Dock(self.0).el().with_padding_even(30.)])
-            .el()
-            .with_background(app_background_color().set_a(0.99).clone().into())
-            .with_clickarea()
-            .el()
+        FocusScope::el(
+            WindowSized(vec![Dock(self.0).el().with_padding_even(30.)])
+                .el()
+                .with_background(app_background_color().set_a(0.99).clone().into())
+                .with_clickarea()
+                .el(),
+        )
     }
 }
 
 #[derive(Clone, Debug)]
 /// Contains a single element with a background color and click area.
+///
+/// The screen is a [FocusScope]: keyboard and gamepad navigation stays within it.
 // TODO: isn't this just [PageScreen] with a single element?
 pub struct DialogScreen(pub Element);
 impl ElementComponent for DialogScreen {
     #[allow(clippy::clone_on_copy)]
     fn render(self: Box<Self>, _: &mut Hooks) -> Element {
-        WindowSized(vec![Dock(vec![self.0]).el().with_padding_even(30.)])
-            .el()
-            .with_background(app_background_color().set_a(0.99).clone().into())
-            .with_clickarea()
-            .el()
+        FocusScope::el(
+            WindowSized(vec![Dock(vec![self.0]).el().with_padding_even(30.)])
+                .el()
+                .with_background(app_background_color().set_a(0.99).clone().into())
+                .with_clickarea()
+                .el(),
+        )
     }
 }
@@ -25,6 +25,23 @@ pub enum ScrollAreaSizing {
     FitParentWidth,
 }
 
+/// Returns how far the content of a scrollable element has been scrolled down with the mouse wheel.
+///
+/// If `max_scroll` is set, the offset is kept between 0 and `max_scroll`.
+pub fn use_scroll_offset(hooks: &mut Hooks, max_scroll: Option<f32>) -> f32 {
+    let clamp = move |scroll: f32| match max_scroll {
+        Some(max_scroll) => scroll.clamp(0., max_scroll.max(0.)),
+        None => scroll,
+    };
+    let (scroll, set_scroll) = hooks.use_state(0.);
+    let scroll = clamp(scroll);
+    hooks.use_runtime_message::<messages::WindowMouseWheel>(move |_world, event| {
+        let delta = event.delta;
+        set_scroll(clamp(scroll - if event.pixels { delta.y } else { delta.y * 20. }));
+    });
+    scroll
+}
+
 /// A scroll area that can be used to scroll its child.
 #[element_component]
 pub fn ScrollArea(
@@ -34,12 +51,8 @@ pub fn ScrollArea(
     /// The child element
     inner: Element,
 ) -> Element {
-    let (scroll, set_scroll) = hooks.use_state(0.);
+    let scroll = -use_scroll_offset(hooks, None);
     let (inner_size, set_inner_size) = hooks.use_state(Vec2::ZERO);
-    hooks.use_runtime_message::<messages::WindowMouseWheel>(move |_world, event| {
-        let delta = event.delta;
-        set_scroll(scroll + if event.pixels { delta.y } else { delta.y * 20. });
-    });
     match sizing {
         ScrollAreaSizing::FitChildrenWidth => {
             UIBase